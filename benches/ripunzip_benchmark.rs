@@ -12,7 +12,8 @@ use std::{
     process::Command,
 };
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ripunzip::{UnzipEngine, UnzipOptions};
 use ripunzip_test_utils::*;
 use std::string::ToString;
 use tempfile::TempDir;
@@ -54,7 +55,7 @@ fn file_comparison(c: &mut Criterion, params: &ZipParams) {
         .join("ripunzip");
 
     let mut group = c.benchmark_group(&desc);
-    group.bench_function(&format!("{} ripunzip", &desc), |b| {
+    group.bench_function(format!("{} ripunzip", &desc), |b| {
         b.iter_batched(
             || create_output_dir_and_zip_file(params),
             |(output_dir, zip_file)| {
@@ -70,7 +71,7 @@ fn file_comparison(c: &mut Criterion, params: &ZipParams) {
             criterion::BatchSize::SmallInput,
         )
     });
-    group.bench_function(&format!("{} unzip", &desc), |b| {
+    group.bench_function(format!("{} unzip", &desc), |b| {
         b.iter_batched(
             || create_output_dir_and_zip_file(params),
             |(output_dir, zip_file)| {
@@ -87,6 +88,124 @@ fn file_comparison(c: &mut Criterion, params: &ZipParams) {
     });
 }
 
+/// Compares the time to unzip a tiny zip file in-process on the calling
+/// thread against using rayon's thread pool. The first parallel unzip also
+/// pays to create the pool, which criterion's warm-up hides, so this
+/// understates the difference for a one-off unzip.
+fn startup_latency_comparison(c: &mut Criterion) {
+    let params = ZipParams::new(
+        FileSizes::Fixed(FileSize::Small),
+        1,
+        zip::CompressionMethod::Stored,
+    );
+    let desc = format!("startup {params}");
+    let mut group = c.benchmark_group(&desc);
+    for single_threaded in [true, false] {
+        let name = if single_threaded {
+            "single-threaded"
+        } else {
+            "parallel"
+        };
+        group.bench_function(format!("{desc} {name}"), |b| {
+            b.iter_batched(
+                || create_output_dir_and_zip_file(&params),
+                |(output_dir, zip_file)| {
+                    let options = UnzipOptions {
+                        output_directory: Some(output_dir.path().to_path_buf()),
+                        single_threaded,
+                        ..Default::default()
+                    };
+                    UnzipEngine::for_file(zip_file.path().to_path_buf())
+                        .unwrap()
+                        .unzip(options)
+                        .unwrap();
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
+/// Measures the throughput of unzipping from a local HTTP server, in
+/// process, with each combination of readahead limit and block size, to
+/// help choose them. The server is the same throughout, so it's only the
+/// unzipping that's timed.
+fn readahead_tuning(c: &mut Criterion) {
+    const MIB: usize = 1024 * 1024;
+    let params = ZipParams::new(FileSizes::Variable, 1000, zip::CompressionMethod::Deflated);
+    let zip_data = get_sample_zip(&params);
+    let server = httptest::Server::run();
+    let zip_len = zip_data.len() as u64;
+    set_up_server(&server, zip_data, ServerType::Ranges);
+    let uri = server.url("/foo").to_string();
+
+    let mut group = c.benchmark_group(format!("readahead {params}"));
+    group.throughput(Throughput::Bytes(zip_len));
+    for readahead_limit in [None, Some(MIB), Some(4 * MIB), Some(16 * MIB)] {
+        for block_size in [256 * 1024, MIB, 4 * MIB] {
+            let id = format!(
+                "limit {} block {}KiB",
+                readahead_limit.map_or("none".to_string(), |limit| format!("{}MiB", limit / MIB)),
+                block_size / 1024
+            );
+            group.bench_function(BenchmarkId::from_parameter(id), |b| {
+                b.iter_batched(
+                    create_output_dir,
+                    |output_dir| {
+                        let options = UnzipOptions {
+                            output_directory: Some(output_dir.path().to_path_buf()),
+                            readahead_block_size: Some(block_size),
+                            ..Default::default()
+                        };
+                        UnzipEngine::for_uri(&uri, readahead_limit, || {})
+                            .unwrap()
+                            .unzip(options)
+                            .unwrap();
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+}
+
+/// Measures the throughput of unzipping from a local HTTP server, in
+/// process, asking for ranges of each size, to help choose one. A local
+/// server has no round trip to speak of, so this shows the cost of the
+/// requests themselves; over a high-latency link, small chunks cost more.
+fn range_chunk_tuning(c: &mut Criterion) {
+    const MIB: usize = 1024 * 1024;
+    let params = ZipParams::new(FileSizes::Variable, 1000, zip::CompressionMethod::Deflated);
+    let zip_data = get_sample_zip(&params);
+    let server = httptest::Server::run();
+    let zip_len = zip_data.len() as u64;
+    set_up_server(&server, zip_data, ServerType::Ranges);
+    let uri = server.url("/foo").to_string();
+
+    let mut group = c.benchmark_group(format!("range chunk {params}"));
+    group.throughput(Throughput::Bytes(zip_len));
+    for range_chunk_size in [None, Some(MIB), Some(8 * MIB), Some(64 * MIB)] {
+        let id = range_chunk_size.map_or("none".to_string(), |size| format!("{}MiB", size / MIB));
+        group.bench_function(BenchmarkId::from_parameter(id), |b| {
+            b.iter_batched(
+                create_output_dir,
+                |output_dir| {
+                    let options = UnzipOptions {
+                        output_directory: Some(output_dir.path().to_path_buf()),
+                        range_chunk_size,
+                        ..Default::default()
+                    };
+                    UnzipEngine::for_uri(&uri, None, || {})
+                        .unwrap()
+                        .unzip(options)
+                        .unwrap();
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
 fn uri_comparison(c: &mut Criterion, params: &ZipParams, server_type: ServerType) {
     let desc = format!("uri {server_type} {params}",);
 
@@ -103,7 +222,7 @@ fn uri_comparison(c: &mut Criterion, params: &ZipParams, server_type: ServerType
     let ripunzip_path = ripunzip_path();
 
     let mut group = c.benchmark_group(&desc);
-    group.bench_function(&format!("{} ripunzip", &desc), |b| {
+    group.bench_function(format!("{} ripunzip", &desc), |b| {
         b.iter_batched(
             create_output_dir_and_server,
             |(output_dir, server)| {
@@ -113,7 +232,7 @@ fn uri_comparison(c: &mut Criterion, params: &ZipParams, server_type: ServerType
             criterion::BatchSize::SmallInput,
         )
     });
-    group.bench_function(&format!("{} unzip", &desc), |b| {
+    group.bench_function(format!("{} unzip", &desc), |b| {
         b.iter_batched(
             create_output_dir_and_server,
             |(output_dir, server)| {
@@ -133,7 +252,7 @@ fn real_world_uri_comparison(c: &mut Criterion) {
     let ripunzip_path = ripunzip_path();
 
     let mut group = c.benchmark_group(desc);
-    group.bench_function(&format!("{} ripunzip", &desc), |b| {
+    group.bench_function(format!("{} ripunzip", &desc), |b| {
         b.iter_batched(
             create_output_dir.clone(),
             |output_dir| {
@@ -142,7 +261,7 @@ fn real_world_uri_comparison(c: &mut Criterion) {
             criterion::BatchSize::SmallInput,
         )
     });
-    group.bench_function(&format!("{} unzip", &desc), |b| {
+    group.bench_function(format!("{} unzip", &desc), |b| {
         b.iter_batched(
             create_output_dir.clone(),
             |output_dir| fetch_uri_with_curl_and_unzip(URI, output_dir),
@@ -199,6 +318,9 @@ fn add_benchmarks(c: &mut Criterion) {
         ZipParams::new(FileSizes::Variable, 1000, zip::CompressionMethod::Deflated),
     ];
     real_world_uri_comparison(c);
+    startup_latency_comparison(c);
+    readahead_tuning(c);
+    range_chunk_tuning(c);
     for z in zips_to_test.iter() {
         file_comparison(c, z);
         for server_type in ServerType::types() {
@@ -0,0 +1,351 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Abstractions over the sources zip files are read from, so that other
+//! sources can be given to [`UnzipEngine::for_reader`](crate::UnzipEngine::for_reader),
+//! and readers which can be shared between threads reading different parts
+//! of the same file at once, as ripunzip does.
+
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+    sync::Arc,
+};
+
+/// Something whose total length is known without reading it, such as a
+/// file, a blob in a database, or an object in an object store.
+pub trait HasLength {
+    /// The total length, in bytes.
+    fn len(&self) -> std::io::Result<u64>;
+
+    /// Whether the length is zero.
+    fn is_empty(&self) -> std::io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+impl HasLength for File {
+    fn len(&self) -> std::io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+impl<R: HasLength> HasLength for BufReader<R> {
+    fn len(&self) -> std::io::Result<u64> {
+        self.get_ref().len()
+    }
+}
+
+impl<T: AsRef<[u8]>> HasLength for Cursor<T> {
+    fn len(&self) -> std::io::Result<u64> {
+        Ok(self.get_ref().as_ref().len() as u64)
+    }
+}
+
+/// Bytes already in memory, shared rather than copied when cloned, so
+/// that a `Cursor<SharedBytes<_>>` can be given to
+/// [`UnzipEngine::for_reader`](crate::UnzipEngine::for_reader) without
+/// each thread extracting files getting its own copy of the zip file, as
+/// it would with a `Cursor<Vec<u8>>`. The same bytes can also be shared
+/// by several engines, or with the rest of the program.
+#[derive(Debug)]
+pub struct SharedBytes<B: ?Sized>(Arc<B>);
+
+impl<B: ?Sized> SharedBytes<B> {
+    /// Share `bytes`.
+    pub fn new(bytes: Arc<B>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<B: ?Sized> Clone for SharedBytes<B> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<B: ?Sized> From<Arc<B>> for SharedBytes<B> {
+    fn from(bytes: Arc<B>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl<B: AsRef<[u8]> + ?Sized> AsRef<[u8]> for SharedBytes<B> {
+    fn as_ref(&self) -> &[u8] {
+        (*self.0).as_ref()
+    }
+}
+
+/// A file which can be cloned cheaply, for example to read different parts
+/// of it on different threads at once. Each clone has its own position, but
+/// they share the file handle, reading with positioned reads, so there's no
+/// locking and cloning can't fail. On platforms other than Unix and
+/// Windows, which have no positioned reads, clones take turns to seek and
+/// read instead.
+#[derive(Debug, Clone)]
+pub struct CloneableFile {
+    file: Arc<File>,
+    position: u64,
+    #[cfg(not(any(unix, windows)))]
+    seek_lock: Arc<std::sync::Mutex<()>>,
+}
+
+impl CloneableFile {
+    /// Open the file at `path` for reading.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(File::open(path)?))
+    }
+
+    /// Read from `file`, starting at the beginning, whatever its current
+    /// position.
+    pub fn new(file: File) -> Self {
+        Self {
+            file: Arc::new(file),
+            position: 0,
+            #[cfg(not(any(unix, windows)))]
+            seek_lock: Arc::default(),
+        }
+    }
+}
+
+impl From<File> for CloneableFile {
+    fn from(file: File) -> Self {
+        Self::new(file)
+    }
+}
+
+impl Read for CloneableFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let bytes_read = std::os::unix::fs::FileExt::read_at(&*self.file, buf, self.position)?;
+        // This moves the handle's own position, which no clone relies on.
+        #[cfg(windows)]
+        let bytes_read = std::os::windows::fs::FileExt::seek_read(&*self.file, buf, self.position)?;
+        #[cfg(not(any(unix, windows)))]
+        let bytes_read = {
+            // A poisoned lock guards nothing that could be left broken.
+            let _guard = self
+                .seek_lock
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            (&*self.file).seek(SeekFrom::Start(self.position))?;
+            (&*self.file).read(buf)?
+        };
+        self.position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl Seek for CloneableFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.len()?.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        Ok(self.position)
+    }
+}
+
+impl HasLength for CloneableFile {
+    fn len(&self) -> std::io::Result<u64> {
+        self.file.len()
+    }
+}
+
+/// Several readers read one after another as if they were one, such as
+/// the segments of a zip file which has been split into several files.
+/// Each clone has its own position, so if the parts can be cloned cheaply,
+/// such as [`CloneableFile`]s, so can this.
+#[derive(Debug, Clone)]
+pub struct Concatenated<R> {
+    parts: Vec<R>,
+    /// Where each part starts, followed by where the last ends.
+    starts: Arc<[u64]>,
+    position: u64,
+}
+
+impl<R: HasLength> Concatenated<R> {
+    /// Read `parts` in order, each of which must stay the same length.
+    pub fn new(parts: Vec<R>) -> std::io::Result<Self> {
+        let mut starts = Vec::with_capacity(parts.len() + 1);
+        let mut end = 0u64;
+        starts.push(end);
+        for part in &parts {
+            end = end.checked_add(part.len()?).ok_or_else(|| {
+                std::io::Error::new(ErrorKind::InvalidInput, "parts too long in total")
+            })?;
+            starts.push(end);
+        }
+        Ok(Self {
+            parts,
+            starts: starts.into(),
+            position: 0,
+        })
+    }
+}
+
+impl<R> Concatenated<R> {
+    /// Where each part starts within the whole.
+    pub fn part_starts(&self) -> &[u64] {
+        &self.starts[..self.parts.len()]
+    }
+
+    /// The parts, to read from independently.
+    pub fn into_parts(self) -> Vec<R> {
+        self.parts
+    }
+}
+
+impl<R: Read + Seek> Read for Concatenated<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // The last part starting at or before the position, so that empty
+        // parts are passed over.
+        let i = self.starts.partition_point(|&start| start <= self.position) - 1;
+        let Some(part) = self.parts.get_mut(i) else {
+            return Ok(0);
+        };
+        part.seek(SeekFrom::Start(self.position - self.starts[i]))?;
+        let len = (self.starts[i + 1] - self.position).min(buf.len() as u64) as usize;
+        let bytes_read = part.read(&mut buf[..len])?;
+        self.position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<R> Seek for Concatenated<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.starts[self.parts.len()].checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        Ok(self.position)
+    }
+}
+
+impl<R> HasLength for Concatenated<R> {
+    fn len(&self) -> std::io::Result<u64> {
+        Ok(self.starts[self.parts.len()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+
+    use std::sync::Arc;
+
+    use super::{CloneableFile, Concatenated, HasLength, SharedBytes};
+
+    #[test]
+    fn test_has_length() {
+        let mut file = tempfile::tempfile().unwrap();
+        assert!(file.is_empty().unwrap());
+        file.write_all(b"Contents").unwrap();
+        assert_eq!(file.len().unwrap(), 8);
+        assert_eq!(BufReader::new(file).len().unwrap(), 8);
+        assert_eq!(Cursor::new(b"Contents").len().unwrap(), 8);
+    }
+
+    #[test]
+    fn test_shared_bytes() {
+        let bytes = Arc::new(b"Contents of A\n".to_vec());
+        let mut first = Cursor::new(SharedBytes::new(bytes.clone()));
+        first.seek(SeekFrom::Start(12)).unwrap();
+        let mut second = first.clone();
+        assert_eq!(Arc::strong_count(&bytes), 3);
+        let mut rest = String::new();
+        first.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "A\n");
+        assert_eq!(second.stream_position().unwrap(), 12);
+        assert_eq!(second.len().unwrap(), 14);
+        let slice: Arc<[u8]> = Arc::from(&b"Contents"[..]);
+        assert_eq!(Cursor::new(SharedBytes::from(slice)).len().unwrap(), 8);
+    }
+
+    #[test]
+    fn test_cloneable_file() {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"Contents of A\n").unwrap();
+        // Wherever the file's position is.
+        let mut first = CloneableFile::new(file);
+        let mut second = first.clone();
+        let mut buf = [0u8; 8];
+        first.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"Contents");
+        // Clones don't share a position.
+        second.seek(SeekFrom::End(-6)).unwrap();
+        let mut rest = String::new();
+        second.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, " of A\n");
+        let mut rest = String::new();
+        first.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, " of A\n");
+        assert_eq!(first.seek(SeekFrom::Current(-2)).unwrap(), 12);
+        assert_eq!(first.len().unwrap(), 14);
+        assert_eq!(
+            first.seek(SeekFrom::Current(-13)).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+        // Past the end, there's nothing to read.
+        first.seek(SeekFrom::Start(20)).unwrap();
+        assert_eq!(first.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_concatenated() {
+        let parts = ["Cont", "", "ents of", " A\n"].map(|part| Cursor::new(part.as_bytes()));
+        let mut first = Concatenated::new(parts.to_vec()).unwrap();
+        assert_eq!(first.len().unwrap(), 14);
+        assert_eq!(first.part_starts(), [0, 4, 4, 11]);
+        let mut second = first.clone();
+        let mut all = String::new();
+        first.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "Contents of A\n");
+        // Clones don't share a position, and reads stop at the end of each
+        // part.
+        second.seek(SeekFrom::Start(2)).unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(second.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"nt");
+        second.seek(SeekFrom::End(-5)).unwrap();
+        let mut rest = String::new();
+        second.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "of A\n");
+        assert_eq!(second.read(&mut buf).unwrap(), 0);
+        assert_eq!(
+            Concatenated::<Cursor<&[u8]>>::new(Vec::new())
+                .unwrap()
+                .len()
+                .unwrap(),
+            0
+        );
+    }
+}
@@ -22,17 +22,165 @@ pub enum RipunzipErrors {
     },
     #[error(transparent)]
     ZipErrorr(#[from] zip::result::ZipError),
+    #[cfg(feature = "http")]
     #[error(transparent)]
     ReqwestErrorr(#[from] reqwest::Error),
+    #[cfg(feature = "ssh")]
+    #[error(transparent)]
+    SshError(#[from] ssh2::Error),
+    #[error("Can't use {uri}: {reason}")]
+    UnsupportedUri { uri: String, reason: String },
+    #[error("Mirror {uri} has length {len}, but another mirror has length {expected}")]
+    MirrorLengthMismatch {
+        uri: String,
+        len: u64,
+        expected: u64,
+    },
+    #[error("{uri} changed while it was being unzipped")]
+    SourceChanged { uri: String },
+    #[error("Reached the download budget of {budget} bytes, having downloaded {downloaded}")]
+    BudgetExceeded { downloaded: u64, budget: u64 },
+    #[error("CRC mismatch in {name}")]
+    CrcMismatch { name: String },
+    #[error("Authentication code mismatch in {name}: it's been tampered with or corrupted, or the password is wrong")]
+    AuthenticationFailed { name: String },
+    #[error(
+        "{name} doesn't match the checksum manifest: its hash is {actual} rather than {expected}"
+    )]
+    ManifestMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    #[cfg(feature = "signature")]
+    #[error("The zip file's signature is invalid: {reason}")]
+    SignatureInvalid { reason: String },
+    #[error("Local header of {name} disagrees with the central directory: {field} is {local} rather than {central}")]
+    HeaderMismatch {
+        name: String,
+        field: &'static str,
+        central: u64,
+        local: u64,
+    },
+    #[error("Extraction aborted")]
+    Aborted,
+    #[error("Extracting {name} panicked: {message}")]
+    Worker { name: String, message: String },
+    #[error("Not found in the zip file: {}", .names.join(", "))]
+    EntriesNotFound { names: Vec<String> },
+    #[error("Expected exactly one file to extract, but found {count}")]
+    NotSingleFile { count: usize },
+    #[error("Not a valid zip file: {reason}{}", .offset.map(|offset| format!(" (at byte {offset})")).unwrap_or_default())]
+    InvalidArchive { reason: String, offset: Option<u64> },
+    #[error("This is a segment of a spanned zip file, which isn't supported. Join the segments into a single zip file first, for example with `zip -s 0 spanned.zip --out joined.zip`.")]
+    SpannedArchiveUnsupported,
+    #[error("The segments don't make up a zip file: {reason}")]
+    InvalidSegments { reason: String },
+    #[error("Not extracting {name} because {} is a symbolic link", .symlink.display())]
+    SymlinkInPath {
+        name: String,
+        symlink: std::path::PathBuf,
+    },
+    #[error("Not extracting {name} because {} is on a different filesystem", .path.display())]
+    DifferentDevice {
+        name: String,
+        path: std::path::PathBuf,
+    },
+    #[error("Files would take more than the memory limit of {limit} bytes")]
+    MemoryLimitExceeded { limit: u64 },
+    #[error("The files extracted add up to more than the limit of {limit} bytes, so the zip file may be a zip bomb")]
+    TotalSizeExceeded { limit: u64 },
+    #[error("{name} is a zip file containing itself, or one it's within, so is a zip bomb")]
+    RecursiveBombDetected { name: String },
+    #[error("Output directory {} is not empty", .directory.display())]
+    OutputDirectoryNotEmpty { directory: std::path::PathBuf },
+    #[error("Another unzip holds the lock {} on the output directory", .path.display())]
+    OutputLocked { path: std::path::PathBuf },
+    #[error("The zip file doesn't fit its length of {len} bytes, so it may be truncated or the server misconfigured: {reason}")]
+    InconsistentArchive { len: u64, reason: String },
+    #[error("Output directory {} doesn't exist", .directory.display())]
+    OutputDirectoryMissing { directory: std::path::PathBuf },
+    #[error("The zip file has {count} entries, more than the limit of {limit}")]
+    TooManyEntries { count: usize, limit: usize },
+    #[error("{name} is nested {depth} levels deep, beyond the limit of {limit}")]
+    PathTooDeep {
+        name: String,
+        depth: usize,
+        limit: usize,
+    },
+    #[error("Unable to create worker threads: {0}")]
+    ThreadPoolBuildError(#[from] rayon::ThreadPoolBuildError),
+    #[error("{source}\nCleaned up partially written files: {}", .paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", "))]
+    PartialFilesCleanedUp {
+        #[source]
+        source: Box<RipunzipErrors>,
+        paths: Vec<std::path::PathBuf>,
+    },
 }
 
+pub mod io;
 mod unzip;
 
+pub use unzip::AbsolutePathPolicy;
+pub use unzip::BackgroundUnzip;
+pub use unzip::Bottleneck;
+pub use unzip::ByteTransformer;
+pub use unzip::CachedExtraction;
+pub use unzip::CompressionStats;
+#[cfg(feature = "http")]
+pub use unzip::Cookies;
+pub use unzip::Decompressor;
+pub use unzip::DecompressorRegistry;
+pub use unzip::DuplicatePolicy;
+pub use unzip::Durability;
+pub use unzip::EntryInfo;
+pub use unzip::EntryKind;
+pub use unzip::EntryTransform;
+pub use unzip::ExtractedEntry;
+pub use unzip::ExtractionCache;
+pub use unzip::ExtractionDelta;
+pub use unzip::ExtractionPlan;
+pub use unzip::ExtractionTimings;
+pub use unzip::FileFactory;
 pub use unzip::FilenameFilter;
+pub use unzip::HashAlgorithm;
+pub use unzip::LineEnding;
+pub use unzip::LineEndingMode;
+pub use unzip::MethodStats;
+pub use unzip::MissingEntries;
+pub use unzip::MultiUnzip;
+pub use unzip::NetworkEvent;
 pub use unzip::NullProgressReporter;
+pub use unzip::OutputLock;
+pub use unzip::OverwriteCallback;
+pub use unzip::OverwriteDecision;
+pub use unzip::OverwritePolicy;
+pub use unzip::PartialFiles;
+pub use unzip::PermissionTemplate;
+pub use unzip::PlannedAction;
+pub use unzip::PlannedDecision;
+pub use unzip::PlannedEntry;
+pub use unzip::ProgressCoalescing;
+#[cfg(feature = "http")]
+pub use unzip::RootCertificates;
+#[cfg(feature = "signature")]
+pub use unzip::SignatureConfig;
+#[cfg(feature = "signature")]
+pub use unzip::SignatureSource;
+pub use unzip::SkipReason;
+pub use unzip::Throughput;
+pub use unzip::UnknownSizes;
+pub use unzip::UnzipControl;
 pub use unzip::UnzipEngine;
+pub use unzip::UnzipIter;
 pub use unzip::UnzipOptions;
+pub use unzip::UnzipOutcome;
 pub use unzip::UnzipProgressReporter;
+#[cfg(feature = "http")]
+pub use unzip::UriOptions;
+pub use unzip::VerifyOutcome;
+pub use unzip::WorkerThreadOptions;
+pub use unzip::ZipEntryMetadata;
 
 #[cfg(test)]
 mod tests {
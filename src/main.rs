@@ -8,13 +8,23 @@
 
 #![forbid(unsafe_code)]
 
-use std::{fmt::Write, path::PathBuf, sync::RwLock};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    io::{BufWriter, ErrorKind},
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::Duration,
+};
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use ripunzip::{
-    FilenameFilter, NullProgressReporter, RipunzipErrors, UnzipEngine, UnzipOptions,
-    UnzipProgressReporter,
+    AbsolutePathPolicy, Cookies, DuplicatePolicy, Durability, EntryInfo, ExtractionPlan,
+    FilenameFilter, HashAlgorithm, LineEnding, LineEndingMode, MissingEntries, NetworkEvent,
+    NullProgressReporter, OutputLock, OverwritePolicy, PartialFiles, PlannedAction, RipunzipErrors,
+    RootCertificates, SignatureConfig, SignatureSource, SkipReason, UnknownSizes, UnzipEngine,
+    UnzipOptions, UnzipOutcome, UnzipProgressReporter, UriOptions, WorkerThreadOptions,
 };
 use wildmatch::WildMatch;
 
@@ -87,12 +97,432 @@ struct UnzipArgs {
     #[arg(long)]
     single_threaded: bool,
 
+    /// Lower the scheduling priority of the threads extracting files by this much, as for `nice`,
+    /// so that extraction in the background doesn't slow down other work. Linux only.
+    #[arg(long, value_name = "INCREMENT", allow_hyphen_values = true)]
+    nice: Option<i32>,
+
+    /// Extract files one at a time in the order they're stored, reading the zip file from start
+    /// to end once. This may be quicker where seeking is slow, such as on spinning disks.
+    #[arg(long)]
+    sequential: bool,
+
+    /// Refuse to write to any filesystem other than the one containing the output directory,
+    /// such as one mounted within it. Unix only.
+    #[arg(long)]
+    restrict_to_device: bool,
+
+    /// Extract entries beneath PREFIX into DIRECTORY instead of the output directory, without
+    /// the prefix. May be given several times, in which case the longest matching prefix wins.
+    #[arg(long = "map", value_name = "PREFIX=DIRECTORY", value_parser = parse_prefix_mapping)]
+    prefix_map: Vec<(String, PathBuf)>,
+
+    /// If everything in the zip file is within a single directory, extract its contents straight
+    /// into the output directory, without that directory. Otherwise, extract as usual.
+    #[arg(long, visible_alias = "flatten")]
+    flatten_single_root: bool,
+
+    /// List the files which would be created or overwritten, without writing anything.
+    #[arg(long, conflicts_with = "stdout")]
+    dry_run: bool,
+
+    /// Before extracting anything, print what will be extracted where, as a line of JSON per
+    /// entry, once every other option has been applied. With --dry-run, nothing is extracted.
+    #[arg(long, conflicts_with = "stdout")]
+    explain: bool,
+
+    /// Append a line of JSON to PATH as each file starts and finishes extracting, and if
+    /// the unzip fails, so that another process can follow progress.
+    #[arg(long, value_name = "PATH")]
+    progress_file: Option<PathBuf>,
+
+    /// Write an index of the zip file to PATH, with a line of JSON giving the name, local
+    /// header offset, compressed size, compression method and CRC of each entry.
+    #[arg(long, value_name = "PATH")]
+    emit_index: Option<PathBuf>,
+
+    /// Check each extracted file's contents against the checksum manifest at PATH, in the format
+    /// written by `sha256sum`, with paths as they are within the zip file. Files which don't
+    /// match fail to extract, and files missing from either are logged.
+    #[arg(long, value_name = "PATH")]
+    verify_manifest: Option<PathBuf>,
+
+    /// Which hash the checksums in the manifest are.
+    #[arg(long, value_name = "ALGORITHM", default_value = "sha256")]
+    hash_algorithm: HashAlgorithmArg,
+
+    /// Check the detached signature at PATH over the whole zip file before extracting anything: a
+    /// raw 64-byte Ed25519ph signature, as defined by RFC 8032, made with the key given by
+    /// --public-key. For a URI, the zip file is downloaded just once, to check it. OpenSSL 3.2 or
+    /// later makes one with `openssl pkeyutl -sign -rawin -pkeyopt instance:Ed25519ph`; minisign
+    /// and signify signatures won't match.
+    #[arg(long, value_name = "PATH", requires = "public_key")]
+    verify_signature: Option<PathBuf>,
+
+    /// The Ed25519 public key for --verify-signature, as 64 hex digits.
+    #[arg(long, value_name = "HEX", value_parser = parse_public_key, requires = "verify_signature")]
+    public_key: Option<[u8; 32]>,
+
+    /// Check the CRCs of all files before writing any of them, so that a
+    /// corrupt zip file doesn't leave partial output behind. This means
+    /// decompressing everything twice, and for URIs, fetching the data twice.
+    #[arg(long)]
+    verify_before_extract: bool,
+
+    /// Refuse to extract files whose local header disagrees with the zip's
+    /// central directory about their size or CRC.
+    #[arg(long)]
+    strict: bool,
+
+    /// Convert line endings in text files to the given style, like
+    /// `unzip -a`. Files which don't look like text are left alone.
+    #[arg(long, value_name = "STYLE")]
+    line_endings: Option<LineEndingArg>,
+
+    /// Only convert line endings in files matching these patterns, which
+    /// may include wildcards. By default, all text files are converted.
+    #[arg(long, value_name = "PATTERN", requires = "line_endings")]
+    text_files: Vec<String>,
+
+    /// Write the contents of the zip's only file to standard output instead
+    /// of creating it. If the zip has several files, name the one you want.
+    #[arg(long, conflicts_with = "output_directory")]
+    stdout: bool,
+
+    /// Refuse to extract entries whose paths have more than this many
+    /// components, to guard against pathologically deep directory trees.
+    #[arg(long, value_name = "DEPTH")]
+    max_path_depth: Option<usize>,
+
+    /// Hard link files with identical contents to one another rather than
+    /// writing each of them.
+    #[arg(long)]
+    dedup: bool,
+
+    /// Fsync extracted files, and perhaps their directories, so that they
+    /// survive a crash or power loss. This can slow unzipping a lot.
+    #[arg(long, value_name = "LEVEL", default_value = "none")]
+    durability: DurabilityArg,
+
+    /// Write through symbolic links which already exist in the output
+    /// directory. By default, files whose paths pass through a symbolic link
+    /// aren't extracted, since they may end up outside the output directory.
+    #[arg(long)]
+    follow_existing_symlinks: bool,
+
+    /// Fail without extracting anything if the output directory already
+    /// exists and isn't empty.
+    #[arg(long)]
+    require_empty_output: bool,
+
+    /// With --require-empty-output, ignore hidden files (whose names start
+    /// with a dot) such as .DS_Store.
+    #[arg(long, requires = "require_empty_output")]
+    ignore_hidden_files: bool,
+
+    /// Lock the output directory while extracting, so that other runs which lock it too wait for
+    /// this one, or fail. The lock is advisory, and unreliable on some network filesystems.
+    #[arg(long, value_name = "MODE")]
+    lock_output: Option<OutputLockArg>,
+
+    /// Fail without extracting anything if the output directory doesn't
+    /// exist, rather than creating it.
+    #[arg(long)]
+    no_create_output_directory: bool,
+
+    /// Don't extract files identical, by size and CRC, to those at the same paths in this
+    /// directory, so that the output directory holds only what's new or changed as a layer over
+    /// it.
+    #[arg(long, value_name = "DIRECTORY")]
+    overlay_base: Option<PathBuf>,
+
+    /// Size of the buffer used to copy each file, which is also the size of each write. Larger
+    /// buffers may help on network filesystems, at the cost of memory per file being extracted.
+    /// Defaults to 256KiB.
+    #[arg(long, value_name = "BYTES")]
+    io_buffer_size: Option<usize>,
+
+    /// The most files to write to at once, whatever the number of threads. Defaults to half the
+    /// limit on open files, as shown by `ulimit -n`.
+    #[arg(long, value_name = "FILES")]
+    max_open_files: Option<usize>,
+
+    /// Record the zip file's identity in this file once extraction succeeds, and skip extraction
+    /// if it already records the same zip file.
+    #[arg(long, value_name = "FILE")]
+    completion_marker: Option<PathBuf>,
+
+    /// Spread files among this many directories named shard-0, shard-1 and so on, by a hash of
+    /// their paths, rather than preserving their directory structure.
+    #[arg(long, value_name = "SHARDS")]
+    shard_into: Option<usize>,
+
+    /// What to do with files which fail to extract partway through, which may look complete.
+    #[arg(long, value_name = "ACTION", default_value = "keep")]
+    partial_files: PartialFilesArg,
+
+    /// How to report progress for files whose uncompressed size the zip file doesn't record.
+    #[arg(long, value_name = "POLICY", default_value = "estimate")]
+    unknown_sizes: UnknownSizesArg,
+
+    /// Skip files larger than this many bytes when uncompressed. Their data isn't downloaded.
+    #[arg(long, value_name = "BYTES")]
+    max_entry_size: Option<u64>,
+
+    /// Fail once more than this many bytes have been extracted in total, counting the data as it's
+    /// decompressed rather than trusting the sizes the zip file records, to guard against zip
+    /// bombs.
+    #[arg(long, value_name = "BYTES")]
+    max_total_size: Option<u64>,
+
+    /// Which to extract of several entries whose names lead to the same path, such as `a.txt` and
+    /// `./a.txt`.
+    #[arg(long, value_name = "POLICY", default_value = "all")]
+    duplicates: DuplicatesArg,
+
+    /// When unzipping from a URI, stop with an error once this many bytes have been downloaded,
+    /// keeping the files extracted so far. Doesn't apply if the server doesn't support ranges.
+    #[arg(long, value_name = "BYTES")]
+    download_budget: Option<u64>,
+
+    /// When unzipping from a URI, read this many bytes from the server at a time. By default,
+    /// 1MiB.
+    #[arg(long, value_name = "BYTES")]
+    readahead_block_size: Option<usize>,
+
+    /// When unzipping from a URI, open a new stream rather than reading on to skip more than this
+    /// many bytes. By default, 2MiB.
+    #[arg(long, value_name = "BYTES")]
+    skip_ahead_threshold: Option<u64>,
+
+    /// When unzipping from a URI, ask the server for at most this many bytes in each range
+    /// request. By default, each request is for the rest of the zip file.
+    #[arg(long, value_name = "BYTES")]
+    range_chunk_size: Option<usize>,
+
+    /// Refuse to extract zip files with more than this many entries, including directories.
+    #[arg(long, value_name = "COUNT")]
+    max_entries: Option<usize>,
+
+    /// Leave holes in extracted files where they contain blocks of zeros, rather than writing the
+    /// zeros, to save disk space for sparse files such as disk images.
+    #[arg(long)]
+    sparse: bool,
+
+    /// Treat only entries whose names end with a slash as directories. By default, an empty entry
+    /// whose mode says it's a directory is one too.
+    #[arg(long)]
+    strict_directory_detection: bool,
+
+    /// Create everything relative to a descriptor for the output directory, never following
+    /// symbolic links, so that nothing can be written outside it even if links are created while
+    /// unzipping. Linux only; elsewhere the usual checks are made.
+    #[arg(long)]
+    anchored_output: bool,
+
+    /// Treat backslashes in entry names as part of file names rather than as path separators, as
+    /// some tools on Windows write them. Has no effect on Windows.
+    #[arg(long)]
+    no_normalize_separators: bool,
+
+    /// What to do with entries whose names are absolute paths, such as `/etc/hosts`.
+    #[arg(long, value_name = "POLICY", default_value = "reject")]
+    absolute_paths: AbsolutePathsArg,
+
+    /// Extract entries whose names are absolute paths within this directory, such as `/` to
+    /// extract them where their names say. Only for trusted zip files.
+    #[arg(long, value_name = "DIR", conflicts_with = "absolute_paths")]
+    absolute_root: Option<PathBuf>,
+
+    /// Rename entries whose names Windows doesn't allow, such as `a:b?.txt` or `CON`, by
+    /// percent-encoding the offending characters, rather than failing. Windows only.
+    #[arg(long)]
+    sanitize_invalid_names: bool,
+
+    /// Give extracted files and directories the modification times stored in the zip file,
+    /// preferring precise times from extra fields, otherwise taken to be UTC. Unix only.
+    #[arg(long)]
+    preserve_mtimes: bool,
+
+    /// With --preserve-mtimes, don't set directories' modification times again after
+    /// extracting the files in them, which otherwise updates them.
+    #[arg(long, requires = "preserve_mtimes")]
+    no_restore_directory_mtimes: bool,
+
+    /// Give extracted files and directories the user and group IDs recorded in the zip file's
+    /// Unix extra fields. Usually requires root. Unix only.
+    #[arg(long)]
+    preserve_ownership: bool,
+
+    /// Extract only the entry with exactly this name, as listed by list-file or list-uri. May be
+    /// given several times. Fails if any aren't in the zip file, unless --ignore-missing-entries
+    /// is given.
+    #[arg(long = "only-entry", value_name = "NAME")]
+    only_entries: Vec<String>,
+
+    /// Warn about names given with --only-entry which aren't in the zip file, rather than failing.
+    #[arg(long)]
+    ignore_missing_entries: bool,
+
+    /// Don't extract files with this extension, such as `map`, whatever its case. May be given
+    /// several times.
+    #[arg(long = "exclude-extension", value_name = "EXTENSION")]
+    exclude_extensions: Vec<String>,
+
     /// Optionally, a list of files to unzip from the zip file. Omit
     /// to unzip all of them. This can include wildcards.
     #[arg(value_name = "FILES")]
     filenames_to_unzip: Vec<String>,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LineEndingArg {
+    /// Unix-style line endings
+    Lf,
+    /// Windows-style line endings
+    Crlf,
+    /// Whichever is usual for this platform
+    Native,
+}
+
+impl From<LineEndingArg> for LineEnding {
+    fn from(arg: LineEndingArg) -> Self {
+        match arg {
+            LineEndingArg::Lf => LineEnding::Lf,
+            LineEndingArg::Crlf => LineEnding::CrLf,
+            LineEndingArg::Native => LineEnding::Native,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum DurabilityArg {
+    /// Don't fsync anything
+    None,
+    /// Fsync each file
+    FsyncFiles,
+    /// Fsync each file and its directory
+    FsyncFilesAndDir,
+}
+
+impl From<DurabilityArg> for Durability {
+    fn from(arg: DurabilityArg) -> Self {
+        match arg {
+            DurabilityArg::None => Durability::None,
+            DurabilityArg::FsyncFiles => Durability::FsyncFiles,
+            DurabilityArg::FsyncFilesAndDir => Durability::FsyncFilesAndDir,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PartialFilesArg {
+    /// Leave them where they are
+    Keep,
+    /// Delete them
+    Delete,
+    /// Add .partial to their names
+    Rename,
+}
+
+impl From<PartialFilesArg> for PartialFiles {
+    fn from(arg: PartialFilesArg) -> Self {
+        match arg {
+            PartialFilesArg::Keep => PartialFiles::Keep,
+            PartialFilesArg::Delete => PartialFiles::Delete,
+            PartialFilesArg::Rename => PartialFiles::Rename,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum UnknownSizesArg {
+    /// Assume they're the same as the compressed size
+    Estimate,
+    /// Report progress for each once it's all extracted
+    Stream,
+}
+
+impl From<UnknownSizesArg> for UnknownSizes {
+    fn from(arg: UnknownSizesArg) -> Self {
+        match arg {
+            UnknownSizesArg::Estimate => UnknownSizes::Estimate,
+            UnknownSizesArg::Stream => UnknownSizes::Stream,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OutputLockArg {
+    /// Wait for any other run to finish
+    Wait,
+    /// Fail if another run holds the lock
+    FailFast,
+}
+
+impl From<OutputLockArg> for OutputLock {
+    fn from(arg: OutputLockArg) -> Self {
+        match arg {
+            OutputLockArg::Wait => OutputLock::Wait,
+            OutputLockArg::FailFast => OutputLock::FailFast,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum AbsolutePathsArg {
+    /// Fail to extract them
+    Reject,
+    /// Extract them within the output directory, without the leading slash
+    Strip,
+}
+
+impl From<AbsolutePathsArg> for AbsolutePathPolicy {
+    fn from(arg: AbsolutePathsArg) -> Self {
+        match arg {
+            AbsolutePathsArg::Reject => AbsolutePathPolicy::Reject,
+            AbsolutePathsArg::Strip => AbsolutePathPolicy::StripLeadingSlash,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum HashAlgorithmArg {
+    /// SHA-256
+    Sha256,
+    /// SHA-384
+    Sha384,
+    /// SHA-512
+    Sha512,
+}
+
+impl From<HashAlgorithmArg> for HashAlgorithm {
+    fn from(arg: HashAlgorithmArg) -> Self {
+        match arg {
+            HashAlgorithmArg::Sha256 => HashAlgorithm::Sha256,
+            HashAlgorithmArg::Sha384 => HashAlgorithm::Sha384,
+            HashAlgorithmArg::Sha512 => HashAlgorithm::Sha512,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum DuplicatesArg {
+    /// Extract them all, each replacing the last
+    All,
+    /// Extract only the one with the latest modification time
+    NewestByTime,
+}
+
+impl From<DuplicatesArg> for DuplicatePolicy {
+    fn from(arg: DuplicatesArg) -> Self {
+        match arg {
+            DuplicatesArg::All => DuplicatePolicy::All,
+            DuplicatesArg::NewestByTime => DuplicatePolicy::NewestByTime,
+        }
+    }
+}
+
 #[derive(Args, Debug)]
 struct FileArgs {
     /// Zip file to unzip
@@ -112,6 +542,27 @@ struct UriArgs {
     /// problem, but may make transfers much less efficient by requiring multiple HTTP streams.
     #[arg(long, value_name = "BYTES")]
     readahead_limit: Option<usize>,
+
+    /// Directory for the temporary copy of the zip file made if the server doesn't support
+    /// range requests. By default, the system's temporary directory is used.
+    #[arg(long, value_name = "DIRECTORY")]
+    temp_dir: Option<PathBuf>,
+
+    /// Another URI from which the same zip file can be fetched. May be given several times.
+    /// Requests are spread across all the URIs, and if one fails, the others are tried.
+    #[arg(long = "mirror", value_name = "URI")]
+    mirrors: Vec<String>,
+
+    /// A PEM file of certificate authorities to trust for HTTPS, in addition to the system's, for
+    /// instance for a proxy with its own CA. May be given several times.
+    #[arg(long = "ca-cert", value_name = "FILE")]
+    ca_certs: Vec<PathBuf>,
+
+    /// A cookie to send with every request to the zip file's URI, such as a session cookie from
+    /// logging in to a download portal. It isn't sent to mirrors on other origins. May be given
+    /// several times.
+    #[arg(long = "cookie", value_name = "NAME=VALUE", value_parser = parse_cookie)]
+    cookies: Vec<(String, String)>,
 }
 
 fn main() -> Result<(), RipunzipErrors> {
@@ -146,31 +597,139 @@ fn unzip(
     unzip_args: UnzipArgs,
     is_silent: bool,
 ) -> Result<(), RipunzipErrors> {
-    let filename_filter: Option<Box<dyn FilenameFilter + Sync>> =
-        if unzip_args.filenames_to_unzip.is_empty() {
-            None
-        } else {
-            Some(Box::new(FileListFilter(RwLock::new(
-                unzip_args
-                    .filenames_to_unzip
-                    .iter()
-                    .map(|s| WildMatch::new(s))
-                    .collect(),
-            ))))
-        };
-    let progress_reporter: Box<dyn UnzipProgressReporter + Sync> = if is_silent {
+    // Creating the thread pool would be wasted on a single thread.
+    if !unzip_args.single_threaded {
+        WorkerThreadOptions {
+            nice: unzip_args.nice,
+        }
+        .build_global()?;
+    }
+    let filename_filter = file_list_filter(&unzip_args.filenames_to_unzip);
+    let text_conversion = unzip_args.line_endings.map(|line_ending| LineEndingMode {
+        line_ending: line_ending.into(),
+        filename_filter: file_list_filter(&unzip_args.text_files),
+    });
+    let progress_reporter: Box<dyn UnzipProgressReporter + Send + Sync> = if unzip_args.dry_run {
+        Box::new(DryRunDisplayer)
+    } else if is_silent || unzip_args.stdout {
         Box::new(NullProgressReporter)
     } else {
         Box::new(ProgressDisplayer::new())
     };
+    let verify_manifest = unzip_args
+        .verify_manifest
+        .as_deref()
+        .map(read_checksum_manifest)
+        .transpose()?;
     let options = UnzipOptions {
         output_directory: unzip_args.output_directory,
         password: unzip_args.password,
         single_threaded: unzip_args.single_threaded,
         filename_filter,
         progress_reporter,
+        verify_before_extract: unzip_args.verify_before_extract,
+        decompressor_registry: None,
+        strict: unzip_args.strict,
+        text_conversion,
+        transform: None,
+        overwrite_policy: OverwritePolicy::Overwrite,
+        max_path_depth: unzip_args.max_path_depth,
+        dedup: unzip_args.dedup,
+        durability: unzip_args.durability.into(),
+        follow_existing_symlinks: unzip_args.follow_existing_symlinks,
+        require_empty_output: unzip_args.require_empty_output,
+        empty_output_ignores_hidden: unzip_args.ignore_hidden_files,
+        output_lock: unzip_args
+            .lock_output
+            .map_or(OutputLock::None, OutputLock::from),
+        create_output_directory: !unzip_args.no_create_output_directory,
+        overlay_base: unzip_args.overlay_base,
+        io_buffer_size: unzip_args.io_buffer_size,
+        sequential: unzip_args.sequential,
+        restrict_to_device: unzip_args.restrict_to_device,
+        dry_run: unzip_args.dry_run,
+        explain: unzip_args.explain,
+        progress_coalescing: None,
+        progress_file: unzip_args.progress_file,
+        emit_index: unzip_args.emit_index,
+        verify_manifest,
+        verify_signature: unzip_args.verify_signature.zip(unzip_args.public_key).map(
+            |(signature, public_key)| SignatureConfig {
+                public_key,
+                signature: SignatureSource::Path(signature),
+            },
+        ),
+        // A URI engine already has any --temp-dir.
+        temp_dir: None,
+        hash_algorithm: unzip_args.hash_algorithm.into(),
+        prefix_map: unzip_args.prefix_map,
+        flatten_single_root: unzip_args.flatten_single_root,
+        max_entry_size: unzip_args.max_entry_size,
+        max_total_size: unzip_args.max_total_size,
+        duplicate_policy: unzip_args.duplicates.into(),
+        permission_template: None,
+        file_factory: None,
+        max_entries: unzip_args.max_entries,
+        sparse: unzip_args.sparse,
+        strict_directory_detection: unzip_args.strict_directory_detection,
+        anchored_output: unzip_args.anchored_output,
+        max_open_files: unzip_args.max_open_files,
+        completion_marker: unzip_args.completion_marker,
+        shard_into: unzip_args.shard_into,
+        partial_files: unzip_args.partial_files.into(),
+        normalize_separators: !unzip_args.no_normalize_separators,
+        absolute_paths: match unzip_args.absolute_root {
+            Some(root) => AbsolutePathPolicy::AllowWithRoot(root),
+            None => unzip_args.absolute_paths.into(),
+        },
+        sanitize_invalid_names: unzip_args.sanitize_invalid_names,
+        preserve_mtimes: unzip_args.preserve_mtimes,
+        restore_directory_mtimes: !unzip_args.no_restore_directory_mtimes,
+        preserve_ownership: unzip_args.preserve_ownership,
+        unknown_sizes: unzip_args.unknown_sizes.into(),
+        download_budget: unzip_args.download_budget,
+        readahead_block_size: unzip_args.readahead_block_size,
+        skip_ahead_threshold: unzip_args.skip_ahead_threshold,
+        range_chunk_size: unzip_args.range_chunk_size,
+        only_entries: (!unzip_args.only_entries.is_empty())
+            .then(|| unzip_args.only_entries.into_iter().collect()),
+        missing_entries: if unzip_args.ignore_missing_entries {
+            MissingEntries::Warn
+        } else {
+            MissingEntries::Error
+        },
+        exclude_extensions: unzip_args.exclude_extensions,
     };
-    engine.unzip(options)
+    if unzip_args.stdout {
+        let result = engine.unzip_to_writer(options, &mut BufWriter::new(std::io::stdout().lock()));
+        if let Err(RipunzipErrors::NotSingleFile { count }) = result {
+            eprintln!("--stdout needs exactly one file, but {count} would be extracted. Name the file you want to extract.");
+        }
+        return result.map(|_| ());
+    }
+    let outcome = engine.unzip(options)?;
+    for (name, path) in &outcome.renamed {
+        log::info!("Extracted {name} to {}", path.display());
+    }
+    for name in &outcome.not_in_manifest {
+        log::info!("Not verified, since not in the checksum manifest: {name}");
+    }
+    for path in &outcome.missing_from_zip {
+        log::info!(
+            "In the checksum manifest but not the zip file: {}",
+            path.display()
+        );
+    }
+    if let Some(cpu_time) = outcome.cpu_time {
+        log::debug!("Used {cpu_time:?} of CPU time in {:?}", outcome.wall_time);
+    }
+    for (name, timings) in &outcome.slowest_entries {
+        log::debug!(
+            "Among the slowest files: {name}, taking {:?}",
+            timings.total()
+        );
+    }
+    Ok(())
 }
 
 fn construct_file_engine(file_args: FileArgs) -> Result<UnzipEngine, RipunzipErrors> {
@@ -178,9 +737,20 @@ fn construct_file_engine(file_args: FileArgs) -> Result<UnzipEngine, RipunzipErr
 }
 
 fn construct_uri_engine(uri_args: UriArgs) -> Result<UnzipEngine, RipunzipErrors> {
-    UnzipEngine::for_uri(
+    let mut root_certificates = RootCertificates::default();
+    for ca_cert in &uri_args.ca_certs {
+        root_certificates.add_pem_file(ca_cert)?;
+    }
+    let uri_options = UriOptions {
+        mirrors: uri_args.mirrors,
+        root_certificates,
+        cookies: Cookies::from_pairs(&uri_args.uri, &uri_args.cookies)?,
+        temp_dir: uri_args.temp_dir,
+    };
+    UnzipEngine::for_uri_with_options(
         &uri_args.uri,
         uri_args.readahead_limit,
+        &uri_options,
         report_on_insufficient_readahead_size,
     )
 }
@@ -193,6 +763,74 @@ fn list(engine: UnzipEngine) -> Result<(), RipunzipErrors> {
     Ok(())
 }
 
+/// Returns a filter matching any of the given wildcard patterns, or `None`
+/// if there are none.
+fn file_list_filter(patterns: &[String]) -> Option<Box<dyn FilenameFilter + Send + Sync>> {
+    if patterns.is_empty() {
+        None
+    } else {
+        Some(Box::new(FileListFilter(RwLock::new(
+            patterns.iter().map(|s| WildMatch::new(s)).collect(),
+        ))))
+    }
+}
+
+/// Parses a `--map` argument of the form `PREFIX=DIRECTORY`.
+fn parse_prefix_mapping(arg: &str) -> Result<(String, PathBuf), String> {
+    let (prefix, directory) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected PREFIX=DIRECTORY, not {arg}"))?;
+    Ok((prefix.to_string(), PathBuf::from(directory)))
+}
+
+/// Reads a `--verify-manifest` file: on each line, a hex checksum, a space,
+/// a space or `*`, and a path.
+fn read_checksum_manifest(path: &Path) -> Result<HashMap<PathBuf, String>, RipunzipErrors> {
+    let context = |e| RipunzipErrors::IOErrorWithContext {
+        context: format!("Failed to read checksum manifest {}", path.display()),
+        source: e,
+    };
+    let text = std::fs::read_to_string(path).map_err(context)?;
+    parse_checksum_manifest(&text)
+        .map_err(|message| context(std::io::Error::new(ErrorKind::InvalidData, message)))
+}
+
+fn parse_checksum_manifest(text: &str) -> Result<HashMap<PathBuf, String>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let (checksum, path) = line
+                .split_once(' ')
+                .filter(|(checksum, _)| checksum.bytes().all(|b| b.is_ascii_hexdigit()))
+                .ok_or_else(|| format!("line {} is malformed", i + 1))?;
+            let path = path.strip_prefix([' ', '*']).unwrap_or(path);
+            let path = path.strip_prefix("./").unwrap_or(path);
+            Ok((PathBuf::from(path), checksum.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// Parses a `--public-key` argument of 64 hex digits.
+fn parse_public_key(arg: &str) -> Result<[u8; 32], String> {
+    if arg.len() != 64 || !arg.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("expected 64 hex digits, not {arg}"));
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&arg[i * 2..i * 2 + 2], 16).unwrap();
+    }
+    Ok(key)
+}
+
+/// Parses a `--cookie` argument of the form `NAME=VALUE`.
+fn parse_cookie(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, not {arg}"))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
 struct FileListFilter(RwLock<Vec<WildMatch>>);
 
 impl FilenameFilter for FileListFilter {
@@ -215,8 +853,22 @@ impl ProgressDisplayer {
 }
 
 impl UnzipProgressReporter for ProgressDisplayer {
-    fn extraction_starting(&self, display_name: &str) {
-        self.0.set_message(format!("Extracting {display_name}"))
+    fn extraction_starting(&self, entry: &EntryInfo) {
+        self.0
+            .set_message(format!("Extracting {}", entry.display_name))
+    }
+
+    fn central_directory_read_starting(&self) {
+        self.0.set_style(
+            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {msg}").unwrap(),
+        );
+        self.0.set_message("Reading the central directory...");
+        self.0.enable_steady_tick(Duration::from_millis(100));
+    }
+
+    fn central_directory_read_finished(&self, _entry_count: usize) {
+        self.0.disable_steady_tick();
+        self.0.set_message("");
     }
 
     fn total_bytes_expected(&self, expected: u64) {
@@ -230,16 +882,67 @@ impl UnzipProgressReporter for ProgressDisplayer {
     fn bytes_extracted(&self, count: u64) {
         self.0.inc(count)
     }
+
+    fn network_event(&self, event: &NetworkEvent) {
+        match event {
+            NetworkEvent::Retry { .. } | NetworkEvent::Failover { .. } => log::warn!("{event}"),
+            _ => log::debug!("{event}"),
+        }
+    }
+
+    fn plan_ready(&self, plan: &ExtractionPlan) {
+        self.0.suspend(|| print_plan(plan))
+    }
+
+    fn all_finished(&self, outcome: &UnzipOutcome) {
+        self.0.finish_with_message(format!(
+            "Extracted {} files ({} bytes)",
+            outcome.files_extracted, outcome.bytes_written
+        ))
+    }
+}
+
+/// Prints the plan for `--explain` to stdout.
+fn print_plan(plan: &ExtractionPlan) {
+    if let Err(e) = plan.write_json(&mut std::io::stdout().lock()) {
+        log::warn!("Failed to print the plan: {e}");
+    }
+}
+
+struct DryRunDisplayer;
+
+impl UnzipProgressReporter for DryRunDisplayer {
+    fn plan_ready(&self, plan: &ExtractionPlan) {
+        print_plan(plan)
+    }
+
+    fn extraction_planned(&self, name: &str, action: PlannedAction) {
+        match action {
+            PlannedAction::Create => println!("create {name}"),
+            PlannedAction::Overwrite => println!("overwrite {name}"),
+        }
+    }
+
+    fn entry_skipped(&self, name: &str, reason: SkipReason) {
+        match reason {
+            SkipReason::AlreadyExists => println!("skip {name} (already exists)"),
+            SkipReason::Unsupported => println!("skip {name} (unsafe path)"),
+            SkipReason::TooLarge => println!("skip {name} (too large)"),
+            SkipReason::Superseded => println!("skip {name} (superseded)"),
+            SkipReason::UpToDate => println!("skip {name} (unchanged from overlay base)"),
+            _ => {}
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::RwLock;
+    use std::{path::PathBuf, sync::RwLock};
 
     use ripunzip::FilenameFilter;
     use wildmatch::WildMatch;
 
-    use crate::FileListFilter;
+    use crate::{parse_checksum_manifest, parse_public_key, FileListFilter};
 
     #[test]
     fn test_filelist_filter() {
@@ -256,4 +959,26 @@ mod tests {
         assert!(filter.should_unzip("moose"));
         assert!(!filter.should_unzip("mouuuuuse"));
     }
+
+    #[test]
+    fn test_parse_checksum_manifest() {
+        let manifest =
+            parse_checksum_manifest("0123abcd  a.txt\n\nABCDEF01 *./dir/b c.bin\n").unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest[&PathBuf::from("a.txt")], "0123abcd");
+        assert_eq!(manifest[&PathBuf::from("dir/b c.bin")], "abcdef01");
+        assert_eq!(
+            parse_checksum_manifest("a.txt\n").unwrap_err(),
+            "line 1 is malformed"
+        );
+    }
+
+    #[test]
+    fn test_parse_public_key() {
+        let key = parse_public_key(&format!("00ff{}", "1A".repeat(30))).unwrap();
+        assert_eq!(key[..3], [0x00, 0xff, 0x1a]);
+        assert!(parse_public_key("00ff").is_err());
+        assert!(parse_public_key(&"+1".repeat(32)).is_err());
+        assert!(parse_public_key(&"zz".repeat(32)).is_err());
+    }
 }
@@ -0,0 +1,123 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::{Path, PathBuf};
+
+use super::{
+    dry_run::{is_drive, strip_drive},
+    UnzipOptions,
+};
+
+/// What to do with entries whose names are absolute paths, such as
+/// `/etc/hosts` or `C:\Windows\win.ini`, which some tools store
+/// deliberately, for example in system images. On Windows, names relative
+/// to a drive, such as `C:foo`, count too, unless
+/// [`UnzipOptions::sanitize_invalid_names`] is going to encode the colon.
+/// However they're handled, `..` segments still can't take an entry above
+/// the directory it's extracted within.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AbsolutePathPolicy {
+    /// Fail to extract them, as entries with other unsafe names do.
+    #[default]
+    Reject,
+    /// Drop the leading slash or drive letter, extracting them within the
+    /// output directory like any other entry, so `/etc/hosts` is extracted
+    /// to `etc/hosts` there.
+    StripLeadingSlash,
+    /// Extract them within this directory rather than the output
+    /// directory, so with a root of `/` they're extracted where their
+    /// names say. This is only appropriate for trusted zip files, when
+    /// privileged. Neither `prefix_map` nor `overlay_base` apply to them,
+    /// and `restrict_to_device` still refers to the output directory.
+    AllowWithRoot(PathBuf),
+}
+
+impl AbsolutePathPolicy {
+    /// Whether the entry with the given name mustn't be extracted.
+    pub(super) fn rejects(&self, name: &str, options: &UnzipOptions) -> bool {
+        *self == Self::Reject && is_absolute(name, options)
+    }
+
+    /// The directory to extract the entry with the given name within, if
+    /// not the usual one.
+    pub(super) fn root(&self, name: &str, options: &UnzipOptions) -> Option<&Path> {
+        match self {
+            Self::AllowWithRoot(root) if is_absolute(name, options) => Some(root),
+            _ => None,
+        }
+    }
+}
+
+fn is_absolute(name: &str, options: &UnzipOptions) -> bool {
+    let drive_relative = cfg!(windows) && !options.sanitize_invalid_names;
+    has_root(name, options.normalize_separators, drive_relative)
+}
+
+/// Whether `name` starts with a separator, which includes UNC names such
+/// as `\\\\server\\share`, or a drive, as decided by [`is_drive`], or, if
+/// `drive_relative`, with a drive letter followed by anything else.
+fn has_root(name: &str, normalize_separators: bool, drive_relative: bool) -> bool {
+    let separators: &[char] = if normalize_separators || cfg!(windows) {
+        &['/', '\\']
+    } else {
+        &['/']
+    };
+    name.starts_with(separators)
+        || name.split(separators).next().is_some_and(|first| {
+            is_drive(first) || (drive_relative && strip_drive(first).is_some())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{has_root, AbsolutePathPolicy};
+    use crate::UnzipOptions;
+
+    #[test]
+    fn test_has_root() {
+        assert!(has_root("/etc/hosts", false, false));
+        assert!(has_root("C:\\Windows", true, false));
+        assert!(has_root("C:/Windows", false, false));
+        assert!(has_root("\\etc\\hosts", true, false));
+        assert!(has_root("\\\\server\\share\\a.txt", true, false));
+        assert!(has_root("//server/share/a.txt", false, false));
+        assert!(!has_root("etc/hosts", false, false));
+        assert!(!has_root("./etc/hosts", false, false));
+        assert!(!has_root("etc/a:b", false, true));
+        assert!(!has_root("C:foo\\bar", true, false));
+        assert!(has_root("C:foo\\bar", true, true));
+        assert!(has_root("c:foo", false, true));
+        assert!(!has_root("ab:foo", false, true));
+        assert_eq!(has_root("notes:/a.txt", false, false), cfg!(windows));
+    }
+
+    #[test]
+    fn test_absolute_path_policy() {
+        let options = UnzipOptions::default();
+        assert!(AbsolutePathPolicy::Reject.rejects("/etc/hosts", &options));
+        assert!(!AbsolutePathPolicy::Reject.rejects("etc/hosts", &options));
+        assert_eq!(
+            AbsolutePathPolicy::Reject.rejects("C:foo", &options),
+            cfg!(windows)
+        );
+        assert!(!AbsolutePathPolicy::StripLeadingSlash.rejects("/etc/hosts", &options));
+        assert_eq!(
+            AbsolutePathPolicy::StripLeadingSlash.root("/etc/hosts", &options),
+            None
+        );
+        let allow = AbsolutePathPolicy::AllowWithRoot(PathBuf::from("/sysroot"));
+        assert!(!allow.rejects("/etc/hosts", &options));
+        assert_eq!(
+            allow.root("/etc/hosts", &options),
+            Some(Path::new("/sysroot"))
+        );
+        assert_eq!(allow.root("etc/hosts", &options), None);
+    }
+}
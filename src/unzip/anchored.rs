@@ -0,0 +1,172 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fs::File, path::Path};
+
+/// An output directory in which we create directories and files using
+/// system calls relative to a file descriptor for it, such as `openat` and
+/// `mkdirat`, never following symbolic links. Unlike checking paths before
+/// writing to them, this leaves no window in which a directory can be
+/// swapped for a link out of the output directory. Only supported on
+/// Linux; elsewhere [`Anchor::open`] returns `None`.
+pub(crate) struct Anchor {
+    #[cfg(target_os = "linux")]
+    root: std::os::fd::OwnedFd,
+    #[cfg(not(target_os = "linux"))]
+    never: std::convert::Infallible,
+}
+
+#[cfg(target_os = "linux")]
+impl Anchor {
+    /// Open the existing directory `root`, or the current directory.
+    pub(crate) fn open(root: Option<&Path>) -> std::io::Result<Option<Self>> {
+        let root = root.unwrap_or(Path::new("."));
+        Ok(Some(Self {
+            root: rustix::fs::openat(rustix::fs::CWD, root, dir_flags(), Mode::empty())?,
+        }))
+    }
+
+    /// Create the directory at `relative` and any of its parents which
+    /// don't exist.
+    pub(crate) fn create_dir_all(&self, relative: &Path) -> std::io::Result<()> {
+        self.open_dir(relative, true).map(|_| ())
+    }
+
+    /// Create or truncate a file to extract into, creating its parent
+    /// directories if need be, with the given mode less the umask.
+    pub(crate) fn create_file(
+        &self,
+        relative: &Path,
+        unix_mode: Option<u32>,
+    ) -> std::io::Result<File> {
+        let (parent, name) = split(relative)?;
+        let mode = Mode::from_raw_mode(unix_mode.unwrap_or(0o666) & 0o7777);
+        let flags = OFlags::WRONLY | OFlags::CREATE | OFlags::TRUNC | OFlags::NOFOLLOW;
+        let fd = rustix::fs::openat(self.open_dir(parent, true)?, name, flags, mode)?;
+        Ok(File::from(fd))
+    }
+
+    /// Set the mode of the file or directory at `relative`.
+    pub(crate) fn set_mode(&self, relative: &Path, mode: u32) -> std::io::Result<()> {
+        let fd = match split(relative) {
+            Ok((parent, name)) => rustix::fs::openat(
+                self.open_dir(parent, false)?,
+                name,
+                OFlags::RDONLY | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+                Mode::empty(),
+            )?,
+            Err(_) => self.open_dir(relative, false)?,
+        };
+        Ok(rustix::fs::fchmod(fd, Mode::from_raw_mode(mode & 0o7777))?)
+    }
+
+    /// Open the directory at `relative`, one component at a time, optionally
+    /// creating any which don't exist.
+    fn open_dir(&self, relative: &Path, create: bool) -> std::io::Result<std::os::fd::OwnedFd> {
+        use std::path::Component;
+        let mut dir = rustix::fs::openat(&self.root, ".", dir_flags(), Mode::empty())?;
+        for component in relative.components() {
+            let Component::Normal(name) = component else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("{} isn't a plain relative path", relative.display()),
+                ));
+            };
+            let open = |dir: &std::os::fd::OwnedFd| {
+                rustix::fs::openat(dir, name, dir_flags() | OFlags::NOFOLLOW, Mode::empty())
+            };
+            dir = match open(&dir) {
+                Err(rustix::io::Errno::NOENT) if create => {
+                    match rustix::fs::mkdirat(&dir, name, Mode::from_raw_mode(0o777)) {
+                        Ok(()) | Err(rustix::io::Errno::EXIST) => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                    open(&dir)?
+                }
+                result => result?,
+            };
+        }
+        Ok(dir)
+    }
+}
+
+#[cfg(target_os = "linux")]
+use rustix::fs::{Mode, OFlags};
+
+#[cfg(target_os = "linux")]
+fn dir_flags() -> OFlags {
+    OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC
+}
+
+/// Split a path to a file into its parent directory and file name.
+#[cfg(target_os = "linux")]
+fn split(relative: &Path) -> std::io::Result<(&Path, &std::ffi::OsStr)> {
+    match (relative.parent(), relative.file_name()) {
+        (Some(parent), Some(name)) => Ok((parent, name)),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} has no file name", relative.display()),
+        )),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Anchor {
+    pub(crate) fn open(_root: Option<&Path>) -> std::io::Result<Option<Self>> {
+        Ok(None)
+    }
+
+    pub(crate) fn create_dir_all(&self, _relative: &Path) -> std::io::Result<()> {
+        match self.never {}
+    }
+
+    pub(crate) fn create_file(
+        &self,
+        _relative: &Path,
+        _unix_mode: Option<u32>,
+    ) -> std::io::Result<File> {
+        match self.never {}
+    }
+
+    pub(crate) fn set_mode(&self, _relative: &Path, _mode: u32) -> std::io::Result<()> {
+        match self.never {}
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::Anchor;
+
+    #[test]
+    fn test_anchor_refuses_symlinks() {
+        let td = tempdir().unwrap();
+        let root = td.path().join("root");
+        let elsewhere = td.path().join("elsewhere");
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::create_dir(&elsewhere).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, root.join("link")).unwrap();
+        let anchor = Anchor::open(Some(&root)).unwrap().unwrap();
+
+        anchor.create_dir_all(Path::new("a/b/c")).unwrap();
+        assert!(root.join("a/b/c").is_dir());
+        anchor.create_file(Path::new("a/b/file"), None).unwrap();
+        assert!(root.join("a/b/file").is_file());
+        anchor.set_mode(Path::new("a/b/file"), 0o600).unwrap();
+
+        assert!(anchor.create_dir_all(Path::new("link/d")).is_err());
+        assert!(anchor.create_file(Path::new("link/file"), None).is_err());
+        assert!(anchor.create_file(Path::new("link"), None).is_err());
+        assert!(anchor.set_mode(Path::new("link"), 0o777).is_err());
+        assert!(anchor.create_dir_all(Path::new("../escape")).is_err());
+        assert_eq!(std::fs::read_dir(&elsewhere).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Sender},
+        Arc, Condvar, Mutex, PoisonError,
+    },
+    thread::JoinHandle,
+};
+
+use crate::RipunzipErrors;
+
+use super::{FilenameFilter, UnzipOutcome};
+
+/// Which entries to extract first, and whom to tell when they have been,
+/// for [`UnzipEngine::unzip_prioritized`](super::UnzipEngine::unzip_prioritized).
+pub(crate) struct Prioritization {
+    filter: Box<dyn FilenameFilter + Send + Sync>,
+    sender: Mutex<Option<Sender<UnzipOutcome>>>,
+    control: UnzipControl,
+}
+
+impl Prioritization {
+    pub(crate) fn is_prioritized(&self, name: &str) -> bool {
+        self.filter.should_unzip(name)
+    }
+
+    /// Let the caller go, now that the prioritized entries are extracted.
+    pub(crate) fn finished(&self, outcome: UnzipOutcome) {
+        if let Some(sender) = self
+            .sender
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            let _ = sender.send(outcome);
+        }
+    }
+
+    /// Wait while the rest of the unzip is paused, then return an error if
+    /// it's been cancelled.
+    pub(crate) fn check_not_cancelled(&self) -> Result<(), RipunzipErrors> {
+        self.control.wait_while_paused();
+        if self.control.0.cancelled.load(Ordering::Relaxed) {
+            return Err(RipunzipErrors::Aborted);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Control {
+    cancelled: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+/// A handle with which to pause, resume or cancel the rest of a
+/// [`BackgroundUnzip`] from any thread, including while another waits for
+/// it to finish.
+///
+/// Pausing and cancelling are cooperative: each entry checks before it
+/// starts, so entries already being extracted are finished, including
+/// reading the rest of their data, and nothing new is started. How soon
+/// the unzip actually stops is therefore bounded by how long the entries
+/// in progress, one per thread, take to finish.
+#[derive(Clone, Default)]
+pub struct UnzipControl(Arc<Control>);
+
+impl UnzipControl {
+    /// Stop starting to extract entries until [`Self::resume`] is called.
+    pub fn pause(&self) {
+        *self.0.paused.lock().unwrap_or_else(PoisonError::into_inner) = true;
+    }
+
+    /// Carry on extracting entries after [`Self::pause`].
+    pub fn resume(&self) {
+        *self.0.paused.lock().unwrap_or_else(PoisonError::into_inner) = false;
+        self.0.resumed.notify_all();
+    }
+
+    /// Whether the unzip has been paused and not resumed since.
+    pub fn is_paused(&self) -> bool {
+        *self.0.paused.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stop extracting any more entries, once those in progress are
+    /// finished, even if paused. [`BackgroundUnzip::wait`] will then fail
+    /// with [`RipunzipErrors::Aborted`] unless everything had been
+    /// extracted.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+        // Taking the lock means a thread about to wait will see this.
+        let _paused = self.0.paused.lock().unwrap_or_else(PoisonError::into_inner);
+        self.0.resumed.notify_all();
+    }
+
+    fn wait_while_paused(&self) {
+        let paused = self.0.paused.lock().unwrap_or_else(PoisonError::into_inner);
+        let _paused = self
+            .0
+            .resumed
+            .wait_while(paused, |paused| {
+                *paused && !self.0.cancelled.load(Ordering::Relaxed)
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+enum Remaining {
+    Running(JoinHandle<Result<UnzipOutcome, RipunzipErrors>>),
+    /// The unzip finished without getting as far as the prioritized
+    /// entries, such as for a dry run.
+    Finished(Box<UnzipOutcome>),
+}
+
+/// An unzip continuing on a thread of its own, returned by
+/// [`UnzipEngine::unzip_prioritized`](super::UnzipEngine::unzip_prioritized)
+/// once the prioritized entries have been extracted. Dropping it leaves
+/// the unzip running, like dropping a [`JoinHandle`], but it will be
+/// stopped if the process exits.
+pub struct BackgroundUnzip {
+    prioritized: UnzipOutcome,
+    control: UnzipControl,
+    remaining: Remaining,
+}
+
+impl BackgroundUnzip {
+    /// Run `unzip` on a thread of its own, returning once it's extracted
+    /// the entries matching `filter`, or failed.
+    pub(crate) fn spawn(
+        filter: Box<dyn FilenameFilter + Send + Sync>,
+        unzip: impl FnOnce(&Prioritization) -> Result<UnzipOutcome, RipunzipErrors> + Send + 'static,
+    ) -> Result<Self, RipunzipErrors> {
+        let (sender, receiver) = channel();
+        let control = UnzipControl::default();
+        let prioritization = Prioritization {
+            filter,
+            sender: Mutex::new(Some(sender)),
+            control: control.clone(),
+        };
+        let thread = std::thread::spawn(move || unzip(&prioritization));
+        match receiver.recv() {
+            Ok(prioritized) => Ok(Self {
+                prioritized,
+                control,
+                remaining: Remaining::Running(thread),
+            }),
+            // It's finished, one way or another.
+            Err(_) => {
+                let outcome = join(thread)?;
+                Ok(Self {
+                    prioritized: outcome.clone(),
+                    control,
+                    remaining: Remaining::Finished(Box::new(outcome)),
+                })
+            }
+        }
+    }
+
+    /// What was extracted before this was returned.
+    pub fn prioritized(&self) -> &UnzipOutcome {
+        &self.prioritized
+    }
+
+    /// Whether the rest of the unzip has finished, so that [`Self::wait`]
+    /// won't block.
+    pub fn is_finished(&self) -> bool {
+        match &self.remaining {
+            Remaining::Running(thread) => thread.is_finished(),
+            Remaining::Finished(_) => true,
+        }
+    }
+
+    /// Stop extracting any more entries, once those in progress are
+    /// finished. [`Self::wait`] will then fail with
+    /// [`RipunzipErrors::Aborted`] unless everything had been extracted.
+    pub fn cancel(&self) {
+        self.control.cancel();
+    }
+
+    /// A handle with which to pause, resume or cancel the rest of the
+    /// unzip.
+    pub fn control(&self) -> UnzipControl {
+        self.control.clone()
+    }
+
+    /// Wait for the rest of the unzip to finish, returning the outcome of
+    /// the whole of it, including the prioritized entries.
+    pub fn wait(self) -> Result<UnzipOutcome, RipunzipErrors> {
+        match self.remaining {
+            Remaining::Running(thread) => join(thread),
+            Remaining::Finished(outcome) => Ok(*outcome),
+        }
+    }
+}
+
+fn join(
+    thread: JoinHandle<Result<UnzipOutcome, RipunzipErrors>>,
+) -> Result<UnzipOutcome, RipunzipErrors> {
+    thread
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
@@ -0,0 +1,185 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fs::File,
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::RipunzipErrors;
+
+use super::{UnzipEngine, UnzipOptions, UnzipOutcome};
+
+/// Present in a cache directory once extraction into it has completed.
+const COMPLETE_SENTINEL: &str = ".ripunzip-complete";
+/// Prefix of the directories into which we extract before moving them
+/// into place.
+const IN_PROGRESS_PREFIX: &str = ".ripunzip-in-progress-";
+
+/// A directory of extracted zip files, each in a subdirectory named after a
+/// key such as the hash of the zip file, so that each zip file need only be
+/// extracted once.
+pub struct ExtractionCache {
+    root: PathBuf,
+}
+
+/// The result of [`ExtractionCache::unzip`].
+#[derive(Debug)]
+pub struct CachedExtraction {
+    /// Where the zip file's contents are.
+    pub directory: PathBuf,
+    /// What was extracted, or `None` if an earlier extraction was reused.
+    pub outcome: Option<UnzipOutcome>,
+}
+
+impl ExtractionCache {
+    /// Use the given directory, which is created if necessary, as a cache.
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// The SHA-256 hash of a file in lowercase hex, suitable for use as a
+    /// key.
+    pub fn key_for_file(path: &Path) -> Result<String, RipunzipErrors> {
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect())
+    }
+
+    /// Extract the zip file into the subdirectory named `key`, unless a
+    /// previous extraction there completed. The output directory in
+    /// `options` is ignored. Files are extracted into a temporary directory
+    /// which is renamed into place once complete, so an interrupted
+    /// extraction is never mistaken for a complete one, and if two
+    /// extractions race then one of them is used.
+    pub fn unzip(
+        &self,
+        engine: UnzipEngine,
+        key: &str,
+        mut options: UnzipOptions,
+    ) -> Result<CachedExtraction, RipunzipErrors> {
+        let mut components = Path::new(key).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) || key.starts_with('.')
+        {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{key} isn't usable as a cache key"),
+            )
+            .into());
+        }
+        let directory = self.root.join(key);
+        if is_complete(&directory) {
+            log::info!("Reusing {}", directory.display());
+            return Ok(CachedExtraction {
+                directory,
+                outcome: None,
+            });
+        }
+        std::fs::create_dir_all(&self.root)?;
+        let in_progress = tempfile::Builder::new()
+            .prefix(IN_PROGRESS_PREFIX)
+            .tempdir_in(&self.root)?;
+        options.output_directory = Some(in_progress.path().to_path_buf());
+        let outcome = engine.unzip(options)?;
+        File::create(in_progress.path().join(COMPLETE_SENTINEL))?.sync_all()?;
+        // Anything already there is left over from an interrupted
+        // extraction, unless another extraction has just finished.
+        if directory.exists() && !is_complete(&directory) {
+            std::fs::remove_dir_all(&directory)?;
+        }
+        match std::fs::rename(in_progress.path(), &directory) {
+            Ok(()) => {
+                // Now that it's been moved, there's nothing to delete.
+                let _ = in_progress.keep();
+            }
+            Err(_) if is_complete(&directory) => {
+                log::info!("{} was extracted concurrently", directory.display())
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(CachedExtraction {
+            directory,
+            outcome: Some(outcome),
+        })
+    }
+}
+
+fn is_complete(directory: &Path) -> bool {
+    directory.join(COMPLETE_SENTINEL).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use tempfile::tempdir;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::ExtractionCache;
+    use crate::{UnzipEngine, UnzipOptions};
+
+    #[test]
+    fn test_extraction_cache() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file("a.txt", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"Contents of A\n").unwrap();
+        zip.finish().unwrap();
+
+        let cache = ExtractionCache::new(td.path().join("cache"));
+        let key = ExtractionCache::key_for_file(&zf).unwrap();
+        assert_eq!(key.len(), 64);
+        // Left over from an interrupted extraction.
+        std::fs::create_dir_all(td.path().join("cache").join(&key)).unwrap();
+        let unzip = || {
+            let engine = UnzipEngine::for_file(zf.clone()).unwrap();
+            cache.unzip(engine, &key, UnzipOptions::default()).unwrap()
+        };
+        let first = unzip();
+        assert_eq!(first.outcome.unwrap().files_extracted, 1);
+        assert_eq!(
+            std::fs::read_to_string(first.directory.join("a.txt")).unwrap(),
+            "Contents of A\n"
+        );
+        let second = unzip();
+        assert!(second.outcome.is_none());
+        assert_eq!(second.directory, first.directory);
+        assert_eq!(
+            std::fs::read_dir(td.path().join("cache")).unwrap().count(),
+            1
+        );
+
+        let engine = UnzipEngine::for_file(zf).unwrap();
+        assert!(cache
+            .unzip(engine, "../escape", UnzipOptions::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_key_for_file() {
+        let td = tempdir().unwrap();
+        let path = td.path().join("abc");
+        std::fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            ExtractionCache::key_for_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+
+use zip::{DateTime, ZipArchive};
+
+use super::local_header::{u16_at, u32_at, u64_at};
+
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const CENTRAL_HEADER_LEN: usize = 46;
+const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+const ZIP64_MARKER: u32 = 0xFFFFFFFF;
+const SYSTEM_DOS: u16 = 0;
+const SYSTEM_UNIX: u16 = 3;
+const DOS_DIRECTORY: u32 = 0x10;
+const DOS_READ_ONLY: u32 = 0x01;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+/// What the central directory says about a file, other than its name.
+pub(crate) struct CentralDirectoryEntry {
+    pub(crate) local_header_offset: u64,
+    pub(crate) compression_method: u16,
+    pub(crate) crc32: u32,
+    pub(crate) compressed_size: u64,
+    pub(crate) size: u64,
+    pub(crate) last_modified: Option<DateTime>,
+    pub(crate) unix_mode: Option<u32>,
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// Read the central directory to find where the local header of each entry
+/// is, in the order they're listed. The zip library only reveals this by
+/// reading each local header, which would mean seeking all over the file.
+pub(crate) fn local_header_offsets<R: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<R>,
+) -> std::io::Result<Vec<u64>> {
+    Ok(read_central_directory(zip_archive)?
+        .into_iter()
+        .map(|entry| entry.local_header_offset)
+        .collect())
+}
+
+/// Read the entries of the central directory, in the order they're listed.
+/// Unlike the zip library, this doesn't touch the local headers, so reads
+/// nothing but the central directory. Local header offsets are adjusted for
+/// any data before the start of the zip, as in a self-extracting zip file,
+/// so are offsets within the underlying reader.
+pub(crate) fn read_central_directory<R: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<R>,
+) -> std::io::Result<Vec<CentralDirectoryEntry>> {
+    Ok(read_central_directory_and_end(zip_archive)?.0)
+}
+
+/// Read the entries of the central directory as [`read_central_directory`]
+/// does, also returning the offset just after the last, where the central
+/// directory should end.
+pub(crate) fn read_central_directory_and_end<R: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<R>,
+) -> std::io::Result<(Vec<CentralDirectoryEntry>, u64)> {
+    let count = zip_archive.len();
+    let mut reader = zip_archive.clone().into_inner();
+    reader.seek(SeekFrom::Start(zip_archive.central_directory_start()))?;
+    let mut reader = BufReader::new(reader);
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut fixed = [0u8; CENTRAL_HEADER_LEN];
+        reader.read_exact(&mut fixed)?;
+        if u32_at(&fixed, 0) != CENTRAL_HEADER_SIGNATURE {
+            return Err(invalid("invalid central directory header signature"));
+        }
+        let name_len = u16_at(&fixed, 28) as usize;
+        let extra_len = u16_at(&fixed, 30) as usize;
+        let comment_len = u16_at(&fixed, 32) as usize;
+        let mut variable = vec![0u8; name_len + extra_len + comment_len];
+        reader.read_exact(&mut variable)?;
+        // The zip64 extra field contains whichever of the uncompressed
+        // size, compressed size and offset didn't fit, in that order.
+        let extra = &variable[name_len..name_len + extra_len];
+        // Like the zip library, put up with a missing uncompressed size,
+        // which is then unknown; see `UnknownSizes`. That's a size which
+        // says it's in the zip64 extra field, when there's no such field.
+        // If there is, but it's too short, the zip file is corrupt.
+        let size_missing = u32_at(&fixed, 24) == ZIP64_MARKER && zip64_extra_field(extra).is_none();
+        let mut zip64_pos = 0;
+        let mut field = |pos| match u32_at(&fixed, pos) {
+            ZIP64_MARKER => {
+                let value = zip64_field(extra, zip64_pos)
+                    .ok_or_else(|| invalid("missing zip64 extra field"));
+                zip64_pos += 8;
+                value
+            }
+            value => Ok(value as u64),
+        };
+        let size = if size_missing {
+            ZIP64_MARKER as u64
+        } else {
+            field(24)?
+        };
+        let compressed_size = field(20)?;
+        let local_header_offset = field(42)?
+            .checked_add(zip_archive.offset())
+            .ok_or_else(|| invalid("local header offset out of range"))?;
+        entries.push(CentralDirectoryEntry {
+            local_header_offset,
+            compression_method: u16_at(&fixed, 10),
+            crc32: u32_at(&fixed, 16),
+            compressed_size,
+            size,
+            last_modified: DateTime::try_from_msdos(u16_at(&fixed, 14), u16_at(&fixed, 12)).ok(),
+            unix_mode: unix_mode(u16_at(&fixed, 4) >> 8, u32_at(&fixed, 38)),
+        });
+    }
+    let end = reader.stream_position()?;
+    Ok((entries, end))
+}
+
+/// Interpret a file's external attributes as the zip library does.
+fn unix_mode(system: u16, external_attributes: u32) -> Option<u32> {
+    if external_attributes == 0 {
+        return None;
+    }
+    let unix_mode = external_attributes >> 16;
+    if unix_mode != 0 || system == SYSTEM_UNIX {
+        return Some(unix_mode);
+    }
+    if system != SYSTEM_DOS {
+        return None;
+    }
+    let mut mode = if external_attributes & DOS_DIRECTORY != 0 {
+        S_IFDIR | 0o775
+    } else {
+        S_IFREG | 0o664
+    };
+    if external_attributes & DOS_READ_ONLY != 0 {
+        mode &= !0o222;
+    }
+    Some(mode)
+}
+
+/// The 64-bit value at `pos` within the zip64 extra field, if present.
+fn zip64_field(extra: &[u8], pos: usize) -> Option<u64> {
+    zip64_extra_field(extra)
+        .filter(|data| data.len() >= pos + 8)
+        .map(|data| u64_at(data, pos))
+}
+
+/// The data of the zip64 extra field among `extra`, if there is one.
+fn zip64_extra_field(mut extra: &[u8]) -> Option<&[u8]> {
+    while extra.len() >= 4 {
+        let id = u16_at(extra, 0);
+        let len = u16_at(extra, 2) as usize;
+        let data = &extra[4..(4 + len).min(extra.len())];
+        if id == ZIP64_EXTRA_FIELD_ID {
+            return Some(data);
+        }
+        extra = &extra[(4 + len).min(extra.len())..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{
+        write::{FileOptions, SimpleFileOptions},
+        ZipArchive, ZipWriter,
+    };
+
+    use super::read_central_directory;
+
+    #[test]
+    fn test_read_central_directory() {
+        // Data before the zip shouldn't throw the offsets out.
+        for prefix in [&b""[..], b"Self-extractor stub"] {
+            check_read_central_directory(prefix);
+        }
+    }
+
+    fn check_read_central_directory(prefix: &[u8]) {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, large_file) in [("a.txt", false), ("b.txt", true), ("c.txt", false)] {
+            let options: SimpleFileOptions = FileOptions::default()
+                .large_file(large_file)
+                .unix_permissions(0o640);
+            zip.start_file(name, options).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        let mut data = prefix.to_vec();
+        data.extend(zip.finish().unwrap().into_inner());
+        let mut zip_archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(zip_archive.offset(), prefix.len() as u64);
+        let entries = read_central_directory(&zip_archive).unwrap();
+        assert_eq!(entries.len(), 3);
+        for (i, entry) in entries.iter().enumerate() {
+            let file = zip_archive.by_index_raw(i).unwrap();
+            assert_eq!(entry.local_header_offset, file.header_start());
+            assert_eq!(entry.compressed_size, file.compressed_size());
+            assert_eq!(entry.size, file.size());
+            assert_eq!(entry.last_modified, file.last_modified());
+            assert_eq!(entry.unix_mode, file.unix_mode());
+        }
+    }
+}
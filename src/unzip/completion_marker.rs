@@ -0,0 +1,126 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use zip::ZipArchive;
+
+use super::determine_stream_len;
+
+/// Identifies a zip file well enough to tell whether it's the one which was
+/// extracted before: its length, and the CRC of everything from the start
+/// of its central directory to the end, which lists the name, size and CRC
+/// of every entry. Only the end of the zip file is read, so for a URI
+/// nothing more is fetched than when it was opened.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct ArchiveIdentity {
+    size: u64,
+    central_directory_crc32: u32,
+}
+
+impl ArchiveIdentity {
+    pub(crate) fn of<T: Read + Seek + Clone>(zip_archive: &ZipArchive<T>) -> std::io::Result<Self> {
+        let mut reader = zip_archive.clone().into_inner();
+        let size = determine_stream_len(&mut reader)?;
+        reader.seek(SeekFrom::Start(zip_archive.central_directory_start()))?;
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let read = reader.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+        }
+        Ok(Self {
+            size,
+            central_directory_crc32: hasher.finalize(),
+        })
+    }
+
+    fn marker_contents(&self) -> String {
+        format!(
+            "ripunzip completion marker\nsize {}\ncentral directory crc32 {:08x}\n",
+            self.size, self.central_directory_crc32
+        )
+    }
+
+    /// Whether the marker at `path` says that this zip file was extracted.
+    pub(crate) fn is_marked(&self, path: &Path) -> std::io::Result<bool> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(contents == self.marker_contents()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) if e.kind() == ErrorKind::InvalidData => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write a marker at `path` saying that this zip file was extracted,
+    /// replacing any existing one atomically.
+    pub(crate) fn write_marker(&self, path: &Path) -> std::io::Result<()> {
+        let parent = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(parent)?;
+        let mut temp = tempfile::NamedTempFile::new_in(parent)?;
+        temp.write_all(self.marker_contents().as_bytes())?;
+        temp.as_file().sync_all()?;
+        temp.persist(path)?;
+        Ok(())
+    }
+}
+
+/// Remove the marker at `path`, if any.
+pub(crate) fn remove_marker(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tempfile::tempdir;
+    use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+    use super::{remove_marker, ArchiveIdentity};
+
+    fn identity(contents: &[u8]) -> ArchiveIdentity {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("a.txt", SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut zip, contents).unwrap();
+        let zip = zip.finish().unwrap();
+        ArchiveIdentity::of(&ZipArchive::new(zip).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_marker() {
+        let td = tempdir().unwrap();
+        let marker = td.path().join("out/.extracted");
+        let a = identity(b"A");
+        let b = identity(b"B");
+        assert_eq!(a, identity(b"A"));
+        assert_ne!(a, b);
+        assert!(!a.is_marked(&marker).unwrap());
+        a.write_marker(&marker).unwrap();
+        assert!(a.is_marked(&marker).unwrap());
+        assert!(!b.is_marked(&marker).unwrap());
+        b.write_marker(&marker).unwrap();
+        assert!(b.is_marked(&marker).unwrap());
+        remove_marker(&marker).unwrap();
+        remove_marker(&marker).unwrap();
+        assert!(!b.is_marked(&marker).unwrap());
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+/// How well the files in a zip file are compressed, overall and for each
+/// compression method. Directories aren't counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Totals across all files.
+    pub overall: MethodStats,
+    /// Totals for the files using each compression method, keyed by the
+    /// method number stored in the zip file, such as 8 for deflate.
+    pub by_method: BTreeMap<u16, MethodStats>,
+}
+
+impl CompressionStats {
+    pub(crate) fn add(&mut self, compression_method: u16, size: u64, compressed_size: u64) {
+        for stats in [
+            &mut self.overall,
+            self.by_method.entry(compression_method).or_default(),
+        ] {
+            stats.files += 1;
+            stats.size += size;
+            stats.compressed_size += compressed_size;
+        }
+    }
+}
+
+/// Totals for a set of files in a zip file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MethodStats {
+    /// The number of files.
+    pub files: usize,
+    /// Their total uncompressed size.
+    pub size: u64,
+    /// Their total compressed size.
+    pub compressed_size: u64,
+}
+
+impl MethodStats {
+    /// The uncompressed size divided by the compressed size, so that higher
+    /// is better, or `None` if there's no compressed data. This is the
+    /// inverse of [`EntryInfo::compression_ratio`](crate::EntryInfo::compression_ratio).
+    pub fn ratio(&self) -> Option<f64> {
+        (self.compressed_size != 0).then(|| self.size as f64 / self.compressed_size as f64)
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use zip::ZipArchive;
+
+use super::{
+    central_directory::read_central_directory_and_end,
+    local_header::{u16_at, u32_at, u64_at},
+};
+use crate::RipunzipErrors;
+
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const EOCD_LEN: u64 = 22;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const ZIP64_LOCATOR_LEN: u64 = 20;
+
+/// Check that the records at the end of the zip file fit together exactly
+/// and end at `len`, as reported by the server: the central directory, then
+/// any zip64 end of central directory record and locator, then the end of
+/// central directory record and its comment. Also check that every local
+/// header comes before the central directory. The zip library is more
+/// forgiving, but for a URI a mismatch means the upload was truncated or
+/// the server is misconfigured, and the ranges we'd request would be
+/// garbage. Only the end of the zip file is read, which has already been
+/// fetched to open it.
+pub(crate) fn check_consistent_length<R: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<R>,
+    len: u64,
+) -> Result<(), RipunzipErrors> {
+    let inconsistent = |reason: String| RipunzipErrors::InconsistentArchive { len, reason };
+    let central_directory_start = zip_archive.central_directory_start();
+    let (entries, mut pos) =
+        read_central_directory_and_end(zip_archive).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof | ErrorKind::InvalidData => {
+                inconsistent(format!("unable to read the central directory: {e}"))
+            }
+            _ => e.into(),
+        })?;
+    if let Some(offset) = entries
+        .iter()
+        .map(|entry| entry.local_header_offset)
+        .find(|offset| *offset >= central_directory_start)
+    {
+        return Err(inconsistent(format!(
+            "a local header at {offset} is after the central directory at {central_directory_start}"
+        )));
+    }
+    let mut reader = zip_archive.clone().into_inner();
+    let mut record_at = |pos: u64, record_len: u64| -> Result<Vec<u8>, RipunzipErrors> {
+        if pos.checked_add(record_len).map_or(true, |end| end > len) {
+            return Err(inconsistent(format!(
+                "expected a {record_len} byte record at {pos}, past the end"
+            )));
+        }
+        let mut record = vec![0u8; record_len as usize];
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut record)?;
+        Ok(record)
+    };
+    if u32_at(&record_at(pos, 4)?, 0) == ZIP64_EOCD_SIGNATURE {
+        // The record's length doesn't count its signature or the length.
+        let record_len = u64_at(&record_at(pos, 12)?, 4);
+        pos = pos
+            .checked_add(12 + record_len)
+            .ok_or_else(|| inconsistent("zip64 record length out of range".to_string()))?;
+        if u32_at(&record_at(pos, 4)?, 0) != ZIP64_LOCATOR_SIGNATURE {
+            return Err(inconsistent(format!(
+                "no zip64 end of central directory locator at {pos}"
+            )));
+        }
+        pos += ZIP64_LOCATOR_LEN;
+    }
+    let eocd = record_at(pos, EOCD_LEN)?;
+    if u32_at(&eocd, 0) != EOCD_SIGNATURE {
+        return Err(inconsistent(format!(
+            "no end of central directory record at {pos}, where the central directory ends"
+        )));
+    }
+    let end = pos + EOCD_LEN + u16_at(&eocd, 20) as u64;
+    if end != len {
+        return Err(inconsistent(format!(
+            "the end of central directory record ends at {end}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+    use super::check_consistent_length;
+    use crate::RipunzipErrors;
+
+    fn sample_zip(comment: &str) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.set_comment(comment).unwrap();
+        zip.start_file("a.txt", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"Contents of A\n").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn check(data: Vec<u8>, len: u64) -> Result<(), RipunzipErrors> {
+        let zip_archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        check_consistent_length(&zip_archive, len)
+    }
+
+    #[test]
+    fn test_check_consistent_length() {
+        for comment in ["", "A comment"] {
+            let data = sample_zip(comment);
+            let len = data.len() as u64;
+            check(data.clone(), len).unwrap();
+            // As if the server claimed the wrong length.
+            assert!(matches!(
+                check(data.clone(), len + 1),
+                Err(RipunzipErrors::InconsistentArchive { .. })
+            ));
+            assert!(matches!(
+                check(data.clone(), len - 1),
+                Err(RipunzipErrors::InconsistentArchive { .. })
+            ));
+            // A self-extractor stub is fine.
+            let mut prefixed = b"Self-extractor stub".to_vec();
+            prefixed.extend(&data);
+            let len = prefixed.len() as u64;
+            check(prefixed, len).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_check_consistent_length_zip64() {
+        // Insert zip64 records, which the zip library only writes for huge
+        // zip files, before the end of central directory record.
+        let data = sample_zip("");
+        let (rest, eocd) = data.split_at(data.len() - 22);
+        let entries = u16::from_le_bytes([eocd[10], eocd[11]]) as u64;
+        let central_directory_size = u32::from_le_bytes(eocd[12..16].try_into().unwrap()) as u64;
+        let central_directory_offset = u32::from_le_bytes(eocd[16..20].try_into().unwrap()) as u64;
+        let mut zip64 = rest.to_vec();
+        let zip64_eocd_offset = zip64.len() as u64;
+        zip64.extend(b"PK\x06\x06");
+        zip64.extend(44u64.to_le_bytes());
+        zip64.extend([45, 0, 45, 0]);
+        zip64.extend([0; 8]);
+        for value in [
+            entries,
+            entries,
+            central_directory_size,
+            central_directory_offset,
+        ] {
+            zip64.extend(value.to_le_bytes());
+        }
+        zip64.extend(b"PK\x06\x07");
+        zip64.extend(0u32.to_le_bytes());
+        zip64.extend(zip64_eocd_offset.to_le_bytes());
+        zip64.extend(1u32.to_le_bytes());
+        zip64.extend(eocd);
+        let len = zip64.len() as u64;
+        check(zip64.clone(), len).unwrap();
+        assert!(matches!(
+            check(zip64, len + 1),
+            Err(RipunzipErrors::InconsistentArchive { .. })
+        ));
+    }
+}
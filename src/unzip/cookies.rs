@@ -0,0 +1,141 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{io::ErrorKind, sync::Arc};
+
+use reqwest::{
+    blocking::ClientBuilder,
+    cookie::{CookieStore, Jar},
+    header::HeaderValue,
+    Url,
+};
+
+use crate::RipunzipErrors;
+
+/// Cookies to send when fetching URIs over HTTP(S), such as a session
+/// cookie from logging in to a download portal. They're sent with every
+/// request to the servers they're for, including each of the range
+/// requests made while reading ahead, for as long as the
+/// [`UnzipEngine`](super::UnzipEngine) lasts.
+#[derive(Clone, Default)]
+pub struct Cookies {
+    jar: Option<Arc<Jar>>,
+    pairs: Option<Arc<OriginCookies>>,
+}
+
+impl Cookies {
+    /// Send the cookies in `jar` to the servers they're for. Any cookies
+    /// the servers set are added to it.
+    pub fn from_jar(jar: Arc<Jar>) -> Self {
+        Self {
+            jar: Some(jar),
+            pairs: None,
+        }
+    }
+
+    /// Send these cookies, given as names and values, with every request to
+    /// the origin (scheme, host and port) of `uri`, but not to any other,
+    /// such as a mirror elsewhere.
+    pub fn from_pairs(uri: &str, pairs: &[(String, String)]) -> Result<Self, RipunzipErrors> {
+        if let Some((name, _)) = pairs.iter().find(|(name, value)| {
+            name.is_empty() || name.contains(['=', ';', ' ']) || value.contains(';')
+        }) {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid cookie {name:?}"),
+            )
+            .into());
+        }
+        if pairs.is_empty() {
+            return Ok(Self::default());
+        }
+        let origin = Url::parse(uri).map_err(|e| {
+            std::io::Error::new(ErrorKind::InvalidInput, format!("invalid URI {uri:?}: {e}"))
+        })?;
+        let header = pairs
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let header = HeaderValue::from_str(&header)
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidInput, e))?;
+        Ok(Self {
+            jar: None,
+            pairs: Some(Arc::new(OriginCookies { origin, header })),
+        })
+    }
+
+    /// Make clients built by `builder` send these cookies.
+    pub(crate) fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(jar) = &self.jar {
+            builder = builder.cookie_provider(jar.clone());
+        }
+        if let Some(pairs) = &self.pairs {
+            builder = builder.cookie_provider(pairs.clone());
+        }
+        builder
+    }
+}
+
+/// Fixed cookies for a single origin.
+struct OriginCookies {
+    origin: Url,
+    /// Already formatted as a `Cookie` header.
+    header: HeaderValue,
+}
+
+impl CookieStore for OriginCookies {
+    fn set_cookies(&self, _cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, _url: &Url) {}
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        (url.origin() == self.origin.origin()).then(|| self.header.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{cookie::CookieStore, Url};
+
+    use super::Cookies;
+
+    #[test]
+    fn test_from_pairs() {
+        let pairs = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let uri = "https://example.com/downloads/foo.zip";
+        let cookies = Cookies::from_pairs(uri, &pairs(&[("session", "abc"), ("lang", "en")]))
+            .unwrap()
+            .pairs
+            .unwrap();
+        let sent = |uri: &str| cookies.cookies(&Url::parse(uri).unwrap());
+        assert_eq!(sent(uri).unwrap(), "session=abc; lang=en");
+        assert_eq!(
+            sent("https://example.com/elsewhere").unwrap(),
+            "session=abc; lang=en"
+        );
+        for other_origin in [
+            "https://mirror.example.com/downloads/foo.zip",
+            "http://example.com/downloads/foo.zip",
+            "https://example.com:8443/downloads/foo.zip",
+        ] {
+            assert!(sent(other_origin).is_none(), "{other_origin}");
+        }
+        assert!(Cookies::from_pairs(uri, &[]).unwrap().pairs.is_none());
+        assert!(Cookies::from_pairs("not a uri", &pairs(&[("a", "b")])).is_err());
+        for invalid in [("", "abc"), ("a=b", "c"), ("a", "b;c"), ("a", "b\nc")] {
+            assert!(
+                Cookies::from_pairs(uri, &pairs(&[invalid])).is_err(),
+                "{invalid:?}"
+            );
+        }
+    }
+}
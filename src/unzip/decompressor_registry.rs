@@ -0,0 +1,70 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{collections::HashMap, io::Read};
+
+use super::integrity::IntegrityFailure;
+
+/// A function which wraps a stream of compressed bytes and returns a stream
+/// of decompressed bytes.
+pub type Decompressor = Box<dyn for<'r> Fn(Box<dyn Read + 'r>) -> Box<dyn Read + 'r> + Send + Sync>;
+
+/// A set of decompressors for compression methods which the zip library
+/// doesn't itself understand, keyed by the compression method number stored
+/// in the zip file. These are consulted only once the zip library has
+/// rejected a file as using an unsupported method, so they can't be used
+/// to override built-in methods. They are not used for encrypted files.
+#[derive(Default)]
+pub struct DecompressorRegistry(HashMap<u16, Decompressor>);
+
+impl DecompressorRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a decompressor for the given compression method number,
+    /// replacing any previously registered for the same method.
+    pub fn register(&mut self, compression_method: u16, decompressor: Decompressor) {
+        self.0.insert(compression_method, decompressor);
+    }
+
+    pub(crate) fn get(&self, compression_method: u16) -> Option<&Decompressor> {
+        self.0.get(&compression_method)
+    }
+}
+
+/// Checks the CRC of data read through it, returning an error at the end
+/// of the stream if it doesn't match. The zip library does this for
+/// methods it knows about; we have to do it ourselves for custom ones.
+pub(crate) struct Crc32CheckingReader<R: Read> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    expected: u32,
+}
+
+impl<R: Read> Crc32CheckingReader<R> {
+    pub(crate) fn new(inner: R, expected: u32) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            expected,
+        }
+    }
+}
+
+impl<R: Read> Read for Crc32CheckingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        if count == 0 && !buf.is_empty() && self.hasher.clone().finalize() != self.expected {
+            return Err(IntegrityFailure::CrcMismatch.into_io_error());
+        }
+        self.hasher.update(&buf[..count]);
+        Ok(count)
+    }
+}
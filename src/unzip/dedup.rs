@@ -0,0 +1,190 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Cursor, ErrorKind, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use zip::read::ZipFile;
+
+use super::mtimes::FileTime;
+
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// What we know about a file's contents before decompressing it. Files
+/// with the same key are probably, but not certainly, identical. The mode,
+/// times and owner we'll give the file are included since hard links share
+/// permissions, times and owners.
+#[derive(Hash, Eq, PartialEq)]
+pub(crate) struct DedupKey {
+    crc32: u32,
+    size: u64,
+    unix_mode: Option<u32>,
+    /// The modification and access times, if we're setting them.
+    times: Option<(FileTime, Option<FileTime>)>,
+    /// The user and group IDs, if we're setting them.
+    owner: Option<(Option<u32>, Option<u32>)>,
+}
+
+impl DedupKey {
+    pub(crate) fn new<R: Read>(
+        file: &ZipFile<R>,
+        unix_mode: Option<u32>,
+        times: Option<(FileTime, Option<FileTime>)>,
+        owner: Option<(Option<u32>, Option<u32>)>,
+    ) -> Self {
+        Self {
+            crc32: file.crc32(),
+            size: file.size(),
+            unix_mode,
+            times,
+            owner,
+        }
+    }
+}
+
+/// Remembers the files written during an unzip, so that later files with
+/// the same contents can be hard linked to them rather than written again.
+#[derive(Default)]
+pub(crate) struct Deduplicator(Mutex<HashMap<DedupKey, PathBuf>>);
+
+impl Deduplicator {
+    /// A previously written file which probably has the same contents as
+    /// the file with the given key, which we're about to write to
+    /// `out_path`.
+    pub(crate) fn candidate(&self, key: &DedupKey, out_path: &Path) -> Option<PathBuf> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|candidate| candidate.as_path() != out_path)
+            .cloned()
+    }
+
+    /// Record that we've written the file with the given key to `out_path`.
+    pub(crate) fn record(&self, key: DedupKey, out_path: &Path) {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| out_path.to_path_buf());
+    }
+}
+
+/// The result of comparing the data to be extracted against an existing
+/// file.
+pub(crate) enum Comparison<'r> {
+    Identical,
+    /// The data differs; this reader yields all of it, including the parts
+    /// already consumed by the comparison.
+    Differs(Box<dyn Read + 'r>),
+}
+
+/// Compare all the data from `reader` with the contents of `candidate`.
+pub(crate) fn compare<'r>(
+    mut reader: Box<dyn Read + 'r>,
+    candidate: &Path,
+) -> std::io::Result<Comparison<'r>> {
+    let mut candidate_file = File::open(candidate)?;
+    let mut matched = 0u64;
+    let mut buf = vec![0u8; CHUNK_LEN];
+    let mut candidate_buf = vec![0u8; CHUNK_LEN];
+    loop {
+        let len = read_fully(&mut reader, &mut buf)?;
+        let candidate_len = read_fully(&mut candidate_file, &mut candidate_buf[..len.max(1)])?;
+        if len == 0 && candidate_len == 0 {
+            return Ok(Comparison::Identical);
+        }
+        if buf[..len] != candidate_buf[..candidate_len] {
+            buf.truncate(len);
+            let prefix = File::open(candidate)?.take(matched);
+            return Ok(Comparison::Differs(Box::new(
+                prefix.chain(Cursor::new(buf)).chain(reader),
+            )));
+        }
+        matched += len as u64;
+    }
+}
+
+/// Fill as much of `buf` as possible, stopping early only at the end of the
+/// data.
+fn read_fully<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+/// Make `out_path` a hard link to `existing`, or a copy of it if the
+/// filesystem doesn't support hard links.
+pub(crate) fn link_or_copy(existing: &Path, out_path: &Path) -> std::io::Result<()> {
+    remove_if_present(out_path)?;
+    if let Err(e) = std::fs::hard_link(existing, out_path) {
+        log::debug!(
+            "Unable to hard link {} to {}, copying instead: {e}",
+            out_path.display(),
+            existing.display()
+        );
+        std::fs::copy(existing, out_path)?;
+    }
+    Ok(())
+}
+
+/// Remove any existing file at `path`, so that writing there can't alter a
+/// file it's hard linked to.
+pub(crate) fn remove_if_present(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use tempfile::tempdir;
+
+    use super::{compare, Comparison, CHUNK_LEN};
+
+    fn compare_with(data: &[u8], existing: &[u8]) -> Option<Vec<u8>> {
+        let td = tempdir().unwrap();
+        let candidate = td.path().join("candidate");
+        std::fs::write(&candidate, existing).unwrap();
+        match compare(Box::new(data), &candidate).unwrap() {
+            Comparison::Identical => None,
+            Comparison::Differs(mut reader) => {
+                let mut all = Vec::new();
+                reader.read_to_end(&mut all).unwrap();
+                Some(all)
+            }
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let long: Vec<u8> = (0..CHUNK_LEN * 3).map(|i| i as u8).collect();
+        assert!(compare_with(&long, &long).is_none());
+        assert!(compare_with(b"", b"").is_none());
+        let mut different = long.clone();
+        different[CHUNK_LEN * 2 + 5] = 0xff;
+        assert_eq!(compare_with(&different, &long).unwrap(), different);
+        assert_eq!(compare_with(&long[..100], &long).unwrap(), &long[..100]);
+        assert_eq!(compare_with(&long, &long[..100]).unwrap(), long);
+    }
+}
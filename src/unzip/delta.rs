@@ -0,0 +1,171 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{FilenameFilter, UnzipOutcome, ZipEntryMetadata};
+
+/// How a zip file differs from an earlier version of it, as found and
+/// extracted by [`UnzipEngine::extract_delta`](super::UnzipEngine::extract_delta).
+/// Each list holds entry names, in the order of the zip file they're
+/// from.
+#[derive(Debug, Default, Clone)]
+pub struct ExtractionDelta {
+    /// Entries which weren't in the earlier zip file.
+    pub added: Vec<String>,
+    /// Entries whose size or CRC differs from the earlier zip file, or,
+    /// for those without a CRC, whose size, compressed size or
+    /// modification time does.
+    pub changed: Vec<String>,
+    /// Entries in the earlier zip file which aren't in this one. Nothing is
+    /// deleted; removing them is up to the caller.
+    pub removed: Vec<String>,
+    /// What was extracted, which is only the added and changed entries.
+    pub outcome: UnzipOutcome,
+}
+
+impl ExtractionDelta {
+    /// Compare the entries of two zip files by name, size and CRC, or
+    /// modification time where there's no CRC, considering only those
+    /// which `filter` accepts.
+    pub(crate) fn compare(
+        previous: &[ZipEntryMetadata],
+        current: &[ZipEntryMetadata],
+        filter: Option<&dyn FilenameFilter>,
+    ) -> Self {
+        let included = |entry: &&ZipEntryMetadata| {
+            filter.map_or(true, |filter| filter.should_unzip(&entry.name))
+        };
+        let previous_by_name = previous
+            .iter()
+            .filter(included)
+            .map(|entry| (entry.name.as_str(), entry))
+            .collect::<HashMap<_, _>>();
+        let mut delta = Self::default();
+        for entry in current.iter().filter(included) {
+            match previous_by_name.get(entry.name.as_str()) {
+                None => delta.added.push(entry.name.clone()),
+                Some(old) if !unchanged(old, entry) => delta.changed.push(entry.name.clone()),
+                Some(_) => {}
+            }
+        }
+        let current_names = current
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<HashSet<_>>();
+        delta.removed = previous
+            .iter()
+            .filter(included)
+            .filter(|entry| !current_names.contains(entry.name.as_str()))
+            .map(|entry| entry.name.clone())
+            .collect();
+        delta
+    }
+}
+
+/// Whether `current` seems to be the same as `previous`. Entries encrypted
+/// in WinZip's AE-2 format have no CRC, so for those the compressed size
+/// and modification time have to do instead, and if either doesn't record
+/// a modification time, they're taken to have changed.
+fn unchanged(previous: &ZipEntryMetadata, current: &ZipEntryMetadata) -> bool {
+    if previous.size != current.size {
+        return false;
+    }
+    // Every empty file has a CRC of zero, as it happens.
+    if current.size == 0 || (previous.crc32 != 0 && current.crc32 != 0) {
+        return previous.crc32 == current.crc32;
+    }
+    previous.compressed_size == current.compressed_size
+        && previous.last_modified.is_some()
+        && previous.last_modified == current.last_modified
+}
+
+/// Accepts only the entries which have been added or changed.
+pub(crate) struct DeltaFilter(pub(crate) HashSet<String>);
+
+impl FilenameFilter for DeltaFilter {
+    fn should_unzip(&self, filename: &str) -> bool {
+        self.0.contains(filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zip::DateTime;
+
+    use super::ExtractionDelta;
+    use crate::{FilenameFilter, ZipEntryMetadata};
+
+    fn entry(name: &str, crc32: u32) -> ZipEntryMetadata {
+        ZipEntryMetadata {
+            name: name.to_string(),
+            size: 1,
+            compressed_size: 1,
+            crc32,
+            last_modified: None,
+            unix_mode: None,
+        }
+    }
+
+    struct NotIgnored;
+
+    impl FilenameFilter for NotIgnored {
+        fn should_unzip(&self, filename: &str) -> bool {
+            !filename.starts_with("ignored")
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let previous = [
+            entry("same", 1),
+            entry("gone", 2),
+            entry("changed", 3),
+            entry("ignored-gone", 4),
+        ];
+        let current = [
+            entry("new", 5),
+            entry("changed", 6),
+            entry("same", 1),
+            entry("ignored-new", 7),
+        ];
+        let delta = ExtractionDelta::compare(&previous, &current, Some(&NotIgnored));
+        assert_eq!(delta.added, ["new"]);
+        assert_eq!(delta.changed, ["changed"]);
+        assert_eq!(delta.removed, ["gone"]);
+        let delta = ExtractionDelta::compare(&previous, &current, None);
+        assert_eq!(delta.added, ["new", "ignored-new"]);
+        assert_eq!(delta.removed, ["gone", "ignored-gone"]);
+    }
+
+    #[test]
+    fn test_compare_without_crc() {
+        let time = |second| Some(DateTime::from_date_and_time(2025, 1, 1, 0, 0, second).unwrap());
+        let aes = |name: &str, last_modified| ZipEntryMetadata {
+            last_modified,
+            ..entry(name, 0)
+        };
+        let previous = [
+            aes("same", time(0)),
+            aes("touched", time(0)),
+            aes("untimed", None),
+            aes("was-plain", time(0)),
+        ];
+        let current = [
+            aes("same", time(0)),
+            aes("touched", time(2)),
+            aes("untimed", None),
+            ZipEntryMetadata {
+                last_modified: time(0),
+                ..entry("was-plain", 1)
+            },
+        ];
+        let delta = ExtractionDelta::compare(&previous, &current, None);
+        assert_eq!(delta.changed, ["touched", "untimed"]);
+    }
+}
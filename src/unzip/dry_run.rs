@@ -0,0 +1,445 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{collections::HashSet, io::Write, path::PathBuf};
+
+use crate::RipunzipErrors;
+
+use super::{
+    duplicates::superseded, index::IndexEntry, invalid_names::target_name, is_directory,
+    overlay::matches_base, overwrite::OverwriteGate, progress_file::json_string, Destination,
+    DuplicatePolicy, SkipReason, UnzipOptions, UnzipOutcome, UnzipProgressReporter,
+    ZipEntryMetadata,
+};
+
+/// What a dry run found that an unzip would do with a file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PlannedAction {
+    /// Create a new file.
+    Create,
+    /// Replace an existing file.
+    Overwrite,
+}
+
+/// What an unzip would do with an entry.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PlannedDecision {
+    /// Extract it to a file.
+    Extract(PlannedAction),
+    /// Create it as a directory.
+    CreateDirectory,
+    /// Leave it out.
+    Skip(SkipReason),
+}
+
+/// What an unzip would do with one entry, once every option has been
+/// applied.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PlannedEntry {
+    /// The name of the entry within the zip file.
+    pub name: String,
+    /// Where the entry's local header is within the zip file.
+    pub offset: u64,
+    /// Compressed size.
+    pub compressed_size: u64,
+    /// Uncompressed size, as recorded in the zip file.
+    pub size: u64,
+    /// The compression method, as recorded in the zip file, such as 8 for
+    /// Deflate.
+    pub method: u16,
+    /// Where it would be extracted, if it's extracted or would have been
+    /// but for an existing file.
+    pub output_path: Option<PathBuf>,
+    /// What would be done with it.
+    pub decision: PlannedDecision,
+}
+
+impl PlannedEntry {
+    /// This entry as a line of JSON, without the newline.
+    pub fn to_json(&self) -> String {
+        let (decision, reason) = match self.decision {
+            PlannedDecision::Extract(PlannedAction::Create) => ("create", None),
+            PlannedDecision::Extract(PlannedAction::Overwrite) => ("overwrite", None),
+            PlannedDecision::CreateDirectory => ("create_directory", None),
+            PlannedDecision::Skip(reason) => ("skip", Some(skip_reason_name(reason))),
+        };
+        let mut json = format!(
+            "{{\"name\":{},\"offset\":{},\"compressed_size\":{},\"size\":{},\"method\":{},\"output_path\":{},\"decision\":{}",
+            json_string(&self.name),
+            self.offset,
+            self.compressed_size,
+            self.size,
+            self.method,
+            self.output_path
+                .as_ref()
+                .map(|path| json_string(&path.to_string_lossy()))
+                .unwrap_or_else(|| "null".to_string()),
+            json_string(decision),
+        );
+        if let Some(reason) = reason {
+            json.push_str(&format!(",\"reason\":{}", json_string(reason)));
+        }
+        json.push('}');
+        json
+    }
+}
+
+fn skip_reason_name(reason: SkipReason) -> &'static str {
+    match reason {
+        SkipReason::Filtered => "filtered",
+        SkipReason::AlreadyExists => "already_exists",
+        SkipReason::Unsupported => "unsupported",
+        SkipReason::UpToDate => "up_to_date",
+        SkipReason::TooLarge => "too_large",
+        SkipReason::Superseded => "superseded",
+    }
+}
+
+/// What an unzip would do with each entry, reflecting every option which
+/// decides whether and where entries are extracted, from
+/// [`UnzipEngine::plan`](super::UnzipEngine::plan) or
+/// [`UnzipOptions::explain`](super::UnzipOptions::explain).
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ExtractionPlan {
+    /// Every entry, sorted by name, except that directories are left out
+    /// when sharding, which ignores them.
+    pub entries: Vec<PlannedEntry>,
+}
+
+impl ExtractionPlan {
+    /// Write the plan as lines of JSON, one per entry.
+    pub fn write_json(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        for entry in &self.entries {
+            writeln!(writer, "{}", entry.to_json())?;
+        }
+        Ok(())
+    }
+
+    /// Tell `progress_reporter` what a dry run found.
+    pub(crate) fn report(&self, progress_reporter: &dyn UnzipProgressReporter) {
+        for entry in &self.entries {
+            match entry.decision {
+                PlannedDecision::Extract(action) => {
+                    progress_reporter.extraction_planned(&entry.name, action)
+                }
+                PlannedDecision::Skip(reason) => {
+                    progress_reporter.entry_skipped(&entry.name, reason)
+                }
+                PlannedDecision::CreateDirectory => {}
+            }
+        }
+    }
+}
+
+/// Work out what extracting the given entries would do, without reading or
+/// writing any files, along with the outcome of a dry run. `entries` and
+/// `index` both list every entry in central directory order, so each
+/// entry's location is found at its index there. Existing files are
+/// checked against the overwrite policy, so a callback policy is consulted
+/// just as it would be for a real unzip.
+pub(crate) fn plan(
+    entries: Vec<ZipEntryMetadata>,
+    index: Vec<IndexEntry>,
+    options: &UnzipOptions,
+    overwrite_gate: &OverwriteGate,
+) -> Result<(ExtractionPlan, UnzipOutcome), RipunzipErrors> {
+    if entries.len() != index.len() {
+        return Err(central_directory_changed());
+    }
+    let mut entries = entries
+        .into_iter()
+        .zip(index)
+        .map(|(entry, location)| {
+            if entry.name == location.name {
+                Ok((entry, location))
+            } else {
+                Err(central_directory_changed())
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    let wanted = |entry: &ZipEntryMetadata| {
+        options
+            .filename_filter
+            .as_ref()
+            .map(|filter| filter.should_unzip(&entry.name))
+            .unwrap_or(true)
+    };
+    let too_large = |entry: &ZipEntryMetadata| {
+        options
+            .max_entry_size
+            .is_some_and(|max_entry_size| entry.size > max_entry_size)
+    };
+    let superseded: HashSet<usize> = match options.duplicate_policy {
+        DuplicatePolicy::All => HashSet::new(),
+        DuplicatePolicy::NewestByTime => superseded(
+            entries
+                .iter()
+                .enumerate()
+                .filter(|(_, (entry, _))| wanted(entry) && !too_large(entry))
+                .map(|(i, (entry, _))| (i, entry.name.as_str(), entry.last_modified)),
+            options,
+        )
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect(),
+    };
+    let mut plan = ExtractionPlan::default();
+    let mut outcome = UnzipOutcome::default();
+    for (i, (entry, location)) in entries.into_iter().enumerate() {
+        let mut planned = |output_path, decision| {
+            plan.entries.push(PlannedEntry {
+                name: entry.name.clone(),
+                offset: location.local_header_offset,
+                compressed_size: entry.compressed_size,
+                size: entry.size,
+                method: location.compression_method,
+                output_path,
+                decision,
+            })
+        };
+        if !wanted(&entry) {
+            planned(None, PlannedDecision::Skip(SkipReason::Filtered));
+            continue;
+        }
+        if is_directory(&entry.name, entry.unix_mode, entry.size, options) {
+            // Sharding ignores directories.
+            if options.shard_into.is_none() {
+                let output_path = target_name(&entry.name, options)
+                    .map(|(name, _)| Destination::new(options, &entry.name, name).path());
+                planned(output_path, PlannedDecision::CreateDirectory);
+                outcome.directories_extracted += 1;
+            }
+            continue;
+        }
+        if too_large(&entry) {
+            planned(None, PlannedDecision::Skip(SkipReason::TooLarge));
+            continue;
+        }
+        if superseded.contains(&i) {
+            planned(None, PlannedDecision::Skip(SkipReason::Superseded));
+            continue;
+        }
+        let Some((name, renamed)) = target_name(&entry.name, options) else {
+            planned(None, PlannedDecision::Skip(SkipReason::Unsupported));
+            continue;
+        };
+        let destination = Destination::new(options, &entry.name, name);
+        if let (Some(base), None) = (&options.overlay_base, destination.prefix) {
+            if matches_base(base, &destination.relative, entry.size, entry.crc32)? {
+                planned(
+                    Some(destination.path()),
+                    PlannedDecision::Skip(SkipReason::UpToDate),
+                );
+                outcome.files_skipped += 1;
+                continue;
+            }
+        }
+        let out_path = destination.path();
+        if renamed {
+            outcome.renamed.insert(entry.name.clone(), out_path.clone());
+        }
+        let exists = out_path.symlink_metadata().is_ok();
+        // An estimate at best if the size isn't recorded.
+        let size = options
+            .unknown_sizes
+            .size(entry.size, entry.compressed_size)
+            .unwrap_or(0);
+        if !overwrite_gate.should_write(&out_path, || entry.clone())? {
+            planned(
+                Some(out_path),
+                PlannedDecision::Skip(SkipReason::AlreadyExists),
+            );
+            outcome.files_skipped += 1;
+            continue;
+        }
+        let action = if exists {
+            PlannedAction::Overwrite
+        } else {
+            PlannedAction::Create
+        };
+        planned(Some(out_path), PlannedDecision::Extract(action));
+        outcome.files_extracted += 1;
+        outcome.bytes_written += size;
+    }
+    Ok((plan, outcome))
+}
+
+/// The error for entries which don't line up with their locations, as
+/// they were read from the central directory separately.
+fn central_directory_changed() -> RipunzipErrors {
+    RipunzipErrors::InvalidArchive {
+        reason: "the central directory changed while it was being read".to_string(),
+        offset: None,
+    }
+}
+
+/// The path, relative to the output directory, at which we'd extract the
+/// entry with the given name, or `None` if it would escape the output
+/// directory. `.` segments and repeated separators are dropped, so
+/// `./foo//./bar.txt` is extracted to `foo/bar.txt`, and `..` segments
+/// remove the segment before, if there is one. Everything which decides
+/// where an entry goes, or whether it may be extracted at all, uses this.
+/// This follows `ZipFile::enclosed_name`, which we can't use without
+/// reading the local header, except that backslashes are only treated as
+/// path separators if `normalize_separators`, or on Windows. On Windows,
+/// names with any other segment containing `:` are refused, since a
+/// segment such as `C:foo` would replace the directory it's joined to.
+pub(super) fn enclosed_name(name: &str, normalize_separators: bool) -> Option<PathBuf> {
+    enclosed_components(name, normalize_separators).map(|components| components.iter().collect())
+}
+
+/// The components of [`enclosed_name`], before they're made into a path.
+pub(super) fn enclosed_components(name: &str, normalize_separators: bool) -> Option<Vec<&str>> {
+    let mut components = components(name, normalize_separators)?;
+    if cfg!(windows) {
+        // Relative to a drive, as in `C:foo`, which like a drive letter on
+        // its own is ignored.
+        if let Some(first) = components.first_mut() {
+            if let Some(rest) = strip_drive(first) {
+                *first = rest;
+            }
+        }
+        if components.iter().any(|component| component.contains(':')) {
+            return None;
+        }
+    }
+    Some(components)
+}
+
+/// What follows the drive letter at the start of `component`, if it
+/// starts with one, so `foo` for `C:foo`.
+pub(super) fn strip_drive(component: &str) -> Option<&str> {
+    match component.as_bytes() {
+        [letter, b':', ..] if letter.is_ascii_alphabetic() => Some(&component[2..]),
+        _ => None,
+    }
+}
+
+/// Whether `component`, the first segment of a name, is a drive, such as
+/// `C:`, which like a leading slash is ignored. On Windows, any first
+/// segment ending in `:` counts, but elsewhere one such as `notes:` is an
+/// ordinary directory name.
+pub(super) fn is_drive(component: &str) -> bool {
+    strip_drive(component) == Some("") || (cfg!(windows) && component.ends_with(':'))
+}
+
+/// [`enclosed_components`], but keeping segments containing `:`, for when
+/// they're going to be made safe some other way.
+pub(super) fn components(name: &str, normalize_separators: bool) -> Option<Vec<&str>> {
+    if name.contains('\0') {
+        return None;
+    }
+    let separators: &[char] = if normalize_separators || cfg!(windows) {
+        &['/', '\\']
+    } else {
+        &['/']
+    };
+    let mut components = Vec::new();
+    for (i, component) in name.split(separators).enumerate() {
+        match component {
+            "" | "." => {}
+            // A drive letter, which like a leading slash is ignored.
+            _ if i == 0 && is_drive(component) => {}
+            ".." => {
+                components.pop()?;
+            }
+            _ => components.push(component),
+        }
+    }
+    Some(components)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{enclosed_name, PlannedAction, PlannedDecision, PlannedEntry};
+    use crate::SkipReason;
+
+    #[test]
+    fn test_planned_entry_to_json() {
+        let mut entry = PlannedEntry {
+            name: "a \"b\".txt".to_string(),
+            offset: 10,
+            compressed_size: 20,
+            size: 30,
+            method: 8,
+            output_path: Some(PathBuf::from("out/a.txt")),
+            decision: PlannedDecision::Extract(PlannedAction::Overwrite),
+        };
+        assert_eq!(
+            entry.to_json(),
+            r#"{"name":"a \"b\".txt","offset":10,"compressed_size":20,"size":30,"method":8,"output_path":"out/a.txt","decision":"overwrite"}"#
+        );
+        entry.output_path = None;
+        entry.decision = PlannedDecision::Skip(SkipReason::Filtered);
+        assert!(entry
+            .to_json()
+            .ends_with(r#""output_path":null,"decision":"skip","reason":"filtered"}"#));
+    }
+
+    #[test]
+    fn test_enclosed_name() {
+        assert_eq!(
+            enclosed_name("a/b.txt", true),
+            Some(PathBuf::from("a/b.txt"))
+        );
+        assert_eq!(
+            enclosed_name("/a/./b.txt", true),
+            Some(PathBuf::from("a/b.txt"))
+        );
+        assert_eq!(
+            enclosed_name("C:\\a\\b.txt", true),
+            Some(PathBuf::from("a/b.txt"))
+        );
+        assert_eq!(
+            enclosed_name("a/../b.txt", true),
+            Some(PathBuf::from("b.txt"))
+        );
+        assert_eq!(enclosed_name("a/../../b.txt", true), None);
+        for messy in [
+            "./foo/./bar.txt",
+            "foo//bar.txt",
+            ".//foo/.//bar.txt",
+            "foo/bar.txt/",
+        ] {
+            assert_eq!(
+                enclosed_name(messy, false),
+                Some(PathBuf::from("foo/bar.txt")),
+                "{messy}"
+            );
+        }
+        assert_eq!(enclosed_name("./../b.txt", true), None);
+        assert_eq!(enclosed_name("foo/.//../../b.txt", true), None);
+        assert_eq!(enclosed_name("a\0b", true), None);
+        assert_eq!(enclosed_name("a/C:/evil", true).is_none(), cfg!(windows));
+        #[cfg(not(windows))]
+        assert_eq!(
+            enclosed_name("notes:/a.txt", true),
+            Some(PathBuf::from("notes:/a.txt"))
+        );
+        assert_eq!(
+            enclosed_name("C:foo\\bar", true),
+            Some(if cfg!(windows) {
+                PathBuf::from("foo/bar")
+            } else {
+                PathBuf::from("C:foo/bar")
+            })
+        );
+        assert_eq!(
+            enclosed_name("a\\b.txt", true),
+            Some(PathBuf::from("a/b.txt"))
+        );
+        #[cfg(not(windows))]
+        assert_eq!(
+            enclosed_name("a\\b.txt", false),
+            Some(PathBuf::from("a\\b.txt"))
+        );
+    }
+}
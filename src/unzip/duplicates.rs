@@ -0,0 +1,99 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    collections::HashSet,
+    io::{Read, Seek},
+    path::PathBuf,
+};
+
+use zip::{DateTime, ZipArchive};
+
+use super::{
+    central_directory::read_central_directory, invalid_names::target_name, SkipReason, UnzipOptions,
+};
+use crate::RipunzipErrors;
+
+/// Which of several entries to extract when their names lead to the same
+/// path, such as `a.txt` and `./a.txt`, as in zip files which have been
+/// appended to by tools that don't replace existing entries. Entries with
+/// exactly the same name can't be told apart: the zip library only ever
+/// reads the last of them listed in the central directory.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum DuplicatePolicy {
+    /// Extract all of them, each replacing the last according to the
+    /// overwrite policy.
+    #[default]
+    All,
+    /// Extract only the one with the latest modification time, reporting
+    /// the others to the progress reporter as
+    /// [`SkipReason::Superseded`]. Of those with the same time, the one
+    /// listed last in the central directory is extracted.
+    NewestByTime,
+}
+
+/// Find, among entries given as their index, name and modification time,
+/// the files which another with the same path supersedes, according to
+/// [`DuplicatePolicy::NewestByTime`], in name order.
+pub(super) fn superseded<'n>(
+    entries: impl IntoIterator<Item = (usize, &'n str, Option<DateTime>)>,
+    options: &UnzipOptions,
+) -> Vec<(usize, &'n str)> {
+    let mut candidates: Vec<(PathBuf, Option<DateTime>, usize, &str)> = entries
+        .into_iter()
+        // Directories don't overwrite one another.
+        .filter(|(_, name, _)| !name.ends_with('/'))
+        .filter_map(|(i, name, last_modified)| {
+            let (path, _) = target_name(name, options)?;
+            Some((path, last_modified, i, name))
+        })
+        .collect();
+    // Each path's entries end up together, oldest first, so each entry is
+    // superseded if the next has the same path.
+    candidates.sort();
+    let mut superseded: Vec<(usize, &str)> = candidates
+        .windows(2)
+        .filter(|pair| pair[0].0 == pair[1].0)
+        .map(|pair| (pair[0].2, pair[0].3))
+        .collect();
+    superseded.sort_by_key(|(_, name)| *name);
+    superseded
+}
+
+/// Find the indices of the files, among those matching the filename filter
+/// and not already being skipped, which another with the same path
+/// supersedes, and report that they won't be extracted. Their times come
+/// from the central directory, so none of their data is read.
+pub(super) fn superseded_entries<T: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<T>,
+    options: &UnzipOptions,
+    skipped: &HashSet<usize>,
+) -> Result<HashSet<usize>, RipunzipErrors> {
+    let entries = read_central_directory(zip_archive)?;
+    let superseded = superseded(
+        entries
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !skipped.contains(i))
+            .filter_map(|(i, entry)| Some((i, zip_archive.name_for_index(i)?, entry.last_modified)))
+            .filter(|(_, name, _)| {
+                options
+                    .filename_filter
+                    .as_ref()
+                    .map(|filter| filter.should_unzip(name))
+                    .unwrap_or(true)
+            }),
+        options,
+    );
+    for (_, name) in &superseded {
+        options
+            .progress_reporter
+            .entry_skipped(name, SkipReason::Superseded);
+    }
+    Ok(superseded.into_iter().map(|(i, _)| i).collect())
+}
@@ -0,0 +1,92 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+/// A reader over a range of bytes within another reader; specifically, the
+/// data of an uncompressed entry within a zip file, so that it can itself be
+/// read as a zip file without copying it anywhere.
+#[derive(Clone)]
+pub(crate) struct EntryReader<R> {
+    inner: R,
+    start: u64,
+    len: u64,
+    pos: u64,
+    /// Whether `inner` might not be positioned at `start + pos`.
+    needs_seek: bool,
+}
+
+impl<R: Read + Seek> EntryReader<R> {
+    pub(crate) fn new(inner: R, start: u64, len: u64) -> Self {
+        Self {
+            inner,
+            start,
+            len,
+            pos: 0,
+            needs_seek: true,
+        }
+    }
+
+    /// A reader over a range of this one's range, reading directly from
+    /// the underlying reader.
+    pub(crate) fn subrange(self, start: u64, len: u64) -> Self {
+        Self::new(self.inner, self.start + start, len)
+    }
+}
+
+impl<R: Read + Seek> Read for EntryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.needs_seek {
+            self.inner.seek(SeekFrom::Start(self.start + self.pos))?;
+            self.needs_seek = false;
+        }
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+        let bytes_read = self.inner.read(&mut buf[..max])?;
+        self.pos += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<R: Read + Seek> Seek for EntryReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        if new_pos != self.pos {
+            self.pos = new_pos;
+            self.needs_seek = true;
+        }
+        Ok(new_pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    use super::EntryReader;
+
+    #[test]
+    fn test_entry_reader() {
+        let mut reader = EntryReader::new(Cursor::new(b"0123456789".to_vec()), 2, 5);
+        let mut s = String::new();
+        reader.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "23456");
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 3);
+        let mut s = String::new();
+        reader.clone().read_to_string(&mut s).unwrap();
+        assert_eq!(s, "56");
+        assert!(reader.seek(SeekFrom::Current(-4)).is_err());
+    }
+}
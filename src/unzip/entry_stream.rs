@@ -0,0 +1,115 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    thread::JoinHandle,
+};
+
+use crate::RipunzipErrors;
+
+use super::{UnzipOutcome, ZipEntryMetadata};
+
+/// A file or directory which has been extracted, as returned by
+/// [`UnzipIter`].
+#[derive(Debug, Clone)]
+pub struct ExtractedEntry {
+    /// Where it was extracted to.
+    pub path: PathBuf,
+    /// Its details from the zip file.
+    pub metadata: ZipEntryMetadata,
+}
+
+/// Passes extracted entries from the threads extracting them to an
+/// [`UnzipIter`], and tells them if it's been dropped.
+pub(crate) struct EntryStream {
+    sender: Mutex<Sender<Result<ExtractedEntry, RipunzipErrors>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl EntryStream {
+    pub(crate) fn send(&self, entry: ExtractedEntry) {
+        // If the iterator has gone, we'll stop at the next entry.
+        let _ = self
+            .sender
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(Ok(entry));
+    }
+
+    /// Returns an error if the iterator has been dropped, so there's no
+    /// point extracting any more entries.
+    pub(crate) fn check_not_cancelled(&self) -> Result<(), RipunzipErrors> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(RipunzipErrors::Aborted);
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the files and directories extracted by
+/// [`UnzipEngine::unzip_iter`](super::UnzipEngine::unzip_iter), in the order
+/// in which they finish extracting. If unzipping fails, the last item is
+/// the error. Dropping the iterator stops any more entries from being
+/// extracted, waiting for those already in progress to finish.
+pub struct UnzipIter {
+    receiver: Receiver<Result<ExtractedEntry, RipunzipErrors>>,
+    cancelled: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UnzipIter {
+    /// Run `unzip` on a thread of its own, passing on the entries it
+    /// extracts and then its error, if any.
+    pub(crate) fn spawn(
+        unzip: impl FnOnce(&EntryStream) -> Result<UnzipOutcome, RipunzipErrors> + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let entry_stream = EntryStream {
+            sender: Mutex::new(sender),
+            cancelled: cancelled.clone(),
+        };
+        let thread = std::thread::spawn(move || {
+            if let Err(e) = unzip(&entry_stream) {
+                let _ = entry_stream
+                    .sender
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .send(Err(e));
+            }
+        });
+        Self {
+            receiver,
+            cancelled,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Iterator for UnzipIter {
+    type Item = Result<ExtractedEntry, RipunzipErrors>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for UnzipIter {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Duration;
+
+use super::MethodStats;
+
+/// How much of the first file to decompress when measuring throughput.
+pub(super) const SAMPLE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Assumed speeds at which a zip file can be extracted, for
+/// [`UnzipEngine::estimate_duration`](crate::UnzipEngine::estimate_duration).
+/// Reading and decompressing happen at the same time, so whichever is
+/// slower for a given zip file decides how long it takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throughput {
+    /// Compressed bytes read per second, such as the download speed for a
+    /// URI.
+    pub read_bytes_per_second: f64,
+    /// Uncompressed bytes decompressed and written per second, across all
+    /// threads.
+    pub decompress_bytes_per_second: f64,
+}
+
+impl Throughput {
+    /// The throughput seen when `compressed_bytes` were read and
+    /// decompressed into `bytes` in `elapsed`.
+    pub(super) fn measured(compressed_bytes: u64, bytes: u64, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        Self {
+            read_bytes_per_second: compressed_bytes as f64 / seconds,
+            decompress_bytes_per_second: bytes as f64 / seconds,
+        }
+    }
+
+    /// How long it would take to extract files adding up to `stats`.
+    /// Speeds which aren't positive and finite are taken to be unlimited.
+    pub(super) fn estimate(&self, stats: &MethodStats) -> Duration {
+        let time = |bytes: u64, bytes_per_second: f64| {
+            if bytes_per_second > 0.0 && bytes_per_second.is_finite() {
+                bytes as f64 / bytes_per_second
+            } else {
+                0.0
+            }
+        };
+        let seconds = time(stats.compressed_size, self.read_bytes_per_second)
+            .max(time(stats.size, self.decompress_bytes_per_second));
+        Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{MethodStats, Throughput};
+
+    #[test]
+    fn test_estimate() {
+        let stats = MethodStats {
+            files: 3,
+            size: 300_000_000,
+            compressed_size: 100_000_000,
+        };
+        let throughput = |read_bytes_per_second, decompress_bytes_per_second| Throughput {
+            read_bytes_per_second,
+            decompress_bytes_per_second,
+        };
+        // A slow network.
+        assert_eq!(
+            throughput(1e6, 1e9).estimate(&stats),
+            Duration::from_secs(100)
+        );
+        // A slow CPU.
+        assert_eq!(
+            throughput(1e9, 1e6).estimate(&stats),
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            throughput(f64::INFINITY, 0.0).estimate(&stats),
+            Duration::ZERO
+        );
+        assert_eq!(
+            throughput(f64::MIN_POSITIVE, 1e6).estimate(&stats),
+            Duration::MAX
+        );
+        let measured = Throughput::measured(1000, 3000, Duration::from_millis(500));
+        assert_eq!(measured, throughput(2000.0, 6000.0));
+        assert_eq!(measured.estimate(&stats), Duration::from_secs(50_000));
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{FilenameFilter, UnzipOptions};
+
+/// Rejects files with any of the extensions, and otherwise defers to the
+/// other filter, if any.
+struct ExcludeExtensionsFilter<'a> {
+    /// Lowercase, each with a leading `.`.
+    suffixes: Vec<String>,
+    filter: Option<Box<dyn FilenameFilter + Send + Sync + 'a>>,
+}
+
+impl FilenameFilter for ExcludeExtensionsFilter<'_> {
+    fn should_unzip(&self, filename: &str) -> bool {
+        !self.excluded(filename)
+            && self
+                .filter
+                .as_ref()
+                .map_or(true, |filter| filter.should_unzip(filename))
+    }
+}
+
+impl ExcludeExtensionsFilter<'_> {
+    fn excluded(&self, filename: &str) -> bool {
+        // Directories are never excluded, whatever they're called.
+        let basename = match filename.rsplit_once('/') {
+            Some((_, "")) => return false,
+            Some((_, basename)) => basename,
+            None => filename,
+        }
+        .to_lowercase();
+        self.suffixes
+            .iter()
+            // A file named just `.map` has no extension.
+            .any(|suffix| basename.len() > suffix.len() && basename.ends_with(suffix.as_str()))
+    }
+}
+
+/// Replace [`UnzipOptions::exclude_extensions`], if any are given, with an
+/// equivalent filename filter.
+pub(crate) fn apply_exclude_extensions(options: &mut UnzipOptions) {
+    if options.exclude_extensions.is_empty() {
+        return;
+    }
+    let suffixes = std::mem::take(&mut options.exclude_extensions)
+        .into_iter()
+        .map(|extension| format!(".{}", extension.trim_start_matches('.').to_lowercase()))
+        .collect();
+    options.filename_filter = Some(Box::new(ExcludeExtensionsFilter {
+        suffixes,
+        filter: options.filename_filter.take(),
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::apply_exclude_extensions;
+    use crate::{FilenameFilter, UnzipOptions};
+
+    struct NotKeep;
+
+    impl FilenameFilter for NotKeep {
+        fn should_unzip(&self, filename: &str) -> bool {
+            !filename.starts_with("keep")
+        }
+    }
+
+    #[test]
+    fn test_exclude_extensions() {
+        let mut options = UnzipOptions {
+            exclude_extensions: vec!["map".to_string(), ".ORIG".to_string(), "tar.gz".to_string()],
+            filename_filter: Some(Box::new(NotKeep)),
+            ..Default::default()
+        };
+        apply_exclude_extensions(&mut options);
+        assert!(options.exclude_extensions.is_empty());
+        let filter = options.filename_filter.unwrap();
+        for (name, extracted) in [
+            ("index.html", true),
+            ("app.js.map", false),
+            ("dir/App.JS.MAP", false),
+            ("patch.orig", false),
+            ("origin", true),
+            ("bundle.tar.gz", false),
+            ("bundle.gz", true),
+            (".map", true),
+            ("dir/.map", true),
+            ("sources.map/", true),
+            ("keep.txt", false),
+        ] {
+            assert_eq!(filter.should_unzip(name), extracted, "{name}");
+        }
+    }
+}
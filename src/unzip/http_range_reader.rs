@@ -6,11 +6,40 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{cmp::min, io::Read};
+use std::{
+    cmp::min,
+    io::Read,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
-use reqwest::blocking::{Client, Response};
+use reqwest::{
+    blocking::{Client, ClientBuilder, Response},
+    header::HeaderName,
+    StatusCode,
+};
 use thiserror::Error;
 
+use super::{
+    network_events::{NetworkEvent, NetworkEventSink},
+    Cookies, RootCertificates,
+};
+
+/// How the caller asked for HTTP clients to be set up.
+#[derive(Clone, Default)]
+pub(crate) struct ClientConfig {
+    pub(crate) root_certificates: RootCertificates,
+    pub(crate) cookies: Cookies,
+}
+
+impl ClientConfig {
+    pub(crate) fn client_builder(&self) -> ClientBuilder {
+        self.cookies.apply(self.root_certificates.client_builder())
+    }
+}
+
 /// Errors that may be returned by a [`RangeFetcher`].
 #[derive(Debug, Error)]
 pub(crate) enum Error {
@@ -20,44 +49,112 @@ pub(crate) enum Error {
     NoContentLength,
     #[error("HTTP resource was zero length")]
     EmptyContentLength,
+    #[error("Mirror {uri} has length {len}, but the first mirror has length {expected}")]
+    MirrorLengthMismatch {
+        uri: String,
+        len: u64,
+        expected: u64,
+    },
     #[error("HTTP GET command failed")]
     HttpGet(#[source] reqwest::Error),
+    #[error("{uri} is served with Content-Encoding {encoding}, so its length and ranges don't match the zip file")]
+    ContentEncoded { uri: String, encoding: String },
     #[error("Reading while fast-forwarding to desired location failed")]
     FastForward(#[source] std::io::Error),
+    #[error("{uri} changed while it was being read")]
+    SourceChanged { uri: String },
 }
 
 /// An object which can fetch different ranges of a URI, using the HTTP
 /// 'accept-range' header where supported. This fetcher works with HTTP servers
 /// that either support or do not support 'accept-range' but can only be used
 /// on HTTP resources which (a) report a `Content-Length`, and (b) do not change
-/// between requests.
+/// between requests. To notice if it does change, we send the `ETag` or
+/// `Last-Modified` time from when we first asked about it as `If-Range` with
+/// every range request, so the server sends all of the new version instead
+/// of a range, and check the `ETag` and `Last-Modified` time of every
+/// response. We ask for the resource without any `Content-Encoding`,
+/// since ranges of an encoded resource don't correspond to ranges of the
+/// resource itself, and fail if the server encodes it anyway.
+///
+/// The same resource may be available from several mirrors, in which case
+/// each fetch goes to the next mirror in turn, moving on to the others if it
+/// fails.
 pub(crate) struct RangeFetcher {
-    uri: String,
+    mirrors: Vec<Mirror>,
     accept_ranges: bool,
     content_length: u64,
     client: Client,
+    /// The mirror to use for the next fetch.
+    next_mirror: AtomicUsize,
+    events: Arc<NetworkEventSink>,
+}
+
+struct Mirror {
+    uri: String,
+    accept_ranges: bool,
+    content_length: u64,
+    etag: Option<String>,
+    last_modified: Option<String>,
 }
 
 impl RangeFetcher {
-    /// Create a new range fetcher for a given resource.
-    pub(crate) fn new(uri: String) -> Result<Self, Error> {
-        let client = reqwest::blocking::Client::new();
-        let response = client.head(&uri).send().map_err(Error::HttpHead)?;
-        let content_length = content_length_via_headers(&response).ok_or(Error::NoContentLength)?;
-        if content_length == 0 {
-            return Err(Error::EmptyContentLength);
+    /// Create a new range fetcher for a given resource, available from
+    /// one or more mirrors. Mirrors which can't be reached are ignored, as
+    /// are those which don't support ranges if others do, but all the rest
+    /// must report the same length. Streams opened and failures are
+    /// reported to `events`.
+    pub(crate) fn new(
+        uris: Vec<String>,
+        client_config: &ClientConfig,
+        events: Arc<NetworkEventSink>,
+    ) -> Result<Self, Error> {
+        let client = client_config
+            .client_builder()
+            .no_gzip()
+            .build()
+            .map_err(Error::HttpHead)?;
+        let mut mirrors = Vec::new();
+        let mut first_error = None;
+        for uri in uris {
+            match Mirror::new(&client, uri) {
+                Ok(mirror) => mirrors.push(mirror),
+                Err(e) => {
+                    log::warn!("Ignoring mirror: {e}");
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        let Some(first) = mirrors.first() else {
+            return Err(first_error.unwrap_or(Error::NoContentLength));
+        };
+        let content_length = first.content_length;
+        if let Some(mismatched) = mirrors.iter().find(|m| m.content_length != content_length) {
+            return Err(Error::MirrorLengthMismatch {
+                uri: mismatched.uri.clone(),
+                len: mismatched.content_length,
+                expected: content_length,
+            });
+        }
+        let accept_ranges = mirrors.iter().any(|mirror| mirror.accept_ranges);
+        if accept_ranges {
+            mirrors.retain(|mirror| mirror.accept_ranges);
         }
-        let accept_ranges = response
-            .headers()
-            .contains_key(reqwest::header::ACCEPT_RANGES);
         Ok(Self {
-            uri,
+            mirrors,
             accept_ranges,
             content_length,
             client,
+            next_mirror: AtomicUsize::new(0),
+            events,
         })
     }
 
+    /// The number of mirrors we're fetching from.
+    pub(crate) fn mirror_count(&self) -> usize {
+        self.mirrors.len()
+    }
+
     /// Return the total length of the resource.
     pub(crate) fn len(&self) -> u64 {
         self.content_length
@@ -70,18 +167,71 @@ impl RangeFetcher {
 
     /// Return a [`Read`] for this resource starting from the given offset.
     /// If the resource supports HTTP ranges, this will start reading from
-    /// the server at that point; otherwise, it will read from the outset
-    /// of the resource but discard bytes before that point. (Clearly that
-    /// can be expensive if you only care about a few bytes later in a
+    /// the server at that point, and ask for no more than up to `end`;
+    /// otherwise, it will read from the outset of the resource but discard
+    /// bytes before that point, and carry on to the end. (Clearly that can
+    /// be expensive if you only care about a few bytes later in a
     /// resource.)
-    pub(crate) fn fetch_range(&self, offset: u64) -> Result<Response, Error> {
-        log::debug!("Fetch range 0x{offset:x}");
-        let mut builder = self.client.get(&self.uri);
+    pub(crate) fn fetch_range(&self, offset: u64, end: u64) -> Result<Response, Error> {
+        let count = self.mirrors.len();
+        let first = self.next_mirror.fetch_add(1, Ordering::Relaxed);
+        let mut mirrors = (0..count).map(|i| &self.mirrors[(first + i) % count]);
+        loop {
+            let mirror = mirrors.next().expect("there's always at least one mirror");
+            match self.fetch_range_from(mirror, offset, end) {
+                Ok(response) => {
+                    self.events.send(NetworkEvent::StreamOpened {
+                        uri: mirror.uri.clone(),
+                        offset,
+                    });
+                    return Ok(response);
+                }
+                // Another mirror might still have the old version, but it
+                // would be no more trustworthy.
+                Err(e) if mirrors.len() > 0 && !matches!(e, Error::SourceChanged { .. }) => {
+                    log::debug!(
+                        "Fetch from {} failed, trying another mirror: {e}",
+                        mirror.uri
+                    );
+                    self.events.send(NetworkEvent::Failover {
+                        uri: mirror.uri.clone(),
+                        offset,
+                        error: e.to_string(),
+                    });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn fetch_range_from(&self, mirror: &Mirror, offset: u64, end: u64) -> Result<Response, Error> {
+        log::debug!("Fetch range 0x{offset:x} from {}", mirror.uri);
+        let mut builder = self
+            .client
+            .get(&mirror.uri)
+            .header(reqwest::header::ACCEPT_ENCODING, "identity");
+        let if_range = mirror.if_range().filter(|_| self.accept_ranges);
         if self.accept_ranges {
-            let range_header = format!("bytes={}-{}", offset, self.len());
+            // The end of the range is inclusive.
+            let range_header = format!("bytes={offset}-{}", end.saturating_sub(1).max(offset));
             builder = builder.header(reqwest::header::RANGE, range_header);
         }
-        let mut response = builder.send().map_err(Error::HttpGet)?;
+        if let Some(if_range) = if_range {
+            builder = builder.header(reqwest::header::IF_RANGE, if_range);
+        }
+        let mut response = builder
+            .send()
+            .and_then(Response::error_for_status)
+            .map_err(Error::HttpGet)?;
+        check_not_encoded(&response, &mirror.uri)?;
+        // Given `If-Range`, the server sends the whole resource if it's
+        // changed.
+        let whole_resource_sent = if_range.is_some() && response.status() == StatusCode::OK;
+        if whole_resource_sent || mirror.changed(&response) {
+            return Err(Error::SourceChanged {
+                uri: mirror.uri.clone(),
+            });
+        }
         if !self.accept_ranges && offset > 0 {
             // Read and discard data prior to 'offset'
             let mut to_read = offset as usize;
@@ -97,6 +247,92 @@ impl RangeFetcher {
     }
 }
 
+impl Mirror {
+    fn new(client: &Client, uri: String) -> Result<Self, Error> {
+        let response = client
+            .head(&uri)
+            .header(reqwest::header::ACCEPT_ENCODING, "identity")
+            .send()
+            .and_then(Response::error_for_status)
+            .map_err(Error::HttpHead)?;
+        check_not_encoded(&response, &uri)?;
+        let content_length = content_length_via_headers(&response).ok_or(Error::NoContentLength)?;
+        if content_length == 0 {
+            return Err(Error::EmptyContentLength);
+        }
+        let accept_ranges = response
+            .headers()
+            .contains_key(reqwest::header::ACCEPT_RANGES);
+        Ok(Self {
+            uri,
+            accept_ranges,
+            content_length,
+            etag: header(&response, reqwest::header::ETAG),
+            last_modified: header(&response, reqwest::header::LAST_MODIFIED),
+        })
+    }
+
+    /// What to send as `If-Range`: the `ETag`, unless it's weak, which
+    /// `If-Range` doesn't allow, or else the `Last-Modified` time.
+    fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    /// Whether `response` is for a different version of the resource than
+    /// we first asked about, as far as we can tell.
+    fn changed(&self, response: &Response) -> bool {
+        let differs = |ours: &Option<String>, name| match (ours, header(response, name)) {
+            (Some(ours), Some(theirs)) => *ours != theirs,
+            _ => false,
+        };
+        differs(&self.etag, reqwest::header::ETAG)
+            || differs(&self.last_modified, reqwest::header::LAST_MODIFIED)
+    }
+}
+
+/// The value of the named header, if it's there and printable.
+fn header(response: &Response, name: HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|hv| hv.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Whether `error`, wherever it came from, is because the resource changed
+/// while we were reading it, and if so, its URI. Errors from the reader
+/// reach us wrapped in I/O errors.
+pub(crate) fn source_changed(error: &std::io::Error) -> Option<&str> {
+    match error.get_ref()?.downcast_ref::<Error>()? {
+        Error::SourceChanged { uri } => Some(uri),
+        _ => None,
+    }
+}
+
+/// Fail if the server has applied a `Content-Encoding` such as gzip to the
+/// resource.
+fn check_not_encoded(response: &Response, uri: &str) -> Result<(), Error> {
+    match content_encoding(response) {
+        Some(encoding) => Err(Error::ContentEncoded {
+            uri: uri.to_string(),
+            encoding,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The `Content-Encoding` of a response, unless it's `identity`.
+pub(crate) fn content_encoding(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .map(|hv| hv.to_str().unwrap_or("<unprintable>").to_string())
+        .filter(|encoding| !encoding.eq_ignore_ascii_case("identity"))
+}
+
 /// Determine the `Content-Length` header. `reqwest` says it does this, but
 /// doesn't: https://github.com/seanmonstar/reqwest/issues/1136
 fn content_length_via_headers(response: &Response) -> Option<u64> {
@@ -111,10 +347,26 @@ fn content_length_via_headers(response: &Response) -> Option<u64> {
 mod tests {
     use httptest::{matchers::*, responders::*, Expectation, Server};
     use ripunzip_test_utils::*;
-    use std::io::Read;
+    use std::{
+        io::Read,
+        sync::{Arc, Mutex},
+    };
     use test_log::test;
 
     use super::RangeFetcher;
+    use crate::{
+        unzip::network_events::{forward_network_events, NetworkEventSink},
+        NetworkEvent, UnzipProgressReporter,
+    };
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<NetworkEvent>>);
+
+    impl UnzipProgressReporter for Recorder {
+        fn network_event(&self, event: &NetworkEvent) {
+            self.0.lock().unwrap().push(event.clone())
+        }
+    }
 
     fn do_test(accept_ranges: bool) {
         let server = Server::run();
@@ -137,7 +389,12 @@ mod tests {
                 )
         });
 
-        let range_fetcher = RangeFetcher::new(server.url("/foo").to_string()).unwrap();
+        let range_fetcher = RangeFetcher::new(
+            vec![server.url("/foo").to_string()],
+            &Default::default(),
+            Default::default(),
+        )
+        .unwrap();
 
         // Test reading the whole thing
         server.expect(if accept_ranges {
@@ -156,16 +413,122 @@ mod tests {
                 .respond_with(status_code(200).body(body))
         });
         assert_eq!(accept_ranges, range_fetcher.accepts_ranges());
-        let mut resp = range_fetcher.fetch_range(0u64).unwrap();
+        let mut resp = range_fetcher.fetch_range(0u64, 10).unwrap();
         let mut throwaway = [0u8; 10];
         resp.read_exact(&mut throwaway).unwrap();
         assert_eq!(std::str::from_utf8(&throwaway).unwrap(), "0123456789");
 
         // Test read only a range
-        let mut resp = range_fetcher.fetch_range(4u64).unwrap();
+        let mut resp = range_fetcher.fetch_range(4u64, 10).unwrap();
+        let mut throwaway = [0u8; 6];
+        resp.read_exact(&mut throwaway).unwrap();
+        assert_eq!(std::str::from_utf8(&throwaway).unwrap(), "456789");
+    }
+
+    #[test]
+    fn test_failover() {
+        let body = "0123456789";
+        let broken = Server::run();
+        let good = Server::run();
+        for server in [&broken, &good] {
+            server.expect(
+                Expectation::matching(request::method_path("HEAD", "/foo"))
+                    .times(..)
+                    .respond_with(RangeAwareResponse::new(
+                        200,
+                        RangeAwareResponseType::LengthOnly(body.len()),
+                    )),
+            );
+        }
+        broken.expect(
+            Expectation::matching(request::method_path("GET", "/foo"))
+                .times(1)
+                .respond_with(status_code(503)),
+        );
+        good.expect(
+            Expectation::matching(request::method_path("GET", "/foo"))
+                .times(1)
+                .respond_with(RangeAwareResponse::new(
+                    206,
+                    RangeAwareResponseType::Body {
+                        body: hyper::body::Bytes::from(body),
+                        expected_range: None,
+                    },
+                )),
+        );
+        let events = Arc::new(NetworkEventSink::default());
+        let range_fetcher = RangeFetcher::new(
+            vec![broken.url("/foo").to_string(), good.url("/foo").to_string()],
+            &Default::default(),
+            events.clone(),
+        )
+        .unwrap();
+        let recorder = Recorder::default();
+        let mut resp = forward_network_events(&events, &recorder, || {
+            range_fetcher.fetch_range(4u64, 10).unwrap()
+        });
         let mut throwaway = [0u8; 6];
         resp.read_exact(&mut throwaway).unwrap();
         assert_eq!(std::str::from_utf8(&throwaway).unwrap(), "456789");
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                NetworkEvent::Failover {
+                    uri: broken.url("/foo").to_string(),
+                    offset: 4,
+                    error: "HTTP GET command failed".to_string(),
+                },
+                NetworkEvent::StreamOpened {
+                    uri: good.url("/foo").to_string(),
+                    offset: 4,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range_header() {
+        let server = Server::run();
+        let body = "0123456789";
+        server.expect(
+            Expectation::matching(request::method_path("HEAD", "/foo"))
+                .times(..)
+                .respond_with(RangeAwareResponse::new(
+                    200,
+                    RangeAwareResponseType::LengthOnly(body.len()),
+                )),
+        );
+        // Ranges include their last byte.
+        for (range, sent) in [("bytes=4-8", "45678"), ("bytes=0-9", body)] {
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", "/foo"),
+                    request::headers(contains(("range", range))),
+                ])
+                .times(1)
+                .respond_with(status_code(206).body(sent)),
+            );
+        }
+        let range_fetcher = RangeFetcher::new(
+            vec![server.url("/foo").to_string()],
+            &Default::default(),
+            Default::default(),
+        )
+        .unwrap();
+        let mut contents = String::new();
+        range_fetcher
+            .fetch_range(4, 9)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "45678");
+        contents.clear();
+        range_fetcher
+            .fetch_range(0, 10)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, body);
     }
 
     #[test]
@@ -0,0 +1,72 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Seek, Write},
+    path::Path,
+};
+
+use zip::ZipArchive;
+
+use super::{central_directory::read_central_directory, progress_file::json_string};
+use crate::RipunzipErrors;
+
+/// Where an entry is and how to read it, for
+/// [`UnzipOptions::emit_index`](super::UnzipOptions::emit_index).
+pub(crate) struct IndexEntry {
+    pub(super) name: String,
+    pub(super) local_header_offset: u64,
+    compressed_size: u64,
+    pub(super) compression_method: u16,
+    crc32: u32,
+}
+
+/// Read the index from the central directory, in the order it lists
+/// entries.
+pub(super) fn index_entries<T: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<T>,
+) -> Result<Vec<IndexEntry>, RipunzipErrors> {
+    let entries = read_central_directory(zip_archive)?;
+    Ok(entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| IndexEntry {
+            name: zip_archive
+                .name_for_index(i)
+                .unwrap_or_default()
+                .to_string(),
+            local_header_offset: entry.local_header_offset,
+            compressed_size: entry.compressed_size,
+            compression_method: entry.compression_method,
+            crc32: entry.crc32,
+        })
+        .collect())
+}
+
+/// Write `entries` to `path` as lines of JSON, replacing anything there.
+pub(super) fn write_index(path: &Path, entries: &[IndexEntry]) -> Result<(), RipunzipErrors> {
+    let context = |e| RipunzipErrors::IOErrorWithContext {
+        context: format!("Failed to write index file {}", path.display()),
+        source: e,
+    };
+    let mut writer = BufWriter::new(File::create(path).map_err(context)?);
+    for entry in entries {
+        writeln!(
+            writer,
+            "{{\"name\":{},\"offset\":{},\"compressed_size\":{},\"method\":{},\"crc32\":{}}}",
+            json_string(&entry.name),
+            entry.local_header_offset,
+            entry.compressed_size,
+            entry.compression_method,
+            entry.crc32
+        )
+        .map_err(context)?;
+    }
+    writer.flush().map_err(context)
+}
@@ -0,0 +1,174 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{ErrorKind, Read};
+
+use crate::RipunzipErrors;
+
+/// The header ID of WinZip's AES extra field.
+const AES_EXTRA_FIELD: u16 = 0x9901;
+
+/// An entry failed its integrity check, carried through
+/// [`std::io::Error`] so that it can be told apart from other failures to
+/// read an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IntegrityFailure {
+    CrcMismatch,
+    AuthenticationFailed,
+}
+
+impl IntegrityFailure {
+    pub(crate) fn into_io_error(self) -> std::io::Error {
+        std::io::Error::new(ErrorKind::InvalidData, self)
+    }
+}
+
+impl std::fmt::Display for IntegrityFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CrcMismatch => write!(f, "Invalid checksum"),
+            Self::AuthenticationFailed => write!(f, "Invalid authentication code"),
+        }
+    }
+}
+
+impl std::error::Error for IntegrityFailure {}
+
+/// If `e` is because the named entry failed its integrity check, the
+/// error to report for it.
+pub(crate) fn integrity_error(name: &str, e: &std::io::Error) -> Option<RipunzipErrors> {
+    let name = name.to_string();
+    match e.get_ref()?.downcast_ref::<IntegrityFailure>()? {
+        IntegrityFailure::CrcMismatch => Some(RipunzipErrors::CrcMismatch { name }),
+        IntegrityFailure::AuthenticationFailed => {
+            Some(RipunzipErrors::AuthenticationFailed { name })
+        }
+    }
+}
+
+/// Whether the extra field `extra` says that the entry is AES encrypted.
+pub(crate) fn is_aes_encrypted(mut extra: &[u8]) -> bool {
+    while let [id0, id1, len0, len1, rest @ ..] = extra {
+        if u16::from_le_bytes([*id0, *id1]) == AES_EXTRA_FIELD {
+            return true;
+        }
+        let len = u16::from_le_bytes([*len0, *len1]) as usize;
+        extra = rest.get(len..).unwrap_or_default();
+    }
+    false
+}
+
+/// Reads an entry's data as decompressed by the zip library, turning its
+/// integrity check failures into [`IntegrityFailure`]s. The library only
+/// says that the data is invalid, so we work out why from what we know of
+/// the entry. For an AES encrypted entry, the library checks the
+/// authentication code which follows the encrypted data, and anything
+/// else invalid must be due to the encrypted data being altered too.
+/// Otherwise, it checks the CRC once it's decompressed the whole entry,
+/// so we check it too.
+pub(crate) struct IntegrityCheckingReader<R: Read> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    expected_crc: u32,
+    /// How much of the entry's recorded size is still to come.
+    remaining: u64,
+    aes_encrypted: bool,
+}
+
+impl<R: Read> IntegrityCheckingReader<R> {
+    pub(crate) fn new(inner: R, expected_crc: u32, size: u64, aes_encrypted: bool) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            expected_crc,
+            remaining: size,
+            aes_encrypted,
+        }
+    }
+
+    fn classify(&self, e: std::io::Error) -> std::io::Error {
+        if e.kind() != ErrorKind::InvalidData {
+            e
+        } else if self.aes_encrypted {
+            IntegrityFailure::AuthenticationFailed.into_io_error()
+        } else if self.remaining == 0 && self.hasher.clone().finalize() != self.expected_crc {
+            IntegrityFailure::CrcMismatch.into_io_error()
+        } else {
+            e
+        }
+    }
+}
+
+impl<R: Read> Read for IntegrityCheckingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf).map_err(|e| self.classify(e))?;
+        self.hasher.update(&buf[..count]);
+        self.remaining = self.remaining.saturating_sub(count as u64);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Read};
+
+    use super::{integrity_error, is_aes_encrypted, IntegrityCheckingReader};
+    use crate::RipunzipErrors;
+
+    /// Gives `data`, then fails as the zip library does.
+    fn failing(data: &'static [u8]) -> impl Read {
+        data.chain(FailingReader)
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(ErrorKind::InvalidData, "whatever"))
+        }
+    }
+
+    fn classify(mut reader: IntegrityCheckingReader<impl Read>) -> Option<RipunzipErrors> {
+        let e = std::io::copy(&mut reader, &mut std::io::sink()).unwrap_err();
+        integrity_error("a.txt", &e)
+    }
+
+    #[test]
+    fn test_integrity_checking_reader() {
+        let crc = crc32fast::hash(b"abc");
+        // All the data arrived, but not as recorded.
+        assert!(matches!(
+            classify(IntegrityCheckingReader::new(failing(b"abd"), crc, 3, false)),
+            Some(RipunzipErrors::CrcMismatch { name }) if name == "a.txt"
+        ));
+        // Not all of it arrived, so something else went wrong.
+        assert!(classify(IntegrityCheckingReader::new(failing(b"ab"), crc, 3, false)).is_none());
+        assert!(classify(IntegrityCheckingReader::new(failing(b"abc"), crc, 3, false)).is_none());
+        assert!(matches!(
+            classify(IntegrityCheckingReader::new(failing(b"a"), crc, 3, true)),
+            Some(RipunzipErrors::AuthenticationFailed { .. })
+        ));
+        let mut reader = IntegrityCheckingReader::new(&b"abc"[..], crc, 3, false);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"abc");
+    }
+
+    #[test]
+    fn test_is_aes_encrypted() {
+        assert!(!is_aes_encrypted(&[]));
+        assert!(is_aes_encrypted(&[
+            0x01, 0x99, 7, 0, 2, 0, b'A', b'E', 3, 8, 0
+        ]));
+        // After another field.
+        assert!(is_aes_encrypted(&[0x55, 0x54, 1, 0, 0, 0x01, 0x99, 0, 0]));
+        assert!(!is_aes_encrypted(&[0x55, 0x54, 3, 0, 0x01, 0x99, 0]));
+        // Truncated.
+        assert!(!is_aes_encrypted(&[0x55, 0x54, 9, 0, 0x01, 0x99, 0, 0]));
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::PathBuf;
+
+use super::{
+    dry_run::{components, enclosed_components},
+    UnzipOptions,
+};
+
+/// Names which Windows reserves for devices, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The path, relative to the output directory, at which to extract the
+/// entry with the given name, as
+/// [`enclosed_name`](super::dry_run::enclosed_name) decides, and whether
+/// it's been renamed because of [`UnzipOptions::sanitize_invalid_names`].
+/// Absolute names are refused according to
+/// [`UnzipOptions::absolute_paths`].
+pub(super) fn target_name(name: &str, options: &UnzipOptions) -> Option<(PathBuf, bool)> {
+    if options.absolute_paths.rejects(name, options) {
+        return None;
+    }
+    if !(options.sanitize_invalid_names && cfg!(windows)) {
+        let components = enclosed_components(name, options.normalize_separators)?;
+        return Some((components.iter().collect(), false));
+    }
+    // Sanitizing encodes every `:`, so these are safe too.
+    Some(sanitize(&components(name, options.normalize_separators)?))
+}
+
+/// The path made of `components`, each made valid on Windows, and whether
+/// any needed to be. Characters Windows doesn't allow are percent-encoded,
+/// as is the last character of a reserved name such as `CON`, so
+/// `a:b?.txt` becomes `a%3Ab%3F.txt` and `con.txt` becomes `co%6E.txt`.
+/// We can't do this once they're a path, since Windows takes `a:` to be a
+/// drive.
+fn sanitize(components: &[&str]) -> (PathBuf, bool) {
+    let mut renamed = false;
+    let path = components
+        .iter()
+        .map(|component| match sanitize_component(component) {
+            Some(sanitized) => {
+                renamed = true;
+                sanitized
+            }
+            None => component.to_string(),
+        })
+        .collect();
+    (path, renamed)
+}
+
+fn sanitize_component(component: &str) -> Option<String> {
+    let stem = component.split('.').next().unwrap_or_default();
+    let reserved = RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved));
+    let mut sanitized = String::with_capacity(component.len());
+    let mut changed = false;
+    let mut chars = component.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let is_last = chars.peek().is_none();
+        let invalid = matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*')
+            || c.is_ascii_control()
+            // Windows drops trailing dots and spaces.
+            || (is_last && matches!(c, '.' | ' '))
+            || (reserved && i + c.len_utf8() == stem.len());
+        if invalid {
+            sanitized.push_str(&format!("%{:02X}", c as u32));
+            changed = true;
+        } else {
+            sanitized.push(c);
+        }
+    }
+    changed.then_some(sanitized)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::sanitize;
+
+    #[test]
+    fn test_sanitize() {
+        for (components, sanitized) in [
+            (&["a:b?.txt"][..], Some("a%3Ab%3F.txt")),
+            (&["dir", "a*b"], Some("dir/a%2Ab")),
+            (&["d<i>r", "a|b\"c"], Some("d%3Ci%3Er/a%7Cb%22c")),
+            (&["CON"], Some("CO%4E")),
+            (&["dir", "con.txt"], Some("dir/co%6E.txt")),
+            (&["lpt1.tar.gz"], Some("lpt%31.tar.gz")),
+            (&["trailing."], Some("trailing%2E")),
+            (&["trailing "], Some("trailing%20")),
+            (&["tab\there"], Some("tab%09here")),
+            (&["console.txt"], None),
+            (&["dir", "a b.txt"], None),
+            (&["100%.txt"], None),
+            (&["héllo.txt"], None),
+        ] {
+            let unchanged = components.iter().collect::<PathBuf>();
+            assert_eq!(
+                sanitize(components),
+                match sanitized {
+                    Some(sanitized) => (sanitized.split('/').collect(), true),
+                    None => (unchanged, false),
+                },
+                "{components:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{Cursor, Read, Write};
+
+use super::{copy_with_buffer_size, FilenameFilter};
+
+/// How much of the start of a file we examine to decide whether it's text.
+const TEXT_SAMPLE_LEN: u64 = 8192;
+
+/// A style of line ending.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// Whichever is usual for the platform we're running on.
+    Native,
+}
+
+impl LineEnding {
+    fn resolve(self) -> Self {
+        match self {
+            LineEnding::Native if cfg!(windows) => LineEnding::CrLf,
+            LineEnding::Native => LineEnding::Lf,
+            other => other,
+        }
+    }
+}
+
+/// Options for converting line endings of text files as they're extracted,
+/// like `unzip -a`.
+pub struct LineEndingMode<'a> {
+    /// The line ending to convert to.
+    pub line_ending: LineEnding,
+    /// Which files to consider for conversion. If absent, all files are
+    /// considered. Either way, a file is only converted if its first few
+    /// kilobytes look like text, so binary files are left alone.
+    pub filename_filter: Option<Box<dyn FilenameFilter + Send + Sync + 'a>>,
+}
+
+impl LineEndingMode<'_> {
+    pub(crate) fn applies_to(&self, filename: &str) -> bool {
+        self.filename_filter
+            .as_ref()
+            .map(|filter| filter.should_unzip(filename))
+            .unwrap_or(true)
+    }
+}
+
+/// Conservatively decide whether some data is text: any NUL or unusual
+/// control character means it isn't.
+fn looks_like_text(sample: &[u8]) -> bool {
+    sample
+        .iter()
+        .all(|&b| b >= 0x20 || matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+}
+
+/// Copy from `reader` to `writer`, converting line endings if the data
+/// looks like text. Returns the number of bytes read.
+pub(crate) fn copy_converting_line_endings<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    line_ending: LineEnding,
+    buffer_size: usize,
+) -> std::io::Result<u64> {
+    let mut sample = Vec::new();
+    reader.take(TEXT_SAMPLE_LEN).read_to_end(&mut sample)?;
+    let is_text = looks_like_text(&sample);
+    let mut reader = Cursor::new(sample).chain(reader);
+    if !is_text {
+        return copy_with_buffer_size(&mut reader, writer, buffer_size);
+    }
+    let mut writer = LineEndingWriter::new(writer, line_ending);
+    let bytes_read = copy_with_buffer_size(&mut reader, &mut writer, buffer_size)?;
+    writer.finish()?;
+    Ok(bytes_read)
+}
+
+/// A [`Write`] which converts any mixture of `\n` and `\r\n` line endings
+/// into the requested style. Lone `\r`s are left alone.
+struct LineEndingWriter<W: Write> {
+    inner: W,
+    line_ending: LineEnding,
+    /// The last byte written was a `\r`. When converting to LF, we've
+    /// held it back until we see whether it's followed by `\n`.
+    after_cr: bool,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> LineEndingWriter<W> {
+    fn new(inner: W, line_ending: LineEnding) -> Self {
+        Self {
+            inner,
+            line_ending: line_ending.resolve(),
+            after_cr: false,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Write out anything held back at the end of the data.
+    fn finish(&mut self) -> std::io::Result<()> {
+        if self.after_cr && self.line_ending == LineEnding::Lf {
+            self.inner.write_all(b"\r")?;
+        }
+        self.after_cr = false;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for LineEndingWriter<W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.clear();
+        for &b in data {
+            match (self.line_ending, b) {
+                (LineEnding::Lf, b'\r') => {
+                    if self.after_cr {
+                        self.buf.push(b'\r');
+                    }
+                }
+                (LineEnding::Lf, b) => {
+                    if self.after_cr && b != b'\n' {
+                        self.buf.push(b'\r');
+                    }
+                    self.buf.push(b);
+                }
+                (_, b'\n') if !self.after_cr => self.buf.extend_from_slice(b"\r\n"),
+                (_, b) => self.buf.push(b),
+            }
+            self.after_cr = b == b'\r';
+        }
+        self.inner.write_all(&self.buf)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy_converting_line_endings, LineEnding};
+
+    fn convert(input: &[u8], line_ending: LineEnding) -> Vec<u8> {
+        let mut output = Vec::new();
+        let bytes_read =
+            copy_converting_line_endings(&mut &input[..], &mut output, line_ending, 4).unwrap();
+        assert_eq!(bytes_read, input.len() as u64);
+        output
+    }
+
+    #[test]
+    fn test_mixed_to_lf() {
+        assert_eq!(
+            convert(b"one\r\ntwo\nthree\rfour\r\n\r\n", LineEnding::Lf),
+            b"one\ntwo\nthree\rfour\n\n"
+        );
+        assert_eq!(convert(b"trailing\r", LineEnding::Lf), b"trailing\r");
+    }
+
+    #[test]
+    fn test_mixed_to_crlf() {
+        assert_eq!(
+            convert(b"one\r\ntwo\nthree\rfour\n\n", LineEnding::CrLf),
+            b"one\r\ntwo\r\nthree\rfour\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_untouched() {
+        let binary = b"\x00\x01one\r\ntwo\n";
+        assert_eq!(convert(binary, LineEnding::Lf), binary);
+        assert_eq!(convert(binary, LineEnding::CrLf), binary);
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use zip::read::ZipFile;
+
+use crate::RipunzipErrors;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const LOCAL_HEADER_LEN: usize = 30;
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+const ZIP64_MARKER: u32 = 0xFFFFFFFF;
+
+/// The fields of a local file header which duplicate information in the
+/// central directory.
+struct LocalHeader {
+    flags: u16,
+    crc32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+}
+
+pub(super) fn u16_at(buf: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes(buf[pos..pos + 2].try_into().unwrap())
+}
+
+pub(super) fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap())
+}
+
+pub(super) fn u64_at(buf: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(buf[pos..pos + 8].try_into().unwrap())
+}
+
+impl LocalHeader {
+    fn read<R: Read + Seek>(reader: &mut R, header_start: u64) -> std::io::Result<Self> {
+        reader.seek(SeekFrom::Start(header_start))?;
+        let mut fixed = [0u8; LOCAL_HEADER_LEN];
+        reader.read_exact(&mut fixed)?;
+        if u32_at(&fixed, 0) != LOCAL_HEADER_SIGNATURE {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "invalid local file header signature",
+            ));
+        }
+        let mut header = Self {
+            flags: u16_at(&fixed, 6),
+            crc32: u32_at(&fixed, 14),
+            compressed_size: u32_at(&fixed, 18) as u64,
+            uncompressed_size: u32_at(&fixed, 22) as u64,
+        };
+        let name_len = u16_at(&fixed, 26) as usize;
+        let extra_len = u16_at(&fixed, 28) as usize;
+        if header.compressed_size == ZIP64_MARKER as u64
+            || header.uncompressed_size == ZIP64_MARKER as u64
+        {
+            // The real sizes are in the zip64 extra field. Unlike in the
+            // central directory, the local version must contain both.
+            let mut variable = vec![0u8; name_len + extra_len];
+            reader.read_exact(&mut variable)?;
+            let mut extra = &variable[name_len..];
+            while extra.len() >= 4 {
+                let id = u16_at(extra, 0);
+                let len = u16_at(extra, 2) as usize;
+                let data = &extra[4..(4 + len).min(extra.len())];
+                if id == ZIP64_EXTRA_FIELD_ID && data.len() >= 16 {
+                    header.uncompressed_size = u64_at(data, 0);
+                    header.compressed_size = u64_at(data, 8);
+                    break;
+                }
+                extra = &extra[(4 + len).min(extra.len())..];
+            }
+        }
+        Ok(header)
+    }
+}
+
+/// Read the extra field of the local header at `header_start`, which may
+/// differ from the one in the central directory.
+pub(crate) fn read_local_extra_field<R: Read + Seek>(
+    reader: &mut R,
+    header_start: u64,
+) -> Result<Vec<u8>, RipunzipErrors> {
+    reader.seek(SeekFrom::Start(header_start))?;
+    let mut fixed = [0u8; LOCAL_HEADER_LEN];
+    reader.read_exact(&mut fixed)?;
+    if u32_at(&fixed, 0) != LOCAL_HEADER_SIGNATURE {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "invalid local file header signature",
+        )
+        .into());
+    }
+    let name_len = u16_at(&fixed, 26) as i64;
+    reader.seek(SeekFrom::Current(name_len))?;
+    let mut extra = vec![0u8; u16_at(&fixed, 28) as usize];
+    reader.read_exact(&mut extra)?;
+    Ok(extra)
+}
+
+/// Check that the local header of `file` agrees with what the central
+/// directory told us about it. `reader` must be a separate reader onto the
+/// same zip file, since `file` is already positioned at its data.
+pub(crate) fn check_local_header<R: Read + Seek, F: Read>(
+    reader: &mut R,
+    file: &ZipFile<F>,
+) -> Result<(), RipunzipErrors> {
+    let local = LocalHeader::read(reader, file.header_start())?;
+    if local.flags & FLAG_DATA_DESCRIPTOR != 0 {
+        // The sizes and CRC follow the data rather than being in the local
+        // header, which will contain zeros.
+        return Ok(());
+    }
+    let mismatch = |field, central, local| RipunzipErrors::HeaderMismatch {
+        name: file.name().to_string(),
+        field,
+        central,
+        local,
+    };
+    if local.compressed_size != file.compressed_size() {
+        return Err(mismatch(
+            "compressed size",
+            file.compressed_size(),
+            local.compressed_size,
+        ));
+    }
+    if local.uncompressed_size != file.size() {
+        return Err(mismatch(
+            "uncompressed size",
+            file.size(),
+            local.uncompressed_size,
+        ));
+    }
+    if local.crc32 != file.crc32() {
+        return Err(mismatch("CRC", file.crc32() as u64, local.crc32 as u64));
+    }
+    Ok(())
+}
@@ -0,0 +1,221 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    collections::HashSet,
+    io::{ErrorKind, Read},
+    path::PathBuf,
+};
+
+use sha2::{digest::DynDigest, Digest, Sha256, Sha384, Sha512};
+
+use super::{dry_run::enclosed_name, UnzipOptions, UnzipOutcome, ZipEntryMetadata};
+use crate::RipunzipErrors;
+
+/// Which hash the checksums in [`UnzipOptions::verify_manifest`] are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// SHA-256, as listed by `sha256sum`.
+    #[default]
+    Sha256,
+    /// SHA-384, as listed by `sha384sum`.
+    Sha384,
+    /// SHA-512, as listed by `sha512sum`.
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn hasher(self) -> Box<dyn DynDigest + Send> {
+        match self {
+            Self::Sha256 => Box::new(Sha256::new()),
+            Self::Sha384 => Box::new(Sha384::new()),
+            Self::Sha512 => Box::new(Sha512::new()),
+        }
+    }
+}
+
+/// The checksums disagreed, carried through [`std::io::Error`] so that it
+/// can be told apart from other failures to read an entry.
+#[derive(Debug)]
+struct ManifestMismatch {
+    expected: String,
+    actual: String,
+}
+
+impl std::fmt::Display for ManifestMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "hash is {} rather than {}", self.actual, self.expected)
+    }
+}
+
+impl std::error::Error for ManifestMismatch {}
+
+/// Wraps a reader of an entry's decompressed data, failing at the end of
+/// it if the data's hash isn't the one expected.
+pub(crate) struct ManifestCheckingReader<R: Read> {
+    inner: R,
+    hasher: Box<dyn DynDigest + Send>,
+    /// Lowercase hex.
+    expected: String,
+}
+
+impl<R: Read> ManifestCheckingReader<R> {
+    pub(crate) fn new(inner: R, algorithm: HashAlgorithm, expected: &str) -> Self {
+        Self {
+            inner,
+            hasher: algorithm.hasher(),
+            expected: expected.to_ascii_lowercase(),
+        }
+    }
+}
+
+impl<R: Read> Read for ManifestCheckingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        if count == 0 && !buf.is_empty() {
+            let actual: String = self
+                .hasher
+                .box_clone()
+                .finalize()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            if actual != self.expected {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    ManifestMismatch {
+                        expected: self.expected.clone(),
+                        actual,
+                    },
+                ));
+            }
+        }
+        self.hasher.update(&buf[..count]);
+        Ok(count)
+    }
+}
+
+/// The checksum expected for the entry named `name`, if any.
+pub(crate) fn expected_checksum<'o>(options: &'o UnzipOptions, name: &str) -> Option<&'o str> {
+    let manifest = options.verify_manifest.as_ref()?;
+    let path = enclosed_name(name, options.normalize_separators)?;
+    manifest.get(&path).map(String::as_str)
+}
+
+/// If `e` is because an entry didn't match the manifest, the error to
+/// report for it.
+pub(crate) fn manifest_error(name: &str, e: &std::io::Error) -> Option<RipunzipErrors> {
+    let mismatch = e.get_ref()?.downcast_ref::<ManifestMismatch>()?;
+    Some(RipunzipErrors::ManifestMismatch {
+        name: name.to_string(),
+        expected: mismatch.expected.clone(),
+        actual: mismatch.actual.clone(),
+    })
+}
+
+/// Which files the manifest and the zip file don't agree on the presence
+/// of, for [`UnzipOutcome::not_in_manifest`] and
+/// [`UnzipOutcome::missing_from_zip`].
+#[derive(Default)]
+pub(crate) struct ManifestCoverage {
+    not_in_manifest: Vec<String>,
+    missing_from_zip: Vec<PathBuf>,
+}
+
+impl ManifestCoverage {
+    /// Compare the manifest, if there is one, with those of `entries`
+    /// which are files matching the filename filter.
+    pub(crate) fn new(options: &UnzipOptions, entries: &[ZipEntryMetadata]) -> Self {
+        let Some(manifest) = &options.verify_manifest else {
+            return Self::default();
+        };
+        let mut present = HashSet::new();
+        let mut not_in_manifest = Vec::new();
+        for entry in entries {
+            let Some(path) = enclosed_name(&entry.name, options.normalize_separators) else {
+                continue;
+            };
+            present.insert(path.clone());
+            let wanted = options
+                .filename_filter
+                .as_ref()
+                .map_or(true, |filter| filter.should_unzip(&entry.name));
+            if wanted && !entry.name.ends_with('/') && !manifest.contains_key(&path) {
+                not_in_manifest.push(entry.name.clone());
+            }
+        }
+        let mut missing_from_zip: Vec<_> = manifest
+            .keys()
+            .filter(|path| !present.contains(*path))
+            .cloned()
+            .collect();
+        missing_from_zip.sort();
+        if !not_in_manifest.is_empty() {
+            log::warn!(
+                "{} files are not in the checksum manifest, so can't be verified",
+                not_in_manifest.len()
+            );
+        }
+        if !missing_from_zip.is_empty() {
+            log::warn!(
+                "{} files in the checksum manifest are not in the zip file",
+                missing_from_zip.len()
+            );
+        }
+        Self {
+            not_in_manifest,
+            missing_from_zip,
+        }
+    }
+
+    pub(crate) fn record(self, outcome: &mut UnzipOutcome) {
+        outcome.not_in_manifest = self.not_in_manifest;
+        outcome.missing_from_zip = self.missing_from_zip;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Read};
+
+    use super::{manifest_error, HashAlgorithm, ManifestCheckingReader};
+    use crate::RipunzipErrors;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_manifest_checking_reader() {
+        let mut contents = String::new();
+        ManifestCheckingReader::new(
+            "hello".as_bytes(),
+            HashAlgorithm::Sha256,
+            &HELLO_SHA256.to_uppercase(),
+        )
+        .read_to_string(&mut contents)
+        .unwrap();
+        assert_eq!(contents, "hello");
+
+        let e =
+            ManifestCheckingReader::new("hellO".as_bytes(), HashAlgorithm::Sha256, HELLO_SHA256)
+                .read_to_string(&mut contents)
+                .unwrap_err();
+        match manifest_error("a.txt", &e) {
+            Some(RipunzipErrors::ManifestMismatch {
+                name,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(name, "a.txt");
+                assert_eq!(expected, HELLO_SHA256);
+                assert_eq!(actual.len(), 64);
+            }
+            other => panic!("{other:?}"),
+        }
+        assert!(manifest_error("a.txt", &std::io::Error::new(ErrorKind::Other, "other")).is_none());
+    }
+}
@@ -6,28 +6,171 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod absolute_paths;
+mod anchored;
+mod background;
+mod cache;
+mod central_directory;
+mod completion_marker;
+mod compression_stats;
+#[cfg(feature = "http")]
+mod consistency;
+#[cfg(feature = "http")]
+mod cookies;
+mod decompressor_registry;
+mod dedup;
+mod delta;
+mod dry_run;
+mod duplicates;
+mod entry_reader;
+mod entry_stream;
+mod estimate;
+mod exclude_extensions;
+#[cfg(feature = "http")]
 mod http_range_reader;
-mod multi_file_seeker;
+mod index;
+mod integrity;
+mod invalid_names;
+mod line_endings;
+mod local_header;
+mod manifest;
+mod mtimes;
+mod multi_unzip;
+mod network_events;
+mod only_entries;
+mod open_archive;
+mod open_file_limit;
+mod output_lock;
+mod overlay;
+mod overwrite;
+mod partial_files;
+mod progress_coalescer;
+mod progress_file;
 mod progress_updater;
+mod recompress;
+#[cfg(feature = "http")]
+mod root_certificates;
+#[cfg(feature = "http")]
 mod seekable_http_reader;
+mod segments;
+#[cfg(feature = "ssh")]
+mod sftp_reader;
+mod shard;
+#[cfg(feature = "signature")]
+mod signature;
+mod single_root;
+mod sparse;
+mod timing;
+mod total_size;
+mod transform;
+mod unix_extra;
+mod unknown_sizes;
+#[cfg(feature = "http")]
+mod uri_options;
+mod worker_threads;
 
 use std::{
+    any::Any,
     borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
     fs::File,
-    io::{ErrorKind, Read, Seek, SeekFrom},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    panic::AssertUnwindSafe,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::{Duration, Instant},
 };
 
 use rayon::prelude::*;
-use zip::{read::ZipFile, ZipArchive};
+use zip::{
+    read::{ZipFile, ZipReadOptions},
+    result::ZipError,
+    CompressionMethod, ZipArchive,
+};
 
 use crate::{
-    unzip::{multi_file_seeker::MultiFileSeeker, progress_updater::ProgressUpdater},
+    io::{CloneableFile, HasLength, SharedBytes},
+    unzip::progress_updater::ProgressUpdater,
     RipunzipErrors,
 };
 
+pub use self::absolute_paths::AbsolutePathPolicy;
+pub use self::background::{BackgroundUnzip, UnzipControl};
+pub use self::cache::{CachedExtraction, ExtractionCache};
+pub use self::compression_stats::{CompressionStats, MethodStats};
+#[cfg(feature = "http")]
+pub use self::cookies::Cookies;
+pub use self::decompressor_registry::{Decompressor, DecompressorRegistry};
+pub use self::delta::ExtractionDelta;
+pub use self::dry_run::{ExtractionPlan, PlannedAction, PlannedDecision, PlannedEntry};
+pub use self::duplicates::DuplicatePolicy;
+pub use self::entry_stream::{ExtractedEntry, UnzipIter};
+pub use self::estimate::Throughput;
+pub use self::line_endings::{LineEnding, LineEndingMode};
+pub use self::manifest::HashAlgorithm;
+pub use self::multi_unzip::MultiUnzip;
+pub use self::network_events::NetworkEvent;
+pub use self::only_entries::MissingEntries;
+pub use self::output_lock::OutputLock;
+pub use self::overwrite::{
+    OverwriteCallback, OverwriteDecision, OverwritePolicy, ZipEntryMetadata,
+};
+pub use self::partial_files::PartialFiles;
+pub use self::progress_coalescer::ProgressCoalescing;
+#[cfg(feature = "http")]
+pub use self::root_certificates::RootCertificates;
+#[cfg(feature = "signature")]
+pub use self::signature::{SignatureConfig, SignatureSource};
+pub use self::timing::{Bottleneck, ExtractionTimings};
+pub use self::transform::{ByteTransformer, EntryTransform};
+pub use self::unknown_sizes::UnknownSizes;
+#[cfg(feature = "http")]
+pub use self::uri_options::UriOptions;
+pub use self::worker_threads::WorkerThreadOptions;
+
+use self::anchored::Anchor;
+use self::background::Prioritization;
+use self::central_directory::{local_header_offsets, read_central_directory};
+use self::completion_marker::{remove_marker, ArchiveIdentity};
+#[cfg(feature = "http")]
+use self::consistency::check_consistent_length;
+use self::decompressor_registry::Crc32CheckingReader;
+use self::dedup::{link_or_copy, remove_if_present, Comparison, DedupKey, Deduplicator};
+use self::delta::DeltaFilter;
+use self::duplicates::superseded_entries;
+use self::entry_reader::EntryReader;
+use self::entry_stream::EntryStream;
+use self::exclude_extensions::apply_exclude_extensions;
+#[cfg(feature = "http")]
+use self::http_range_reader::ClientConfig;
+use self::index::{index_entries, write_index, IndexEntry};
+use self::integrity::{integrity_error, is_aes_encrypted, IntegrityCheckingReader};
+use self::invalid_names::target_name;
+use self::line_endings::copy_converting_line_endings;
+use self::local_header::check_local_header;
+use self::manifest::{expected_checksum, manifest_error, ManifestCheckingReader, ManifestCoverage};
+use self::mtimes::{set_times, DirectoryMtimes, FileTime};
+#[cfg(feature = "http")]
+use self::network_events::forward_network_events;
+use self::only_entries::restrict_to_only_entries;
+use self::open_archive::open_archive;
+use self::open_file_limit::OpenFileLimit;
+use self::output_lock::lock_output;
+use self::overlay::matches_base;
+use self::overwrite::OverwriteGate;
+use self::partial_files::PartialFileTracker;
+use self::progress_coalescer::coalesce_progress;
+use self::progress_file::add_progress_file;
+use self::recompress::{recompress_to, WriteSeek};
+#[cfg(feature = "http")]
 use self::seekable_http_reader::{AccessPattern, SeekableHttpReader, SeekableHttpReaderEngine};
+use self::shard::{shard_path, PathLocks};
+use self::single_root::apply_flatten_single_root;
+use self::sparse::SparseWriter;
+use self::timing::{source_reading_time, thread_cpu_time, Timed, TimedSource};
+use self::total_size::{total_size_error, TotalSize};
+use self::transform::TransformingWriter;
+use self::unix_extra::{set_owner, UnixExtra};
 
 pub(crate) fn determine_stream_len<R: Seek>(stream: &mut R) -> std::io::Result<u64> {
     let old_pos = stream.stream_position()?;
@@ -44,22 +187,512 @@ pub struct UnzipOptions<'a, 'b> {
     pub output_directory: Option<PathBuf>,
     /// Password if encrypted.
     pub password: Option<String>,
-    /// Whether to run in single-threaded mode.
+    /// Whether to run in single-threaded mode. Files are then extracted
+    /// one after another on the calling thread, and rayon's thread pool is
+    /// never used, so it isn't created if it doesn't already exist. This
+    /// can be much quicker for tiny zip files.
     pub single_threaded: bool,
     /// A filename filter, optionally
-    pub filename_filter: Option<Box<dyn FilenameFilter + Sync + 'a>>,
+    pub filename_filter: Option<Box<dyn FilenameFilter + Send + Sync + 'a>>,
     /// An object to receive notifications of unzip progress.
-    pub progress_reporter: Box<dyn UnzipProgressReporter + Sync + 'b>,
+    pub progress_reporter: Box<dyn UnzipProgressReporter + Send + Sync + 'b>,
+    /// Whether to check the CRC of every entry to be extracted before
+    /// writing any files, so that a corrupt archive never leaves partial
+    /// output. This requires decompressing everything twice. For URIs it
+    /// also means the archive data is fetched twice, unless the server
+    /// lacks range support and we've already fallen back to downloading
+    /// the whole file into a temporary file.
+    pub verify_before_extract: bool,
+    /// Decompressors for compression methods not supported by the zip
+    /// library, optionally.
+    pub decompressor_registry: Option<DecompressorRegistry>,
+    /// The central directory is always the authoritative source of each
+    /// file's location, size and CRC. In strict mode, we also check that each
+    /// file's local header agrees with it, and fail to extract the file
+    /// if not. Such disagreement may indicate a malicious zip file designed to
+    /// be interpreted differently by different tools. This requires reading
+    /// each local header twice.
+    pub strict: bool,
+    /// Whether to convert line endings in text files, optionally.
+    pub text_conversion: Option<LineEndingMode<'a>>,
+    /// A function which may give a [`ByteTransformer`] through which to
+    /// pass each entry's data as it's written, for example to rewrite or
+    /// scan it without reading the extracted files again. The data is
+    /// transformed after any line ending conversion. Transformed entries
+    /// aren't deduplicated. Transforms run on the worker threads, so must
+    /// be `Send`.
+    pub transform: Option<EntryTransform<'a>>,
+    /// What to do if a file we're extracting already exists.
+    pub overwrite_policy: OverwritePolicy<'a>,
+    /// The maximum number of components in the path of any entry, such
+    /// that `a/b/c.txt` has a depth of 3. Entries nested more deeply fail to
+    /// extract, to guard against pathologically deep directory trees.
+    pub max_path_depth: Option<usize>,
+    /// Whether to hard link files with identical contents to the first such
+    /// file written during this unzip, rather than writing them again. Each
+    /// file is still decompressed to check that its contents really are the
+    /// same. If the filesystem doesn't support hard links, files are copied
+    /// instead. Files whose line endings are converted aren't deduplicated,
+    /// nor are files which are to have different modes or, with
+    /// `preserve_mtimes` or `preserve_ownership`, times or owners, since
+    /// hard links share them.
+    pub dedup: bool,
+    /// Whether to make sure extracted files are on disk before finishing.
+    pub durability: Durability,
+    /// Whether to write through symbolic links which already exist within
+    /// the output directory. By default, we refuse to extract any entry
+    /// whose path passes through or ends at a symbolic link, since it may
+    /// take us outside the output directory.
+    pub follow_existing_symlinks: bool,
+    /// Whether to fail, before writing anything, if the output directory
+    /// already exists and isn't empty.
+    pub require_empty_output: bool,
+    /// When requiring an empty output directory, ignore hidden files such
+    /// as `.DS_Store`; that is, those whose names start with a dot.
+    pub empty_output_ignores_hidden: bool,
+    /// Whether to lock the output directory while extracting into it, so
+    /// that other unzips which lock it wait or fail rather than writing
+    /// there at the same time. It's taken before anything else, so checks
+    /// such as `require_empty_output` and `completion_marker` see what an
+    /// earlier unzip holding it left. See [`OutputLock`] for how, and
+    /// caveats about network filesystems. Not locked for a dry run.
+    pub output_lock: OutputLock,
+    /// Whether to create the output directory, and any missing parents, if
+    /// it doesn't exist. Otherwise unzipping fails before anything is
+    /// written, as when the output directory should be on a mount which
+    /// may not be present.
+    pub create_output_directory: bool,
+    /// A directory, laid out like the output directory, holding files which
+    /// needn't be extracted, optionally. Files in the zip file which are
+    /// the same size and have the same CRC-32 as the file at the same path
+    /// in this directory are reported as [`SkipReason::UpToDate`] rather
+    /// than extracted, so that the output directory ends up holding only
+    /// what's new or changed, as a layer over this one. Nothing in this
+    /// directory is changed. Entries sent elsewhere by `prefix_map` are
+    /// extracted regardless, as are AES encrypted entries which don't
+    /// record their CRC.
+    pub overlay_base: Option<PathBuf>,
+    /// The size of the buffer used to copy each file's decompressed data to
+    /// its destination, which is also the size of each write. One buffer is
+    /// allocated per file being extracted, so with many threads large
+    /// buffers add up. Defaults to 256KiB.
+    pub io_buffer_size: Option<usize>,
+    /// Whether to extract files one at a time in the order they're stored,
+    /// so that the zip file is read from start to end just once. This is
+    /// quicker than parallel extraction only where seeking is very slow,
+    /// as on spinning disks. Implies `single_threaded`.
+    pub sequential: bool,
+    /// Whether to refuse to write anywhere but the filesystem containing
+    /// the output directory, for instance beneath a mount point within it
+    /// or, with `follow_existing_symlinks`, through a symbolic link to
+    /// another filesystem. Only supported on Unix, where filesystems are
+    /// identified by their device number; elsewhere unzipping fails.
+    pub restrict_to_device: bool,
+    /// Whether to only report, via the progress reporter, what unzipping
+    /// would do, without writing anything. Each file to be extracted is
+    /// reported to `extraction_planned`, and each file not to be extracted
+    /// to `entry_skipped`, according to the filename filter, overwrite
+    /// policy and maximum entry size. Only the central directory is read,
+    /// so nothing more is downloaded. Other checks which could fail a real
+    /// unzip, such as `verify_before_extract`, aren't made.
+    pub dry_run: bool,
+    /// Whether to work out what will be extracted where, as
+    /// [`UnzipEngine::plan`] does, before extracting anything, logging each
+    /// entry's plan as a line of JSON at info level and passing the whole
+    /// plan to [`UnzipProgressReporter::plan_ready`]. For a real unzip, a
+    /// callback overwrite policy is consulted about existing files both
+    /// while planning and again while extracting.
+    pub explain: bool,
+    /// Whether to batch up the bytes reported to
+    /// [`UnzipProgressReporter::bytes_extracted`] rather than reporting
+    /// every read, which with many small files may happen thousands of
+    /// times a second.
+    pub progress_coalescing: Option<ProgressCoalescing>,
+    /// A file to which to append progress as it happens, for another
+    /// process to watch, as well as reporting it to the progress reporter.
+    /// Each event is a line of JSON with an `event` field:
+    /// `total_bytes_expected`, `central_directory_read_starting`,
+    /// `central_directory_read_finished` and `metadata_ready` to begin
+    /// with, `started` and `finished` for each entry, `skipped`, then
+    /// `all_finished`, or `error` if the unzip fails. Most include the
+    /// running total of compressed bytes extracted. Each line is written in
+    /// full as soon as it happens.
+    pub progress_file: Option<PathBuf>,
+    /// A file to write an index of the zip file to, read from the central
+    /// directory before anything's extracted, so that other tools can later
+    /// find entries without reading the central directory themselves. Each
+    /// entry, in central directory order, is a line of JSON like
+    /// `{"name":"a.txt","offset":0,"compressed_size":14,"method":8,"crc32":1234}`,
+    /// where `offset` is that of the entry's local header from the start of
+    /// the file, including anything before the zip file proper, such as a
+    /// self-extractor stub, and `method` is the zip compression method
+    /// number. Any existing file is replaced. Not written for a dry run.
+    pub emit_index: Option<PathBuf>,
+    /// Checksums which extracted files must have, optionally, by their
+    /// paths within the zip file, as listed by `sha256sum`. Each listed
+    /// file's decompressed data is hashed as it's extracted, and if it
+    /// doesn't match, the file fails with
+    /// [`RipunzipErrors::ManifestMismatch`]. Files which aren't listed, and
+    /// listed files which aren't in the zip file, are logged and recorded
+    /// in [`UnzipOutcome::not_in_manifest`] and
+    /// [`UnzipOutcome::missing_from_zip`].
+    pub verify_manifest: Option<HashMap<PathBuf, String>>,
+    /// Optionally, a detached signature over the whole zip file to check
+    /// before extracting anything, failing with
+    /// [`RipunzipErrors::SignatureInvalid`] if it doesn't match. Unlike
+    /// `verify_manifest`, this authenticates the zip file as a whole. The
+    /// whole zip file has to be read first, so for an HTTP(S) URI it's
+    /// downloaded once into a temporary file, which is then extracted, so
+    /// that what's extracted is what was checked; a local file is checked
+    /// and extracted through the same handle. Not checked for a dry run.
+    /// Also checked by [`UnzipEngine::unzip_to_writer`] and
+    /// [`UnzipEngine::concat_to`]. Only available with the `signature`
+    /// feature.
+    #[cfg(feature = "signature")]
+    pub verify_signature: Option<SignatureConfig>,
+    /// Where to create temporary files while unzipping, such as the copy of
+    /// a remote zip file downloaded to check its signature. By default,
+    /// wherever the engine was told to put them, such as
+    /// [`UriOptions::temp_dir`], or else the system's temporary directory.
+    pub temp_dir: Option<PathBuf>,
+    /// Which hash the checksums in `verify_manifest` are. SHA-256 by
+    /// default.
+    pub hash_algorithm: HashAlgorithm,
+    /// Directories other than the output directory into which to extract
+    /// entries beneath given prefixes, such as `bin/` to `/usr/local/bin`.
+    /// The longest matching prefix wins, and the prefix itself is removed
+    /// from the entry's path. Prefixes match whole path components, so
+    /// `bin` doesn't match `binaries/a`. Each directory is treated like the
+    /// output directory for the entries mapped to it, so symbolic link and
+    /// device checks apply within it. Entries matching no prefix go to the
+    /// output directory.
+    pub prefix_map: Vec<(String, PathBuf)>,
+    /// Whether to extract the contents of the zip file's top-level
+    /// directory, if everything is within a single one, straight into the
+    /// output directory, as for a release archive like `app-1.0/...`. This
+    /// is done by adding that directory to `prefix_map`, mapped to the
+    /// output directory, so `overlay_base` doesn't apply. If there's no
+    /// such directory, the zip file is extracted as usual.
+    pub flatten_single_root: bool,
+    /// The largest uncompressed size of any file to extract, optionally.
+    /// Larger files are reported to the progress reporter as skipped rather
+    /// than extracted, and their data is never read, so for a URI it's never
+    /// downloaded.
+    pub max_entry_size: Option<u64>,
+    /// The most uncompressed data to extract in total, optionally. Unlike
+    /// `max_entry_size`, this counts the data as it's decompressed rather
+    /// than trusting the sizes the zip file records, so the unzip fails
+    /// with [`RipunzipErrors::TotalSizeExceeded`] part way through a zip
+    /// bomb, such as one of many entries of compressed zeros, instead of
+    /// filling the disk. Files being extracted when the limit is reached
+    /// are left partly written, as for any other failure. This applies to
+    /// [`UnzipEngine::unzip_to_writer`] and [`UnzipEngine::concat_to`] too,
+    /// and for an engine from [`UnzipEngine::for_entry`], whatever was
+    /// decompressed to get at the zip file counts towards it.
+    pub max_total_size: Option<u64>,
+    /// Which to extract of several entries whose names lead to the same
+    /// path. By default, all of them.
+    pub duplicate_policy: DuplicatePolicy,
+    /// A function deciding the mode of each extracted file and directory,
+    /// overriding the mode stored in the zip file, if any. Returning `None`
+    /// leaves the stored or default mode. Files are created with this mode
+    /// rather than changed to it afterwards, so they're never more
+    /// accessible than intended. Only used on Unix.
+    pub permission_template: Option<PermissionTemplate<'a>>,
+    /// A function to create each file to extract into, given its path,
+    /// instead of creating it as usual, for example to record it before it
+    /// exists, or to open it with other flags. It must return a file open
+    /// for writing and empty, as [`File::create`] does, at that path, since
+    /// its permissions, times and owner are then set there, and it's
+    /// removed from there if the unzip fails. It's called from whichever
+    /// worker thread is extracting the file, several at once unless
+    /// `single_threaded`, so must be `Send` and `Sync`. The file's mode is
+    /// set once it's written rather than when it's created. Files which are
+    /// deduplicated are linked or copied without it, and it's used even
+    /// with `anchored_output`, whose protections then don't apply to files.
+    pub file_factory: Option<FileFactory<'a>>,
+    /// The most entries, including directories, which the zip file may
+    /// contain, optionally. Zip files with more entries fail to extract
+    /// before anything is written, to guard against exhausting inodes.
+    /// The count comes from the central directory, so this is cheap.
+    pub max_entries: Option<usize>,
+    /// Whether to leave holes in extracted files where they contain whole
+    /// blocks of zeros, rather than writing the zeros, to save disk space
+    /// for large sparse files such as disk images. The files read the same
+    /// either way. Filesystems which don't support sparse files allocate
+    /// the space anyway.
+    pub sparse: bool,
+    /// Whether only entries whose names end with a slash are directories.
+    /// Otherwise, as some tools store directories without the slash, an
+    /// empty entry whose mode says that it's a directory is one too.
+    pub strict_directory_detection: bool,
+    /// Whether to create each directory and file relative to a descriptor
+    /// for the output directory, one path component at a time, never
+    /// following a symbolic link. Nothing can then be written outside the
+    /// output directory, even if symbolic links are created within it while
+    /// unzipping, and existing links are refused whatever
+    /// `follow_existing_symlinks` says. Deduplication is skipped, since its
+    /// links are made by path. Only supported on Linux; elsewhere the usual
+    /// checks by path are made instead.
+    pub anchored_output: bool,
+    /// The most output files to have open at once across all threads, so
+    /// that extracting in parallel can't fail for lack of file descriptors.
+    /// Defaults to half the process's limit on open files, if it has one.
+    pub max_open_files: Option<usize>,
+    /// A file in which to record which zip file was extracted, once
+    /// extraction has completed successfully. If the file already records
+    /// this zip file, identified by its length and the CRC of its central
+    /// directory, nothing is extracted. Otherwise it's removed before
+    /// extraction starts, so an interrupted extraction is never mistaken
+    /// for a complete one.
+    pub completion_marker: Option<PathBuf>,
+    /// Optionally, a number of shard directories among which to spread
+    /// files rather than preserving their directory structure, as for
+    /// processing each shard separately. Each file is extracted to
+    /// `shard-<k>/<basename>`, where `k` is the CRC-32 of its path within
+    /// the zip file modulo the number of shards, and directory entries are
+    /// ignored. Files which end up at the same path are handled according
+    /// to the overwrite policy; unless single threaded, which of them comes
+    /// first is unpredictable. Where each file went is recorded in
+    /// [`UnzipOutcome::shards`].
+    pub shard_into: Option<usize>,
+    /// What to do with files which fail to extract after they've been
+    /// created, which may otherwise look complete. Files extracted before
+    /// the failure are left alone. By default, they're kept.
+    pub partial_files: PartialFiles,
+    /// Whether backslashes in entry names are path separators, as written
+    /// by some tools on Windows, rather than part of the file name. Zip
+    /// files should only use forward slashes, but without this such zip
+    /// files extract on Unix to files named like `dir\file.txt` rather than
+    /// to the intended directories. On Windows, backslashes are always
+    /// separators. A warning is logged for zip files with such names.
+    pub normalize_separators: bool,
+    /// What to do with entries whose names are absolute paths, such as
+    /// `/etc/hosts`. By default, they fail to extract.
+    pub absolute_paths: AbsolutePathPolicy,
+    /// Whether to give extracted files and directories the modification
+    /// times stored in the zip file, rather than the time they're
+    /// extracted. Where an entry has an NTFS or Info-ZIP extended timestamp
+    /// extra field, its modification time is used, to the nanosecond for
+    /// NTFS, along with any access time. Otherwise the entry's DOS time is
+    /// used, which is only to two seconds and doesn't record the time zone,
+    /// so is taken to be UTC. Only supported on Unix.
+    pub preserve_mtimes: bool,
+    /// With `preserve_mtimes`, whether to set the modification times of
+    /// directories again once everything's been extracted, deepest first,
+    /// since writing files into a directory changes its modification time.
+    /// There's no need if you only care about files' times.
+    pub restore_directory_mtimes: bool,
+    /// Whether to give extracted files and directories the user and group
+    /// IDs recorded in Info-ZIP Unix extra fields, where there are any.
+    /// Usually only root is allowed to do this, and extraction fails
+    /// otherwise. Only supported on Unix.
+    pub preserve_ownership: bool,
+    /// Whether, on Windows, to rename entries whose names Windows doesn't
+    /// allow, such as those containing `:` or `?`, or device names like
+    /// `CON`, rather than fail to extract them. The offending characters
+    /// are percent-encoded, so `a:b?.txt` is extracted to `a%3Ab%3F.txt`,
+    /// and each renamed entry is listed in [`UnzipOutcome::renamed`].
+    /// Elsewhere, this has no effect.
+    pub sanitize_invalid_names: bool,
+    /// What to assume about the uncompressed size of entries for which the
+    /// zip file doesn't record it, when reporting progress. By default it's
+    /// estimated from the compressed size.
+    pub unknown_sizes: UnknownSizes,
+    /// The most bytes to download when unzipping from a URI, optionally,
+    /// counting everything downloaded so far, including the central
+    /// directory. Once that's reached, extraction stops with
+    /// [`RipunzipErrors::BudgetExceeded`], leaving files already extracted
+    /// in place. With a filter, that's as many of the chosen files as the
+    /// budget allows. It doesn't apply if the server doesn't support range
+    /// requests, in which case the whole zip file has already been
+    /// downloaded, nor to zip files read over SFTP.
+    pub download_budget: Option<u64>,
+    /// When unzipping from a URI, how many bytes to read from the HTTP(S)
+    /// stream at a time, optionally, rather than 1MiB. Other threads wait
+    /// for each block, so smaller blocks let them start sooner, but there
+    /// are more of them to keep track of. Measure with the
+    /// `readahead_tuning` benchmark before changing this.
+    pub readahead_block_size: Option<usize>,
+    /// When unzipping from a URI, how far ahead a read must be, when we're
+    /// expecting to skip over entries, before we open a new HTTP(S) stream
+    /// there rather than reading on, optionally, rather than 2MiB. Opening
+    /// streams is slow, especially over HTTPS. Only one stream is open at a
+    /// time, whatever these options.
+    pub skip_ahead_threshold: Option<u64>,
+    /// When unzipping from a URI, the most bytes to ask for in each HTTP(S)
+    /// range request, optionally, whatever the entries are. By default each
+    /// request asks for everything from where it starts to the end of the
+    /// zip file, and we stop reading when we no longer need it, which suits
+    /// most servers. Some, such as object stores which charge or throttle
+    /// per byte requested, do better with bounded ranges of several MiB;
+    /// too small, and there are many requests, each with a round trip. Use
+    /// the `range_chunk_tuning` benchmark to compare.
+    pub range_chunk_size: Option<usize>,
+    /// The exact names of the entries to extract, optionally, as they
+    /// appear in the zip file. All others are skipped, and reported to the
+    /// progress reporter as filtered, so for a URI their data is never
+    /// downloaded. This is quicker than a filename filter for a known set of
+    /// names. If there's a filename filter as well, entries must be named
+    /// here and pass it to be extracted.
+    pub only_entries: Option<HashSet<String>>,
+    /// What to do if any names in `only_entries` aren't in the zip file.
+    pub missing_entries: MissingEntries,
+    /// Extensions of files not to extract, such as `map` or `orig`, with or
+    /// without a leading `.`, compared case-insensitively. Such files are
+    /// reported to the progress reporter as filtered, so for a URI their
+    /// data is never downloaded. Directories are extracted whatever their
+    /// names. If there's a filename filter as well, files must pass it and
+    /// not have these extensions to be extracted.
+    pub exclude_extensions: Vec<String>,
+}
+
+impl Default for UnzipOptions<'_, '_> {
+    fn default() -> Self {
+        Self {
+            output_directory: None,
+            password: None,
+            single_threaded: false,
+            filename_filter: None,
+            progress_reporter: Box::new(NullProgressReporter),
+            verify_before_extract: false,
+            decompressor_registry: None,
+            strict: false,
+            text_conversion: None,
+            transform: None,
+            overwrite_policy: OverwritePolicy::Overwrite,
+            max_path_depth: None,
+            dedup: false,
+            durability: Durability::None,
+            follow_existing_symlinks: false,
+            require_empty_output: false,
+            empty_output_ignores_hidden: false,
+            output_lock: OutputLock::None,
+            create_output_directory: true,
+            overlay_base: None,
+            io_buffer_size: None,
+            sequential: false,
+            restrict_to_device: false,
+            dry_run: false,
+            explain: false,
+            progress_coalescing: None,
+            progress_file: None,
+            emit_index: None,
+            verify_manifest: None,
+            #[cfg(feature = "signature")]
+            verify_signature: None,
+            temp_dir: None,
+            hash_algorithm: HashAlgorithm::default(),
+            prefix_map: Vec::new(),
+            flatten_single_root: false,
+            max_entry_size: None,
+            max_total_size: None,
+            duplicate_policy: DuplicatePolicy::All,
+            permission_template: None,
+            file_factory: None,
+            max_entries: None,
+            sparse: false,
+            strict_directory_detection: false,
+            anchored_output: false,
+            max_open_files: None,
+            completion_marker: None,
+            shard_into: None,
+            partial_files: PartialFiles::Keep,
+            normalize_separators: true,
+            absolute_paths: AbsolutePathPolicy::Reject,
+            sanitize_invalid_names: false,
+            preserve_mtimes: false,
+            restore_directory_mtimes: true,
+            preserve_ownership: false,
+            unknown_sizes: UnknownSizes::Estimate,
+            download_budget: None,
+            readahead_block_size: None,
+            skip_ahead_threshold: None,
+            range_chunk_size: None,
+            only_entries: None,
+            missing_entries: MissingEntries::Error,
+            exclude_extensions: Vec::new(),
+        }
+    }
+}
+
+/// How hard to try to make sure extracted files survive a crash or power
+/// loss. Without this, a crash soon after unzipping may leave files empty
+/// or partially written on some filesystems. Each fsync waits for data to
+/// reach the disk, so this can make unzipping many small files, or
+/// unzipping onto spinning disks, many times slower.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Durability {
+    /// Leave it to the operating system to write files to disk eventually.
+    #[default]
+    None,
+    /// Fsync each file after extracting it.
+    FsyncFiles,
+    /// Fsync each file and the directory containing it, so that the
+    /// directory entry is also on disk. Directories are only fsynced on
+    /// Unix.
+    FsyncFilesAndDir,
+}
+
+/// Details of a file on which extraction is starting.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryInfo<'a> {
+    /// The name of the file, suitable for display.
+    pub display_name: &'a str,
+    /// Uncompressed size.
+    pub size: u64,
+    /// Compressed size.
+    pub compressed_size: u64,
+    /// How the file was compressed.
+    pub compression_method: CompressionMethod,
+}
+
+impl<'a> EntryInfo<'a> {
+    fn new<R: Read>(file: &ZipFile<R>, display_name: &'a str) -> Self {
+        Self {
+            display_name,
+            size: file.size(),
+            compressed_size: file.compressed_size(),
+            compression_method: file.compression(),
+        }
+    }
+
+    /// The compressed size as a proportion of the uncompressed size, or
+    /// `None` for an empty file.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.size != 0).then(|| self.compressed_size as f64 / self.size as f64)
+    }
 }
 
 /// A trait of types which wish to hear progress updates on the unzip.
 pub trait UnzipProgressReporter: Sync {
-    /// Extraction has begun on a file.
-    fn extraction_starting(&self, _display_name: &str) {}
+    /// Extraction has begun on a file. Reporters which only want the name
+    /// can use `entry.display_name`.
+    fn extraction_starting(&self, _entry: &EntryInfo) {}
     /// Extraction has finished on a file.
     fn extraction_finished(&self, _display_name: &str) {}
     /// The total number of compressed bytes we expect to extract.
     fn total_bytes_expected(&self, _expected: u64) {}
+    /// Reading each entry's details from the central directory, the index
+    /// of the zip file's entries, has begun. The central directory itself
+    /// was fetched and parsed when the [`UnzipEngine`] was created, before
+    /// there was a reporter to tell, so this only measures going through
+    /// it, which for a zip file with very many entries can still take a
+    /// while. This is called once per unzip, after `total_bytes_expected`.
+    fn central_directory_read_starting(&self) {}
+    /// Every entry's details have been read, listing `entry_count` entries,
+    /// including directories. `metadata_ready` follows.
+    fn central_directory_read_finished(&self, _entry_count: usize) {}
+    /// The central directory has been read, so the details of every entry
+    /// are known, in central directory order. This is called once per
+    /// unzip, before any file's data is read, so for a URI before anything
+    /// more is downloaded; dropping an [`UnzipIter`] now stops the unzip
+    /// before it starts.
+    fn metadata_ready(&self, _entries: &[ZipEntryMetadata]) {}
+    /// With [`UnzipOptions::explain`], what the unzip will do with each
+    /// entry. This is called once per unzip, after `metadata_ready` and
+    /// before any file is extracted.
+    fn plan_ready(&self, _plan: &ExtractionPlan) {}
     /// Some bytes of a file have been decompressed. This is probably
     /// the best way to display an overall progress bar. This should eventually
     /// add up to the number you're given using `total_bytes_expected`.
@@ -70,6 +703,152 @@ pub trait UnzipProgressReporter: Sync {
     /// bytes without downloading the whole zip file first, which rather
     /// defeats the point.
     fn bytes_extracted(&self, _count: u64) {}
+    /// An entry, identified by its name within the zip file, was not
+    /// extracted. Entries excluded by the filename filter are all reported,
+    /// in name order, before any extraction starts, as are those which are
+    /// too large, separately. Entries skipped for other reasons are
+    /// reported between `extraction_starting` and `extraction_finished` for
+    /// that entry.
+    fn entry_skipped(&self, _name: &str, _reason: SkipReason) {}
+    /// A dry run found that an entry, identified by its name within the zip
+    /// file, would be extracted. Entries are reported in name order.
+    fn extraction_planned(&self, _name: &str, _action: PlannedAction) {}
+    /// Something happened while fetching a URI over HTTP(S), such as a
+    /// stream being opened or a failed request being retried. Only events
+    /// during unzipping or verification are reported, from a thread of
+    /// their own, so they may arrive slightly late.
+    fn network_event(&self, _event: &NetworkEvent) {}
+    /// The whole unzip has completed successfully. This is not called if
+    /// any file failed to extract.
+    fn all_finished(&self, _outcome: &UnzipOutcome) {}
+}
+
+/// Why an entry was not extracted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SkipReason {
+    /// The filename filter excluded it.
+    Filtered,
+    /// A file already existed at its destination and the
+    /// [`OverwritePolicy`] said to leave it alone.
+    AlreadyExists,
+    /// It can't be extracted by this version of ripunzip. Only reported by
+    /// a dry run, for entries whose names would take them outside the
+    /// output directory; otherwise such entries fail to extract.
+    Unsupported,
+    /// An identical file is already in [`UnzipOptions::overlay_base`].
+    UpToDate,
+    /// It's larger than [`UnzipOptions::max_entry_size`].
+    TooLarge,
+    /// Another entry extracting to the same path is newer, and
+    /// [`UnzipOptions::duplicate_policy`] says to extract only the newest.
+    Superseded,
+}
+
+/// A summary of a successful unzip.
+#[derive(Debug, Default, Clone)]
+pub struct UnzipOutcome {
+    /// The number of files written.
+    pub files_extracted: usize,
+    /// The number of directory entries extracted.
+    pub directories_extracted: usize,
+    /// The total number of uncompressed bytes written.
+    pub bytes_written: u64,
+    /// The number of files not extracted because they already existed.
+    pub files_skipped: usize,
+    /// The number of extracted files which were hard linked or copied to
+    /// an identical file rather than written.
+    pub files_deduplicated: usize,
+    /// How long was spent reading, decompressing and writing files.
+    pub timings: ExtractionTimings,
+    /// The CPU time used extracting entries, added up across all threads,
+    /// which compared with `wall_time` shows how many cores the unzip kept
+    /// busy, so how many unzips a host can run at once. Only time spent
+    /// on entries is counted, not reading the central directory or, for a
+    /// URI, fetching data on other threads. `None` where a thread's CPU
+    /// time can't be measured, which includes Windows, NetBSD, Solaris and
+    /// illumos.
+    pub cpu_time: Option<Duration>,
+    /// How long the unzip took, from start to finish. For a
+    /// [`MultiUnzip`], that of all its zip files together, which ran at
+    /// the same time.
+    pub wall_time: Duration,
+    /// With [`UnzipOptions::shard_into`], where each extracted file was
+    /// written, by its name within the zip file.
+    pub shards: BTreeMap<String, PathBuf>,
+    /// With [`UnzipOptions::sanitize_invalid_names`], where each entry whose
+    /// name had to be changed was written, by its name within the zip file.
+    pub renamed: BTreeMap<String, PathBuf>,
+    /// The names of the files which took longest to extract, and how long
+    /// each part of extracting each took, slowest first. At most ten are
+    /// listed. These are only recorded when debug logging is enabled, in
+    /// which case every file's timings are logged as it's finished.
+    pub slowest_entries: Vec<(String, ExtractionTimings)>,
+    /// With [`UnzipOptions::verify_manifest`], the names of files in the zip
+    /// file, and matching the filename filter, which the manifest doesn't
+    /// list, so which weren't verified.
+    pub not_in_manifest: Vec<String>,
+    /// With [`UnzipOptions::verify_manifest`], the paths listed in the
+    /// manifest which aren't in the zip file.
+    pub missing_from_zip: Vec<PathBuf>,
+}
+
+impl UnzipOutcome {
+    fn add(&mut self, other: &UnzipOutcome) {
+        self.files_extracted += other.files_extracted;
+        self.directories_extracted += other.directories_extracted;
+        self.bytes_written += other.bytes_written;
+        self.files_skipped += other.files_skipped;
+        self.files_deduplicated += other.files_deduplicated;
+        self.timings.add(&other.timings);
+        self.cpu_time = match (self.cpu_time, other.cpu_time) {
+            (Some(cpu_time), Some(other)) => Some(cpu_time + other),
+            (cpu_time, other) => cpu_time.or(other),
+        };
+        // Outcomes added together overlap, such as entries extracted at
+        // the same time, so this is at least as long as any of them.
+        self.wall_time = self.wall_time.max(other.wall_time);
+        self.shards.extend(other.shards.clone());
+        self.renamed.extend(other.renamed.clone());
+        self.not_in_manifest
+            .extend(other.not_in_manifest.iter().cloned());
+        self.missing_from_zip
+            .extend(other.missing_from_zip.iter().cloned());
+        if !other.slowest_entries.is_empty() {
+            self.slowest_entries
+                .extend(other.slowest_entries.iter().cloned());
+            self.slowest_entries
+                .sort_by_key(|(_, timings)| std::cmp::Reverse(timings.total()));
+            self.slowest_entries.truncate(SLOWEST_ENTRIES_REPORTED);
+        }
+    }
+
+    /// A guess at what limited the speed of the unzip, from the
+    /// [`ExtractionTimings`], to help choose options for the next one.
+    pub fn bottleneck(&self) -> Option<Bottleneck> {
+        self.timings.bottleneck()
+    }
+}
+
+/// The size of the buffer used to copy each file, unless
+/// [`UnzipOptions::io_buffer_size`] says otherwise. Extracting large files,
+/// 8KiB and 64KiB buffers were respectively about twice and 30% slower,
+/// while 1MiB was no faster.
+const DEFAULT_IO_BUFFER_SIZE: usize = 256 * 1024;
+
+/// How many of the slowest entries to list in a [`VerifyOutcome`] or
+/// [`UnzipOutcome`].
+const SLOWEST_ENTRIES_REPORTED: usize = 10;
+
+/// A summary of a successful [`UnzipEngine::verify_parallel`].
+#[derive(Debug, Default, Clone)]
+pub struct VerifyOutcome {
+    /// The number of files whose CRCs were checked.
+    pub files_verified: usize,
+    /// The total number of uncompressed bytes checked.
+    pub bytes_verified: u64,
+    /// The names of the files which took longest to check, and how long
+    /// each took, slowest first. At most ten are listed.
+    pub slowest_entries: Vec<(String, Duration)>,
 }
 
 /// A progress reporter which does nothing.
@@ -80,6 +859,12 @@ impl UnzipProgressReporter for NullProgressReporter {}
 /// An object which can unzip a zip file, in its entirety, from a local
 /// file or from a network stream. It tries to do this in parallel wherever
 /// possible.
+///
+/// The central directory is read and parsed once, when the engine is
+/// created, and everything done with the engine afterwards shares it. So,
+/// for example, [`UnzipEngine::list`] followed by [`UnzipEngine::unzip`]
+/// reads it only once, and for a URI the end of the zip file, where it's
+/// kept, isn't fetched again.
 pub struct UnzipEngine {
     zipfile: Box<dyn UnzipEngineImpl>,
     /// If we are unzipping a URI and the remote server does not support
@@ -88,10 +873,35 @@ pub struct UnzipEngine {
     /// It is None if we are unzipping a local file, or a URI that supports
     /// range requests.
     _temp_file: Option<tempfile::NamedTempFile>,
+    /// Where to create any temporary files, if not the system's temporary
+    /// directory.
+    temp_dir: Option<PathBuf>,
     compressed_length: u64,
-    directory_creator: DirectoryCreator,
+    /// The identities of the zip files this one was opened within by
+    /// [`UnzipEngine::for_entry`], outermost first.
+    enclosing: Vec<ArchiveIdentity>,
+    /// How much [`UnzipEngine::for_entry`] decompressed to get at this zip
+    /// file, which counts towards [`UnzipOptions::max_total_size`].
+    decompressed: u64,
+}
+
+/// Whether an entry is a file or a directory.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EntryKind {
+    /// A file.
+    File,
+    /// A directory, whose name ends with a slash.
+    Directory,
 }
 
+/// A function creating a file to extract into, given its path, for
+/// [`UnzipOptions::file_factory`].
+pub type FileFactory<'a> = Box<dyn Fn(&Path) -> std::io::Result<File> + Send + Sync + 'a>;
+
+/// A function deciding the Unix mode of an entry, given its path within the
+/// zip file, or `None` to use the mode stored in the zip file.
+pub type PermissionTemplate<'a> = Box<dyn Fn(&Path, EntryKind) -> Option<u32> + Send + Sync + 'a>;
+
 /// Code which can determine whether to unzip a given filename.
 pub trait FilenameFilter {
     /// Returns true if the given filename should be unzipped.
@@ -100,65 +910,288 @@ pub trait FilenameFilter {
 
 /// The underlying engine used by the unzipper. This is different
 /// for files and URIs.
-trait UnzipEngineImpl {
+trait UnzipEngineImpl: Send {
     fn unzip(
         &mut self,
-        options: UnzipOptions,
-        directory_creator: &DirectoryCreator,
-    ) -> Vec<RipunzipErrors>;
+        options: &UnzipOptions,
+        state: &ExtractionState,
+    ) -> (UnzipOutcome, Vec<RipunzipErrors>);
+
+    /// Check the CRC of each entry that would be extracted, without
+    /// writing anything.
+    fn verify(&mut self, options: &UnzipOptions) -> Result<(), RipunzipErrors>;
+
+    fn verify_parallel(&self, options: &UnzipOptions) -> Result<VerifyOutcome, RipunzipErrors>;
+
+    /// Write the contents of the only file to be extracted to `writer`.
+    fn unzip_to_writer(
+        &mut self,
+        options: &UnzipOptions,
+        total_size: &TotalSize,
+        writer: &mut dyn Write,
+    ) -> Result<UnzipOutcome, RipunzipErrors>;
+
+    /// Write the contents of every file to be extracted to `writer`, each
+    /// after the separator for it.
+    fn concat_to(
+        &mut self,
+        options: &UnzipOptions,
+        total_size: &TotalSize,
+        writer: &mut dyn Write,
+        separator: &mut dyn FnMut(&EntryInfo) -> String,
+        sort_by_name: bool,
+    ) -> Result<UnzipOutcome, RipunzipErrors>;
 
     // Due to lack of RPITIT we'll return a Vec<String> here
     fn list(&self) -> Result<Vec<String>, RipunzipErrors>;
+
+    /// The zip file's comment, from the end of central directory record.
+    fn comment(&self) -> &[u8];
+
+    /// The number of entries, including directories.
+    fn entry_count(&self) -> usize;
+
+    /// Details of every entry, read from the central directory alone.
+    fn entry_metadata(&self) -> Result<Vec<ZipEntryMetadata>, RipunzipErrors>;
+
+    fn compression_stats(&self) -> Result<CompressionStats, RipunzipErrors>;
+
+    /// Where each entry is, read from the central directory alone.
+    fn index(&self) -> Result<Vec<IndexEntry>, RipunzipErrors>;
+
+    fn archive_identity(&self) -> Result<ArchiveIdentity, RipunzipErrors>;
+
+    fn extract_to_memory(
+        &self,
+        filter: &dyn FilenameFilter,
+        memory_limit: Option<u64>,
+    ) -> Result<HashMap<PathBuf, Vec<u8>>, RipunzipErrors>;
+
+    fn extract_raw_entry_to(
+        &self,
+        name: &str,
+        writer: &mut dyn Write,
+    ) -> Result<CompressionMethod, RipunzipErrors>;
+
+    fn peek_entry(&self, name: &str, n: usize) -> Result<Vec<u8>, RipunzipErrors>;
+
+    fn recompress_to(
+        &self,
+        out: &mut dyn WriteSeek,
+        method: CompressionMethod,
+        level: Option<i64>,
+    ) -> Result<(), RipunzipErrors>;
+
+    /// Open the named entry, which must itself be a zip file.
+    fn open_entry(
+        &mut self,
+        name: &str,
+        temp_dir: Option<&Path>,
+    ) -> Result<NestedZip, RipunzipErrors>;
+
+    /// A reader of the whole zip file, from its start.
+    #[cfg(feature = "signature")]
+    fn archive_reader(&self) -> std::io::Result<Box<dyn Read + '_>>;
+
+    /// Whether reading the zip file means downloading it, so that it's
+    /// worth keeping a copy rather than reading it again.
+    #[cfg(feature = "signature")]
+    fn is_remote(&self) -> bool;
+
+    /// Read the central directory again, from the same reader, so that
+    /// it's that of the zip file as it is now.
+    #[cfg(feature = "signature")]
+    fn reopen(&mut self) -> Result<(), RipunzipErrors>;
 }
 
-/// Engine which knows how to unzip a file.
+/// A zip file within another zip file: an engine to unzip it, its length,
+/// and a temporary file holding it if it couldn't be read in place.
+type NestedZip = (
+    Box<dyn UnzipEngineImpl>,
+    u64,
+    Option<tempfile::NamedTempFile>,
+);
+
+/// Engine which knows how to unzip a file, or anything else we can read
+/// from any position.
 #[derive(Clone)]
-struct UnzipFileEngine(ZipArchive<MultiFileSeeker>);
+struct UnzipFileEngine<R = CloneableFile>(ZipArchive<TimedSource<R>>);
+
+impl<R: Read + Seek + Clone> UnzipFileEngine<R> {
+    fn open(reader: R) -> Result<Self, RipunzipErrors> {
+        Ok(Self(open_archive(TimedSource::new(reader))?))
+    }
+}
 
-impl UnzipEngineImpl for UnzipFileEngine {
+impl<R: Read + Seek + Clone + Send + Sync + 'static> UnzipEngineImpl for UnzipFileEngine<R> {
     fn unzip(
         &mut self,
-        options: UnzipOptions,
-        directory_creator: &DirectoryCreator,
-    ) -> Vec<RipunzipErrors> {
-        unzip_serial_or_parallel(
-            self.0.len(),
+        options: &UnzipOptions,
+        state: &ExtractionState,
+    ) -> (UnzipOutcome, Vec<RipunzipErrors>) {
+        unzip_serial_or_parallel(self.0.len(), options, state, || self.0.clone(), || {})
+    }
+
+    fn verify(&mut self, options: &UnzipOptions) -> Result<(), RipunzipErrors> {
+        verify(&mut self.0, options)
+    }
+
+    fn verify_parallel(&self, options: &UnzipOptions) -> Result<VerifyOutcome, RipunzipErrors> {
+        verify_parallel(&self.0, options)
+    }
+
+    fn unzip_to_writer(
+        &mut self,
+        options: &UnzipOptions,
+        total_size: &TotalSize,
+        writer: &mut dyn Write,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        unzip_to_writer(&mut self.0, options, total_size, writer)
+    }
+
+    fn concat_to(
+        &mut self,
+        options: &UnzipOptions,
+        total_size: &TotalSize,
+        writer: &mut dyn Write,
+        separator: &mut dyn FnMut(&EntryInfo) -> String,
+        sort_by_name: bool,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        concat_to(
+            &mut self.0,
             options,
-            directory_creator,
-            || self.0.clone(),
-            || {},
+            total_size,
+            writer,
+            separator,
+            sort_by_name,
         )
     }
 
     fn list(&self) -> Result<Vec<String>, RipunzipErrors> {
         list(&self.0)
     }
+
+    fn comment(&self) -> &[u8] {
+        self.0.comment()
+    }
+
+    fn entry_count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn entry_metadata(&self) -> Result<Vec<ZipEntryMetadata>, RipunzipErrors> {
+        entry_metadata(&self.0)
+    }
+
+    fn compression_stats(&self) -> Result<CompressionStats, RipunzipErrors> {
+        compression_stats(&self.0)
+    }
+
+    fn index(&self) -> Result<Vec<IndexEntry>, RipunzipErrors> {
+        index_entries(&self.0)
+    }
+
+    fn archive_identity(&self) -> Result<ArchiveIdentity, RipunzipErrors> {
+        Ok(ArchiveIdentity::of(&self.0)?)
+    }
+
+    fn extract_to_memory(
+        &self,
+        filter: &dyn FilenameFilter,
+        memory_limit: Option<u64>,
+    ) -> Result<HashMap<PathBuf, Vec<u8>>, RipunzipErrors> {
+        extract_to_memory(self.0.clone(), filter, memory_limit)
+    }
+
+    fn extract_raw_entry_to(
+        &self,
+        name: &str,
+        writer: &mut dyn Write,
+    ) -> Result<CompressionMethod, RipunzipErrors> {
+        extract_raw_entry_to(self.0.clone(), name, writer)
+    }
+
+    fn peek_entry(&self, name: &str, n: usize) -> Result<Vec<u8>, RipunzipErrors> {
+        peek_entry(self.0.clone(), name, n)
+    }
+
+    fn recompress_to(
+        &self,
+        out: &mut dyn WriteSeek,
+        method: CompressionMethod,
+        level: Option<i64>,
+    ) -> Result<(), RipunzipErrors> {
+        recompress_to(self.0.clone(), out, method, level)
+    }
+
+    fn open_entry(
+        &mut self,
+        name: &str,
+        temp_dir: Option<&Path>,
+    ) -> Result<NestedZip, RipunzipErrors> {
+        open_entry(&mut self.0, name, temp_dir, EntryReader::new)
+    }
+
+    #[cfg(feature = "signature")]
+    fn archive_reader(&self) -> std::io::Result<Box<dyn Read + '_>> {
+        let mut reader = self.0.clone().into_inner();
+        reader.rewind()?;
+        Ok(Box::new(reader))
+    }
+
+    #[cfg(feature = "signature")]
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "signature")]
+    fn reopen(&mut self) -> Result<(), RipunzipErrors> {
+        self.0 = open_archive(self.0.clone().into_inner())?;
+        Ok(())
+    }
 }
 
 /// Engine which knows how to unzip a URI; specifically a URI fetched from
 /// an HTTP server which supports `Range` requests.
+#[cfg(feature = "http")]
 #[derive(Clone)]
 struct UnzipUriEngine<F: Fn()>(
-    Arc<SeekableHttpReaderEngine>,
-    ZipArchive<SeekableHttpReader>,
+    std::sync::Arc<SeekableHttpReaderEngine>,
+    ZipArchive<TimedSource<SeekableHttpReader>>,
     F,
 );
 
-impl<F: Fn()> UnzipEngineImpl for UnzipUriEngine<F> {
+#[cfg(feature = "http")]
+impl<F: Fn()> UnzipUriEngine<F> {
+    /// Apply the options which affect how the zip file is fetched.
+    fn configure(&self, options: &UnzipOptions) {
+        self.0.set_download_budget(options.download_budget);
+        self.0
+            .set_tuning(options.readahead_block_size, options.skip_ahead_threshold);
+        self.0.set_range_chunk_size(options.range_chunk_size);
+    }
+}
+
+#[cfg(feature = "http")]
+impl<F: Fn() + Send> UnzipEngineImpl for UnzipUriEngine<F> {
     fn unzip(
         &mut self,
-        options: UnzipOptions,
-        directory_creator: &DirectoryCreator,
-    ) -> Vec<RipunzipErrors> {
-        self.0
-            .set_expected_access_pattern(AccessPattern::SequentialIsh);
-        let result = unzip_serial_or_parallel(
-            self.1.len(),
-            options,
-            directory_creator,
-            || self.1.clone(),
-            || self.0.read_skip_expected(),
-        );
+        options: &UnzipOptions,
+        state: &ExtractionState,
+    ) -> (UnzipOutcome, Vec<RipunzipErrors>) {
+        let reporter = options.progress_reporter.as_ref();
+        self.configure(options);
+        let result = forward_network_events(self.0.network_events(), reporter, || {
+            self.0
+                .set_expected_access_pattern(AccessPattern::SequentialIsh);
+            unzip_serial_or_parallel(
+                self.1.len(),
+                options,
+                state,
+                || self.1.clone(),
+                || self.0.read_skip_expected(),
+            )
+        });
         let stats = self.0.get_stats();
         if stats.cache_shrinks > 0 {
             self.2()
@@ -166,9 +1199,296 @@ impl<F: Fn()> UnzipEngineImpl for UnzipUriEngine<F> {
         result
     }
 
+    fn verify(&mut self, options: &UnzipOptions) -> Result<(), RipunzipErrors> {
+        let reporter = options.progress_reporter.as_ref();
+        self.configure(options);
+        forward_network_events(self.0.network_events(), reporter, || {
+            self.0
+                .set_expected_access_pattern(AccessPattern::SequentialIsh);
+            verify(&mut self.1, options)
+        })
+    }
+
+    fn verify_parallel(&self, options: &UnzipOptions) -> Result<VerifyOutcome, RipunzipErrors> {
+        let reporter = options.progress_reporter.as_ref();
+        self.configure(options);
+        forward_network_events(self.0.network_events(), reporter, || {
+            self.0
+                .set_expected_access_pattern(AccessPattern::RandomAccess);
+            verify_parallel(&self.1, options)
+        })
+    }
+
+    fn unzip_to_writer(
+        &mut self,
+        options: &UnzipOptions,
+        total_size: &TotalSize,
+        writer: &mut dyn Write,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        let reporter = options.progress_reporter.as_ref();
+        self.configure(options);
+        forward_network_events(self.0.network_events(), reporter, || {
+            self.0
+                .set_expected_access_pattern(AccessPattern::SequentialIsh);
+            unzip_to_writer(&mut self.1, options, total_size, writer)
+        })
+    }
+
+    fn concat_to(
+        &mut self,
+        options: &UnzipOptions,
+        total_size: &TotalSize,
+        writer: &mut dyn Write,
+        separator: &mut dyn FnMut(&EntryInfo) -> String,
+        sort_by_name: bool,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        let reporter = options.progress_reporter.as_ref();
+        self.configure(options);
+        forward_network_events(self.0.network_events(), reporter, || {
+            self.0.set_expected_access_pattern(if sort_by_name {
+                AccessPattern::RandomAccess
+            } else {
+                AccessPattern::SequentialIsh
+            });
+            concat_to(
+                &mut self.1,
+                options,
+                total_size,
+                writer,
+                separator,
+                sort_by_name,
+            )
+        })
+    }
+
     fn list(&self) -> Result<Vec<String>, RipunzipErrors> {
         list(&self.1)
     }
+
+    fn comment(&self) -> &[u8] {
+        self.1.comment()
+    }
+
+    fn entry_count(&self) -> usize {
+        self.1.len()
+    }
+
+    fn entry_metadata(&self) -> Result<Vec<ZipEntryMetadata>, RipunzipErrors> {
+        entry_metadata(&self.1)
+    }
+
+    fn compression_stats(&self) -> Result<CompressionStats, RipunzipErrors> {
+        compression_stats(&self.1)
+    }
+
+    fn index(&self) -> Result<Vec<IndexEntry>, RipunzipErrors> {
+        index_entries(&self.1)
+    }
+
+    fn archive_identity(&self) -> Result<ArchiveIdentity, RipunzipErrors> {
+        Ok(ArchiveIdentity::of(&self.1)?)
+    }
+
+    fn extract_to_memory(
+        &self,
+        filter: &dyn FilenameFilter,
+        memory_limit: Option<u64>,
+    ) -> Result<HashMap<PathBuf, Vec<u8>>, RipunzipErrors> {
+        extract_to_memory(self.1.clone(), filter, memory_limit)
+    }
+
+    fn extract_raw_entry_to(
+        &self,
+        name: &str,
+        writer: &mut dyn Write,
+    ) -> Result<CompressionMethod, RipunzipErrors> {
+        extract_raw_entry_to(self.1.clone(), name, writer)
+    }
+
+    fn peek_entry(&self, name: &str, n: usize) -> Result<Vec<u8>, RipunzipErrors> {
+        peek_entry(self.1.clone(), name, n)
+    }
+
+    fn recompress_to(
+        &self,
+        out: &mut dyn WriteSeek,
+        method: CompressionMethod,
+        level: Option<i64>,
+    ) -> Result<(), RipunzipErrors> {
+        self.0
+            .set_expected_access_pattern(AccessPattern::SequentialIsh);
+        recompress_to(self.1.clone(), out, method, level)
+    }
+
+    fn open_entry(
+        &mut self,
+        name: &str,
+        temp_dir: Option<&Path>,
+    ) -> Result<NestedZip, RipunzipErrors> {
+        let nested = open_entry(&mut self.1, name, temp_dir, EntryReader::new)?;
+        // Having read the nested zip's central directory, we'll read its
+        // files roughly in order.
+        self.0
+            .set_expected_access_pattern(AccessPattern::SequentialIsh);
+        Ok(nested)
+    }
+
+    #[cfg(feature = "signature")]
+    fn archive_reader(&self) -> std::io::Result<Box<dyn Read + '_>> {
+        self.0
+            .set_expected_access_pattern(AccessPattern::SequentialIsh);
+        let mut reader = self.1.clone().into_inner();
+        // Reading at the end is an error, rather than returning nothing.
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.rewind()?;
+        Ok(Box::new(reader.take(len)))
+    }
+
+    #[cfg(feature = "signature")]
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "signature")]
+    fn reopen(&mut self) -> Result<(), RipunzipErrors> {
+        self.1 = open_archive(self.1.clone().into_inner())?;
+        Ok(())
+    }
+}
+
+/// Engine which knows how to unzip a zip file stored, uncompressed, within
+/// another zip file.
+#[derive(Clone)]
+struct UnzipNestedEngine<T>(ZipArchive<EntryReader<T>>);
+
+impl<T: Read + Seek + Clone + Send + Sync + 'static> UnzipEngineImpl for UnzipNestedEngine<T> {
+    fn unzip(
+        &mut self,
+        options: &UnzipOptions,
+        state: &ExtractionState,
+    ) -> (UnzipOutcome, Vec<RipunzipErrors>) {
+        unzip_serial_or_parallel(self.0.len(), options, state, || self.0.clone(), || {})
+    }
+
+    fn verify(&mut self, options: &UnzipOptions) -> Result<(), RipunzipErrors> {
+        verify(&mut self.0, options)
+    }
+
+    fn verify_parallel(&self, options: &UnzipOptions) -> Result<VerifyOutcome, RipunzipErrors> {
+        verify_parallel(&self.0, options)
+    }
+
+    fn unzip_to_writer(
+        &mut self,
+        options: &UnzipOptions,
+        total_size: &TotalSize,
+        writer: &mut dyn Write,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        unzip_to_writer(&mut self.0, options, total_size, writer)
+    }
+
+    fn concat_to(
+        &mut self,
+        options: &UnzipOptions,
+        total_size: &TotalSize,
+        writer: &mut dyn Write,
+        separator: &mut dyn FnMut(&EntryInfo) -> String,
+        sort_by_name: bool,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        concat_to(
+            &mut self.0,
+            options,
+            total_size,
+            writer,
+            separator,
+            sort_by_name,
+        )
+    }
+
+    fn list(&self) -> Result<Vec<String>, RipunzipErrors> {
+        list(&self.0)
+    }
+
+    fn comment(&self) -> &[u8] {
+        self.0.comment()
+    }
+
+    fn entry_count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn entry_metadata(&self) -> Result<Vec<ZipEntryMetadata>, RipunzipErrors> {
+        entry_metadata(&self.0)
+    }
+
+    fn compression_stats(&self) -> Result<CompressionStats, RipunzipErrors> {
+        compression_stats(&self.0)
+    }
+
+    fn index(&self) -> Result<Vec<IndexEntry>, RipunzipErrors> {
+        index_entries(&self.0)
+    }
+
+    fn archive_identity(&self) -> Result<ArchiveIdentity, RipunzipErrors> {
+        Ok(ArchiveIdentity::of(&self.0)?)
+    }
+
+    fn extract_to_memory(
+        &self,
+        filter: &dyn FilenameFilter,
+        memory_limit: Option<u64>,
+    ) -> Result<HashMap<PathBuf, Vec<u8>>, RipunzipErrors> {
+        extract_to_memory(self.0.clone(), filter, memory_limit)
+    }
+
+    fn extract_raw_entry_to(
+        &self,
+        name: &str,
+        writer: &mut dyn Write,
+    ) -> Result<CompressionMethod, RipunzipErrors> {
+        extract_raw_entry_to(self.0.clone(), name, writer)
+    }
+
+    fn peek_entry(&self, name: &str, n: usize) -> Result<Vec<u8>, RipunzipErrors> {
+        peek_entry(self.0.clone(), name, n)
+    }
+
+    fn recompress_to(
+        &self,
+        out: &mut dyn WriteSeek,
+        method: CompressionMethod,
+        level: Option<i64>,
+    ) -> Result<(), RipunzipErrors> {
+        recompress_to(self.0.clone(), out, method, level)
+    }
+
+    fn open_entry(
+        &mut self,
+        name: &str,
+        temp_dir: Option<&Path>,
+    ) -> Result<NestedZip, RipunzipErrors> {
+        open_entry(&mut self.0, name, temp_dir, |reader, start, len| {
+            reader.subrange(start, len)
+        })
+    }
+
+    #[cfg(feature = "signature")]
+    fn archive_reader(&self) -> std::io::Result<Box<dyn Read + '_>> {
+        let mut reader = self.0.clone().into_inner();
+        reader.rewind()?;
+        Ok(Box::new(reader))
+    }
+
+    #[cfg(feature = "signature")]
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "signature")]
+    fn reopen(&mut self) -> Result<(), RipunzipErrors> {
+        self.0 = open_archive(self.0.clone().into_inner())?;
+        Ok(())
+    }
 }
 
 impl UnzipEngine {
@@ -179,58 +1499,164 @@ impl UnzipEngine {
         // performance difference.
         // let zipfile = BufReader::new(zipfile);
         let compressed_length = determine_stream_len(&mut zipfile)?;
-        let zipfile = MultiFileSeeker::new(&zipfile_path)?;
+        // Everything is read through this one handle, so that nothing can
+        // swap the file for another part way through.
+        let zipfile = CloneableFile::new(zipfile);
         Ok(Self {
-            zipfile: Box::new(UnzipFileEngine(ZipArchive::new(zipfile)?)),
+            zipfile: Box::new(UnzipFileEngine::open(zipfile)?),
             _temp_file: None,
+            temp_dir: None,
             compressed_length,
-            directory_creator: DirectoryCreator::default(),
+            enclosing: Vec::new(),
+            decompressed: 0,
+        })
+    }
+
+    /// Create an unzip engine which knows how to unzip a zip file from any
+    /// other source which can be read from any position, such as a blob in
+    /// a database or an object in an object store. The reader is cloned
+    /// for each thread extracting files, and each clone must keep its own
+    /// position, so that they can all be read independently, as with
+    /// [`CloneableFile`].
+    pub fn for_reader<R>(reader: R) -> Result<Self, RipunzipErrors>
+    where
+        R: Read + Seek + HasLength + Clone + Send + Sync + 'static,
+    {
+        let compressed_length = reader.len()?;
+        Ok(Self {
+            zipfile: Box::new(UnzipFileEngine::open(reader)?),
+            _temp_file: None,
+            temp_dir: None,
+            compressed_length,
+            enclosing: Vec::new(),
+            decompressed: 0,
         })
     }
 
+    /// Create an unzip engine which knows how to unzip a zip file split
+    /// into segments, all of which are given, in order. That may be a zip
+    /// file split by `zip -s` into `part.z01`, `part.z02` and so on,
+    /// followed by `part.zip`, or one simply cut into pieces, such as
+    /// 7-Zip's `part.zip.001`, `part.zip.002` and so on. The segments are
+    /// read as one, so there's no need to join them first, and are checked
+    /// against the end of central directory record, failing with
+    /// [`RipunzipErrors::InvalidSegments`] if any are missing, truncated or
+    /// out of order. Swapped segments holding nothing but the middle of an
+    /// entry can only be caught by that entry's CRC check, while extracting.
+    pub fn for_segments(paths: Vec<PathBuf>) -> Result<Self, RipunzipErrors> {
+        Self::for_reader(segments::join_segments(&paths)?)
+    }
+
+    /// Create an unzip engine which knows how to unzip a zip file already
+    /// in memory, such as an `Arc<Vec<u8>>` or an `Arc<[u8]>`. Unlike
+    /// giving [`UnzipEngine::for_reader`] a `Cursor<Vec<u8>>`, the bytes
+    /// aren't copied for each thread extracting files, and they can be
+    /// shared with other engines at the same time. They can't simply be
+    /// borrowed, since the engine may extract on other threads, so a
+    /// `&'static [u8]` should be given to [`UnzipEngine::for_reader`] in a
+    /// [`Cursor`](std::io::Cursor) instead.
+    pub fn for_bytes<B>(bytes: Arc<B>) -> Result<Self, RipunzipErrors>
+    where
+        B: AsRef<[u8]> + ?Sized + Send + Sync + 'static,
+    {
+        Self::for_reader(std::io::Cursor::new(SharedBytes::new(bytes)))
+    }
+
     /// Create an unzip engine which knows how to unzip a URI.
     /// Parameters:
     /// - the URI
     /// - unzip options
     /// - how big a readahead buffer to create in memory.
-    /// - a progress reporter (set of callbacks)
     /// - an additional callback to warn if performance was impaired by
     ///   rewinding the HTTP stream. (This implies the readahead buffer was
     ///   too small.)
-    pub fn for_uri<F: Fn() + 'static>(
+    ///
+    /// With the `ssh` feature, `sftp://user@host/path.zip` URIs are also
+    /// supported, in which case the readahead limit and callback are unused.
+    ///
+    /// HTTPS servers must have certificates which the TLS backend trusts. To
+    /// trust others, such as those of a proxy with its own certificate
+    /// authority, or to fetch from mirrors or send cookies, use
+    /// [`UnzipEngine::for_uri_with_options`]. Only available with the `http`
+    /// feature.
+    #[cfg(feature = "http")]
+    pub fn for_uri<F: Fn() + Send + 'static>(
         uri: &str,
         readahead_limit: Option<usize>,
         callback_on_rewind: F,
     ) -> Result<Self, RipunzipErrors> {
+        Self::for_uri_with_options(
+            uri,
+            readahead_limit,
+            &UriOptions::default(),
+            callback_on_rewind,
+        )
+    }
+
+    /// Create an unzip engine which knows how to unzip a URI, like
+    /// [`UnzipEngine::for_uri`], fetching it as `options` says. Only the
+    /// temporary directory is used for `sftp://` URIs. Only available with
+    /// the `http` feature.
+    #[cfg(feature = "http")]
+    pub fn for_uri_with_options<F: Fn() + Send + 'static>(
+        uri: &str,
+        readahead_limit: Option<usize>,
+        options: &UriOptions,
+        callback_on_rewind: F,
+    ) -> Result<Self, RipunzipErrors> {
+        let temp_dir = options.temp_dir.clone();
+        if uri.starts_with("sftp://") {
+            return Ok(Self {
+                temp_dir,
+                ..Self::for_sftp_uri(uri)?
+            });
+        }
+        let mut uris = vec![uri.to_string()];
+        uris.extend(options.mirrors.iter().cloned());
+        let client_config = ClientConfig {
+            root_certificates: options.root_certificates.clone(),
+            cookies: options.cookies.clone(),
+        };
         let seekable_http_reader = SeekableHttpReaderEngine::new(
-            uri.to_string(),
+            uris.clone(),
             readahead_limit,
             AccessPattern::RandomAccess,
+            &client_config,
         );
         let (compressed_length, zipfile_impl, temp_file) = match seekable_http_reader {
-            Ok(seekable_http_reader) => (
-                seekable_http_reader.len(),
-                Box::new(UnzipUriEngine(
-                    seekable_http_reader.clone(),
-                    ZipArchive::new(seekable_http_reader.create_reader())?,
-                    callback_on_rewind,
-                )) as Box<dyn UnzipEngineImpl>,
-                None,
-            ),
+            Err(seekable_http_reader::Error::RangeFetcherError(
+                http_range_reader::Error::MirrorLengthMismatch { uri, len, expected },
+            )) => return Err(RipunzipErrors::MirrorLengthMismatch { uri, len, expected }),
+            Ok(seekable_http_reader) => {
+                let len = seekable_http_reader.len();
+                let zip_archive = open_archive(TimedSource::new(
+                    seekable_http_reader.clone().create_reader(),
+                ))?;
+                check_consistent_length(&zip_archive, len)?;
+                (
+                    len,
+                    Box::new(UnzipUriEngine(
+                        seekable_http_reader,
+                        zip_archive,
+                        callback_on_rewind,
+                    )) as Box<dyn UnzipEngineImpl>,
+                    None,
+                )
+            }
             Err(_) => {
-                // This server probably doesn't support HTTP ranges.
-                // Let's fall back to fetching the request into a temporary
-                // file then unzipping.
+                // This server probably doesn't support HTTP ranges, or
+                // compresses the zip file on the way, so the ranges don't
+                // line up. Let's fall back to fetching the request into a
+                // temporary file, decoding it if need be, then unzipping.
                 log::warn!("HTTP(S) server does not support range requests - falling back to fetching whole file.");
-                let mut response = reqwest::blocking::get(uri)?;
-                let mut tempfile = tempfile::NamedTempFile::new()?;
+                let mut response = get_from_mirrors(&uris, &client_config)?;
+                let mut tempfile = create_temp_file(temp_dir.as_deref())?;
                 std::io::copy(&mut response, &mut tempfile)?;
                 let compressed_length = determine_stream_len(tempfile.as_file_mut())?;
-                let zipfile_reader = MultiFileSeeker::new(tempfile.path())?;
+                let zipfile_reader = CloneableFile::open(tempfile.path())?;
                 (
                     compressed_length,
-                    Box::new(UnzipFileEngine(ZipArchive::new(zipfile_reader)?))
-                        as Box<dyn UnzipEngineImpl>,
+                    Box::new(UnzipFileEngine::open(zipfile_reader)?) as Box<dyn UnzipEngineImpl>,
                     Some(tempfile),
                 )
             }
@@ -238,8 +1664,73 @@ impl UnzipEngine {
         Ok(Self {
             zipfile: zipfile_impl,
             _temp_file: temp_file,
+            temp_dir,
             compressed_length,
-            directory_creator: DirectoryCreator::default(),
+            enclosing: Vec::new(),
+            decompressed: 0,
+        })
+    }
+
+    /// Create an unzip engine which reads only the parts of the zip file
+    /// it needs over SFTP.
+    #[cfg(feature = "ssh")]
+    fn for_sftp_uri(uri: &str) -> Result<Self, RipunzipErrors> {
+        let reader = sftp_reader::SftpReader::open(uri)?;
+        let compressed_length = reader.len();
+        Ok(Self {
+            zipfile: Box::new(UnzipFileEngine::open(reader)?),
+            _temp_file: None,
+            temp_dir: None,
+            compressed_length,
+            enclosing: Vec::new(),
+            decompressed: 0,
+        })
+    }
+
+    #[cfg(all(feature = "http", not(feature = "ssh")))]
+    fn for_sftp_uri(uri: &str) -> Result<Self, RipunzipErrors> {
+        Err(RipunzipErrors::UnsupportedUri {
+            uri: uri.to_string(),
+            reason: "ripunzip was built without the ssh feature".to_string(),
+        })
+    }
+
+    /// Create an unzip engine which knows how to unzip a zip file stored
+    /// as the named entry within this one. If the entry is uncompressed
+    /// it's read in place, so for a URI we still fetch only the parts we
+    /// need. Otherwise it must be decompressed into a temporary file first.
+    ///
+    /// To guard against zip bombs which are quines, or otherwise nest
+    /// forever, this fails with [`RipunzipErrors::RecursiveBombDetected`]
+    /// if the entry is the same zip file as this one or any it was itself
+    /// opened within, comparing their lengths and the CRCs of their
+    /// central directories, which list the CRC of every entry.
+    pub fn for_entry(mut self, name: &str) -> Result<Self, RipunzipErrors> {
+        let (zipfile, compressed_length, temp_file) = self
+            .zipfile
+            .open_entry(name, self.temp_dir.as_deref())
+            .map_err(detect_network_error)?;
+        let mut enclosing = self.enclosing;
+        enclosing.push(self.zipfile.archive_identity()?);
+        if enclosing.contains(&zipfile.archive_identity()?) {
+            return Err(RipunzipErrors::RecursiveBombDetected {
+                name: name.to_string(),
+            });
+        }
+        // Unless it's read in place, it's been decompressed into the
+        // temporary file.
+        let decompressed = match temp_file {
+            Some(_) => self.decompressed.saturating_add(compressed_length),
+            None => self.decompressed,
+        };
+        Ok(Self {
+            zipfile,
+            // If we're reading in place from a temporary file, keep it.
+            _temp_file: temp_file.or(self._temp_file),
+            temp_dir: self.temp_dir,
+            compressed_length,
+            enclosing,
+            decompressed,
         })
     }
 
@@ -250,18 +1741,554 @@ impl UnzipEngine {
     }
 
     // Perform the unzip.
-    pub fn unzip(mut self, options: UnzipOptions) -> Result<(), RipunzipErrors> {
+    pub fn unzip(self, options: UnzipOptions) -> Result<UnzipOutcome, RipunzipErrors> {
+        self.unzip_streaming(options, None, None)
+    }
+
+    /// Perform the unzip on another thread, returning an iterator over the
+    /// files and directories extracted as each one finishes, so that they
+    /// can be processed while the rest are still being extracted. Entries
+    /// which aren't extracted, such as existing files which are skipped,
+    /// aren't returned. See [`UnzipIter`] for how errors and cancellation
+    /// work.
+    pub fn unzip_iter(self, options: UnzipOptions<'static, 'static>) -> UnzipIter {
+        UnzipIter::spawn(move |entry_stream| {
+            self.unzip_streaming(options, Some(entry_stream), None)
+        })
+    }
+
+    /// Extract the entries matching `priority_filter` first, returning once
+    /// they're extracted, and carry on extracting the rest on another
+    /// thread, so that the prioritized files can be used in the meantime.
+    /// The filename filter in `options`, if any, still applies to both.
+    /// The unzip is otherwise the same as [`UnzipEngine::unzip`], so only
+    /// finishes, writing any completion marker, once everything's
+    /// extracted. If extracting the prioritized entries fails, or anything
+    /// before them, the error is returned here and nothing more is
+    /// extracted. The rest of the unzip can be paused, resumed or
+    /// cancelled through [`BackgroundUnzip::control`].
+    pub fn unzip_prioritized(
+        self,
+        options: UnzipOptions<'static, 'static>,
+        priority_filter: Box<dyn FilenameFilter + Send + Sync>,
+    ) -> Result<BackgroundUnzip, RipunzipErrors> {
+        BackgroundUnzip::spawn(priority_filter, move |prioritization| {
+            self.unzip_streaming(options, None, Some(prioritization))
+        })
+    }
+
+    /// Extract only the entries which are new or have changed since an
+    /// earlier version of this zip file, whose entries are given as
+    /// returned by [`UnzipEngine::entry_metadata`], comparing them by name,
+    /// size and CRC, or for AES encrypted entries without a CRC, by size
+    /// and modification time. Entries which haven't changed are reported
+    /// to the progress reporter as filtered. The filename filter in
+    /// `options`, if any, applies to the comparison too.
+    pub fn extract_delta(
+        self,
+        previous: &[ZipEntryMetadata],
+        options: UnzipOptions,
+    ) -> Result<ExtractionDelta, RipunzipErrors> {
+        let mut delta = ExtractionDelta::compare(
+            previous,
+            &self.zipfile.entry_metadata()?,
+            options
+                .filename_filter
+                .as_deref()
+                .map(|filter| filter as &dyn FilenameFilter),
+        );
+        let to_extract = delta.added.iter().chain(&delta.changed).cloned().collect();
+        delta.outcome = self.unzip(UnzipOptions {
+            filename_filter: Some(Box::new(DeltaFilter(to_extract))),
+            ..options
+        })?;
+        Ok(delta)
+    }
+
+    fn unzip_streaming(
+        self,
+        mut options: UnzipOptions,
+        entry_stream: Option<&EntryStream>,
+        prioritization: Option<&Prioritization>,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        let progress_file = add_progress_file(&mut options)?;
+        let result = self.unzip_streaming_inner(options, entry_stream, prioritization);
+        if let (Some(progress_file), Err(e)) = (progress_file, &result) {
+            progress_file.error(e);
+        }
+        result
+    }
+
+    /// Check the zip file's signature, as
+    /// [`UnzipOptions::verify_signature`] does, failing with
+    /// [`RipunzipErrors::SignatureInvalid`] if it doesn't match. This is
+    /// for methods which don't take options, such as
+    /// [`Self::extract_to_memory`]. For a URI the zip file is first
+    /// downloaded into a temporary file, which this engine then reads
+    /// instead. Otherwise it carries on reading through the same handle as
+    /// was checked, so replacing the file at its path makes no difference,
+    /// and reads the central directory again in case the file changed
+    /// before it was checked. Only available with the `signature` feature.
+    #[cfg(feature = "signature")]
+    pub fn verify_signature(&mut self, config: &SignatureConfig) -> Result<(), RipunzipErrors> {
+        log::info!("Checking the zip file's signature");
+        let mut reader = self.zipfile.archive_reader()?;
+        if !self.zipfile.is_remote() {
+            config.verify(&mut reader, None)?;
+            drop(reader);
+            return self.zipfile.reopen();
+        }
+        let mut tempfile = create_temp_file(self.temp_dir.as_deref())?;
+        config
+            .verify(&mut reader, Some(tempfile.as_file_mut()))
+            .map_err(detect_network_error)?;
+        drop(reader);
+        let zipfile_reader = CloneableFile::open(tempfile.path())?;
+        self.zipfile = Box::new(UnzipFileEngine::open(zipfile_reader)?);
+        self._temp_file = Some(tempfile);
+        Ok(())
+    }
+
+    fn unzip_streaming_inner(
+        mut self,
+        mut options: UnzipOptions,
+        entry_stream: Option<&EntryStream>,
+        prioritization: Option<&Prioritization>,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
         log::debug!("Starting extract");
+        let started = Instant::now();
+        if options.shard_into == Some(0) {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "can't shard files into 0 directories",
+            )
+            .into());
+        }
+        coalesce_progress(&mut options);
+        // Held until we've finished extracting. Taken first, so that the
+        // checks below see what the unzip holding it before us left.
+        let _output_lock = if options.dry_run || options.output_lock == OutputLock::None {
+            None
+        } else {
+            if !options.create_output_directory {
+                check_output_directory_exists(&options)?;
+            }
+            create_output_directory(&options)?;
+            lock_output(&options)?
+        };
+        let identity = match &options.completion_marker {
+            Some(marker) => {
+                let identity = self.zipfile.archive_identity()?;
+                let marker_error = |e| RipunzipErrors::IOErrorWithContext {
+                    context: format!("Failed to check completion marker {}", marker.display()),
+                    source: e,
+                };
+                if identity.is_marked(marker).map_err(marker_error)? {
+                    log::info!("{} shows this zip file was extracted", marker.display());
+                    let outcome = UnzipOutcome::default();
+                    options.progress_reporter.all_finished(&outcome);
+                    return Ok(outcome);
+                }
+                if !options.dry_run {
+                    remove_marker(marker).map_err(marker_error)?;
+                }
+                Some(identity)
+            }
+            None => None,
+        };
+        if let Some(temp_dir) = &options.temp_dir {
+            self.temp_dir = Some(temp_dir.clone());
+        }
+        #[cfg(feature = "signature")]
+        if let (Some(config), false) = (&options.verify_signature, options.dry_run) {
+            self.verify_signature(config)?;
+        }
         options
             .progress_reporter
             .total_bytes_expected(self.compressed_length);
-        let errors = self.zipfile.unzip(options, &self.directory_creator);
+        if let Some(limit) = options.max_entries {
+            let count = self.zipfile.entry_count();
+            if count > limit {
+                return Err(RipunzipErrors::TooManyEntries { count, limit });
+            }
+        }
+        options.progress_reporter.central_directory_read_starting();
+        let entries = self.zipfile.entry_metadata()?;
+        options
+            .progress_reporter
+            .central_directory_read_finished(entries.len());
+        if let (Some(path), false) = (&options.emit_index, options.dry_run) {
+            write_index(path, &self.zipfile.index()?)?;
+        }
+        apply_entry_options(&mut options, &entries)?;
+        let manifest_coverage = ManifestCoverage::new(&options, &entries);
+        options.progress_reporter.metadata_ready(&entries);
+        warn_about_backslashes(&entries, &options);
+        if let Some(entry_stream) = entry_stream {
+            entry_stream.check_not_cancelled()?;
+        }
+        if options.require_empty_output {
+            check_output_empty(&options)?;
+        }
+        if !options.create_output_directory {
+            check_output_directory_exists(&options)?;
+        }
+        if options.verify_before_extract && !options.dry_run {
+            log::debug!("Verifying CRCs before extract");
+            self.zipfile
+                .verify(&options)
+                .map_err(detect_network_error)?;
+        }
+        if !options.dry_run {
+            create_output_directory(&options)?;
+        }
+        let state = ExtractionState {
+            directory_creator: DirectoryCreator::default(),
+            overwrite_gate: OverwriteGate::new(std::mem::take(&mut options.overwrite_policy)),
+            deduplicator: options.dedup.then(Deduplicator::default),
+            device: options
+                .restrict_to_device
+                .then(|| output_device(options.output_directory.as_deref()))
+                .transpose()?,
+            prefix_devices: if options.restrict_to_device {
+                options
+                    .prefix_map
+                    .iter()
+                    .map(|(_, directory)| output_device(Some(directory)))
+                    .collect::<Result<_, _>>()?
+            } else {
+                Vec::new()
+            },
+            entry_stream,
+            prioritization,
+            open_files: OpenFileLimit::new(options.max_open_files),
+            path_locks: PathLocks::default(),
+            partial_files: PartialFileTracker::new(options.partial_files),
+            directory_mtimes: (options.preserve_mtimes && options.restore_directory_mtimes)
+                .then(DirectoryMtimes::default),
+            total_size: TotalSize::new(options.max_total_size, self.decompressed)?,
+        };
+        if options.dry_run || options.explain {
+            let (plan, mut outcome) = dry_run::plan(
+                entries,
+                self.zipfile.index()?,
+                &options,
+                &state.overwrite_gate,
+            )?;
+            if options.explain {
+                for entry in &plan.entries {
+                    log::info!("Plan: {}", entry.to_json());
+                }
+                options.progress_reporter.plan_ready(&plan);
+            }
+            if options.dry_run {
+                plan.report(options.progress_reporter.as_ref());
+                manifest_coverage.record(&mut outcome);
+                outcome.wall_time = started.elapsed();
+                options.progress_reporter.all_finished(&outcome);
+                return Ok(outcome);
+            }
+        }
+        let (mut outcome, errors) = self.zipfile.unzip(&options, &state);
+        let mut errors = errors
+            .into_iter()
+            .map(detect_network_error)
+            .collect::<Vec<_>>();
+        // If the zip file changed on the server, or we ran out of download
+        // budget, that's the root of any other errors.
+        if let Some(i) = errors.iter().position(|e| {
+            matches!(
+                e,
+                RipunzipErrors::SourceChanged { .. } | RipunzipErrors::BudgetExceeded { .. }
+            )
+        }) {
+            errors.swap(0, i);
+        }
         // Return the first error code, if any.
-        errors.into_iter().next().map(Result::Err).unwrap_or(Ok(()))
+        if let Some(error) = errors.into_iter().next() {
+            let paths = state.partial_files.cleaned_up();
+            if paths.is_empty() {
+                return Err(error);
+            }
+            return Err(RipunzipErrors::PartialFilesCleanedUp {
+                source: Box::new(error),
+                paths,
+            });
+        }
+        if let Some(directory_mtimes) = &state.directory_mtimes {
+            directory_mtimes.restore()?;
+        }
+        manifest_coverage.record(&mut outcome);
+        if let (Some(identity), Some(marker)) = (identity, &options.completion_marker) {
+            identity
+                .write_marker(marker)
+                .map_err(|e| RipunzipErrors::IOErrorWithContext {
+                    context: format!("Failed to write completion marker {}", marker.display()),
+                    source: e,
+                })?;
+        }
+        outcome.wall_time = started.elapsed();
+        options.progress_reporter.all_finished(&outcome);
+        Ok(outcome)
+    }
+
+    /// Check the CRC of every file matching the filename filter, if any,
+    /// without writing anything. Unlike `verify_before_extract`, this
+    /// checks files in any order on all available cores, so is quickest
+    /// for a local file; it fails on the first corrupt file found, which
+    /// may not be the first in the zip. Only the password, filename
+    /// filter, decompressor registry and strictness options are used.
+    pub fn verify_parallel(&self, options: &UnzipOptions) -> Result<VerifyOutcome, RipunzipErrors> {
+        self.zipfile
+            .verify_parallel(options)
+            .map_err(detect_network_error)
+    }
+
+    /// Check the signature, if asked to, and apply the options which
+    /// decide which entries to write, for [`Self::unzip_to_writer`] and
+    /// [`Self::concat_to`], returning the count of data written so far.
+    fn prepare_to_write(
+        &mut self,
+        options: &mut UnzipOptions,
+    ) -> Result<TotalSize, RipunzipErrors> {
+        if let Some(temp_dir) = &options.temp_dir {
+            self.temp_dir = Some(temp_dir.clone());
+        }
+        #[cfg(feature = "signature")]
+        if let Some(config) = &options.verify_signature {
+            self.verify_signature(config)?;
+        }
+        let total_size = TotalSize::new(options.max_total_size, self.decompressed)?;
+        let entries = self.zipfile.entry_metadata()?;
+        restrict_to_only_entries(options, &entries)?;
+        apply_exclude_extensions(options);
+        Ok(total_size)
+    }
+
+    /// Write the contents of a single file to `writer` rather than creating
+    /// any files. This fails unless the zip file contains exactly one file
+    /// (ignoring directories) matching the filename filter, if any. The
+    /// output directory, overwrite policy and parallelism options are
+    /// ignored.
+    pub fn unzip_to_writer(
+        mut self,
+        mut options: UnzipOptions,
+        writer: &mut dyn Write,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        let progress_file = add_progress_file(&mut options)?;
+        coalesce_progress(&mut options);
+        let result = self.prepare_to_write(&mut options).and_then(|total_size| {
+            self.zipfile
+                .unzip_to_writer(&options, &total_size, writer)
+                .map_err(detect_network_error)
+        });
+        match result {
+            Ok(outcome) => {
+                options.progress_reporter.all_finished(&outcome);
+                Ok(outcome)
+            }
+            Err(e) => {
+                if let Some(progress_file) = progress_file {
+                    progress_file.error(&e);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Write the contents of every file matching the filename filter, if
+    /// any, to `writer` one after another, each preceded by whatever
+    /// `separator` returns for it, such as `=== name ===\n`. This is handy
+    /// for dumping a bundle of logs or configuration files in one go.
+    /// Files are written in central directory order, which depends on how
+    /// the zip file was made, unless `sort_by_name` is set, in which case
+    /// they're written in order of their names. Directories are skipped.
+    /// As with [`UnzipEngine::unzip_to_writer`], the output directory,
+    /// overwrite policy and parallelism options are ignored.
+    pub fn concat_to<W: Write>(
+        mut self,
+        mut options: UnzipOptions,
+        mut writer: W,
+        mut separator: impl FnMut(&EntryInfo) -> String,
+        sort_by_name: bool,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        let progress_file = add_progress_file(&mut options)?;
+        coalesce_progress(&mut options);
+        let result = self.prepare_to_write(&mut options).and_then(|total_size| {
+            self.zipfile
+                .concat_to(
+                    &options,
+                    &total_size,
+                    &mut writer,
+                    &mut separator,
+                    sort_by_name,
+                )
+                .map_err(detect_network_error)
+        });
+        match result {
+            Ok(outcome) => {
+                options.progress_reporter.all_finished(&outcome);
+                Ok(outcome)
+            }
+            Err(e) => {
+                if let Some(progress_file) = progress_file {
+                    progress_file.error(&e);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Decompress the files whose names match `filter` into memory rather
+    /// than writing them anywhere, returning a map from their paths within
+    /// the zip file to their contents. Fails if the contents would add up to
+    /// more than `memory_limit` bytes. Encrypted files aren't supported.
+    /// No signature is checked, so with the `signature` feature, call
+    /// `verify_signature` first to check one. Rather than
+    /// [`UnzipOptions::max_total_size`], `memory_limit` caps how much is
+    /// decompressed.
+    pub fn extract_to_memory(
+        &self,
+        filter: &dyn FilenameFilter,
+        memory_limit: Option<u64>,
+    ) -> Result<HashMap<PathBuf, Vec<u8>>, RipunzipErrors> {
+        self.zipfile
+            .extract_to_memory(filter, memory_limit)
+            .map_err(detect_network_error)
+    }
+
+    /// Write the data of the named entry exactly as it's stored in the zip
+    /// file, without decompressing or decrypting it, returning the
+    /// compression method needed to make sense of it. For an encrypted
+    /// entry this includes the encryption header. This works even for
+    /// entries which are corrupt or use an unsupported compression method.
+    pub fn extract_raw_entry_to<W: Write>(
+        &self,
+        name: &str,
+        mut writer: W,
+    ) -> Result<CompressionMethod, RipunzipErrors> {
+        self.zipfile
+            .extract_raw_entry_to(name, &mut writer)
+            .map_err(detect_network_error)
+    }
+
+    /// Decompress the first `n` bytes of the named entry, or all of it if
+    /// it's shorter, for instance to check what kind of file it is. Only
+    /// as much of the entry is read as is needed, so for a URI not much
+    /// more than that is fetched. Encrypted files aren't supported.
+    pub fn peek_entry(&self, name: &str, n: usize) -> Result<Vec<u8>, RipunzipErrors> {
+        self.zipfile
+            .peek_entry(name, n)
+            .map_err(detect_network_error)
+    }
+
+    /// Decompress every entry and write it to a new zip file on `out`,
+    /// compressed with `method` at `level`, or the method's default level
+    /// if `None`, returning `out` once the zip file is finished. This reads
+    /// each entry's data once, in central directory order, without writing
+    /// anything to disk along the way. Entries are written in the same
+    /// order, keeping their names, comments, modification times and Unix
+    /// permissions, so the result is reproducible. Encrypted files aren't
+    /// supported.
+    pub fn recompress_to<W: Write + Seek>(
+        &self,
+        mut out: W,
+        method: CompressionMethod,
+        level: Option<i64>,
+    ) -> Result<W, RipunzipErrors> {
+        self.zipfile
+            .recompress_to(&mut out, method, level)
+            .map_err(detect_network_error)?;
+        Ok(out)
+    }
+
+    /// The zip file's comment, if it has one and it's valid UTF-8. This is
+    /// read along with the central directory, so is available without
+    /// fetching anything more.
+    pub fn archive_comment(&self) -> Option<String> {
+        let comment = self.archive_comment_bytes();
+        if comment.is_empty() {
+            return None;
+        }
+        String::from_utf8(comment.to_vec()).ok()
+    }
+
+    /// The zip file's comment as raw bytes, which is empty if there's no
+    /// comment.
+    pub fn archive_comment_bytes(&self) -> &[u8] {
+        self.zipfile.comment()
+    }
+
+    /// Details of every entry, in central directory order, read from the
+    /// central directory alone. Keep these to pass to
+    /// [`UnzipEngine::extract_delta`] for the next version of the zip file.
+    pub fn entry_metadata(&self) -> Result<Vec<ZipEntryMetadata>, RipunzipErrors> {
+        self.zipfile.entry_metadata()
+    }
+
+    /// What unzipping with the given options would do with each entry and
+    /// where it would be extracted, once filters, renaming, duplicate and
+    /// overwrite policies and the rest have been applied, without writing
+    /// anything. Only the central directory is read, so for a URI nothing
+    /// more is fetched. A callback overwrite policy is consulted about
+    /// existing files, as for a dry run.
+    pub fn plan(&self, mut options: UnzipOptions) -> Result<ExtractionPlan, RipunzipErrors> {
+        let entries = self.zipfile.entry_metadata()?;
+        apply_entry_options(&mut options, &entries)?;
+        let overwrite_gate = OverwriteGate::new(std::mem::take(&mut options.overwrite_policy));
+        let (plan, _) = dry_run::plan(entries, self.zipfile.index()?, &options, &overwrite_gate)?;
+        Ok(plan)
+    }
+
+    /// How well the files are compressed, overall and for each compression
+    /// method, read from the central directory alone, so for a URI nothing
+    /// more than that is fetched.
+    pub fn compression_stats(&self) -> Result<CompressionStats, RipunzipErrors> {
+        self.zipfile.compression_stats()
+    }
+
+    /// Roughly how long extracting every file would take at the given
+    /// speeds, from the sizes in the central directory, for instance to
+    /// tell the user before starting. Time spent creating directories and
+    /// files isn't accounted for, so this may be well short for many small
+    /// files.
+    pub fn estimate_duration(
+        &self,
+        assumed_throughput: Throughput,
+    ) -> Result<Duration, RipunzipErrors> {
+        Ok(assumed_throughput.estimate(&self.compression_stats()?.overall))
     }
 
-    /// List the filenames in the archive
-    pub fn list(self) -> Result<impl Iterator<Item = String>, RipunzipErrors> {
+    /// Measure how quickly this zip file can be read and decompressed by
+    /// decompressing up to 4MiB of its first non-empty file, to pass to
+    /// [`UnzipEngine::estimate_duration`]. This is measured on a single
+    /// thread, so underestimates decompression when unzipping in parallel;
+    /// for a URI, it includes the time to open a stream. `None` if there
+    /// are no non-empty files. Encrypted files aren't supported.
+    pub fn measure_throughput(&self) -> Result<Option<Throughput>, RipunzipErrors> {
+        let entries = self.entry_metadata()?;
+        let Some(entry) = entries
+            .iter()
+            .find(|entry| !entry.name.ends_with('/') && entry.size > 0)
+        else {
+            return Ok(None);
+        };
+        let start = Instant::now();
+        let sample = self.peek_entry(&entry.name, estimate::SAMPLE_BYTES as usize)?;
+        let elapsed = start.elapsed();
+        // Assume the compressed data read was in proportion.
+        let compressed_bytes =
+            (entry.compressed_size as f64 * sample.len() as f64 / entry.size as f64) as u64;
+        Ok(Some(Throughput::measured(
+            compressed_bytes,
+            sample.len() as u64,
+            elapsed,
+        )))
+    }
+
+    /// List the filenames in the archive, from the central directory read
+    /// when the engine was created, so without any further I/O.
+    pub fn list(&self) -> Result<impl Iterator<Item = String>, RipunzipErrors> {
         // In future this might be a more dynamic iterator type.
         self.zipfile.list().map(|mut v| {
             // Names are returned in a HashMap iteration order so let's
@@ -272,6 +2299,37 @@ impl UnzipEngine {
     }
 }
 
+/// Replace the options which depend on what's in the zip file with their
+/// equivalents for `entries`.
+fn apply_entry_options(
+    options: &mut UnzipOptions,
+    entries: &[ZipEntryMetadata],
+) -> Result<(), RipunzipErrors> {
+    restrict_to_only_entries(options, entries)?;
+    apply_exclude_extensions(options);
+    apply_flatten_single_root(options, entries);
+    Ok(())
+}
+
+/// Warn if any entry names contain backslashes, which suggests a broken
+/// tool that used them as path separators.
+fn warn_about_backslashes(entries: &[ZipEntryMetadata], options: &UnzipOptions) {
+    let mut with_backslashes = entries.iter().filter(|entry| entry.name.contains('\\'));
+    let Some(first) = with_backslashes.next() else {
+        return;
+    };
+    let count = with_backslashes.count() + 1;
+    let treatment = if options.normalize_separators || cfg!(windows) {
+        "path separators"
+    } else {
+        "part of file names"
+    };
+    log::warn!(
+        "{count} entries, such as {}, have backslashes in their names, which are treated as {treatment}",
+        first.name
+    );
+}
+
 /// Return a list of filenames from the zip. For now this is infallible
 /// but provide the option of an error code in case we do something
 /// smarter in future.
@@ -281,30 +2339,593 @@ fn list<'a, T: Read + Seek + 'a>(
     Ok(zip_archive.file_names().map(|s| s.to_string()).collect())
 }
 
-fn unzip_serial_or_parallel<'a, T: Read + Seek + 'a>(
+/// Open the named entry of `zip_archive` as a zip file. `entry_reader`
+/// makes a reader over a range of the zip file's reader.
+fn open_entry<T: Read + Seek + Clone, R: Read + Seek + Clone + Send + Sync + 'static>(
+    zip_archive: &mut ZipArchive<T>,
+    name: &str,
+    temp_dir: Option<&Path>,
+    entry_reader: impl FnOnce(T, u64, u64) -> EntryReader<R>,
+) -> Result<NestedZip, RipunzipErrors> {
+    let i = zip_archive
+        .index_for_name(name)
+        .ok_or(ZipError::FileNotFound)?;
+    let (data_start, len) = {
+        let file = zip_archive.by_index_raw(i)?;
+        let readable_in_place =
+            file.compression() == CompressionMethod::Stored && !file.encrypted();
+        (
+            file.data_start().filter(|_| readable_in_place),
+            file.compressed_size(),
+        )
+    };
+    if let Some(data_start) = data_start {
+        let reader = entry_reader(zip_archive.clone().into_inner(), data_start, len);
+        return Ok((
+            Box::new(UnzipNestedEngine(open_archive(reader)?)),
+            len,
+            None,
+        ));
+    }
+    log::info!("{name} is compressed - extracting it to a temporary file first.");
+    let mut tempfile = create_temp_file(temp_dir)?;
+    std::io::copy(&mut zip_archive.by_index(i)?, &mut tempfile)?;
+    let compressed_length = determine_stream_len(tempfile.as_file_mut())?;
+    let zipfile_reader = CloneableFile::open(tempfile.path())?;
+    Ok((
+        Box::new(UnzipFileEngine::open(zipfile_reader)?),
+        compressed_length,
+        Some(tempfile),
+    ))
+}
+
+/// Copy all the data from `reader` to `writer`, reading and writing up to
+/// `buffer_size` bytes at a time. Returns the number of bytes copied.
+pub(crate) fn copy_with_buffer_size<R: Read + ?Sized, W: Write + ?Sized>(
+    reader: &mut R,
+    writer: &mut W,
+    buffer_size: usize,
+) -> std::io::Result<u64> {
+    let mut buf = vec![0u8; buffer_size.max(1)];
+    let mut copied = 0;
+    loop {
+        let len = match reader.read(&mut buf) {
+            Ok(0) => return Ok(copied),
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..len])?;
+        copied += len as u64;
+    }
+}
+
+/// Start a GET request to the first of the mirrors which responds
+/// successfully. A gzip `Content-Encoding` is decoded as we read; others
+/// aren't supported.
+#[cfg(feature = "http")]
+fn get_from_mirrors(
+    uris: &[String],
+    client_config: &ClientConfig,
+) -> Result<reqwest::blocking::Response, RipunzipErrors> {
+    let client = client_config.client_builder().build()?;
+    let mut first_error = None;
+    for uri in uris {
+        match client
+            .get(uri)
+            .send()
+            .and_then(reqwest::blocking::Response::error_for_status)
+        {
+            Ok(response) => {
+                if let Some(encoding) = http_range_reader::content_encoding(&response) {
+                    return Err(RipunzipErrors::UnsupportedUri {
+                        uri: uri.clone(),
+                        reason: format!("served with unsupported Content-Encoding {encoding}"),
+                    });
+                }
+                return Ok(response);
+            }
+            Err(e) => {
+                log::warn!("Unable to fetch {uri}: {e}");
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    Err(match first_error {
+        Some(e) => e.into(),
+        None => RipunzipErrors::UnsupportedUri {
+            uri: String::new(),
+            reason: "no mirrors given".to_string(),
+        },
+    })
+}
+
+/// If `error` happened because the zip file changed on the server while we
+/// were reading it, say so, rather than whatever that led to.
+fn detect_network_error(error: RipunzipErrors) -> RipunzipErrors {
+    #[cfg(feature = "http")]
+    {
+        let io_error = match &error {
+            RipunzipErrors::IOError(e)
+            | RipunzipErrors::IOErrorWithContext { source: e, .. }
+            | RipunzipErrors::ZipErrorr(ZipError::Io(e)) => e,
+            _ => return error,
+        };
+        if let Some(uri) = http_range_reader::source_changed(io_error) {
+            return RipunzipErrors::SourceChanged {
+                uri: uri.to_string(),
+            };
+        }
+        if let Some((downloaded, budget)) = seekable_http_reader::budget_exceeded(io_error) {
+            return RipunzipErrors::BudgetExceeded { downloaded, budget };
+        }
+    }
+    error
+}
+
+/// Create a temporary file, which is deleted when dropped, in the given
+/// directory or else the system's temporary directory.
+fn create_temp_file(temp_dir: Option<&Path>) -> std::io::Result<tempfile::NamedTempFile> {
+    match temp_dir {
+        Some(temp_dir) => tempfile::NamedTempFile::new_in(temp_dir),
+        None => tempfile::NamedTempFile::new(),
+    }
+}
+
+/// Fail if the output directory is missing.
+fn check_output_directory_exists(options: &UnzipOptions) -> Result<(), RipunzipErrors> {
+    match &options.output_directory {
+        Some(directory) if !directory.exists() => Err(RipunzipErrors::OutputDirectoryMissing {
+            directory: directory.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Create the output directory, and any missing parents, so that it
+/// exists even if nothing is extracted into it.
+fn create_output_directory(options: &UnzipOptions) -> Result<(), RipunzipErrors> {
+    let Some(directory) = &options.output_directory else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(directory).map_err(|e| RipunzipErrors::IOErrorWithContext {
+        context: format!("Failed to create output directory {}", directory.display()),
+        source: e,
+    })
+}
+
+/// Fail if the output directory exists and contains anything, except
+/// perhaps hidden files.
+fn check_output_empty(options: &UnzipOptions) -> Result<(), RipunzipErrors> {
+    let directory = options
+        .output_directory
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."));
+    let entries = match std::fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(RipunzipErrors::IOErrorWithContext {
+                context: format!("Failed to read directory {}", directory.display()),
+                source: e,
+            })
+        }
+    };
+    for entry in entries {
+        let entry = entry?;
+        if options.empty_output_ignores_hidden
+            && entry.file_name().to_string_lossy().starts_with('.')
+        {
+            continue;
+        }
+        return Err(RipunzipErrors::OutputDirectoryNotEmpty { directory });
+    }
+    Ok(())
+}
+
+/// Decompress every entry which would be extracted, discarding the output,
+/// so that the zip library checks each CRC. Entries are visited in index
+/// order so that a URI is read in a single pass.
+fn verify<T: Read + Seek + Clone>(
+    zip_archive: &mut ZipArchive<T>,
+    options: &UnzipOptions,
+) -> Result<(), RipunzipErrors> {
+    for i in 0..zip_archive.len() {
+        with_file_by_index(zip_archive, i, options, |mut file, decompressor| {
+            if file.is_dir() {
+                return Ok(());
+            }
+            if let Some(filename_filter) = &options.filename_filter {
+                if !filename_filter.should_unzip(file.name()) {
+                    return Ok(());
+                }
+            }
+            verify_file(&mut file, decompressor).map(|_| ())
+        })?;
+    }
+    Ok(())
+}
+
+/// Decompress a file, checking its CRC, and return its length.
+fn verify_file<R: Read>(
+    file: &mut ZipFile<R>,
+    decompressor: Option<&Decompressor>,
+) -> Result<u64, RipunzipErrors> {
+    let name = file.name().to_string();
+    let mut reader = decompressing_reader(file, decompressor);
+    std::io::copy(&mut reader, &mut std::io::sink())
+        .map_err(|e| integrity_error(&name, &e).unwrap_or_else(|| e.into()))
+}
+
+/// Check the CRCs of all files matching the filter, each on whichever
+/// thread is free with its own clone of the zip archive.
+fn verify_parallel<T: Read + Seek + Clone + Sync>(
+    zip_archive: &ZipArchive<T>,
+    options: &UnzipOptions,
+) -> Result<VerifyOutcome, RipunzipErrors> {
+    let timings = (0..zip_archive.len())
+        .into_par_iter()
+        .map(|i| {
+            with_file_by_index(
+                &mut zip_archive.clone(),
+                i,
+                options,
+                |mut file, decompressor| {
+                    let wanted = options
+                        .filename_filter
+                        .as_ref()
+                        .map(|filter| filter.should_unzip(file.name()))
+                        .unwrap_or(true);
+                    if file.is_dir() || !wanted {
+                        return Ok(None);
+                    }
+                    let start = Instant::now();
+                    let len = verify_file(&mut file, decompressor)?;
+                    Ok(Some((file.name().to_string(), len, start.elapsed())))
+                },
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut outcome = VerifyOutcome::default();
+    let mut timings: Vec<_> = timings.into_iter().flatten().collect();
+    outcome.files_verified = timings.len();
+    outcome.bytes_verified = timings.iter().map(|(_, len, _)| len).sum();
+    timings.sort_by_key(|(_, _, duration)| std::cmp::Reverse(*duration));
+    outcome.slowest_entries = timings
+        .into_iter()
+        .take(SLOWEST_ENTRIES_REPORTED)
+        .map(|(name, _, duration)| (name, duration))
+        .collect();
+    Ok(outcome)
+}
+
+/// Find the only file to be extracted and write its contents to `writer`.
+fn unzip_to_writer<T: Read + Seek + Clone>(
+    zip_archive: &mut ZipArchive<T>,
+    options: &UnzipOptions,
+    total_size: &TotalSize,
+    mut writer: &mut dyn Write,
+) -> Result<UnzipOutcome, RipunzipErrors> {
+    let names: Vec<_> = zip_archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .filter(|name| {
+            options
+                .filename_filter
+                .as_ref()
+                .map(|filter| filter.should_unzip(name))
+                .unwrap_or(true)
+        })
+        .collect();
+    let [name] = names[..] else {
+        return Err(RipunzipErrors::NotSingleFile { count: names.len() });
+    };
+    let i = zip_archive
+        .index_for_name(name)
+        .ok_or(ZipError::FileNotFound)?;
+    let compressed_size = zip_archive.by_index_raw(i)?.compressed_size();
+    options
+        .progress_reporter
+        .total_bytes_expected(compressed_size);
+    let outcome = write_entry(
+        zip_archive,
+        i,
+        options,
+        total_size,
+        &mut writer,
+        &mut |_| String::new(),
+    )?;
+    writer.flush()?;
+    Ok(outcome)
+}
+
+/// Write the contents of every file to be extracted to `writer`, in
+/// central directory order or by name, each after its separator.
+fn concat_to<T: Read + Seek + Clone>(
+    zip_archive: &mut ZipArchive<T>,
+    options: &UnzipOptions,
+    total_size: &TotalSize,
+    writer: &mut dyn Write,
+    separator: &mut dyn FnMut(&EntryInfo) -> String,
+    sort_by_name: bool,
+) -> Result<UnzipOutcome, RipunzipErrors> {
+    let mut entries: Vec<_> = entry_metadata(zip_archive)?
+        .into_iter()
+        .enumerate()
+        .filter(|(_, entry)| !entry.name.ends_with('/'))
+        .filter(|(_, entry)| {
+            options
+                .filename_filter
+                .as_ref()
+                .map(|filter| filter.should_unzip(&entry.name))
+                .unwrap_or(true)
+        })
+        .collect();
+    if sort_by_name {
+        entries.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+    }
+    let progress_reporter = options.progress_reporter.as_ref();
+    progress_reporter
+        .total_bytes_expected(entries.iter().map(|(_, entry)| entry.compressed_size).sum());
+    let mut outcome = UnzipOutcome::default();
+    for (i, _) in entries {
+        outcome.add(&write_entry(
+            zip_archive,
+            i,
+            options,
+            total_size,
+            writer,
+            separator,
+        )?);
+    }
+    writer.flush()?;
+    Ok(outcome)
+}
+
+/// Write the contents of the entry at index `i` to `writer`, after
+/// whatever `before` returns for it.
+fn write_entry<T: Read + Seek + Clone>(
+    zip_archive: &mut ZipArchive<T>,
+    i: usize,
+    options: &UnzipOptions,
+    total_size: &TotalSize,
+    mut writer: &mut dyn Write,
+    before: &mut dyn FnMut(&EntryInfo) -> String,
+) -> Result<UnzipOutcome, RipunzipErrors> {
+    let progress_reporter = options.progress_reporter.as_ref();
+    with_file_by_index(zip_archive, i, options, |mut file, decompressor| {
+        let name = file.name().to_string();
+        let compressed_size = file.compressed_size();
+        let info = EntryInfo::new(&file, &name);
+        writer.write_all(before(&info).as_bytes())?;
+        progress_reporter.extraction_starting(&info);
+        let line_ending = options
+            .text_conversion
+            .as_ref()
+            .filter(|mode| mode.applies_to(&name))
+            .map(|mode| mode.line_ending);
+        let transformer = options
+            .transform
+            .as_ref()
+            .and_then(|transform| transform(file.name()));
+        let mut reader = total_size.limit(decompressing_reader(&mut file, decompressor));
+        let buffer_size = options.io_buffer_size.unwrap_or(DEFAULT_IO_BUFFER_SIZE);
+        let bytes_written = copy_entry_data(
+            &mut reader,
+            &mut writer,
+            line_ending,
+            transformer,
+            buffer_size,
+        )
+        .map_err(|e| match integrity_error(&name, &e) {
+            Some(error @ RipunzipErrors::AuthenticationFailed { .. }) => error,
+            _ => total_size_error(&e).unwrap_or_else(|| e.into()),
+        })?;
+        progress_reporter.bytes_extracted(compressed_size);
+        progress_reporter.extraction_finished(&name);
+        Ok(UnzipOutcome {
+            files_extracted: 1,
+            bytes_written,
+            ..Default::default()
+        })
+    })
+}
+
+/// Details of every entry, in central directory order, which is the order
+/// in which the zip library indexes them.
+fn entry_metadata<T: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<T>,
+) -> Result<Vec<ZipEntryMetadata>, RipunzipErrors> {
+    let entries = read_central_directory(zip_archive)?;
+    Ok(entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let name = zip_archive.name_for_index(i).unwrap_or_default();
+            ZipEntryMetadata::from_central_directory(name.to_string(), entry)
+        })
+        .collect())
+}
+
+/// Add up the sizes of the files in the central directory.
+fn compression_stats<T: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<T>,
+) -> Result<CompressionStats, RipunzipErrors> {
+    let entries = read_central_directory(zip_archive)?;
+    let mut stats = CompressionStats::default();
+    for (i, entry) in entries.iter().enumerate() {
+        if !zip_archive
+            .name_for_index(i)
+            .unwrap_or_default()
+            .ends_with('/')
+        {
+            stats.add(entry.compression_method, entry.size, entry.compressed_size);
+        }
+    }
+    Ok(stats)
+}
+
+/// Copy the named entry's stored data to `writer`.
+fn extract_raw_entry_to<T: Read + Seek>(
+    mut zip_archive: ZipArchive<T>,
+    name: &str,
+    writer: &mut dyn Write,
+) -> Result<CompressionMethod, RipunzipErrors> {
+    let i = zip_archive
+        .index_for_name(name)
+        .ok_or(ZipError::FileNotFound)?;
+    let mut file = zip_archive.by_index_raw(i)?;
+    std::io::copy(&mut file, writer)?;
+    Ok(file.compression())
+}
+
+/// The most to allocate for an entry's contents before reading them, as
+/// the size the zip file records may be nonsense.
+const MAX_PREALLOCATION: u64 = 1 << 20;
+
+/// Decompress matching files into memory, in the order they appear in the
+/// zip file.
+fn extract_to_memory<T: Read + Seek>(
+    mut zip_archive: ZipArchive<T>,
+    filter: &dyn FilenameFilter,
+    memory_limit: Option<u64>,
+) -> Result<HashMap<PathBuf, Vec<u8>>, RipunzipErrors> {
+    let mut files = HashMap::new();
+    let mut remaining = memory_limit.unwrap_or(u64::MAX);
+    for i in 0..zip_archive.len() {
+        match zip_archive.name_for_index(i) {
+            Some(name) if !name.ends_with('/') && filter.should_unzip(name) => {}
+            _ => continue,
+        }
+        let file = zip_archive.by_index(i)?;
+        // As if extracting with the default options.
+        let path = dry_run::enclosed_name(file.name(), true).ok_or_else(|| {
+            std::io::Error::new(ErrorKind::Unsupported, "path not safe to extract")
+        })?;
+        let exceeded = |limit| RipunzipErrors::MemoryLimitExceeded { limit };
+        // If the size isn't recorded, don't guess, and only find out
+        // whether it fits by reading it.
+        let size = UnknownSizes::Stream.size(file.size(), file.compressed_size());
+        if size.is_some_and(|size| size > remaining) {
+            return Err(exceeded(memory_limit.unwrap_or_default()));
+        }
+        // Don't trust the declared size when reading, nor so far as to
+        // allocate all of it up front.
+        let capacity = size.unwrap_or(0).min(remaining).min(MAX_PREALLOCATION);
+        let mut contents = Vec::with_capacity(capacity as usize);
+        file.take(remaining.saturating_add(1))
+            .read_to_end(&mut contents)?;
+        remaining = remaining
+            .checked_sub(contents.len() as u64)
+            .ok_or_else(|| exceeded(memory_limit.unwrap_or_default()))?;
+        files.insert(path, contents);
+    }
+    Ok(files)
+}
+
+/// Decompress the first `n` bytes of the named entry.
+fn peek_entry<T: Read + Seek>(
+    mut zip_archive: ZipArchive<T>,
+    name: &str,
+    n: usize,
+) -> Result<Vec<u8>, RipunzipErrors> {
+    let file = zip_archive.by_name(name)?;
+    let mut contents = Vec::with_capacity(n.min(file.size() as usize));
+    file.take(n as u64).read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Combine the results from extracting each file.
+fn summarize(
+    results: Vec<Result<UnzipOutcome, RipunzipErrors>>,
+) -> (UnzipOutcome, Vec<RipunzipErrors>) {
+    let mut outcome = UnzipOutcome::default();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(file_outcome) => outcome.add(&file_outcome),
+            Err(e) => errors.push(e),
+        }
+    }
+    (outcome, errors)
+}
+
+fn unzip_serial_or_parallel<'a, T: Read + Seek + Clone + 'a>(
     len: usize,
-    options: UnzipOptions,
-    directory_creator: &DirectoryCreator,
+    options: &UnzipOptions,
+    state: &ExtractionState,
     get_ziparchive_clone: impl Fn() -> ZipArchive<T> + Sync,
     // Call when a file is going to be skipped
     file_skip_callback: impl Fn() + Sync + Send + Clone,
-) -> Vec<RipunzipErrors> {
-    let progress_reporter: &dyn UnzipProgressReporter = options.progress_reporter.as_ref();
-    match (options.filename_filter, options.single_threaded) {
+) -> (UnzipOutcome, Vec<RipunzipErrors>) {
+    let mut skipped = match options.max_entry_size {
+        Some(max_entry_size) => {
+            match too_large_entries(&get_ziparchive_clone(), options, max_entry_size) {
+                Ok(too_large) => too_large,
+                Err(e) => return (UnzipOutcome::default(), vec![e]),
+            }
+        }
+        None => HashSet::new(),
+    };
+    if options.duplicate_policy == DuplicatePolicy::NewestByTime {
+        match superseded_entries(&get_ziparchive_clone(), options, &skipped) {
+            Ok(superseded) => skipped.extend(superseded),
+            Err(e) => return (UnzipOutcome::default(), vec![e]),
+        }
+    }
+    let prioritized = match state.prioritization {
+        Some(prioritization) => {
+            let (outcome, errors) = unzip_prioritized(
+                options,
+                state,
+                &get_ziparchive_clone,
+                prioritization,
+                &mut skipped,
+                file_skip_callback.clone(),
+            );
+            if !errors.is_empty() {
+                return (outcome, errors);
+            }
+            prioritization.finished(outcome.clone());
+            outcome
+        }
+        None => UnzipOutcome::default(),
+    };
+    let summarize = |results| {
+        let (mut outcome, errors) = summarize(results);
+        outcome.add(&prioritized);
+        (outcome, errors)
+    };
+    if options.sequential {
+        return summarize(unzip_sequentially(
+            options,
+            state,
+            get_ziparchive_clone(),
+            &skipped,
+            file_skip_callback,
+        ));
+    }
+    // The data of files we're not extracting may lie between those we are.
+    let skipping = !skipped.is_empty();
+    let extract = |i| {
+        let r = extract_file_by_index(&get_ziparchive_clone, i, options, state);
+        if skipping {
+            file_skip_callback();
+        }
+        r
+    };
+    let results = match (&options.filename_filter, options.single_threaded) {
+        // No rayon here: even its first use spawns a thread per CPU, which
+        // costs more than extracting a small zip.
         (None, true) => (0..len)
-            .map(|i| {
-                extract_file_by_index(
-                    &get_ziparchive_clone,
-                    i,
-                    &options.output_directory,
-                    &options.password,
-                    progress_reporter,
-                    directory_creator,
-                )
-            })
-            .filter_map(Result::err)
+            .filter(|i| !skipped.contains(i))
+            .map(extract)
             .collect(),
         (None, false) => {
+            // Sharded files all go straight into the shard directories.
+            if options.shard_into.is_none() {
+                create_directories(&get_ziparchive_clone(), options, state, &skipped);
+            }
             // We use par_bridge here rather than into_par_iter because it turns
             // out to better preserve ordering of the IDs in the input range,
             // i.e. we're more likely to ask our initial threads to act upon
@@ -313,18 +2934,9 @@ fn unzip_serial_or_parallel<'a, T: Read + Seek + 'a>(
             // bound) that's beneficial because we can start to decompress
             // and write data to disk as soon as it arrives from the network.
             (0..len)
+                .filter(|i| !skipped.contains(i))
                 .par_bridge()
-                .map(|i| {
-                    extract_file_by_index(
-                        &get_ziparchive_clone,
-                        i,
-                        &options.output_directory,
-                        &options.password,
-                        progress_reporter,
-                        directory_creator,
-                    )
-                })
-                .filter_map(Result::err)
+                .map(extract)
                 .collect()
         }
         (Some(filename_filter), single_threaded) => {
@@ -339,11 +2951,11 @@ fn unzip_serial_or_parallel<'a, T: Read + Seek + 'a>(
             if !single_threaded {
                 log::warn!("Unzipping specific files - assuming --single-threaded since we currently cannot unzip specific files in a multi-threaded mode. If you need that, consider launching multiple copies of ripunzip in parallel.");
             }
-            let mut filenames: Vec<_> = get_ziparchive_clone()
+            let zip_archive = get_ziparchive_clone();
+            let (mut filenames, mut filtered_out): (Vec<_>, Vec<_>) = zip_archive
                 .file_names()
-                .filter(|name| filename_filter.as_ref().should_unzip(name))
                 .map(|s| s.to_string())
-                .collect();
+                .partition(|name| filename_filter.as_ref().should_unzip(name));
             // The filenames returned by the file_names() method above are in
             // HashMap iteration order (i.e. random). To avoid creating lots
             // of HTTPS streams for files which are nearby each other in the
@@ -353,140 +2965,762 @@ fn unzip_serial_or_parallel<'a, T: Read + Seek + 'a>(
             // zipped in alphabetical order, or close to it. If we're wrong,
             // we'll just end up rewinding, that is, creating extra redundant
             // HTTP(S) streams.
+            filenames.retain(|name| {
+                zip_archive
+                    .index_for_name(name)
+                    .map_or(true, |i| !skipped.contains(&i))
+            });
             filenames.sort();
             log::info!("Will unzip {} matching filenames", filenames.len());
+            filtered_out.sort();
+            for name in &filtered_out {
+                options
+                    .progress_reporter
+                    .entry_skipped(name, SkipReason::Filtered);
+            }
             file_skip_callback();
 
-            // let progress_reporter: &dyn UnzipProgressReporter = options.progress_reporter.as_ref();
             filenames
                 .into_iter()
                 .map(|name| {
-                    let myzip: &mut zip::ZipArchive<T> = &mut get_ziparchive_clone();
-                    let file: ZipFile<T> = match &options.password {
-                        None => myzip.by_name(&name)?,
-                        Some(string) => myzip.by_name_decrypt(&name, string.as_bytes())?,
-                    };
-                    let r = extract_file(
-                        file,
-                        &options.output_directory,
-                        progress_reporter,
-                        directory_creator,
-                    );
+                    let i = zip_archive
+                        .index_for_name(&name)
+                        .ok_or(ZipError::FileNotFound)?;
+                    let r = extract_file_by_index(&get_ziparchive_clone, i, options, state);
                     file_skip_callback();
                     r
                 })
-                .filter_map(Result::err)
                 .collect()
         }
-    }
-}
-
-fn extract_file_by_index<'a, T: Read + Seek + 'a>(
-    get_ziparchive_clone: impl Fn() -> ZipArchive<T> + Sync,
-    i: usize,
-    output_directory: &Option<PathBuf>,
-    password: &Option<String>,
-    progress_reporter: &dyn UnzipProgressReporter,
-    directory_creator: &DirectoryCreator,
-) -> Result<(), RipunzipErrors> {
-    let myzip: &mut zip::ZipArchive<T> = &mut get_ziparchive_clone();
-    let file: ZipFile<T> = match password {
-        None => myzip.by_index(i)?,
-        Some(string) => myzip.by_index_decrypt(i, string.as_bytes())?,
     };
-    extract_file(file, output_directory, progress_reporter, directory_creator)
+    summarize(results)
 }
 
-fn extract_file<R: Read>(
-    file: ZipFile<R>,
-    output_directory: &Option<PathBuf>,
-    progress_reporter: &dyn UnzipProgressReporter,
-    directory_creator: &DirectoryCreator,
-) -> Result<(), RipunzipErrors> {
-    let name = file
-        .enclosed_name()
+/// Create every directory which the given entries will be extracted into,
+/// parents first, so that threads extracting files in parallel don't race
+/// to create the same directories. Directories which fail the symbolic link
+/// or device checks, or can't be created, are left for the entries within
+/// them to fail on.
+fn create_directories<T: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<T>,
+    options: &UnzipOptions,
+    state: &ExtractionState,
+    skipped: &HashSet<usize>,
+) {
+    let mut directories = BTreeMap::new();
+    for i in (0..zip_archive.len()).filter(|i| !skipped.contains(i)) {
+        let Some(name) = zip_archive.name_for_index(i) else {
+            continue;
+        };
+        let Some((path, _)) = target_name(name, options) else {
+            continue;
+        };
+        if options
+            .max_path_depth
+            .is_some_and(|limit| path.components().count() > limit)
+        {
+            continue;
+        }
+        let path = if name.ends_with('/') {
+            path
+        } else {
+            match path.parent() {
+                Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+                _ => continue,
+            }
+        };
+        let destination = Destination::new(options, name, path);
+        directories.entry(destination.path()).or_insert(destination);
+    }
+    log::debug!("Creating {} directories up front", directories.len());
+    for (path, destination) in directories {
+        let checked = (options.follow_existing_symlinks
+            || check_no_symlinks(destination.root, &destination.relative).is_ok())
+            && destination.device(state).map_or(true, |device| {
+                check_same_device(destination.root, &destination.relative, device).is_ok()
+            });
+        if checked {
+            let created = match destination.anchor(options, state) {
+                Ok(Some(anchor)) => anchor
+                    .create_dir_all(&destination.relative)
+                    .map_err(RipunzipErrors::from),
+                Ok(None) => state.directory_creator.create_dir_all(&path),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = created {
+                log::debug!("Unable to create {} up front: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// Extract the files matching the filter, if any, in the order in which
+/// they're stored in the zip file.
+fn unzip_sequentially<T: Read + Seek + Clone>(
+    options: &UnzipOptions,
+    state: &ExtractionState,
+    mut zip_archive: ZipArchive<T>,
+    skipped: &HashSet<usize>,
+    file_skip_callback: impl Fn(),
+) -> Vec<Result<UnzipOutcome, RipunzipErrors>> {
+    let mut indices: Vec<usize> = (0..zip_archive.len())
+        .filter(|i| !skipped.contains(i))
+        .collect();
+    match local_header_offsets(&zip_archive) {
+        // The zip library lists files in central directory order, which is
+        // usually but not necessarily the order they're stored.
+        Ok(offsets) => indices.sort_by_key(|&i| offsets[i]),
+        Err(e) => {
+            log::warn!("Unable to find the order of files in the zip file, so extracting them in central directory order: {e}");
+        }
+    }
+    if let Some(filename_filter) = &options.filename_filter {
+        let mut filtered_out: Vec<_> = zip_archive
+            .file_names()
+            .filter(|name| !filename_filter.should_unzip(name))
+            .map(|name| name.to_string())
+            .collect();
+        filtered_out.sort();
+        for name in &filtered_out {
+            options
+                .progress_reporter
+                .entry_skipped(name, SkipReason::Filtered);
+        }
+        indices.retain(|&i| {
+            zip_archive
+                .name_for_index(i)
+                .map(|name| filename_filter.should_unzip(name))
+                .unwrap_or(false)
+        });
+    }
+    // Files we're not extracting may lie between those we are.
+    let skipping = options.filename_filter.is_some() || !skipped.is_empty();
+    if skipping {
+        file_skip_callback();
+    }
+    let mut extra_field_reader = extra_field_reader(&zip_archive, options);
+    indices
+        .into_iter()
+        .map(|i| {
+            let r = with_file_by_index(&mut zip_archive, i, options, |file, decompressor| {
+                let extra = read_unix_extra(extra_field_reader.as_mut(), &file)?;
+                extract_file(file, decompressor, extra, options, state)
+            });
+            if skipping {
+                file_skip_callback();
+            }
+            r
+        })
+        .collect()
+}
+
+/// Extract the entries matching the priority filter, as well as any
+/// filename filter, in the order they're stored, adding them to `skipped`
+/// so that they're not extracted again.
+fn unzip_prioritized<T: Read + Seek + Clone>(
+    options: &UnzipOptions,
+    state: &ExtractionState,
+    get_ziparchive_clone: &(impl Fn() -> ZipArchive<T> + Sync),
+    prioritization: &Prioritization,
+    skipped: &mut HashSet<usize>,
+    file_skip_callback: impl Fn() + Sync + Send,
+) -> (UnzipOutcome, Vec<RipunzipErrors>) {
+    let zip_archive = get_ziparchive_clone();
+    let mut indices: Vec<usize> = (0..zip_archive.len())
+        .filter(|i| !skipped.contains(i))
+        .filter(|&i| {
+            zip_archive.name_for_index(i).is_some_and(|name| {
+                prioritization.is_prioritized(name)
+                    && options
+                        .filename_filter
+                        .as_ref()
+                        .map_or(true, |filter| filter.should_unzip(name))
+            })
+        })
+        .collect();
+    if let Ok(offsets) = local_header_offsets(&zip_archive) {
+        indices.sort_by_key(|&i| offsets[i]);
+    }
+    log::info!("Unzipping {} prioritized files first", indices.len());
+    skipped.extend(&indices);
+    // They're unlikely to be next to each other.
+    file_skip_callback();
+    let extract = |i| {
+        let r = extract_file_by_index(get_ziparchive_clone, i, options, state);
+        file_skip_callback();
+        r
+    };
+    summarize(if options.single_threaded || options.sequential {
+        indices.into_iter().map(extract).collect()
+    } else {
+        indices.into_par_iter().map(extract).collect()
+    })
+}
+
+/// Find the indices of the files, among those matching the filename filter,
+/// larger than `max_entry_size`, and report in name order that they won't be
+/// extracted. Their sizes come from the central directory, so none of their
+/// data is read.
+fn too_large_entries<T: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<T>,
+    options: &UnzipOptions,
+    max_entry_size: u64,
+) -> Result<HashSet<usize>, RipunzipErrors> {
+    let entries = read_central_directory(zip_archive)?;
+    let mut too_large: Vec<(usize, &str)> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.size > max_entry_size)
+        .filter_map(|(i, _)| Some((i, zip_archive.name_for_index(i)?)))
+        .filter(|(_, name)| {
+            !name.ends_with('/')
+                && options
+                    .filename_filter
+                    .as_ref()
+                    .map(|filter| filter.should_unzip(name))
+                    .unwrap_or(true)
+        })
+        .collect();
+    too_large.sort_by_key(|(_, name)| *name);
+    for (_, name) in &too_large {
+        options
+            .progress_reporter
+            .entry_skipped(name, SkipReason::TooLarge);
+    }
+    Ok(too_large.into_iter().map(|(i, _)| i).collect())
+}
+
+fn extract_file_by_index<'a, T: Read + Seek + Clone + 'a>(
+    get_ziparchive_clone: impl Fn() -> ZipArchive<T> + Sync,
+    i: usize,
+    options: &UnzipOptions,
+    state: &ExtractionState,
+) -> Result<UnzipOutcome, RipunzipErrors> {
+    let myzip: &mut zip::ZipArchive<T> = &mut get_ziparchive_clone();
+    let mut extra_field_reader = extra_field_reader(myzip, options);
+    with_file_by_index(myzip, i, options, |file, decompressor| {
+        let extra = read_unix_extra(extra_field_reader.as_mut(), &file)?;
+        extract_file(file, decompressor, extra, options, state)
+    })
+}
+
+/// A second reader onto the zip file for entries' local headers, if we'll
+/// need their extra fields, which aren't all in the central directory.
+fn extra_field_reader<T: Read + Seek + Clone>(
+    zip_archive: &ZipArchive<T>,
+    options: &UnzipOptions,
+) -> Option<T> {
+    (options.preserve_mtimes || options.preserve_ownership)
+        .then(|| zip_archive.clone().into_inner())
+}
+
+fn read_unix_extra<R: Read + Seek, F: Read>(
+    extra_field_reader: Option<&mut R>,
+    file: &ZipFile<F>,
+) -> Result<UnixExtra, RipunzipErrors> {
+    match extra_field_reader {
+        Some(reader) => UnixExtra::of(reader, file),
+        None => Ok(UnixExtra::default()),
+    }
+}
+
+/// Opens the file at the given index and passes it to `f`. If the zip
+/// library doesn't support its compression method but the user registered
+/// a decompressor for it, `f` instead receives the raw compressed file along
+/// with that decompressor. In strict mode, the file's local header is
+/// checked against the central directory first.
+fn with_file_by_index<T: Read + Seek + Clone, O>(
+    myzip: &mut ZipArchive<T>,
+    i: usize,
+    options: &UnzipOptions,
+    mut f: impl FnMut(ZipFile<T>, Option<&Decompressor>) -> Result<O, RipunzipErrors>,
+) -> Result<O, RipunzipErrors> {
+    // We need a second reader to look at the local header, since the zip
+    // library doesn't expose it.
+    let mut header_reader = options.strict.then(|| myzip.clone().into_inner());
+    let mut f = |file: ZipFile<T>, decompressor| {
+        if let Some(header_reader) = &mut header_reader {
+            check_local_header(header_reader, &file)?;
+        }
+        f(file, decompressor)
+    };
+    let password = options.password.as_deref().map(str::as_bytes);
+    let result = myzip
+        .by_index_with_options(i, ZipReadOptions::new().password(password))
+        .map(|file| f(file, None));
+    let e = match result {
+        Ok(result) => return result,
+        Err(e) => e,
+    };
+    match (e, &options.decompressor_registry) {
+        (ZipError::CompressionMethodNotSupported(method), Some(registry)) => {
+            let raw_file = myzip.by_index_raw(i)?;
+            match registry.get(method) {
+                Some(decompressor) if !raw_file.encrypted() => f(raw_file, Some(decompressor)),
+                _ => Err(ZipError::CompressionMethodNotSupported(method).into()),
+            }
+        }
+        (e, _) => Err(e.into()),
+    }
+}
+
+/// Copy an entry's decompressed data from `reader` to `writer`, converting
+/// line endings and transforming it if asked to, and return how many bytes
+/// were written, which after converting or transforming may not be how
+/// many were read.
+fn copy_entry_data<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    line_ending: Option<LineEnding>,
+    transformer: Option<ByteTransformer>,
+    buffer_size: usize,
+) -> std::io::Result<u64> {
+    let mut writer = CountingWriter {
+        inner: writer,
+        written: 0,
+    };
+    let mut copy = |mut writer: &mut dyn Write| match line_ending {
+        Some(line_ending) => {
+            copy_converting_line_endings(reader, &mut writer, line_ending, buffer_size)
+        }
+        None => copy_with_buffer_size(reader, writer, buffer_size),
+    };
+    match transformer {
+        None => {
+            copy(&mut writer)?;
+        }
+        Some(transformer) => {
+            let mut writer = TransformingWriter::new(&mut writer, transformer);
+            copy(&mut writer)?;
+            writer.finish()?;
+        }
+    }
+    Ok(writer.written)
+}
+
+/// Counts the bytes its inner writer accepts.
+struct CountingWriter<W: Write> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.written += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns a reader of the decompressed contents of `file`, which is
+/// raw compressed data if a custom decompressor is given.
+fn decompressing_reader<'r, R: Read>(
+    file: &'r mut ZipFile<R>,
+    decompressor: Option<&Decompressor>,
+) -> Box<dyn Read + 'r> {
+    match decompressor {
+        None => {
+            let crc32 = file.crc32();
+            let size = file.size();
+            let aes_encrypted = file.extra_data().is_some_and(is_aes_encrypted);
+            Box::new(IntegrityCheckingReader::new(
+                file,
+                crc32,
+                size,
+                aes_encrypted,
+            ))
+        }
+        Some(decompressor) => {
+            let crc32 = file.crc32();
+            Box::new(Crc32CheckingReader::new(
+                decompressor(Box::new(file)),
+                crc32,
+            ))
+        }
+    }
+}
+
+fn extract_file<R: Read>(
+    file: ZipFile<R>,
+    decompressor: Option<&Decompressor>,
+    extra: UnixExtra,
+    options: &UnzipOptions,
+    state: &ExtractionState,
+) -> Result<UnzipOutcome, RipunzipErrors> {
+    let name = target_name(file.name(), options)
+        .map(|(name, _)| name)
         .as_deref()
         .map(Path::to_string_lossy)
         .unwrap_or_else(|| Cow::Borrowed("<unprintable>"))
         .to_string();
-    if let Err(e) = extract_file_inner(file, output_directory, progress_reporter, directory_creator)
-    {
-        eprintln!("Failed to extract {name}");
-        return Err(e);
+    let cpu_started = thread_cpu_time();
+    // A panic, say in a custom decompressor or callback, fails only this
+    // entry rather than the thread and every other entry with it.
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        extract_file_inner(file, decompressor, extra, options, state)
+    }))
+    .unwrap_or_else(|panic| {
+        Err(RipunzipErrors::Worker {
+            name: name.clone(),
+            message: panic_message(panic.as_ref()),
+        })
+    })
+    .map(|mut outcome| {
+        outcome.cpu_time = cpu_started
+            .zip(thread_cpu_time())
+            .map(|(started, finished)| finished.saturating_sub(started));
+        outcome
+    });
+    match result {
+        Err(e) => {
+            eprintln!("Failed to extract {name}");
+            Err(e)
+        }
+        // Only worth keeping track of if anyone's going to see it.
+        Ok(mut outcome) if outcome.files_extracted > 0 && log::log_enabled!(log::Level::Debug) => {
+            let timings = outcome.timings;
+            log::debug!(
+                "Extracted {name} in {:?}: decompressing {:?}, reading {:?}, writing {:?}",
+                timings.total(),
+                timings.decompressing,
+                timings.reading,
+                timings.writing
+            );
+            outcome.slowest_entries.push((name, timings));
+            Ok(outcome)
+        }
+        outcome => outcome,
     }
+}
 
-    Ok(())
+/// The message a panic was raised with, if any.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
 }
 
-/// Extracts a file from a zip file.
+/// Extracts a file from a zip file, with `extra` being what its extra
+/// fields record if we need them.
 fn extract_file_inner<R: Read>(
     mut file: ZipFile<R>,
-    output_directory: &Option<PathBuf>,
-    progress_reporter: &dyn UnzipProgressReporter,
-    directory_creator: &DirectoryCreator,
-) -> Result<(), RipunzipErrors> {
-    let name = file
-        .enclosed_name()
+    decompressor: Option<&Decompressor>,
+    extra: UnixExtra,
+    options: &UnzipOptions,
+    state: &ExtractionState,
+) -> Result<UnzipOutcome, RipunzipErrors> {
+    state.overwrite_gate.check_not_aborted()?;
+    if let Some(entry_stream) = state.entry_stream {
+        entry_stream.check_not_cancelled()?;
+    }
+    if let Some(prioritization) = state.prioritization {
+        prioritization.check_not_cancelled()?;
+    }
+    let progress_reporter: &dyn UnzipProgressReporter = options.progress_reporter.as_ref();
+    let (name, renamed) = target_name(file.name(), options)
         .ok_or_else(|| std::io::Error::new(ErrorKind::Unsupported, "path not safe to extract"))?;
     let display_name = name.display().to_string();
-    let out_path = match output_directory {
-        Some(output_directory) => output_directory.join(name),
-        None => name,
-    };
-    progress_reporter.extraction_starting(&display_name);
+    if let Some(limit) = options.max_path_depth {
+        let depth = name.components().count();
+        if depth > limit {
+            return Err(RipunzipErrors::PathTooDeep {
+                name: display_name,
+                depth,
+                limit,
+            });
+        }
+    }
+    let is_dir = is_directory(file.name(), file.unix_mode(), file.size(), options);
+    if is_dir && options.shard_into.is_some() {
+        log::debug!("Ignoring directory {display_name} when sharding");
+        return Ok(UnzipOutcome::default());
+    }
+    let unix_mode = options
+        .permission_template
+        .as_ref()
+        .and_then(|template| {
+            let kind = if is_dir {
+                EntryKind::Directory
+            } else {
+                EntryKind::File
+            };
+            template(&name, kind)
+        })
+        .or_else(|| file.unix_mode());
+    let destination = Destination::new(options, file.name(), name);
+    if !options.follow_existing_symlinks {
+        check_no_symlinks(destination.root, &destination.relative)?;
+    }
+    if let Some(device) = destination.device(state) {
+        check_same_device(destination.root, &destination.relative, device)?;
+    }
+    let anchor = destination.anchor(options, state)?;
+    let out_path = destination.path();
+    progress_reporter.extraction_starting(&EntryInfo::new(&file, &display_name));
     log::debug!(
         "Start extract of file at {:x}, length {:x}, name {}",
         file.data_start().unwrap_or_default(),
         file.compressed_size(),
         display_name
     );
-    if file.name().ends_with('/') {
-        directory_creator.create_dir_all(&out_path)?;
+    let mut outcome = UnzipOutcome::default();
+    if renamed {
+        log::debug!("Renamed {} to {display_name}", file.name());
+        outcome
+            .renamed
+            .insert(file.name().to_string(), out_path.clone());
+    }
+    let last_modified = extra
+        .modified
+        .or_else(|| file.last_modified().map(FileTime::from))
+        .filter(|_| options.preserve_mtimes);
+    // Held until we've finished with the file, including syncing it.
+    let _open_file = (!is_dir).then(|| state.open_files.acquire());
+    // Declared before the partial file, so that it's dropped after it and
+    // a partial file is cleaned up while the path is still held.
+    let _path_guard = options
+        .shard_into
+        .is_some()
+        .then(|| state.path_locks.lock(&out_path));
+    let mut partial_file = None;
+    // The file we wrote, kept open to sync it, unless it was linked.
+    let mut written_file = None;
+    if is_dir {
+        match &anchor {
+            Some(anchor) => anchor.create_dir_all(&destination.relative).map_err(|e| {
+                RipunzipErrors::IOErrorWithContext {
+                    context: format!("Failed to create directory {}", out_path.display()),
+                    source: e,
+                }
+            })?,
+            None => {
+                // Make sure of the directories which are entries themselves,
+                // in case they've been removed since we created them.
+                if !out_path.is_dir() {
+                    state.directory_creator.forget(&out_path);
+                }
+                state.directory_creator.create_dir_all(&out_path)?
+            }
+        }
+        outcome.directories_extracted = 1;
     } else {
-        if let Some(parent) = out_path.parent() {
-            directory_creator.create_dir_all(parent)?;
+        // With an anchor, parent directories are created with the file.
+        if let (None, Some(parent)) = (&anchor, out_path.parent()) {
+            state.directory_creator.create_dir_all(parent)?;
         }
-        let out_file = File::create(&out_path).map_err(|e| RipunzipErrors::IOErrorWithContext {
-            context: format!("Failed to create file {}", out_path.display()),
-            source: e,
-        })?;
-        // Progress bar strategy. The overall progress across the entire zip file must be
-        // denoted in terms of *compressed* bytes, since at the outset we don't know the uncompressed
-        // size of each file. Yet, within a given file, we update progress based on the bytes
-        // of uncompressed data written, once per 1MB, because that's the information that we happen
-        // to have available. So, calculate how many compressed bytes relate to 1MB of uncompressed
-        // data, and the remainder.
-        let uncompressed_size = file.size();
+        if let (Some(base), None) = (&options.overlay_base, destination.prefix) {
+            if matches_base(base, &destination.relative, file.size(), file.crc32())? {
+                log::debug!("{display_name} is unchanged from the overlay base");
+                progress_reporter.entry_skipped(file.name(), SkipReason::UpToDate);
+                progress_reporter.bytes_extracted(file.compressed_size());
+                progress_reporter.extraction_finished(&display_name);
+                outcome.files_skipped = 1;
+                return Ok(outcome);
+            }
+        }
+        if !state
+            .overwrite_gate
+            .should_write(&out_path, || ZipEntryMetadata::new(&file))?
+        {
+            log::debug!("Skipping existing file {}", out_path.display());
+            progress_reporter.entry_skipped(file.name(), SkipReason::AlreadyExists);
+            progress_reporter.bytes_extracted(file.compressed_size());
+            progress_reporter.extraction_finished(&display_name);
+            outcome.files_skipped = 1;
+            return Ok(outcome);
+        }
+        if options.shard_into.is_some() {
+            outcome
+                .shards
+                .insert(file.name().to_string(), out_path.clone());
+        }
+        let line_ending = options
+            .text_conversion
+            .as_ref()
+            .filter(|mode| mode.applies_to(file.name()))
+            .map(|mode| mode.line_ending);
+        let transformer = options
+            .transform
+            .as_ref()
+            .and_then(|transform| transform(file.name()));
+        // We can't compare the contents of files whose line endings we'd
+        // convert, or which we'd transform, until afterwards, so don't try.
+        let dedup_key = DedupKey::new(
+            &file,
+            unix_mode,
+            last_modified.map(|modified| (modified, extra.accessed)),
+            options.preserve_ownership.then_some((extra.uid, extra.gid)),
+        );
+        let deduplicator = state.deduplicator.as_ref().filter(|_| anchor.is_none());
+        let dedup_candidate = deduplicator
+            .filter(|_| line_ending.is_none() && transformer.is_none())
+            .and_then(|deduplicator| deduplicator.candidate(&dedup_key, &out_path));
         let compressed_size = file.compressed_size();
-        let mut progress_updater = ProgressUpdater::new(
-            |external_progress| {
-                progress_reporter.bytes_extracted(external_progress);
+        let uncompressed_size = options
+            .unknown_sizes
+            .size(file.size(), compressed_size)
+            .unwrap_or(0);
+        let stored = file.compression() == CompressionMethod::Stored && decompressor.is_none();
+        let expected_checksum = expected_checksum(options, file.name());
+        let mut reader = state
+            .total_size
+            .limit(decompressing_reader(&mut file, decompressor));
+        if let Some(expected) = expected_checksum {
+            reader = Box::new(ManifestCheckingReader::new(
+                reader,
+                options.hash_algorithm,
+                expected,
+            ));
+        }
+        let reader = match dedup_candidate {
+            None => Some(reader),
+            Some(candidate) => match dedup::compare(reader, &candidate).map_err(|e| {
+                manifest_error(&display_name, &e)
+                    .or_else(|| total_size_error(&e))
+                    .unwrap_or_else(|| e.into())
+            })? {
+                Comparison::Identical => {
+                    log::debug!(
+                        "{} is identical to {}",
+                        out_path.display(),
+                        candidate.display()
+                    );
+                    link_or_copy(&candidate, &out_path).map_err(|e| {
+                        RipunzipErrors::IOErrorWithContext {
+                            context: format!("Failed to link file {}", out_path.display()),
+                            source: e,
+                        }
+                    })?;
+                    progress_reporter.bytes_extracted(compressed_size);
+                    outcome.files_deduplicated = 1;
+                    None
+                }
+                Comparison::Differs(reader) => Some(reader),
             },
-            compressed_size,
-            uncompressed_size,
-            1024 * 1024,
-        );
-        let mut out_file = progress_streams::ProgressWriter::new(out_file, |bytes_written| {
-            progress_updater.progress(bytes_written as u64)
-        });
-        // Using a BufWriter here doesn't improve performance even on a VM with
-        // spinny disks.
-        if let Err(e) = std::io::copy(&mut file, &mut out_file) {
-            return Err(RipunzipErrors::IOErrorWithContext {
-                context: format!("Failed to write directory {:?}", out_file.into_inner()),
+        };
+        if let Some(reader) = reader {
+            if deduplicator.is_some() {
+                // The file may be a hard link to one we wrote earlier.
+                remove_if_present(&out_path)?;
+            }
+            let create = || match (&options.file_factory, &anchor) {
+                (Some(file_factory), _) => file_factory(&out_path),
+                (None, Some(anchor)) => anchor.create_file(&destination.relative, unix_mode),
+                (None, None) => create_file(&out_path, unix_mode),
+            };
+            let out_file = match (create(), &anchor, out_path.parent()) {
+                // The parent directory we remember creating may have been
+                // removed since.
+                (Err(e), None, Some(parent)) if e.kind() == ErrorKind::NotFound => {
+                    state.directory_creator.forget(parent);
+                    state.directory_creator.create_dir_all(parent)?;
+                    create()
+                }
+                (result, _, _) => result,
+            }
+            .map_err(|e| RipunzipErrors::IOErrorWithContext {
+                context: format!("Failed to create file {}", out_path.display()),
                 source: e,
-            });
+            })?;
+            partial_file = Some(state.partial_files.track(&out_path));
+            // Progress bar strategy. The overall progress across the entire zip file must be
+            // denoted in terms of *compressed* bytes, since at the outset we don't know the uncompressed
+            // size of each file. Yet, within a given file, we update progress based on the bytes
+            // of uncompressed data written, once per 1MB, because that's the information that we happen
+            // to have available. So, calculate how many compressed bytes relate to 1MB of uncompressed
+            // data, and the remainder.
+            let mut progress_updater = ProgressUpdater::new(
+                |external_progress| {
+                    progress_reporter.bytes_extracted(external_progress);
+                },
+                compressed_size,
+                uncompressed_size,
+                1024 * 1024,
+            );
+            let mut reader = Timed::new(reader);
+            let out_file = SparseWriter::new(out_file, options.sparse);
+            let mut out_file =
+                progress_streams::ProgressWriter::new(Timed::new(out_file), |bytes_written| {
+                    progress_updater.progress(bytes_written as u64)
+                });
+            // Using a BufWriter here doesn't improve performance even on a VM with
+            // spinny disks; the copy buffer determines the size of each write.
+            let buffer_size = options.io_buffer_size.unwrap_or(DEFAULT_IO_BUFFER_SIZE);
+            let reading_before = source_reading_time();
+            let copy_result = copy_entry_data(
+                &mut reader,
+                &mut out_file,
+                line_ending,
+                transformer,
+                buffer_size,
+            );
+            if stored {
+                outcome.timings.reading = reader.elapsed();
+            } else {
+                // Whatever isn't spent reading the zip file is spent
+                // decompressing.
+                outcome.timings.reading = source_reading_time() - reading_before;
+                outcome.timings.decompressing =
+                    reader.elapsed().saturating_sub(outcome.timings.reading);
+            }
+            drop(reader);
+            let out_file = out_file.into_inner();
+            match copy_result {
+                Ok(bytes_written) => outcome.bytes_written = bytes_written,
+                Err(e) => {
+                    // A bad CRC is reported as a failure to write the
+                    // file, as ever, but tampering deserves to be called out.
+                    if let Some(error @ RipunzipErrors::AuthenticationFailed { .. }) =
+                        integrity_error(&display_name, &e)
+                    {
+                        return Err(error);
+                    }
+                    if let Some(error) = manifest_error(&display_name, &e) {
+                        return Err(error);
+                    }
+                    if let Some(error) = total_size_error(&e) {
+                        return Err(error);
+                    }
+                    return Err(RipunzipErrors::IOErrorWithContext {
+                        context: format!("Failed to write directory {:?}", out_file.into_inner()),
+                        source: e,
+                    });
+                }
+            }
+            outcome.timings.writing = out_file.elapsed();
+            written_file = Some(out_file.into_inner().finish().map_err(|e| {
+                RipunzipErrors::IOErrorWithContext {
+                    context: format!("Failed to set the length of {}", out_path.display()),
+                    source: e,
+                }
+            })?);
+            progress_updater.finish();
+            if let Some(deduplicator) = deduplicator {
+                deduplicator.record(dedup_key, &out_path);
+            }
         }
-        progress_updater.finish();
+        outcome.files_extracted = 1;
     }
 
+    // Changing the owner may clear setuid and setgid bits, so comes first.
+    if options.preserve_ownership {
+        set_owner(&out_path, &extra)?;
+    }
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
-        if let Some(mode) = file.unix_mode() {
-            if let Err(e) =
-                std::fs::set_permissions(&out_path, std::fs::Permissions::from_mode(mode))
-            {
+        if let Some(mode) = unix_mode {
+            let result = match &anchor {
+                Some(anchor) => anchor.set_mode(&destination.relative, mode),
+                None => std::fs::set_permissions(&out_path, std::fs::Permissions::from_mode(mode)),
+            };
+            if let Err(e) = result {
                 return Err(RipunzipErrors::IOErrorWithContext {
                     context: format!(
                         "Failed to set permissions {} for {}",
@@ -498,6 +3732,18 @@ fn extract_file_inner<R: Read>(
             }
         }
     }
+    if let Some(last_modified) = last_modified {
+        set_times(&out_path, last_modified, extra.accessed)?;
+        if let (true, Some(directory_mtimes)) = (is_dir, &state.directory_mtimes) {
+            directory_mtimes.record(out_path.clone(), last_modified);
+        }
+    }
+    if !is_dir {
+        sync_to_disk(&out_path, written_file.as_ref(), options.durability)?;
+    }
+    if let Some(partial_file) = partial_file {
+        partial_file.complete();
+    }
     log::debug!(
         "Finished extract of file at {:x}, length {:x}, name {}",
         file.data_start().unwrap_or_default(),
@@ -505,53 +3751,349 @@ fn extract_file_inner<R: Read>(
         display_name
     );
     progress_reporter.extraction_finished(&display_name);
+    if let Some(entry_stream) = state.entry_stream {
+        entry_stream.send(ExtractedEntry {
+            path: out_path,
+            metadata: ZipEntryMetadata::new(&file),
+        });
+    }
+    Ok(outcome)
+}
+
+/// Whether the entry with the given name, mode and uncompressed size is a
+/// directory, according to [`UnzipOptions::strict_directory_detection`].
+fn is_directory(name: &str, unix_mode: Option<u32>, size: u64, options: &UnzipOptions) -> bool {
+    const S_IFMT: u32 = 0o170000;
+    const S_IFDIR: u32 = 0o040000;
+    name.ends_with('/')
+        || (name.ends_with('\\') && (options.normalize_separators || cfg!(windows)))
+        || (!options.strict_directory_detection
+            && size == 0
+            && unix_mode.is_some_and(|mode| mode & S_IFMT == S_IFDIR))
+}
+
+/// Create or truncate a file to extract into. On Unix, a newly created file
+/// is given the mode it'll end up with, less the umask, from the start.
+fn create_file(path: &Path, unix_mode: Option<u32>) -> std::io::Result<File> {
+    let mut open_options = std::fs::OpenOptions::new();
+    open_options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if let Some(mode) = unix_mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        open_options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = unix_mode;
+    open_options.open(path)
+}
+
+/// Where to extract an entry: the directory it must stay within, and its
+/// path relative to that directory.
+struct Destination<'o> {
+    root: Option<&'o Path>,
+    relative: PathBuf,
+    /// The index of the matching prefix in the prefix map, if any.
+    prefix: Option<usize>,
+}
+
+impl<'o> Destination<'o> {
+    /// Find where to extract the entry with the given name in the zip file
+    /// and enclosed name.
+    fn new(options: &'o UnzipOptions, entry_name: &str, name: PathBuf) -> Self {
+        if let Some(root) = options.absolute_paths.root(entry_name, options) {
+            return Self {
+                root: Some(root),
+                relative: name,
+                prefix: None,
+            };
+        }
+        let shard = options.shard_into.map(|shards| shard_path(&name, shards));
+        let matched = options
+            .prefix_map
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (prefix, directory))| {
+                let relative = name.strip_prefix(prefix).ok()?;
+                Some((i, directory, relative.to_path_buf()))
+            })
+            .max_by_key(|(_, _, relative)| {
+                name.components().count() - relative.components().count()
+            });
+        let mut destination = match matched {
+            Some((i, directory, relative)) => Self {
+                root: Some(directory),
+                relative,
+                prefix: Some(i),
+            },
+            None => Self {
+                root: options.output_directory.as_deref(),
+                relative: name,
+                prefix: None,
+            },
+        };
+        if let Some(shard) = shard {
+            destination.relative = shard;
+        }
+        destination
+    }
+
+    fn path(&self) -> PathBuf {
+        match self.root {
+            Some(root) => root.join(&self.relative),
+            None => self.relative.clone(),
+        }
+    }
+
+    /// The device we must stay on, if any.
+    fn device(&self, state: &ExtractionState) -> Option<u64> {
+        match self.prefix {
+            Some(i) => state.prefix_devices.get(i).copied(),
+            None => state.device,
+        }
+    }
+
+    /// A descriptor for the root to create things relative to, creating the
+    /// root if need be, if `anchored_output` is set and supported here.
+    fn anchor(
+        &self,
+        options: &UnzipOptions,
+        state: &ExtractionState,
+    ) -> Result<Option<Anchor>, RipunzipErrors> {
+        if !options.anchored_output {
+            return Ok(None);
+        }
+        if let Some(root) = self.root {
+            state.directory_creator.create_dir_all(root)?;
+        }
+        Anchor::open(self.root).map_err(|e| RipunzipErrors::IOErrorWithContext {
+            context: format!(
+                "Failed to open output directory {}",
+                self.root.unwrap_or(Path::new(".")).display()
+            ),
+            source: e,
+        })
+    }
+}
+
+/// Check that no part of the path to which we'll extract `name` is an
+/// existing symbolic link. The output directory itself may be one.
+fn check_no_symlinks(output_directory: Option<&Path>, name: &Path) -> Result<(), RipunzipErrors> {
+    let mut path = output_directory.map(Path::to_path_buf).unwrap_or_default();
+    for component in name.components() {
+        path.push(component);
+        match path.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(RipunzipErrors::SymlinkInPath {
+                    name: name.display().to_string(),
+                    symlink: path,
+                });
+            }
+            Ok(_) => {}
+            // Nothing beneath a path which doesn't exist can exist either.
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+/// The device of the output directory or, if it doesn't exist yet, of its
+/// nearest existing ancestor.
+fn output_device(output_directory: Option<&Path>) -> Result<u64, RipunzipErrors> {
+    let output_directory = output_directory.unwrap_or(Path::new("."));
+    let existing = output_directory
+        .ancestors()
+        .find(|path| path.exists())
+        .unwrap_or(Path::new("."));
+    device_of(existing).map_err(|e| RipunzipErrors::IOErrorWithContext {
+        context: format!("Failed to find the device of {}", existing.display()),
+        source: e,
+    })
+}
+
+/// Check that every existing part of the path to which we'll extract
+/// `name` is on the given device.
+fn check_same_device(
+    output_directory: Option<&Path>,
+    name: &Path,
+    device: u64,
+) -> Result<(), RipunzipErrors> {
+    let mut path = output_directory.map(Path::to_path_buf).unwrap_or_default();
+    for component in name.components() {
+        path.push(component);
+        match device_of(&path) {
+            Ok(path_device) if path_device != device => {
+                return Err(RipunzipErrors::DifferentDevice {
+                    name: name.display().to_string(),
+                    path,
+                });
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn device_of(path: &Path) -> std::io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    // Follow symbolic links, since that's what writing through them does.
+    Ok(path.metadata()?.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_path: &Path) -> std::io::Result<u64> {
+    Err(std::io::Error::new(
+        ErrorKind::Unsupported,
+        "restricting extraction to one device is only supported on Unix",
+    ))
+}
+
+/// Fsync the file at `path`, through `file` if it's still open, and
+/// perhaps its directory, as requested.
+fn sync_to_disk(
+    path: &Path,
+    file: Option<&File>,
+    durability: Durability,
+) -> Result<(), RipunzipErrors> {
+    let context = |path: &Path| {
+        let context = format!("Failed to fsync {}", path.display());
+        move |e| RipunzipErrors::IOErrorWithContext { context, source: e }
+    };
+    if durability == Durability::None {
+        return Ok(());
+    }
+    match file {
+        Some(file) => file.sync_all(),
+        // Windows needs write access to flush a file, while elsewhere the
+        // file may be read-only by now.
+        None => std::fs::OpenOptions::new()
+            .read(true)
+            .write(cfg!(windows))
+            .open(path)
+            .and_then(|file| file.sync_all()),
+    }
+    .map_err(context(path))?;
+    let sync = |path: &Path| {
+        File::open(path)
+            .and_then(|f| f.sync_all())
+            .map_err(context(path))
+    };
+    // Windows can't open directories as files.
+    if cfg!(unix) && durability == Durability::FsyncFilesAndDir {
+        match path.parent() {
+            Some(parent) if parent != Path::new("") => sync(parent)?,
+            _ => sync(Path::new("."))?,
+        }
+    }
     Ok(())
 }
 
+/// State shared between all the threads extracting files from a zip.
+struct ExtractionState<'a> {
+    directory_creator: DirectoryCreator,
+    overwrite_gate: OverwriteGate<'a>,
+    deduplicator: Option<Deduplicator>,
+    /// The device of the output directory, if we mustn't write to any other.
+    device: Option<u64>,
+    /// The devices of the directories in the prefix map, in the same order,
+    /// if we mustn't write to any others.
+    prefix_devices: Vec<u64>,
+    /// Where to send each entry once it's extracted, if anywhere.
+    entry_stream: Option<&'a EntryStream>,
+    /// Which entries to extract before any others, if any.
+    prioritization: Option<&'a Prioritization>,
+    open_files: OpenFileLimit,
+    /// Used when sharding, when more than one file may go to the same path.
+    path_locks: PathLocks,
+    partial_files: PartialFileTracker,
+    /// Directories whose modification times to set again at the end.
+    directory_mtimes: Option<DirectoryMtimes>,
+    total_size: TotalSize,
+}
+
 /// An engine used to ensure we don't conflict in creating directories
-/// between threads
+/// between threads. It remembers the directories it's created or found,
+/// so that asking for them again costs no system calls. If one turns out
+/// to be missing after all, it must be forgotten before asking for it
+/// again.
 #[derive(Default)]
-struct DirectoryCreator(Mutex<()>);
+struct DirectoryCreator {
+    lock: Mutex<()>,
+    known: RwLock<HashSet<PathBuf>>,
+}
 
 impl DirectoryCreator {
     fn create_dir_all(&self, path: &Path) -> Result<(), RipunzipErrors> {
-        // Fast path - avoid locking if the directory exists
-        if path.exists() {
-            return Ok(());
-        }
-        let _exclusivity = self.0.lock().unwrap();
-        if path.exists() {
+        if self
+            .known
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(path)
+        {
             return Ok(());
         }
-
-        if let Err(e) = std::fs::create_dir_all(path) {
-            return Err(RipunzipErrors::IOErrorWithContext {
-                context: format!("Failed to create directory {}", path.display()),
-                source: e,
-            });
+        // Fast path - avoid locking if the directory exists
+        if !path.exists() {
+            let _exclusivity = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+            if !path.exists() {
+                if let Err(e) = std::fs::create_dir_all(path) {
+                    return Err(RipunzipErrors::IOErrorWithContext {
+                        context: format!("Failed to create directory {}", path.display()),
+                        source: e,
+                    });
+                }
+            }
         }
-
+        self.known
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path.to_path_buf());
         Ok(())
     }
+
+    /// Forget `path` and everything beneath it, so that it's looked for on
+    /// disk again next time.
+    fn forget(&self, path: &Path) {
+        self.known
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|known| !known.starts_with(path));
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::FilenameFilter;
-    use crate::{NullProgressReporter, UnzipEngine, UnzipOptions};
+    use super::{shard, FilenameFilter};
+    #[cfg(feature = "http")]
+    use crate::NetworkEvent;
+    use crate::{
+        AbsolutePathPolicy, Bottleneck, ByteTransformer, DecompressorRegistry, DuplicatePolicy,
+        Durability, EntryInfo, EntryTransform, ExtractionPlan, ExtractionTimings, LineEnding,
+        LineEndingMode, NullProgressReporter, OutputLock, OverwriteDecision, OverwritePolicy,
+        PartialFiles, PlannedAction, PlannedDecision, RipunzipErrors, SkipReason, Throughput,
+        UnknownSizes, UnzipEngine, UnzipOptions, UnzipOutcome, UnzipProgressReporter,
+        ZipEntryMetadata,
+    };
+    #[cfg(feature = "signature")]
+    use crate::{SignatureConfig, SignatureSource};
+    #[cfg(feature = "http")]
     use httptest::Server;
+    #[cfg(feature = "http")]
     use ripunzip_test_utils::*;
     use std::{
-        collections::HashSet,
+        collections::{HashMap, HashSet},
         env::{current_dir, set_current_dir},
         fs::{read_to_string, File},
         io::{Cursor, Seek, Write},
-        path::Path,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        time::Duration,
     };
     use tempfile::tempdir;
     use test_log::test;
     use zip::{unstable::write::FileOptionsExt, write::ExtendedFileOptions};
-    use zip::{write::FileOptions, ZipWriter};
+    use zip::{write::FileOptions, CompressionMethod, ZipWriter};
 
     struct UnzipSomeFilter;
     impl FilenameFilter for UnzipSomeFilter {
@@ -563,7 +4105,7 @@ mod tests {
 
     fn run_with_and_without_a_filename_filter<F>(fun: F)
     where
-        F: Fn(bool, Option<Box<dyn FilenameFilter + Sync>>),
+        F: Fn(bool, Option<Box<dyn FilenameFilter + Send + Sync>>),
     {
         fun(true, None);
         fun(false, Some(Box::new(UnzipSomeFilter)));
@@ -603,54 +4145,3451 @@ mod tests {
             zip.start_file("test/a.txt", options.clone()).unwrap();
             zip.write_all(b"Contents of A\n").unwrap();
         }
-        zip.start_file("b.txt", options.clone()).unwrap();
-        zip.write_all(b"Contents of B\n").unwrap();
-        zip.start_file("test/c.txt", options).unwrap();
-        zip.write_all(b"Contents of C\n").unwrap();
-        zip.finish().unwrap();
+        zip.start_file("b.txt", options.clone()).unwrap();
+        zip.write_all(b"Contents of B\n").unwrap();
+        zip.start_file("test/c.txt", options).unwrap();
+        zip.write_all(b"Contents of C\n").unwrap();
+        zip.finish().unwrap();
+    }
+
+    fn check_files_exist(path: &Path, include_a_txt: bool) {
+        let a = path.join("test/a.txt");
+        let b = path.join("b.txt");
+        let c = path.join("test/c.txt");
+        if include_a_txt {
+            assert_eq!(read_to_string(a).unwrap(), "Contents of A\n");
+        } else {
+            assert!(!a.exists());
+        }
+        assert_eq!(read_to_string(b).unwrap(), "Contents of B\n");
+        assert_eq!(read_to_string(c).unwrap(), "Contents of C\n");
+    }
+
+    #[test]
+    #[ignore] // because the chdir changes global state
+    fn test_extract_no_path() {
+        run_with_and_without_a_filename_filter(|create_a, filename_filter| {
+            let td = tempdir().unwrap();
+            let zf = td.path().join("z.zip");
+            create_zip_file(&zf, create_a);
+            let old_dir = current_dir().unwrap();
+            set_current_dir(td.path()).unwrap();
+            let options = UnzipOptions {
+                output_directory: None,
+                password: None,
+                single_threaded: false,
+                filename_filter,
+                progress_reporter: Box::new(NullProgressReporter),
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+            set_current_dir(old_dir).unwrap();
+            check_files_exist(td.path(), create_a);
+        });
+    }
+
+    #[test]
+    fn test_extract_with_path() {
+        run_with_and_without_a_filename_filter(|create_a, filename_filter| {
+            let td = tempdir().unwrap();
+            let zf = td.path().join("z.zip");
+            create_zip_file(&zf, create_a);
+            let outdir = td.path().join("outdir");
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                password: None,
+                single_threaded: false,
+                filename_filter,
+                progress_reporter: Box::new(NullProgressReporter),
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+            check_files_exist(&outdir, create_a);
+        });
+    }
+
+    #[test]
+    fn test_extract_encrypted_with_path() {
+        run_with_and_without_a_filename_filter(|create_a, filename_filter| {
+            let td = tempdir().unwrap();
+            let zf = td.path().join("z.zip");
+            create_encrypted_zip_file(&zf, create_a);
+            let outdir = td.path().join("outdir");
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                password: Some("1Password".to_string()),
+                single_threaded: false,
+                filename_filter,
+                progress_reporter: Box::new(NullProgressReporter),
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+            check_files_exist(&outdir, create_a);
+        });
+    }
+
+    #[test]
+    fn test_aes_authentication() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let options = FileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .with_aes_encryption(zip::AesMode::Aes256, "1Password");
+        create_zip(File::create(&zf).unwrap(), true, Some(options));
+        let options = || UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            password: Some("1Password".to_string()),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .verify_parallel(&options())
+            .unwrap();
+        UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options())
+            .unwrap();
+        check_files_exist(&td.path().join("outdir"), true);
+
+        // Small files are written in the AE-2 format, without a CRC, so
+        // only the authentication code shows that it's been tampered with.
+        let mut archive = zip::ZipArchive::new(File::open(&zf).unwrap()).unwrap();
+        let i = archive.index_for_name("b.txt").unwrap();
+        let file = archive.by_index_raw(i).unwrap();
+        assert_eq!(file.crc32(), 0);
+        // After the salt and the password verification value.
+        let pos = file.data_start().unwrap() as usize + 18;
+        drop(file);
+        let mut body = std::fs::read(&zf).unwrap();
+        body[pos] ^= 1;
+        std::fs::write(&zf, body).unwrap();
+        let result = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .verify_parallel(&options());
+        assert!(
+            matches!(result, Err(RipunzipErrors::AuthenticationFailed { ref name }) if name == "b.txt")
+        );
+        let result = UnzipEngine::for_file(zf).unwrap().unzip(UnzipOptions {
+            single_threaded: true,
+            ..options()
+        });
+        assert!(
+            matches!(result, Err(RipunzipErrors::AuthenticationFailed { ref name }) if name == "b.txt")
+        );
+    }
+
+    #[test]
+    fn test_for_reader() {
+        let td = tempdir().unwrap();
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        zip_data.rewind().unwrap();
+        let len = zip_data.get_ref().len() as u64;
+        let engine = UnzipEngine::for_reader(zip_data).unwrap();
+        assert_eq!(engine.zip_length(), len);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        engine.unzip(options).unwrap();
+        check_files_exist(&outdir, true);
+    }
+
+    #[test]
+    fn test_for_bytes() {
+        let td = tempdir().unwrap();
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let bytes = Arc::new(zip_data.into_inner());
+        // Several engines can share the bytes, on several threads.
+        std::thread::scope(|scope| {
+            for i in 0..2 {
+                let outdir = td.path().join(format!("outdir{i}"));
+                let engine = UnzipEngine::for_bytes(bytes.clone()).unwrap();
+                assert_eq!(engine.zip_length(), bytes.len() as u64);
+                scope.spawn(move || {
+                    let options = UnzipOptions {
+                        output_directory: Some(outdir.clone()),
+                        ..Default::default()
+                    };
+                    engine.unzip(options).unwrap();
+                    check_files_exist(&outdir, true);
+                });
+            }
+        });
+        // No engine holds on to them afterwards.
+        assert_eq!(Arc::strong_count(&bytes), 1);
+        let slice: Arc<[u8]> = Arc::from(bytes.as_slice());
+        assert_eq!(
+            UnzipEngine::for_bytes(slice)
+                .unwrap()
+                .list()
+                .unwrap()
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let filenames: HashSet<_> = UnzipEngine::for_file(zf).unwrap().list().unwrap().collect();
+        assert_eq!(
+            filenames,
+            ["test/", "test/a.txt", "b.txt", "test/c.txt"]
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect()
+        )
+    }
+
+    #[test]
+    fn test_verify_before_extract() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let mut body = zip_data.into_inner();
+        // Corrupt the stored contents of the last file so its CRC no longer
+        // matches.
+        let pos = body
+            .windows(13)
+            .position(|w| w == b"Contents of C")
+            .unwrap();
+        body[pos + 12] = b'X';
+        std::fs::write(&zf, body).unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            password: None,
+            single_threaded: false,
+            filename_filter: None,
+            progress_reporter: Box::new(NullProgressReporter),
+            verify_before_extract: true,
+            ..Default::default()
+        };
+        let result = UnzipEngine::for_file(zf).unwrap().unzip(options);
+        assert!(
+            matches!(result, Err(RipunzipErrors::CrcMismatch { ref name }) if name == "test/c.txt")
+        );
+        assert!(!outdir.exists());
+    }
+
+    #[test]
+    fn test_prepended_data() {
+        // As in a self-extracting zip file, with a stub before the zip.
+        let td = tempdir().unwrap();
+        let mut data = b"#!/bin/sh\necho 'Not a zip file'\nexit 1\n".to_vec();
+        let mut zip = Cursor::new(Vec::new());
+        create_zip(&mut zip, true, None);
+        data.extend(zip.into_inner());
+        let zf = td.path().join("sfx");
+        std::fs::write(&zf, &data).unwrap();
+
+        for (i, sequential) in [false, true].into_iter().enumerate() {
+            let outdir = td.path().join(format!("outdir{i}"));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                sequential,
+                strict: true,
+                verify_before_extract: true,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            check_files_exist(&outdir, true);
+        }
+        let engine = UnzipEngine::for_file(zf).unwrap();
+        assert_eq!(engine.compression_stats().unwrap().overall.files, 3);
+        assert_eq!(engine.peek_entry("b.txt", 8).unwrap(), b"Contents");
+
+        #[cfg(feature = "http")]
+        {
+            let server = Server::run();
+            set_up_server(&server, data, ServerType::Ranges);
+            let outdir = td.path().join("outdir_uri");
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                sequential: true,
+                ..Default::default()
+            };
+            UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {})
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            check_files_exist(&outdir, true);
+        }
+    }
+
+    #[test]
+    fn test_shard_into() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.add_directory::<_, ExtendedFileOptions>("a/", Default::default())
+            .unwrap();
+        let names = (0..50).map(|i| format!("a/{i}.txt")).collect::<Vec<_>>();
+        for name in names.iter().chain([&"b/0.txt".to_string()]) {
+            zip.start_file::<_, ExtendedFileOptions>(name.as_str(), Default::default())
+                .unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            shard_into: Some(4),
+            overwrite_policy: OverwritePolicy::Skip,
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        // a/0.txt and b/0.txt may or may not be in the same shard.
+        let (a, b) = (
+            shard::shard_path(Path::new("a/0.txt"), 4),
+            shard::shard_path(Path::new("b/0.txt"), 4),
+        );
+        assert_eq!(outcome.files_skipped, usize::from(a == b));
+        assert_eq!(outcome.files_extracted, 51 - outcome.files_skipped);
+        assert_eq!(outcome.directories_extracted, 0);
+        assert!(!outdir.join("a").exists());
+        // Which of the two comes first is unpredictable.
+        for name in names.iter().filter(|name| a != b || *name != "a/0.txt") {
+            let path = outdir.join(shard::shard_path(Path::new(name), 4));
+            assert_eq!(outcome.shards[name], path);
+            assert_eq!(read_to_string(path).unwrap(), *name);
+        }
+        let shards = std::fs::read_dir(&outdir).unwrap().count();
+        assert!((1..=4).contains(&shards));
+
+        // In a single shard, the two 0.txt files collide.
+        let outdir = td.path().join("outdir1");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            shard_into: Some(1),
+            overwrite_policy: OverwritePolicy::Skip,
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        assert_eq!(outcome.files_skipped, 1);
+        assert_eq!(outcome.files_extracted, 50);
+        assert_eq!(
+            std::fs::read_dir(outdir.join("shard-0")).unwrap().count(),
+            50
+        );
+
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir0")),
+            shard_into: Some(0),
+            ..Default::default()
+        };
+        assert!(UnzipEngine::for_file(zf).unwrap().unzip(options).is_err());
+    }
+
+    #[test]
+    fn test_partial_files() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file::<_, ExtendedFileOptions>("small.txt", Default::default())
+            .unwrap();
+        zip.write_all(b"Small").unwrap();
+        zip.start_file::<_, ExtendedFileOptions>(
+            "large.bin",
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )
+        .unwrap();
+        zip.write_all(&vec![b'L'; 4 * 1024 * 1024]).unwrap();
+        zip.finish().unwrap();
+        // Corrupt the middle of the large file, which is only noticed once
+        // all of it has been written.
+        let mut body = std::fs::read(&zf).unwrap();
+        let middle = body.len() / 2;
+        body[middle] = b'X';
+        std::fs::write(&zf, body).unwrap();
+
+        for (partial_files, remaining) in [
+            (PartialFiles::Keep, Some("large.bin")),
+            (PartialFiles::Delete, None),
+            (PartialFiles::Rename, Some("large.bin.partial")),
+        ] {
+            let outdir = td.path().join(format!("{partial_files:?}"));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                single_threaded: true,
+                partial_files,
+                ..Default::default()
+            };
+            let result = UnzipEngine::for_file(zf.clone()).unwrap().unzip(options);
+            let mut files = std::fs::read_dir(&outdir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>();
+            files.sort();
+            let expected = ["large.bin", "large.bin.partial", "small.txt"]
+                .into_iter()
+                .filter(|name| *name == "small.txt" || Some(*name) == remaining)
+                .collect::<Vec<_>>();
+            assert_eq!(files, expected);
+            // The zip crate notices the bad CRC as it reads the last of the
+            // data, so it's reported as a failure to write the file.
+            let is_checksum_error = |e: &RipunzipErrors| matches!(e, RipunzipErrors::IOErrorWithContext { source, .. } if source.kind() == std::io::ErrorKind::InvalidData);
+            match (partial_files, result) {
+                (PartialFiles::Keep, Err(e)) => assert!(is_checksum_error(&e), "{e:?}"),
+                (_, Err(RipunzipErrors::PartialFilesCleanedUp { source, paths })) => {
+                    assert!(is_checksum_error(&source), "{source:?}");
+                    assert_eq!(paths, [outdir.join(remaining.unwrap_or("large.bin"))]);
+                }
+                (_, other) => panic!("unexpected result {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_partial_files_write_error() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file::<_, ExtendedFileOptions>("full.bin", Default::default())
+            .unwrap();
+        zip.write_all(&vec![b'F'; 1024 * 1024]).unwrap();
+        zip.finish().unwrap();
+        // Writes to /dev/full fail as if the disk were full.
+        let outdir = td.path().join("outdir");
+        std::fs::create_dir(&outdir).unwrap();
+        std::os::unix::fs::symlink("/dev/full", outdir.join("full.bin")).unwrap();
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            follow_existing_symlinks: true,
+            partial_files: PartialFiles::Delete,
+            ..Default::default()
+        };
+        let result = UnzipEngine::for_file(zf).unwrap().unzip(options);
+        assert!(
+            matches!(result, Err(RipunzipErrors::PartialFilesCleanedUp { ref paths, .. }) if paths == &[outdir.join("full.bin")])
+        );
+        assert!(outdir.join("full.bin").symlink_metadata().is_err());
+    }
+
+    #[test]
+    fn test_sequential() {
+        struct AllButB;
+        impl FilenameFilter for AllButB {
+            fn should_unzip(&self, filename: &str) -> bool {
+                filename != "b.txt"
+            }
+        }
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for name in ["z.txt", "b.txt", "a.txt"] {
+            zip.start_file::<_, ExtendedFileOptions>(name, Default::default())
+                .unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            filename_filter: Some(Box::new(AllButB)),
+            progress_reporter: Box::new(StartRecorder(started.clone())),
+            sequential: true,
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_extracted, 2);
+        let started: Vec<_> = started
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, _, _)| name.clone())
+            .collect();
+        assert_eq!(started, ["z.txt", "a.txt"]);
+        assert!(outcome.bottleneck().is_some());
+    }
+
+    #[test]
+    fn test_max_entry_size() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for (name, len) in [("small.txt", 10), ("big.bin", 11), ("other.txt", 5)] {
+            zip.start_file::<_, ExtendedFileOptions>(name, Default::default())
+                .unwrap();
+            zip.write_all(&vec![b'x'; len]).unwrap();
+        }
+        zip.finish().unwrap();
+        for (single_threaded, sequential) in [(false, false), (true, false), (true, true)] {
+            let outdir = td
+                .path()
+                .join(format!("out-{single_threaded}-{sequential}"));
+            let skipped = Arc::new(Mutex::new(Vec::new()));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                progress_reporter: Box::new(SkipRecorder(skipped.clone())),
+                single_threaded,
+                sequential,
+                max_entry_size: Some(10),
+                ..Default::default()
+            };
+            let outcome = UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            assert_eq!(outcome.files_extracted, 2);
+            assert!(outdir.join("small.txt").exists());
+            assert!(!outdir.join("big.bin").exists());
+            assert_eq!(
+                *skipped.lock().unwrap(),
+                [("big.bin".to_string(), SkipReason::TooLarge)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_newest_duplicates() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let at = |year| {
+            FileOptions::<ExtendedFileOptions>::default()
+                .last_modified_time(zip::DateTime::from_date_and_time(year, 1, 2, 3, 4, 6).unwrap())
+        };
+        // The newer is listed first, so would be overwritten by the older.
+        for (name, year, contents) in [
+            ("dir/a.txt", 2021, "Newer A"),
+            ("./dir/a.txt", 2020, "Older A"),
+            ("b.txt", 2020, "Contents of B"),
+        ] {
+            zip.start_file(name, at(year)).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        for (single_threaded, sequential) in [(false, false), (true, false), (true, true)] {
+            let outdir = td
+                .path()
+                .join(format!("out-{single_threaded}-{sequential}"));
+            let skipped = Arc::new(Mutex::new(Vec::new()));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                progress_reporter: Box::new(SkipRecorder(skipped.clone())),
+                single_threaded,
+                sequential,
+                duplicate_policy: DuplicatePolicy::NewestByTime,
+                ..Default::default()
+            };
+            let outcome = UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            assert_eq!(outcome.files_extracted, 2);
+            assert_eq!(read_to_string(outdir.join("dir/a.txt")).unwrap(), "Newer A");
+            assert!(outdir.join("b.txt").exists());
+            assert_eq!(
+                *skipped.lock().unwrap(),
+                [("./dir/a.txt".to_string(), SkipReason::Superseded)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let unzip = |max_entries| {
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                max_entries: Some(max_entries),
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone()).unwrap().unzip(options)
+        };
+        assert!(matches!(
+            unzip(3),
+            Err(RipunzipErrors::TooManyEntries { count: 4, limit: 3 })
+        ));
+        assert!(!outdir.exists());
+        unzip(4).unwrap();
+        check_files_exist(&outdir, true);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_sparse() {
+        use std::os::unix::fs::MetadataExt;
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut data = vec![0u8; 4 * 1024 * 1024];
+        data[5000] = 1;
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file::<_, ExtendedFileOptions>("disk.img", Default::default())
+            .unwrap();
+        zip.write_all(&data).unwrap();
+        zip.finish().unwrap();
+        let unzip = |sparse| {
+            let outdir = td.path().join(format!("outdir-{sparse}"));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                sparse,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            let path = outdir.join("disk.img");
+            assert_eq!(std::fs::read(&path).unwrap(), data);
+            std::fs::metadata(&path).unwrap()
+        };
+        let dense = unzip(false);
+        let sparse = unzip(true);
+        assert_eq!(sparse.len(), dense.len());
+        assert!(sparse.blocks() < dense.blocks());
+    }
+
+    #[test]
+    fn test_deep_tree() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let mut names = Vec::new();
+        for i in 0..4 {
+            for j in 0..4 {
+                for k in 0..8 {
+                    names.push(format!("{i}/a/b/{j}/c/d/{k}.txt"));
+                }
+            }
+        }
+        // No directory entries, so every directory is a file's parent.
+        for name in &names {
+            zip.start_file::<_, ExtendedFileOptions>(name.as_str(), Default::default())
+                .unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_extracted, names.len());
+        for name in &names {
+            assert_eq!(read_to_string(outdir.join(name)).unwrap(), *name);
+        }
+    }
+
+    #[test]
+    fn test_max_open_files() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let names = (0..200).map(|i| format!("{i}.txt")).collect::<Vec<_>>();
+        for name in &names {
+            zip.start_file::<_, ExtendedFileOptions>(name.as_str(), Default::default())
+                .unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            durability: Durability::FsyncFiles,
+            max_open_files: Some(1),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_extracted, names.len());
+        for name in &names {
+            assert_eq!(read_to_string(outdir.join(name)).unwrap(), *name);
+        }
+    }
+
+    #[test]
+    fn test_directory_creator_remembers() {
+        let td = tempdir().unwrap();
+        let path = td.path().join("a/b");
+        let directory_creator = super::DirectoryCreator::default();
+        directory_creator.create_dir_all(&path).unwrap();
+        assert!(path.is_dir());
+        // Once created, a directory isn't looked for on disk again.
+        std::fs::remove_dir(&path).unwrap();
+        directory_creator.create_dir_all(&path).unwrap();
+        assert!(!path.exists());
+        // Unless it's forgotten, along with those beneath it.
+        directory_creator.forget(&td.path().join("a"));
+        directory_creator.create_dir_all(&path).unwrap();
+        assert!(path.is_dir());
+    }
+
+    #[test]
+    fn test_directory_removed_during_extraction() {
+        struct RemoveDirectory(PathBuf);
+        impl UnzipProgressReporter for RemoveDirectory {
+            fn extraction_starting(&self, entry: &EntryInfo) {
+                // Once the first file's directory has been created,
+                // something else removes it.
+                if entry.display_name == "a/b/2.txt" {
+                    std::fs::remove_dir_all(&self.0).unwrap();
+                }
+            }
+        }
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let options: FileOptions<ExtendedFileOptions> = FileOptions::default();
+        zip.start_file("a/b/1.txt", options.clone()).unwrap();
+        zip.write_all(b"1").unwrap();
+        zip.start_file("a/b/2.txt", options.clone()).unwrap();
+        zip.write_all(b"2").unwrap();
+        zip.add_directory("a/c/", options).unwrap();
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            single_threaded: true,
+            progress_reporter: Box::new(RemoveDirectory(outdir.join("a"))),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert!(!outdir.join("a/b/1.txt").exists());
+        assert_eq!(read_to_string(outdir.join("a/b/2.txt")).unwrap(), "2");
+        assert!(outdir.join("a/c").is_dir());
+    }
+
+    #[test]
+    fn test_directory_without_slash() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["dir", "empty.txt"] {
+            zip.start_file::<_, ExtendedFileOptions>(name, Default::default())
+                .unwrap();
+        }
+        let mut body = zip.finish().unwrap().into_inner();
+        // Give the first entry in the central directory a directory mode,
+        // in its external file attributes.
+        let central_directory = body.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        body[central_directory + 38..central_directory + 42]
+            .copy_from_slice(&(0o40755u32 << 16).to_le_bytes());
+        std::fs::write(&zf, body).unwrap();
+        for strict_directory_detection in [false, true] {
+            let outdir = td
+                .path()
+                .join(format!("outdir-{strict_directory_detection}"));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                strict_directory_detection,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            assert_eq!(outdir.join("dir").is_dir(), !strict_directory_detection);
+            assert!(outdir.join("empty.txt").is_file());
+        }
+    }
+
+    #[test]
+    fn test_prefix_map() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for name in [
+            "bin/tool",
+            "bin/extra/more",
+            "binaries/other",
+            "share/doc/README",
+            "top.txt",
+        ] {
+            zip.start_file::<_, ExtendedFileOptions>(name, Default::default())
+                .unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let bin = td.path().join("bin");
+        let extra = td.path().join("extra");
+        let share = td.path().join("share");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            prefix_map: vec![
+                ("bin/".to_string(), bin.clone()),
+                ("bin/extra".to_string(), extra.clone()),
+                ("share".to_string(), share.clone()),
+            ],
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_extracted, 5);
+        for (path, contents) in [
+            (bin.join("tool"), "bin/tool"),
+            (extra.join("more"), "bin/extra/more"),
+            (outdir.join("binaries/other"), "binaries/other"),
+            (share.join("doc/README"), "share/doc/README"),
+            (outdir.join("top.txt"), "top.txt"),
+        ] {
+            assert_eq!(std::fs::read_to_string(path).unwrap(), contents);
+        }
+        assert!(!outdir.join("bin").exists());
+    }
+
+    #[test]
+    fn test_absolute_paths() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for (name, contents) in [("/etc/hosts", "127.0.0.1 localhost\n"), ("b.txt", "B\n")] {
+            zip.start_file(name, FileOptions::<ExtendedFileOptions>::default())
+                .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let unzip = |outdir: &Path, absolute_paths| {
+            let options = UnzipOptions {
+                output_directory: Some(outdir.to_path_buf()),
+                single_threaded: true,
+                absolute_paths,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone()).unwrap().unzip(options)
+        };
+
+        let outdir = td.path().join("reject");
+        assert!(unzip(&outdir, AbsolutePathPolicy::Reject).is_err());
+        assert!(!outdir.join("etc/hosts").exists());
+
+        let outdir = td.path().join("strip");
+        unzip(&outdir, AbsolutePathPolicy::StripLeadingSlash).unwrap();
+        assert_eq!(
+            read_to_string(outdir.join("etc/hosts")).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+        assert_eq!(read_to_string(outdir.join("b.txt")).unwrap(), "B\n");
+
+        let outdir = td.path().join("allow");
+        let root = td.path().join("root");
+        unzip(&outdir, AbsolutePathPolicy::AllowWithRoot(root.clone())).unwrap();
+        assert_eq!(
+            read_to_string(root.join("etc/hosts")).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+        assert!(!outdir.join("etc").exists());
+        assert_eq!(read_to_string(outdir.join("b.txt")).unwrap(), "B\n");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_colon_in_first_segment() {
+        // Only a drive letter makes a name absolute, so this is an ordinary
+        // directory name, whatever the policy.
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file(
+            "notes:/a.txt",
+            FileOptions::<ExtendedFileOptions>::default(),
+        )
+        .unwrap();
+        zip.write_all(b"A\n").unwrap();
+        zip.finish().unwrap();
+        for absolute_paths in [
+            AbsolutePathPolicy::Reject,
+            AbsolutePathPolicy::StripLeadingSlash,
+        ] {
+            let outdir = td.path().join(format!("{absolute_paths:?}"));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                single_threaded: true,
+                absolute_paths,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            assert_eq!(read_to_string(outdir.join("notes:/a.txt")).unwrap(), "A\n");
+            assert!(!outdir.join("a.txt").exists());
+        }
+    }
+
+    #[test]
+    fn test_file_factory() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let created = Mutex::new(HashSet::new());
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            file_factory: Some(Box::new(|path: &Path| {
+                created.lock().unwrap().insert(path.to_path_buf());
+                File::create(path)
+            })),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        check_files_exist(&outdir, true);
+        assert_eq!(
+            created.into_inner().unwrap(),
+            ["test/a.txt", "b.txt", "test/c.txt"]
+                .into_iter()
+                .map(|name| outdir.join(name))
+                .collect()
+        );
+
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("refused")),
+            file_factory: Some(Box::new(|_: &Path| {
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "refused"))
+            })),
+            ..Default::default()
+        };
+        match UnzipEngine::for_file(zf).unwrap().unzip(options) {
+            Err(RipunzipErrors::IOErrorWithContext { source, .. }) => {
+                assert_eq!(source.to_string(), "refused")
+            }
+            result => panic!("{result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_output_lock() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        std::fs::create_dir(&outdir).unwrap();
+        let lock_path = td
+            .path()
+            .canonicalize()
+            .unwrap()
+            .join(".outdir.ripunzip.lock");
+        let lock = File::create(&lock_path).unwrap();
+        fs2::FileExt::lock_exclusive(&lock).unwrap();
+        let unzip = |output_lock| {
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                output_lock,
+                require_empty_output: true,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone()).unwrap().unzip(options)
+        };
+        match unzip(OutputLock::FailFast) {
+            Err(RipunzipErrors::OutputLocked { path }) => assert_eq!(path, lock_path),
+            result => panic!("{result:?}"),
+        }
+        assert!(!outdir.join("b.txt").exists());
+
+        std::thread::scope(|scope| {
+            let waiting = scope.spawn(|| unzip(OutputLock::Wait));
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!waiting.is_finished());
+            drop(lock);
+            waiting.join().unwrap().unwrap();
+        });
+        check_files_exist(&outdir, true);
+        // The lock file is left behind, and is released, and a second
+        // unzip waiting for the first sees what it extracted.
+        assert!(lock_path.exists());
+        match unzip(OutputLock::FailFast) {
+            Err(RipunzipErrors::OutputDirectoryNotEmpty { .. }) => {}
+            result => panic!("{result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_verify_manifest() {
+        use sha2::{Digest, Sha256};
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let sha256 = |contents: &str| -> String {
+            Sha256::digest(contents.as_bytes())
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect()
+        };
+        let manifest = |c_contents| {
+            HashMap::from([
+                (PathBuf::from("test/a.txt"), sha256("Contents of A\n")),
+                (PathBuf::from("test/c.txt"), sha256(c_contents)),
+                (PathBuf::from("d.txt"), sha256("Contents of D\n")),
+            ])
+        };
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            verify_manifest: Some(manifest("Contents of C\n")),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        check_files_exist(&outdir, true);
+        assert_eq!(outcome.not_in_manifest, ["b.txt"]);
+        assert_eq!(outcome.missing_from_zip, [PathBuf::from("d.txt")]);
+
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir2")),
+            verify_manifest: Some(manifest("Something else\n")),
+            ..Default::default()
+        };
+        let result = UnzipEngine::for_file(zf).unwrap().unzip(options);
+        match result {
+            Err(RipunzipErrors::ManifestMismatch {
+                name,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(name, "test/c.txt");
+                assert_eq!(expected, sha256("Something else\n"));
+                assert_eq!(actual, sha256("Contents of C\n"));
+            }
+            _ => panic!("{result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_flatten_single_root() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.add_directory::<_, ExtendedFileOptions>("app-1.0/", Default::default())
+            .unwrap();
+        for name in ["app-1.0/README", "app-1.0/bin/app"] {
+            zip.start_file::<_, ExtendedFileOptions>(name, Default::default())
+                .unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            flatten_single_root: true,
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_extracted, 2);
+        for (path, contents) in [
+            (outdir.join("README"), "app-1.0/README"),
+            (outdir.join("bin/app"), "app-1.0/bin/app"),
+        ] {
+            assert_eq!(std::fs::read_to_string(path).unwrap(), contents);
+        }
+        assert!(!outdir.join("app-1.0").exists());
+
+        // Without a single root, it's a normal extract.
+        let zf = td.path().join("z2.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir2");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            flatten_single_root: true,
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        check_files_exist(&outdir, true);
+    }
+
+    #[test]
+    fn test_io_buffer_size() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            io_buffer_size: Some(3),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.bytes_written, 42);
+        check_files_exist(&outdir, true);
+    }
+
+    #[test]
+    fn test_verify_parallel() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let mut body = zip_data.into_inner();
+        std::fs::write(&zf, &body).unwrap();
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .verify_parallel(&UnzipOptions::default())
+            .unwrap();
+        assert_eq!(outcome.files_verified, 3);
+        assert_eq!(outcome.bytes_verified, 42);
+        assert_eq!(outcome.slowest_entries.len(), 3);
+
+        let pos = body
+            .windows(13)
+            .position(|w| w == b"Contents of C")
+            .unwrap();
+        body[pos + 12] = b'X';
+        std::fs::write(&zf, body).unwrap();
+        let result = UnzipEngine::for_file(zf)
+            .unwrap()
+            .verify_parallel(&UnzipOptions::default());
+        assert!(
+            matches!(result, Err(RipunzipErrors::CrcMismatch { ref name }) if name == "test/c.txt")
+        );
+    }
+
+    #[test]
+    fn test_extract_raw_entry_to() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let contents = "Contents of D\n".repeat(100);
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let options = FileOptions::<ExtendedFileOptions>::default()
+            .compression_method(CompressionMethod::Deflated);
+        zip.start_file("d.txt", options).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+        zip.finish().unwrap();
+
+        let engine = UnzipEngine::for_file(zf.clone()).unwrap();
+        let mut raw = Vec::new();
+        let method = engine.extract_raw_entry_to("d.txt", &mut raw).unwrap();
+        assert_eq!(method, CompressionMethod::Deflated);
+        assert!(raw.len() < contents.len());
+        let body = std::fs::read(zf).unwrap();
+        assert!(body.windows(raw.len()).any(|w| w == raw));
+        assert!(engine.extract_raw_entry_to("missing", &mut raw).is_err());
+    }
+
+    #[test]
+    fn test_unzip_outcome_slowest_entries() {
+        let entry = |i: u64| UnzipOutcome {
+            files_extracted: 1,
+            slowest_entries: vec![(
+                format!("{i}"),
+                ExtractionTimings {
+                    writing: std::time::Duration::from_millis(i),
+                    ..Default::default()
+                },
+            )],
+            ..Default::default()
+        };
+        let mut outcome = UnzipOutcome::default();
+        for i in [5, 12, 3, 8, 1, 11, 7, 2, 10, 4, 9, 6] {
+            outcome.add(&entry(i));
+        }
+        assert_eq!(outcome.files_extracted, 12);
+        let names: Vec<_> = outcome
+            .slowest_entries
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["12", "11", "10", "9", "8", "7", "6", "5", "4", "3"]);
+    }
+
+    #[test]
+    fn test_bottleneck_reading() {
+        /// Reads slowly, like a zip file at a slow URI.
+        #[derive(Clone)]
+        struct SlowReader(Cursor<Vec<u8>>);
+
+        impl std::io::Read for SlowReader {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                self.0.read(buf)
+            }
+        }
+
+        impl Seek for SlowReader {
+            fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+
+        impl crate::io::HasLength for SlowReader {
+            fn len(&self) -> std::io::Result<u64> {
+                self.0.len()
+            }
+        }
+
+        let mut zip = Cursor::new(Vec::new());
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        create_zip(&mut zip, true, Some(options));
+        let reader = SlowReader(Cursor::new(zip.into_inner()));
+        let td = tempdir().unwrap();
+        let options = UnzipOptions {
+            output_directory: Some(td.path().to_path_buf()),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_reader(reader)
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        // Reading the compressed data isn't taken for decompressing it.
+        assert_eq!(outcome.bottleneck(), Some(Bottleneck::Reading));
+        assert!(outcome.timings.reading >= std::time::Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_messy_paths() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let options = FileOptions::<()>::default();
+        zip.add_directory("./dir//", options).unwrap();
+        for (name, contents) in [
+            ("./foo/./bar.txt", "bar"),
+            ("foo//baz.txt", "baz"),
+            (".//foo/.//qux.txt", "qux"),
+            ("dir/./sub/../x.txt", "x"),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        assert_eq!(outcome.files_extracted, 4);
+        assert_eq!(read_to_string(outdir.join("foo/bar.txt")).unwrap(), "bar");
+        assert_eq!(read_to_string(outdir.join("foo/baz.txt")).unwrap(), "baz");
+        assert_eq!(read_to_string(outdir.join("foo/qux.txt")).unwrap(), "qux");
+        assert_eq!(read_to_string(outdir.join("dir/x.txt")).unwrap(), "x");
+        let mut names: Vec<_> = std::fs::read_dir(&outdir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["dir", "foo"]);
+        struct AllFiles;
+        impl FilenameFilter for AllFiles {
+            fn should_unzip(&self, _filename: &str) -> bool {
+                true
+            }
+        }
+        let memory = UnzipEngine::for_file(zf)
+            .unwrap()
+            .extract_to_memory(&AllFiles, None)
+            .unwrap();
+        let mut paths: Vec<_> = memory.keys().cloned().collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            ["dir/x.txt", "foo/bar.txt", "foo/baz.txt", "foo/qux.txt"]
+                .map(std::path::PathBuf::from)
+        );
+
+        // A `..` which would escape the output directory once `.` and
+        // empty segments are collapsed is still rejected.
+        let zf = td.path().join("escape.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file("./foo/.//../../escape.txt", FileOptions::<()>::default())
+            .unwrap();
+        zip.finish().unwrap();
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        assert!(UnzipEngine::for_file(zf).unwrap().unzip(options).is_err());
+        assert!(!td.path().join("escape.txt").exists());
+    }
+
+    #[test]
+    fn test_only_entries() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            only_entries: Some(HashSet::from([
+                "b.txt".to_string(),
+                "test/c.txt".to_string(),
+            ])),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        assert_eq!(outcome.files_extracted, 2);
+        check_files_exist(&outdir, false);
+
+        let outdir = td.path().join("outdir2");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            only_entries: Some(HashSet::from(["b.txt".to_string(), "nope".to_string()])),
+            ..Default::default()
+        };
+        let result = UnzipEngine::for_file(zf).unwrap().unzip(options);
+        assert!(matches!(
+            result,
+            Err(RipunzipErrors::EntriesNotFound { names }) if names == ["nope"]
+        ));
+        assert!(!outdir.join("b.txt").exists());
+    }
+
+    #[test]
+    fn test_unknown_sizes() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let contents = "Contents of a large file\n".repeat(200_000);
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file::<_, ExtendedFileOptions>("large.txt", Default::default())
+            .unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+        zip.finish().unwrap();
+        // Replace the size in the central directory with the Zip64 marker,
+        // without adding a Zip64 extra field.
+        let mut body = std::fs::read(&zf).unwrap();
+        let central_header = body
+            .windows(4)
+            .position(|w| w == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        let compressed_size = u32::from_le_bytes(
+            body[central_header + 20..central_header + 24]
+                .try_into()
+                .unwrap(),
+        ) as u64;
+        body[central_header + 24..central_header + 28].copy_from_slice(&[0xff; 4]);
+        std::fs::write(&zf, body).unwrap();
+
+        #[derive(Default)]
+        struct ProgressRecorder(Mutex<Vec<u64>>);
+        impl UnzipProgressReporter for Arc<ProgressRecorder> {
+            fn bytes_extracted(&self, count: u64) {
+                self.0.lock().unwrap().push(count);
+            }
+        }
+
+        for unknown_sizes in [UnknownSizes::Estimate, UnknownSizes::Stream] {
+            let outdir = td.path().join(format!("{unknown_sizes:?}"));
+            let recorder = Arc::new(ProgressRecorder::default());
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                progress_reporter: Box::new(recorder.clone()),
+                unknown_sizes,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            assert_eq!(read_to_string(outdir.join("large.txt")).unwrap(), contents);
+            // However much more data there is than estimated, progress adds
+            // up to the compressed size.
+            let progress = recorder.0.lock().unwrap();
+            assert_eq!(progress.iter().sum::<u64>(), compressed_size);
+            if unknown_sizes == UnknownSizes::Stream {
+                assert_eq!(progress.len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize_separators() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file::<_, ExtendedFileOptions>("dir\\file.txt", Default::default())
+            .unwrap();
+        zip.write_all(b"Contents of file").unwrap();
+        zip.finish().unwrap();
+
+        let outdir = td.path().join("normalized");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        assert_eq!(
+            read_to_string(outdir.join("dir/file.txt")).unwrap(),
+            "Contents of file"
+        );
+
+        #[cfg(not(windows))]
+        {
+            let outdir = td.path().join("literal");
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                normalize_separators: false,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+            assert_eq!(
+                read_to_string(outdir.join("dir\\file.txt")).unwrap(),
+                "Contents of file"
+            );
+            assert!(!outdir.join("dir").exists());
+        }
+    }
+
+    #[test]
+    fn test_sanitize_invalid_names() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file::<_, ExtendedFileOptions>("a:b?.txt", Default::default())
+            .unwrap();
+        zip.write_all(b"Contents of a:b?").unwrap();
+        zip.start_file::<_, ExtendedFileOptions>("dir/con.txt", Default::default())
+            .unwrap();
+        zip.write_all(b"Contents of con").unwrap();
+        zip.finish().unwrap();
+
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            sanitize_invalid_names: true,
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_extracted, 2);
+        #[cfg(windows)]
+        {
+            assert_eq!(
+                read_to_string(outdir.join("a%3Ab%3F.txt")).unwrap(),
+                "Contents of a:b?"
+            );
+            assert_eq!(
+                read_to_string(outdir.join("dir/co%6E.txt")).unwrap(),
+                "Contents of con"
+            );
+            assert_eq!(
+                outcome.renamed.into_iter().collect::<Vec<_>>(),
+                [
+                    ("a:b?.txt".to_string(), outdir.join("a%3Ab%3F.txt")),
+                    (
+                        "dir/con.txt".to_string(),
+                        outdir.join("dir").join("co%6E.txt")
+                    ),
+                ]
+            );
+        }
+        // Elsewhere such names are fine.
+        #[cfg(not(windows))]
+        {
+            assert_eq!(
+                read_to_string(outdir.join("a:b?.txt")).unwrap(),
+                "Contents of a:b?"
+            );
+            assert!(outcome.renamed.is_empty());
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extra_field_times() {
+        use std::time::{Duration, UNIX_EPOCH};
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let dos_time = zip::DateTime::from_date_and_time(2021, 1, 2, 3, 4, 8).unwrap();
+        let extended_timestamp = |times: &[u32]| {
+            let mut data = vec![(1 << times.len()) - 1];
+            for time in times {
+                data.extend(time.to_le_bytes());
+            }
+            data
+        };
+        // Modification time 1600000000.1234567, and no access time.
+        let mut ntfs = vec![0, 0, 0, 0, 1, 0, 24, 0];
+        ntfs.extend((116_444_736_000_000_000u64 + 16_000_000_001_234_567).to_le_bytes());
+        ntfs.extend([0; 16]);
+        let mut options =
+            FileOptions::<ExtendedFileOptions>::default().last_modified_time(dos_time);
+        options.add_extra_data(0x000a, &ntfs, false).unwrap();
+        options
+            .add_extra_data(
+                0x5455,
+                extended_timestamp(&[1_600_000_000, 1_700_000_000]),
+                false,
+            )
+            .unwrap();
+        zip.start_file("a.txt", options).unwrap();
+        zip.write_all(b"Contents of A").unwrap();
+        let mut options =
+            FileOptions::<ExtendedFileOptions>::default().last_modified_time(dos_time);
+        options
+            .add_extra_data(0x5455, extended_timestamp(&[1_500_000_000]), true)
+            .unwrap();
+        zip.start_file("b.txt", options).unwrap();
+        zip.write_all(b"Contents of B").unwrap();
+        zip.start_file(
+            "c.txt",
+            FileOptions::<ExtendedFileOptions>::default().last_modified_time(dos_time),
+        )
+        .unwrap();
+        zip.write_all(b"Contents of C").unwrap();
+        zip.finish().unwrap();
+
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            preserve_mtimes: true,
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        let metadata = |name| std::fs::metadata(outdir.join(name)).unwrap();
+        let at = |seconds, nanoseconds| UNIX_EPOCH + Duration::new(seconds, nanoseconds);
+        assert_eq!(
+            metadata("a.txt").modified().unwrap(),
+            at(1_600_000_000, 123_456_700)
+        );
+        assert_eq!(metadata("a.txt").accessed().unwrap(), at(1_700_000_000, 0));
+        assert_eq!(metadata("b.txt").modified().unwrap(), at(1_500_000_000, 0));
+        assert_eq!(metadata("c.txt").modified().unwrap(), at(1609556648, 0));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_preserve_mtimes() {
+        use std::time::{Duration, UNIX_EPOCH};
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let at = |year, second| {
+            FileOptions::<ExtendedFileOptions>::default().last_modified_time(
+                zip::DateTime::from_date_and_time(year, 1, 2, 3, 4, second).unwrap(),
+            )
+        };
+        zip.add_directory("dir/", at(2020, 6)).unwrap();
+        zip.start_file("dir/a.txt", at(2021, 8)).unwrap();
+        zip.write_all(b"Contents of A").unwrap();
+        zip.finish().unwrap();
+        let dir_mtime = UNIX_EPOCH + Duration::from_secs(1577934246);
+        let file_mtime = UNIX_EPOCH + Duration::from_secs(1609556648);
+        let mtime = |path: &Path| std::fs::metadata(path).unwrap().modified().unwrap();
+
+        for restore_directory_mtimes in [true, false] {
+            let outdir = td.path().join(format!("outdir{restore_directory_mtimes}"));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                // So that the directory is extracted before the file.
+                single_threaded: true,
+                preserve_mtimes: true,
+                restore_directory_mtimes,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            assert_eq!(mtime(&outdir.join("dir/a.txt")), file_mtime);
+            // Writing the file changed the directory's time.
+            assert_eq!(
+                mtime(&outdir.join("dir")) == dir_mtime,
+                restore_directory_mtimes
+            );
+        }
+    }
+
+    #[test]
+    fn test_recompress_to() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let contents = "Contents of D\n".repeat(100);
+        let last_modified = zip::DateTime::from_date_and_time(2020, 2, 3, 4, 5, 6).unwrap();
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let options = FileOptions::<ExtendedFileOptions>::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(last_modified)
+            .unix_permissions(0o640);
+        zip.set_comment("Archive comment").unwrap();
+        zip.add_directory("dir/", options.clone()).unwrap();
+        zip.start_file(
+            "dir/d.txt",
+            options.clone().with_file_comment("File comment"),
+        )
+        .unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+        zip.add_symlink("link", "dir/d.txt", options.clone())
+            .unwrap();
+        zip.start_file("a.txt", options).unwrap();
+        zip.write_all(b"Contents of A").unwrap();
+        zip.finish().unwrap();
+
+        let engine = UnzipEngine::for_file(zf.clone()).unwrap();
+        let recompressed = engine
+            .recompress_to(Cursor::new(Vec::new()), CompressionMethod::Zstd, Some(3))
+            .unwrap()
+            .into_inner();
+        let again = engine
+            .recompress_to(Cursor::new(Vec::new()), CompressionMethod::Zstd, Some(3))
+            .unwrap()
+            .into_inner();
+        assert_eq!(recompressed, again);
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(recompressed)).unwrap();
+        assert_eq!(archive.comment(), b"Archive comment");
+        assert_eq!(
+            (0..archive.len())
+                .map(|i| archive.name_for_index(i).unwrap())
+                .collect::<Vec<_>>(),
+            ["dir/", "dir/d.txt", "link", "a.txt"]
+        );
+        let mut file = archive.by_name("dir/d.txt").unwrap();
+        assert_eq!(file.compression(), CompressionMethod::Zstd);
+        assert_eq!(file.comment(), "File comment");
+        assert_eq!(file.last_modified(), Some(last_modified));
+        assert_eq!(file.unix_mode().unwrap() & 0o777, 0o640);
+        assert!(file.compressed_size() < contents.len() as u64);
+        assert_eq!(std::io::read_to_string(&mut file).unwrap(), contents);
+        drop(file);
+        assert!(archive.by_name("dir/").unwrap().is_dir());
+        let mut link = archive.by_name("link").unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(std::io::read_to_string(&mut link).unwrap(), "dir/d.txt");
+    }
+
+    #[test]
+    fn test_peek_entry() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let contents = "Contents of D\n".repeat(100);
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let options = FileOptions::<ExtendedFileOptions>::default()
+            .compression_method(CompressionMethod::Deflated);
+        zip.start_file("d.txt", options).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+        zip.finish().unwrap();
+
+        let engine = UnzipEngine::for_file(zf.clone()).unwrap();
+        assert_eq!(engine.peek_entry("d.txt", 8).unwrap(), b"Contents");
+        assert_eq!(engine.peek_entry("d.txt", 0).unwrap(), b"");
+        assert_eq!(
+            engine.peek_entry("d.txt", 10000).unwrap(),
+            contents.as_bytes()
+        );
+        assert!(matches!(
+            engine.peek_entry("missing", 8),
+            Err(RipunzipErrors::ZipErrorr(
+                zip::result::ZipError::FileNotFound
+            ))
+        ));
+
+        #[cfg(feature = "http")]
+        {
+            let server = Server::run();
+            set_up_server(&server, std::fs::read(zf).unwrap(), ServerType::Ranges);
+            let engine =
+                UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap();
+            assert_eq!(engine.peek_entry("d.txt", 8).unwrap(), b"Contents");
+        }
+    }
+
+    #[test]
+    fn test_extract_delta() {
+        let td = tempdir().unwrap();
+        let write_zip = |path: &Path, files: &[(&str, &str)]| {
+            let mut zip = ZipWriter::new(File::create(path).unwrap());
+            for (name, contents) in files {
+                zip.start_file::<_, ExtendedFileOptions>(*name, Default::default())
+                    .unwrap();
+                zip.write_all(contents.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        };
+        let old = td.path().join("old.zip");
+        write_zip(&old, &[("same", "A"), ("changed", "B"), ("gone", "C")]);
+        let new = td.path().join("new.zip");
+        write_zip(&new, &[("same", "A"), ("changed", "b"), ("added", "D")]);
+        let outdir = td.path().join("outdir");
+        let engine = UnzipEngine::for_file(old).unwrap();
+        let previous = engine.entry_metadata().unwrap();
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        engine.unzip(options).unwrap();
+        // Make sure that unchanged files aren't written again.
+        std::fs::write(outdir.join("same"), "untouched").unwrap();
+
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        let delta = UnzipEngine::for_file(new)
+            .unwrap()
+            .extract_delta(&previous, options)
+            .unwrap();
+        assert_eq!(delta.added, ["added"]);
+        assert_eq!(delta.changed, ["changed"]);
+        assert_eq!(delta.removed, ["gone"]);
+        assert_eq!(delta.outcome.files_extracted, 2);
+        assert_eq!(read_to_string(outdir.join("same")).unwrap(), "untouched");
+        assert_eq!(read_to_string(outdir.join("changed")).unwrap(), "b");
+        assert_eq!(read_to_string(outdir.join("added")).unwrap(), "D");
+        assert!(outdir.join("gone").exists());
+    }
+
+    #[test]
+    fn test_completion_marker() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let marker = outdir.join(".extracted");
+        let unzip = |zf: &Path| {
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                completion_marker: Some(marker.clone()),
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.to_path_buf())
+                .unwrap()
+                .unzip(options)
+                .unwrap()
+        };
+        assert!(unzip(&zf).files_extracted > 0);
+        assert!(marker.exists());
+        std::fs::write(outdir.join("test/a.txt"), "Changed").unwrap();
+        // The same zip file isn't extracted again.
+        assert_eq!(unzip(&zf).files_extracted, 0);
+        assert_eq!(
+            read_to_string(outdir.join("test/a.txt")).unwrap(),
+            "Changed"
+        );
+
+        // A different one is, even to the same place.
+        let other = td.path().join("other.zip");
+        create_zip_file(&other, false);
+        assert!(unzip(&other).files_extracted > 0);
+        assert!(unzip(&zf).files_extracted > 0);
+        assert_ne!(
+            read_to_string(outdir.join("test/a.txt")).unwrap(),
+            "Changed"
+        );
+
+        // A failed extraction leaves no marker.
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            completion_marker: Some(marker.clone()),
+            max_path_depth: Some(1),
+            ..Default::default()
+        };
+        assert!(UnzipEngine::for_file(other)
+            .unwrap()
+            .unzip(options)
+            .is_err());
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn test_compression_stats() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let mut zip =
+            ZipWriter::new_append(File::options().read(true).write(true).open(&zf).unwrap())
+                .unwrap();
+        let options = FileOptions::<ExtendedFileOptions>::default()
+            .compression_method(CompressionMethod::Deflated);
+        zip.start_file("d.txt", options).unwrap();
+        zip.write_all("Contents of D\n".repeat(100).as_bytes())
+            .unwrap();
+        zip.finish().unwrap();
+
+        let stats = UnzipEngine::for_file(zf)
+            .unwrap()
+            .compression_stats()
+            .unwrap();
+        assert_eq!(stats.overall.files, 4);
+        assert_eq!(stats.overall.size, 42 + 1400);
+        let stored = stats.by_method[&0];
+        assert_eq!((stored.files, stored.size), (3, 42));
+        assert_eq!(stored.ratio(), Some(1.0));
+        let deflated = stats.by_method[&8];
+        assert_eq!((deflated.files, deflated.size), (1, 1400));
+        assert!(deflated.ratio().unwrap() > 10.0);
+        assert_eq!(stats.by_method.len(), 2);
+    }
+
+    #[test]
+    fn test_estimate_duration() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let engine = UnzipEngine::for_file(zf).unwrap();
+        // The three files hold 42 bytes, stored.
+        let estimate = engine
+            .estimate_duration(Throughput {
+                read_bytes_per_second: 21.0,
+                decompress_bytes_per_second: 42.0,
+            })
+            .unwrap();
+        assert_eq!(estimate, Duration::from_secs(2));
+        let measured = engine.measure_throughput().unwrap().unwrap();
+        assert!(measured.read_bytes_per_second > 0.0);
+        assert!(measured.decompress_bytes_per_second > 0.0);
+        engine.estimate_duration(measured).unwrap();
+
+        let empty = td.path().join("empty.zip");
+        ZipWriter::new(File::create(&empty).unwrap())
+            .finish()
+            .unwrap();
+        let engine = UnzipEngine::for_file(empty).unwrap();
+        assert!(engine.measure_throughput().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_custom_decompressor() {
+        const CUSTOM_METHOD: u16 = 65000;
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let mut body = zip_data.into_inner();
+        // Relabel every stored file as using our custom method, in both the
+        // local headers and the central directory.
+        for (signature, method_offset) in [(b"PK\x03\x04", 8), (b"PK\x01\x02", 10)] {
+            let positions: Vec<_> = body
+                .windows(4)
+                .enumerate()
+                .filter(|(_, w)| w == signature)
+                .map(|(pos, _)| pos + method_offset)
+                .collect();
+            for pos in positions {
+                if body[pos..pos + 2] == [0, 0] {
+                    body[pos..pos + 2].copy_from_slice(&CUSTOM_METHOD.to_le_bytes());
+                }
+            }
+        }
+        std::fs::write(&zf, body).unwrap();
+
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        assert!(UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .is_err());
+
+        let mut decompressor_registry = DecompressorRegistry::new();
+        decompressor_registry.register(CUSTOM_METHOD, Box::new(|r| r));
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            decompressor_registry: Some(decompressor_registry),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        check_files_exist(&outdir, true);
+    }
+
+    #[test]
+    fn test_strict_local_header_mismatch() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let mut body = zip_data.into_inner();
+        // Make the local header of b.txt claim a different uncompressed size
+        // from the central directory.
+        let header_start = body
+            .windows(35)
+            .position(|w| w.starts_with(b"PK\x03\x04") && &w[30..] == b"b.txt")
+            .unwrap();
+        body[header_start + 22] += 1;
+        std::fs::write(&zf, body).unwrap();
+
+        // By default, the central directory is trusted.
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        check_files_exist(&outdir, true);
+
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir2")),
+            strict: true,
+            ..Default::default()
+        };
+        let result = UnzipEngine::for_file(zf).unwrap().unzip(options);
+        assert!(matches!(
+            result,
+            Err(RipunzipErrors::HeaderMismatch { ref name, field: "uncompressed size", central: 14, local: 15 })
+                if name == "b.txt"
+        ));
+    }
+
+    #[test]
+    fn test_streamed_zip_with_data_descriptors() {
+        // Written without seeking, so each file's sizes and CRC follow its
+        // data in a data descriptor rather than being in its local header.
+        let mut zip = ZipWriter::new_stream(Vec::new());
+        let options = FileOptions::<ExtendedFileOptions>::default()
+            .compression_method(CompressionMethod::Deflated);
+        for name in ["a.txt", "b.txt"] {
+            zip.start_file(name, options.clone()).unwrap();
+            zip.write_all(format!("Contents of {name}\n").repeat(50).as_bytes())
+                .unwrap();
+        }
+        let body = zip.finish().unwrap().into_inner();
+        assert_ne!(body[6] & 0x08, 0, "no data descriptor flag");
+
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        std::fs::write(&zf, &body).unwrap();
+        #[allow(unused_mut)]
+        let mut engines = vec![UnzipEngine::for_file(zf).unwrap()];
+        #[cfg(feature = "http")]
+        let server = Server::run();
+        #[cfg(feature = "http")]
+        {
+            set_up_server(&server, body, ServerType::Ranges);
+            engines
+                .push(UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap());
+        }
+        for (i, engine) in engines.into_iter().enumerate() {
+            let outdir = td.path().join(format!("outdir{i}"));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                strict: true,
+                verify_before_extract: true,
+                ..Default::default()
+            };
+            let outcome = engine.unzip(options).unwrap();
+            assert_eq!(outcome.files_extracted, 2);
+            assert_eq!(
+                read_to_string(outdir.join("b.txt")).unwrap(),
+                "Contents of b.txt\n".repeat(50)
+            );
+        }
+    }
+
+    #[test]
+    fn test_text_conversion() {
+        struct TxtFilter;
+        impl FilenameFilter for TxtFilter {
+            fn should_unzip(&self, filename: &str) -> bool {
+                filename.ends_with(".txt")
+            }
+        }
+
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let options: FileOptions<ExtendedFileOptions> = FileOptions::default();
+        zip.start_file("mixed.txt", options.clone()).unwrap();
+        zip.write_all(b"one\r\ntwo\nthree\r\n").unwrap();
+        zip.start_file("mixed.dat", options.clone()).unwrap();
+        zip.write_all(b"one\r\ntwo\nthree\r\n").unwrap();
+        zip.start_file("binary.txt", options).unwrap();
+        zip.write_all(b"\x00\xffone\r\ntwo\n").unwrap();
+        zip.finish().unwrap();
+
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            text_conversion: Some(LineEndingMode {
+                line_ending: LineEnding::Lf,
+                filename_filter: Some(Box::new(TxtFilter)),
+            }),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(
+            std::fs::read(outdir.join("mixed.txt")).unwrap(),
+            b"one\ntwo\nthree\n"
+        );
+        // What's written, not what's read.
+        assert_eq!(outcome.bytes_written, 14 + 16 + 11);
+        assert_eq!(
+            std::fs::read(outdir.join("mixed.dat")).unwrap(),
+            b"one\r\ntwo\nthree\r\n"
+        );
+        assert_eq!(
+            std::fs::read(outdir.join("binary.txt")).unwrap(),
+            b"\x00\xffone\r\ntwo\n"
+        );
+    }
+
+    #[test]
+    fn test_transform() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let upper_case_c = || -> Option<EntryTransform> {
+            Some(Box::new(|name: &str| -> Option<ByteTransformer> {
+                (name == "test/c.txt").then(|| {
+                    Box::new(|chunk: &[u8]| {
+                        let mut chunk = chunk.to_ascii_uppercase();
+                        // Mark the end of the data.
+                        if chunk.is_empty() {
+                            chunk.extend(b"EOF\n");
+                        }
+                        chunk
+                    }) as ByteTransformer
+                })
+            }))
+        };
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            transform: upper_case_c(),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        assert_eq!(
+            read_to_string(outdir.join("test/c.txt")).unwrap(),
+            "CONTENTS OF C\nEOF\n"
+        );
+        assert_eq!(outcome.bytes_written, 42 + 4);
+        assert_eq!(
+            read_to_string(outdir.join("b.txt")).unwrap(),
+            "Contents of B\n"
+        );
+
+        let mut out = Vec::new();
+        let options = UnzipOptions {
+            only_entries: Some(HashSet::from(["test/c.txt".to_string()])),
+            transform: upper_case_c(),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf)
+            .unwrap()
+            .unzip_to_writer(options, &mut out)
+            .unwrap();
+        assert_eq!(out, b"CONTENTS OF C\nEOF\n");
+        assert_eq!(outcome.bytes_written, out.len() as u64);
+    }
+
+    struct FinishRecorder(Arc<Mutex<Option<UnzipOutcome>>>);
+
+    impl UnzipProgressReporter for FinishRecorder {
+        fn all_finished(&self, outcome: &UnzipOutcome) {
+            *self.0.lock().unwrap() = Some(outcome.clone());
+        }
+    }
+
+    #[test]
+    fn test_emit_index() {
+        let td = tempdir().unwrap();
+        let mut data = b"Self-extractor stub".to_vec();
+        let mut cursor = Cursor::new(&mut data);
+        cursor.seek(std::io::SeekFrom::End(0)).unwrap();
+        create_zip(cursor, true, None);
+        let zf = td.path().join("z.zip");
+        std::fs::write(&zf, &data).unwrap();
+        let index = td.path().join("index.jsonl");
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            emit_index: Some(index.clone()),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        let index = std::fs::read_to_string(index).unwrap();
+        let lines: Vec<&str> = index.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with(r#"{"name":"test/","offset":19,"compressed_size":0,"#));
+        for line in lines {
+            let field = |name: &str| -> u64 {
+                let value = &line[line.find(&format!("\"{name}\":")).unwrap() + name.len() + 3..];
+                value[..value.find([',', '}']).unwrap()].parse().unwrap()
+            };
+            let name = &line[9..line.find("\",").unwrap()];
+            // Each offset is that of the entry's local header.
+            let offset = field("offset") as usize;
+            assert_eq!(&data[offset..offset + 4], b"PK\x03\x04");
+            assert_eq!(
+                &data[offset + 30..offset + 30 + name.len()],
+                name.as_bytes()
+            );
+            assert_eq!(field("method"), 0);
+        }
+    }
+
+    #[test]
+    fn test_progress_file() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let progress_file = td.path().join("progress.jsonl");
+        let finished = Arc::new(Mutex::new(None));
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            single_threaded: true,
+            progress_reporter: Box::new(FinishRecorder(finished.clone())),
+            progress_file: Some(progress_file.clone()),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        // The progress reporter still hears about everything.
+        assert!(finished.lock().unwrap().is_some());
+        let progress = read_to_string(&progress_file).unwrap();
+        let lines = progress.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0], r#"{"event":"total_bytes_expected","bytes":428}"#);
+        assert_eq!(lines[1], r#"{"event":"central_directory_read_starting"}"#);
+        assert_eq!(
+            lines[2],
+            r#"{"event":"central_directory_read_finished","entries":4}"#
+        );
+        assert_eq!(
+            lines[6],
+            r#"{"event":"started","name":"test/a.txt","size":14,"compressed_size":14,"bytes_extracted":0}"#
+        );
+        assert_eq!(
+            lines[11],
+            r#"{"event":"finished","name":"test/c.txt","bytes_extracted":42}"#
+        );
+        assert_eq!(
+            lines[12],
+            r#"{"event":"all_finished","files_extracted":3,"bytes_written":42,"bytes_extracted":42}"#
+        );
+
+        // Failures are recorded too, after what's already there.
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            password: Some("wrong".to_string()),
+            progress_file: Some(progress_file.clone()),
+            ..Default::default()
+        };
+        let zf = td.path().join("encrypted.zip");
+        create_encrypted_zip_file(&zf, true);
+        assert!(UnzipEngine::for_file(zf).unwrap().unzip(options).is_err());
+        let progress = read_to_string(&progress_file).unwrap();
+        assert!(progress.starts_with(&lines.join("\n")));
+        assert!(progress
+            .lines()
+            .last()
+            .unwrap()
+            .starts_with(r#"{"event":"error","message":"#));
+    }
+
+    #[test]
+    fn test_all_finished() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let finished = Arc::new(Mutex::new(None));
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            progress_reporter: Box::new(FinishRecorder(finished.clone())),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        assert_eq!(outcome.files_extracted, 3);
+        assert_eq!(outcome.directories_extracted, 1);
+        assert_eq!(outcome.bytes_written, 42);
+        assert_eq!(
+            finished.lock().unwrap().as_ref().unwrap().files_extracted,
+            3
+        );
+
+        // Not called on failure
+        let finished = Arc::new(Mutex::new(None));
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            password: Some("wrong".to_string()),
+            progress_reporter: Box::new(FinishRecorder(finished.clone())),
+            ..Default::default()
+        };
+        let zf = td.path().join("encrypted.zip");
+        create_encrypted_zip_file(&zf, true);
+        assert!(UnzipEngine::for_file(zf).unwrap().unzip(options).is_err());
+        assert!(finished.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unzip_iter() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        let mut entries = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip_iter(options)
+            .map(|entry| {
+                let entry = entry.unwrap();
+                // Each entry is complete by the time we hear about it.
+                assert!(entry.path.exists());
+                (entry.path, entry.metadata.name)
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                (outdir.join("b.txt"), "b.txt".to_string()),
+                (outdir.join("test"), "test/".to_string()),
+                (outdir.join("test/a.txt"), "test/a.txt".to_string()),
+                (outdir.join("test/c.txt"), "test/c.txt".to_string()),
+            ]
+        );
+
+        // Errors come last.
+        let zf = td.path().join("encrypted.zip");
+        create_encrypted_zip_file(&zf, true);
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("encrypted")),
+            password: Some("wrong".to_string()),
+            ..Default::default()
+        };
+        let results = UnzipEngine::for_file(zf)
+            .unwrap()
+            .unzip_iter(options)
+            .collect::<Vec<_>>();
+        assert!(results.last().unwrap().is_err());
+    }
+
+    struct SlowReporter;
+
+    impl UnzipProgressReporter for SlowReporter {
+        fn extraction_starting(&self, _entry: &EntryInfo) {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_unzip_iter_cancel() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for i in 0..20 {
+            zip.start_file::<_, ExtendedFileOptions>(format!("{i}.txt"), Default::default())
+                .unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            single_threaded: true,
+            progress_reporter: Box::new(SlowReporter),
+            ..Default::default()
+        };
+        let mut entries = UnzipEngine::for_file(zf).unwrap().unzip_iter(options);
+        entries.next().unwrap().unwrap();
+        drop(entries);
+        // Nothing more is written once the iterator has gone.
+        let extracted = std::fs::read_dir(&outdir).unwrap().count();
+        assert!(extracted < 20);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(std::fs::read_dir(&outdir).unwrap().count(), extracted);
+    }
+
+    #[test]
+    fn test_unzip_prioritized() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for i in 0..20 {
+            zip.start_file::<_, ExtendedFileOptions>(format!("{i}.txt"), Default::default())
+                .unwrap();
+        }
+        zip.finish().unwrap();
+        struct Last;
+        impl FilenameFilter for Last {
+            fn should_unzip(&self, filename: &str) -> bool {
+                filename == "19.txt"
+            }
+        }
+        let unzip = |outdir: &Path| {
+            let options = UnzipOptions {
+                output_directory: Some(outdir.to_path_buf()),
+                single_threaded: true,
+                progress_reporter: Box::new(SlowReporter),
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip_prioritized(options, Box::new(Last))
+                .unwrap()
+        };
+
+        let outdir = td.path().join("outdir");
+        let background = unzip(&outdir);
+        assert!(outdir.join("19.txt").exists());
+        assert_eq!(background.prioritized().files_extracted, 1);
+        let outcome = background.wait().unwrap();
+        assert_eq!(outcome.files_extracted, 20);
+        assert_eq!(std::fs::read_dir(&outdir).unwrap().count(), 20);
+
+        let outdir = td.path().join("cancelled");
+        let background = unzip(&outdir);
+        background.cancel();
+        assert!(matches!(background.wait(), Err(RipunzipErrors::Aborted)));
+        assert!(outdir.join("19.txt").exists());
+        assert!(std::fs::read_dir(&outdir).unwrap().count() < 20);
+
+        // Nothing more is extracted while paused, once the entry in
+        // progress is finished.
+        let outdir = td.path().join("paused");
+        let background = unzip(&outdir);
+        let control = background.control();
+        control.pause();
+        assert!(control.is_paused());
+        std::thread::sleep(Duration::from_millis(100));
+        let count = std::fs::read_dir(&outdir).unwrap().count();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(std::fs::read_dir(&outdir).unwrap().count(), count);
+        assert!(count < 20);
+        assert!(!background.is_finished());
+        control.resume();
+        assert_eq!(background.wait().unwrap().files_extracted, 20);
+
+        // Cancelling while paused doesn't wait to be resumed.
+        let background = unzip(&td.path().join("paused_then_cancelled"));
+        background.control().pause();
+        background.cancel();
+        assert!(matches!(background.wait(), Err(RipunzipErrors::Aborted)));
+    }
+
+    /// Records reading the central directory, the entries reported as
+    /// ready, and names of entries as they start extracting.
+    #[derive(Clone, Default)]
+    struct MetadataRecorder(Arc<Mutex<Vec<String>>>);
+
+    impl UnzipProgressReporter for MetadataRecorder {
+        fn central_directory_read_starting(&self) {
+            self.0.lock().unwrap().push("reading".to_string());
+        }
+
+        fn central_directory_read_finished(&self, entry_count: usize) {
+            self.0.lock().unwrap().push(format!("read {entry_count}"));
+        }
+
+        fn metadata_ready(&self, entries: &[ZipEntryMetadata]) {
+            let mut events = self.0.lock().unwrap();
+            events.extend(entries.iter().map(|entry| format!("ready {}", entry.name)));
+        }
+
+        fn extraction_starting(&self, entry: &EntryInfo) {
+            let mut events = self.0.lock().unwrap();
+            events.push(format!("start {}", entry.display_name));
+        }
+    }
+
+    #[test]
+    fn test_metadata_ready() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        #[allow(unused_mut)]
+        let mut engines = vec![UnzipEngine::for_file(zf.clone()).unwrap()];
+        #[cfg(feature = "http")]
+        let server = Server::run();
+        #[cfg(feature = "http")]
+        {
+            set_up_server(&server, std::fs::read(&zf).unwrap(), ServerType::Ranges);
+            engines
+                .push(UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap());
+        }
+        for (i, engine) in engines.into_iter().enumerate() {
+            let recorder = MetadataRecorder::default();
+            let options = UnzipOptions {
+                output_directory: Some(td.path().join(format!("{i}"))),
+                single_threaded: true,
+                progress_reporter: Box::new(recorder.clone()),
+                ..Default::default()
+            };
+            engine.unzip(options).unwrap();
+            let events = recorder.0.lock().unwrap();
+            assert_eq!(events[..2], ["reading", "read 4"]);
+            let events = &events[2..];
+            // Every entry is ready before any starts.
+            let ready = events
+                .iter()
+                .take_while(|e| e.starts_with("ready "))
+                .count();
+            assert!(events[ready..].iter().all(|e| e.starts_with("start ")));
+            assert_eq!(events.len(), ready * 2);
+            assert!(events.contains(&"ready test/a.txt".to_string()));
+        }
+    }
+
+    struct StartRecorder(Arc<Mutex<Vec<(String, u64, CompressionMethod)>>>);
+
+    impl UnzipProgressReporter for StartRecorder {
+        fn extraction_starting(&self, entry: &EntryInfo) {
+            self.0.lock().unwrap().push((
+                entry.display_name.to_string(),
+                entry.size,
+                entry.compression_method,
+            ));
+        }
+    }
+
+    #[test]
+    fn test_extraction_starting_entry_info() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, false);
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            single_threaded: true,
+            progress_reporter: Box::new(StartRecorder(started.clone())),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        let mut started = started.lock().unwrap().clone();
+        started.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            started,
+            [
+                ("b.txt".to_string(), 14, CompressionMethod::Stored),
+                ("test".to_string(), 0, CompressionMethod::Stored),
+                ("test/c.txt".to_string(), 14, CompressionMethod::Stored),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_path_depth() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        let deep_name = format!("{}file.txt", "d/".repeat(1000));
+        zip.start_file::<_, ExtendedFileOptions>(deep_name.as_str(), Default::default())
+            .unwrap();
+        zip.write_all(b"deep").unwrap();
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            max_path_depth: Some(100),
+            ..Default::default()
+        };
+        match UnzipEngine::for_file(zf).unwrap().unzip(options) {
+            Err(RipunzipErrors::PathTooDeep { name, depth, limit }) => {
+                assert_eq!(name, deep_name);
+                assert_eq!(depth, 1001);
+                assert_eq!(limit, 100);
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert!(!outdir.join("d").exists());
+    }
+
+    struct SkipRecorder(Arc<Mutex<Vec<(String, SkipReason)>>>);
+
+    impl UnzipProgressReporter for SkipRecorder {
+        fn entry_skipped(&self, name: &str, reason: SkipReason) {
+            self.0.lock().unwrap().push((name.to_string(), reason));
+        }
+    }
+
+    #[test]
+    fn test_entry_skipped() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        std::fs::create_dir_all(&outdir).unwrap();
+        std::fs::write(outdir.join("b.txt"), "Old B").unwrap();
+        let skipped = Arc::new(Mutex::new(Vec::new()));
+        let options = UnzipOptions {
+            output_directory: Some(outdir),
+            filename_filter: Some(Box::new(UnzipSomeFilter)),
+            progress_reporter: Box::new(SkipRecorder(skipped.clone())),
+            overwrite_policy: OverwritePolicy::Skip,
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(
+            *skipped.lock().unwrap(),
+            [
+                ("test/".to_string(), SkipReason::Filtered),
+                ("test/a.txt".to_string(), SkipReason::Filtered),
+                ("b.txt".to_string(), SkipReason::AlreadyExists),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exclude_extensions() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let skipped = Arc::new(Mutex::new(Vec::new()));
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            progress_reporter: Box::new(SkipRecorder(skipped.clone())),
+            exclude_extensions: vec!["TXT".to_string()],
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_extracted, 0);
+        assert!(outdir.join("test").is_dir());
+        assert!(!outdir.join("b.txt").exists());
+        let mut skipped = skipped.lock().unwrap().clone();
+        skipped.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            skipped,
+            [
+                ("b.txt".to_string(), SkipReason::Filtered),
+                ("test/a.txt".to_string(), SkipReason::Filtered),
+                ("test/c.txt".to_string(), SkipReason::Filtered),
+            ]
+        );
+    }
+
+    #[derive(Default)]
+    struct PlanRecorder(Mutex<Vec<String>>);
+
+    impl UnzipProgressReporter for Arc<PlanRecorder> {
+        fn extraction_planned(&self, name: &str, action: PlannedAction) {
+            self.0.lock().unwrap().push(format!("{action:?} {name}"));
+        }
+
+        fn entry_skipped(&self, name: &str, reason: SkipReason) {
+            self.0.lock().unwrap().push(format!("{reason:?} {name}"));
+        }
+
+        fn plan_ready(&self, plan: &ExtractionPlan) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("plan of {}", plan.entries.len()));
+        }
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        std::fs::create_dir_all(outdir.join("test")).unwrap();
+        std::fs::write(outdir.join("b.txt"), "Old B").unwrap();
+        std::fs::write(outdir.join("test/c.txt"), "Old C").unwrap();
+        let recorder = Arc::new(PlanRecorder::default());
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let asked_in_callback = asked.clone();
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            progress_reporter: Box::new(recorder.clone()),
+            overwrite_policy: OverwritePolicy::Callback(Box::new(move |_, metadata| {
+                asked_in_callback.lock().unwrap().push(metadata.size);
+                match metadata.name.as_str() {
+                    "b.txt" => OverwriteDecision::Skip,
+                    _ => OverwriteDecision::Overwrite,
+                }
+            })),
+            dry_run: true,
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "AlreadyExists b.txt",
+                "Create test/a.txt",
+                "Overwrite test/c.txt"
+            ]
+        );
+        assert_eq!(*asked.lock().unwrap(), [14, 14]);
+        assert_eq!(outcome.files_extracted, 2);
+        assert_eq!(outcome.files_skipped, 1);
+        assert!(!outdir.join("test/a.txt").exists());
+        assert_eq!(read_to_string(outdir.join("b.txt")).unwrap(), "Old B");
+        assert_eq!(read_to_string(outdir.join("test/c.txt")).unwrap(), "Old C");
+    }
+
+    #[test]
+    fn test_plan() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        std::fs::create_dir_all(&outdir).unwrap();
+        std::fs::write(outdir.join("b.txt"), "Old B").unwrap();
+        let options = || UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            overwrite_policy: OverwritePolicy::Skip,
+            only_entries: Some(
+                ["b.txt", "test/", "test/a.txt"]
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let engine = UnzipEngine::for_file(zf).unwrap();
+        let plan = engine.plan(options()).unwrap();
+        let decisions: Vec<_> = plan
+            .entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.name.as_str(),
+                    entry.output_path.clone(),
+                    entry.decision,
+                )
+            })
+            .collect();
+        assert_eq!(
+            decisions,
+            [
+                (
+                    "b.txt",
+                    Some(outdir.join("b.txt")),
+                    PlannedDecision::Skip(SkipReason::AlreadyExists)
+                ),
+                (
+                    "test/",
+                    Some(outdir.join("test")),
+                    PlannedDecision::CreateDirectory
+                ),
+                (
+                    "test/a.txt",
+                    Some(outdir.join("test/a.txt")),
+                    PlannedDecision::Extract(PlannedAction::Create)
+                ),
+                (
+                    "test/c.txt",
+                    None,
+                    PlannedDecision::Skip(SkipReason::Filtered)
+                ),
+            ]
+        );
+        // The directory comes first in the zip file.
+        assert_eq!(plan.entries[1].offset, 0);
+        assert!(plan.entries[2].offset > 0);
+        assert_eq!(plan.entries[2].size, 14);
+        assert_eq!(plan.entries[2].method, 0);
+        assert!(!outdir.join("test").exists());
+
+        // Explaining a real unzip plans the same, then extracts.
+        let recorder = Arc::new(PlanRecorder::default());
+        let options = UnzipOptions {
+            progress_reporter: Box::new(recorder.clone()),
+            explain: true,
+            ..options()
+        };
+        let outcome = engine.unzip(options).unwrap();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            ["plan of 4", "Filtered test/c.txt", "AlreadyExists b.txt"]
+        );
+        assert_eq!(outcome.files_extracted, 1);
+        assert_eq!(
+            read_to_string(outdir.join("test/a.txt")).unwrap(),
+            "Contents of A\n"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_permission_template() {
+        use crate::EntryKind;
+        use std::os::unix::fs::PermissionsExt;
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            permission_template: Some(Box::new(|path, kind| match kind {
+                EntryKind::Directory => Some(0o700),
+                EntryKind::File if path == Path::new("b.txt") => None,
+                EntryKind::File => Some(0o600),
+            })),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        let mode = |name| {
+            std::fs::metadata(outdir.join(name))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777
+        };
+        assert_eq!(mode("test"), 0o700);
+        assert_eq!(mode("test/a.txt"), 0o600);
+        assert_eq!(mode("b.txt"), 0o755);
+    }
+
+    #[test]
+    fn test_durability() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            durability: Durability::FsyncFilesAndDir,
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        check_files_exist(&outdir, true);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_existing_symlinks() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.start_file::<_, ExtendedFileOptions>("link/evil.txt", Default::default())
+            .unwrap();
+        zip.write_all(b"Escaped").unwrap();
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let elsewhere = td.path().join("elsewhere");
+        std::fs::create_dir_all(&outdir).unwrap();
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, outdir.join("link")).unwrap();
+
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        match UnzipEngine::for_file(zf.clone()).unwrap().unzip(options) {
+            Err(RipunzipErrors::SymlinkInPath { name, symlink }) => {
+                assert_eq!(name, "link/evil.txt");
+                assert_eq!(symlink, outdir.join("link"));
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert!(!elsewhere.join("evil.txt").exists());
+
+        let options = UnzipOptions {
+            output_directory: Some(outdir),
+            follow_existing_symlinks: true,
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(
+            read_to_string(elsewhere.join("evil.txt")).unwrap(),
+            "Escaped"
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_anchored_output() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        zip.add_directory::<_, ExtendedFileOptions>("dir/", Default::default())
+            .unwrap();
+        for name in ["dir/sub/ok.txt", "link/evil.txt"] {
+            zip.start_file::<_, ExtendedFileOptions>(
+                name,
+                FileOptions::default().unix_permissions(0o600),
+            )
+            .unwrap();
+            zip.write_all(b"Escaped").unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let elsewhere = td.path().join("elsewhere");
+        std::fs::create_dir_all(&outdir).unwrap();
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, outdir.join("link")).unwrap();
+
+        for single_threaded in [false, true] {
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                follow_existing_symlinks: true,
+                anchored_output: true,
+                single_threaded,
+                ..Default::default()
+            };
+            assert!(UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .is_err());
+            assert!(!elsewhere.join("evil.txt").exists());
+        }
+
+        std::fs::remove_file(outdir.join("link")).unwrap();
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            anchored_output: true,
+            ..Default::default()
+        };
+        UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        let ok = outdir.join("dir/sub/ok.txt");
+        assert_eq!(read_to_string(&ok).unwrap(), "Escaped");
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(ok.metadata().unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(
+            read_to_string(outdir.join("link/evil.txt")).unwrap(),
+            "Escaped"
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_restrict_to_device() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for name in ["ok.txt", "proc/evil.txt"] {
+            zip.start_file::<_, ExtendedFileOptions>(name, Default::default())
+                .unwrap();
+            zip.write_all(b"Escaped").unwrap();
+        }
+        zip.finish().unwrap();
+        // /proc is always a filesystem of its own.
+        let outdir = td.path().join("outdir");
+        std::fs::create_dir_all(&outdir).unwrap();
+        std::os::unix::fs::symlink("/proc", outdir.join("proc")).unwrap();
+
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            follow_existing_symlinks: true,
+            restrict_to_device: true,
+            ..Default::default()
+        };
+        match UnzipEngine::for_file(zf).unwrap().unzip(options) {
+            Err(RipunzipErrors::DifferentDevice { name, path }) => {
+                assert_eq!(name, "proc/evil.txt");
+                assert_eq!(path, outdir.join("proc"));
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert!(outdir.join("ok.txt").exists());
+    }
+
+    #[test]
+    fn test_extract_to_memory() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        struct InTest;
+        impl FilenameFilter for InTest {
+            fn should_unzip(&self, filename: &str) -> bool {
+                filename.starts_with("test/")
+            }
+        }
+        let engine = UnzipEngine::for_file(zf).unwrap();
+        let files = engine.extract_to_memory(&InTest, Some(28)).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[Path::new("test/a.txt")], b"Contents of A\n");
+        assert_eq!(files[Path::new("test/c.txt")], b"Contents of C\n");
+        assert!(matches!(
+            engine.extract_to_memory(&InTest, Some(27)),
+            Err(RipunzipErrors::MemoryLimitExceeded { limit: 27 })
+        ));
+    }
+
+    #[test]
+    fn test_require_empty_output() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        let unzip = |ignore_hidden| {
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                require_empty_output: true,
+                empty_output_ignores_hidden: ignore_hidden,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone()).unwrap().unzip(options)
+        };
+        std::fs::create_dir_all(&outdir).unwrap();
+        std::fs::write(outdir.join(".DS_Store"), "").unwrap();
+        assert!(matches!(
+            unzip(false),
+            Err(RipunzipErrors::OutputDirectoryNotEmpty { .. })
+        ));
+        assert!(!outdir.join("b.txt").exists());
+        unzip(true).unwrap();
+        check_files_exist(&outdir, true);
+        assert!(matches!(
+            unzip(true),
+            Err(RipunzipErrors::OutputDirectoryNotEmpty { .. })
+        ));
+    }
+
+    #[test]
+    fn test_create_output_directory() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("mount/outdir");
+        let unzip = |create_output_directory, dry_run| {
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                create_output_directory,
+                dry_run,
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone()).unwrap().unzip(options)
+        };
+        for dry_run in [true, false] {
+            assert!(matches!(
+                unzip(false, dry_run),
+                Err(RipunzipErrors::OutputDirectoryMissing { directory }) if directory == outdir
+            ));
+        }
+        assert!(!td.path().join("mount").exists());
+        unzip(true, true).unwrap();
+        assert!(!outdir.exists());
+        unzip(true, false).unwrap();
+        check_files_exist(&outdir, true);
+        // It exists now, so there's nothing to create.
+        unzip(false, false).unwrap();
+
+        // Even a zip file with nothing in it gets an output directory.
+        let empty = td.path().join("empty.zip");
+        ZipWriter::new(File::create(&empty).unwrap())
+            .finish()
+            .unwrap();
+        let empty_outdir = td.path().join("empty");
+        let options = UnzipOptions {
+            output_directory: Some(empty_outdir.clone()),
+            ..Default::default()
+        };
+        UnzipEngine::for_file(empty)
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        assert!(empty_outdir.is_dir());
+    }
+
+    #[test]
+    fn test_overlay_base() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let base = td.path().join("base");
+        std::fs::create_dir_all(base.join("test/a.txt")).unwrap();
+        std::fs::write(base.join("b.txt"), "Contents of B\n").unwrap();
+        // The same size, but different.
+        std::fs::write(base.join("test/c.txt"), "Contents of X\n").unwrap();
+        for dry_run in [true, false] {
+            let outdir = td.path().join(format!("upper-{dry_run}"));
+            let skipped = Arc::new(Mutex::new(Vec::new()));
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                progress_reporter: Box::new(SkipRecorder(skipped.clone())),
+                overlay_base: Some(base.clone()),
+                dry_run,
+                ..Default::default()
+            };
+            let outcome = UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            assert_eq!((outcome.files_extracted, outcome.files_skipped), (2, 1));
+            assert_eq!(
+                *skipped.lock().unwrap(),
+                [("b.txt".to_string(), SkipReason::UpToDate)]
+            );
+            if !dry_run {
+                assert!(!outdir.join("b.txt").exists());
+                assert_eq!(
+                    read_to_string(outdir.join("test/c.txt")).unwrap(),
+                    "Contents of C\n"
+                );
+                assert!(outdir.join("test/a.txt").is_file());
+            }
+        }
+        assert_eq!(
+            read_to_string(base.join("test/c.txt")).unwrap(),
+            "Contents of X\n"
+        );
+    }
+
+    #[test]
+    fn test_overwrite_callback() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        std::fs::create_dir_all(outdir.join("test")).unwrap();
+        std::fs::write(outdir.join("b.txt"), "Old B").unwrap();
+        std::fs::write(outdir.join("test/c.txt"), "Old C").unwrap();
+
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let asked2 = asked.clone();
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            overwrite_policy: OverwritePolicy::Callback(Box::new(move |path, metadata| {
+                assert!(path.exists());
+                asked2.lock().unwrap().push(metadata.name.clone());
+                if metadata.name == "b.txt" {
+                    OverwriteDecision::Skip
+                } else {
+                    OverwriteDecision::Overwrite
+                }
+            })),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        let mut asked = asked.lock().unwrap().clone();
+        asked.sort();
+        assert_eq!(asked, ["b.txt", "test/c.txt"]);
+        assert_eq!(outcome.files_extracted, 2);
+        assert_eq!(outcome.files_skipped, 1);
+        assert_eq!(read_to_string(outdir.join("b.txt")).unwrap(), "Old B");
+        assert_eq!(
+            read_to_string(outdir.join("test/c.txt")).unwrap(),
+            "Contents of C\n"
+        );
+
+        // Abort
+        let options = UnzipOptions {
+            output_directory: Some(outdir),
+            overwrite_policy: OverwritePolicy::Callback(Box::new(|_, _| OverwriteDecision::Abort)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            UnzipEngine::for_file(zf).unwrap().unzip(options),
+            Err(RipunzipErrors::Aborted)
+        ));
+    }
+
+    #[test]
+    fn test_worker_panic() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let outdir = td.path().join("outdir");
+        std::fs::create_dir_all(outdir.join("test")).unwrap();
+        std::fs::write(outdir.join("b.txt"), "Old B").unwrap();
+        std::fs::write(outdir.join("test/c.txt"), "Old C").unwrap();
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            // So that b.txt's callback poisons the lock before c.txt's.
+            single_threaded: true,
+            overwrite_policy: OverwritePolicy::Callback(Box::new(|_, metadata| {
+                if metadata.name == "b.txt" {
+                    panic!("injected panic");
+                }
+                OverwriteDecision::Overwrite
+            })),
+            ..Default::default()
+        };
+        let result = UnzipEngine::for_file(zf).unwrap().unzip(options);
+        match result {
+            Err(RipunzipErrors::Worker { name, message }) => {
+                assert_eq!(name, "b.txt");
+                assert_eq!(message, "injected panic");
+            }
+            result => panic!("unexpected result {result:?}"),
+        }
+        // The other entries were unaffected.
+        assert_eq!(read_to_string(outdir.join("b.txt")).unwrap(), "Old B");
+        assert_eq!(
+            read_to_string(outdir.join("test/a.txt")).unwrap(),
+            "Contents of A\n"
+        );
+        assert_eq!(
+            read_to_string(outdir.join("test/c.txt")).unwrap(),
+            "Contents of C\n"
+        );
+    }
+
+    #[test]
+    fn test_unzip_to_writer() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        struct OnlyB;
+        impl FilenameFilter for OnlyB {
+            fn should_unzip(&self, filename: &str) -> bool {
+                filename == "b.txt"
+            }
+        }
+        struct ExpectedRecorder(Arc<Mutex<Vec<u64>>>);
+        impl UnzipProgressReporter for ExpectedRecorder {
+            fn total_bytes_expected(&self, expected: u64) {
+                self.0.lock().unwrap().push(expected);
+            }
+        }
+        let expected = Arc::new(Mutex::new(Vec::new()));
+        let options = UnzipOptions {
+            filename_filter: Some(Box::new(OnlyB)),
+            progress_reporter: Box::new(ExpectedRecorder(expected.clone())),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let outcome = UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip_to_writer(options, &mut output)
+            .unwrap();
+        assert_eq!(output, b"Contents of B\n");
+        assert_eq!(outcome.bytes_written, 14);
+        // Only the file being written is expected, as it's stored.
+        assert_eq!(*expected.lock().unwrap(), [14]);
+
+        let result = UnzipEngine::for_file(zf)
+            .unwrap()
+            .unzip_to_writer(UnzipOptions::default(), &mut Vec::new());
+        assert!(matches!(
+            result,
+            Err(RipunzipErrors::NotSingleFile { count: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_concat_to() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_zip_file(&zf, true);
+        let concat = |options, sort_by_name| {
+            let mut output = Vec::new();
+            let outcome = UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .concat_to(
+                    options,
+                    &mut output,
+                    |info: &EntryInfo| format!("=== {} ===\n", info.display_name),
+                    sort_by_name,
+                )
+                .unwrap();
+            (String::from_utf8(output).unwrap(), outcome)
+        };
+        let (output, outcome) = concat(UnzipOptions::default(), false);
+        assert_eq!(
+            output,
+            "=== test/a.txt ===\nContents of A\n\
+             === b.txt ===\nContents of B\n\
+             === test/c.txt ===\nContents of C\n"
+        );
+        assert_eq!(outcome.files_extracted, 3);
+        assert_eq!(outcome.bytes_written, 42);
+
+        let options = UnzipOptions {
+            filename_filter: Some(Box::new(UnzipSomeFilter)),
+            ..Default::default()
+        };
+        let (output, _) = concat(options, true);
+        assert_eq!(
+            output,
+            "=== b.txt ===\nContents of B\n=== test/c.txt ===\nContents of C\n"
+        );
+    }
+
+    #[test]
+    fn test_dedup() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for (name, contents) in [
+            ("one.txt", "Same contents\n"),
+            ("dir/two.txt", "Same contents\n"),
+            ("three.txt", "Other contents\n"),
+        ] {
+            zip.start_file::<_, ExtendedFileOptions>(name, Default::default())
+                .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            single_threaded: true,
+            dedup: true,
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_extracted, 3);
+        assert_eq!(outcome.files_deduplicated, 1);
+        assert_eq!(
+            read_to_string(outdir.join("dir/two.txt")).unwrap(),
+            "Same contents\n"
+        );
+        assert_eq!(
+            read_to_string(outdir.join("three.txt")).unwrap(),
+            "Other contents\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |name| std::fs::metadata(outdir.join(name)).unwrap().ino();
+            assert_eq!(inode("one.txt"), inode("dir/two.txt"));
+            assert_ne!(inode("one.txt"), inode("three.txt"));
+        }
+
+        // Hard links share their times, so files which are to have
+        // different times can't be linked.
+        let zf = td.path().join("times.zip");
+        let mut zip = ZipWriter::new(File::create(&zf).unwrap());
+        for (name, year) in [("one.txt", 2020), ("two.txt", 2021), ("three.txt", 2020)] {
+            zip.start_file(
+                name,
+                FileOptions::<ExtendedFileOptions>::default().last_modified_time(
+                    zip::DateTime::from_date_and_time(year, 1, 2, 3, 4, 6).unwrap(),
+                ),
+            )
+            .unwrap();
+            zip.write_all(b"Same contents\n").unwrap();
+        }
+        zip.finish().unwrap();
+        let outdir = td.path().join("times");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            single_threaded: true,
+            dedup: true,
+            preserve_mtimes: true,
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+        assert_eq!(outcome.files_deduplicated, 1);
+        let mtime = |name| {
+            std::fs::metadata(outdir.join(name))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+        assert_ne!(mtime("one.txt"), mtime("two.txt"));
+        assert_eq!(mtime("one.txt"), mtime("three.txt"));
+    }
+
+    fn create_nested_zip(path: &Path) {
+        let mut inner = Cursor::new(Vec::new());
+        create_zip(&mut inner, true, None);
+        let inner = inner.into_inner();
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, method) in [
+            ("stored.zip", zip::CompressionMethod::Stored),
+            ("deflated.zip", zip::CompressionMethod::Deflated),
+        ] {
+            let options: FileOptions<ExtendedFileOptions> =
+                FileOptions::default().compression_method(method);
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&inner).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_extract_nested() {
+        let td = tempdir().unwrap();
+        let zf = td.path().join("z.zip");
+        create_nested_zip(&zf);
+        for name in ["stored.zip", "deflated.zip"] {
+            let outdir = td.path().join(name);
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                ..Default::default()
+            };
+            UnzipEngine::for_file(zf.clone())
+                .unwrap()
+                .for_entry(name)
+                .unwrap()
+                .unzip(options)
+                .unwrap();
+            check_files_exist(&outdir, true);
+        }
+        assert!(UnzipEngine::for_file(zf)
+            .unwrap()
+            .for_entry("missing.zip")
+            .is_err());
+    }
+
+    /// A zip file of `count` entries each of a MiB of zeros, deflated.
+    fn zeros_zip(count: usize) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..count {
+            zip.start_file(
+                format!("zeros{i}"),
+                FileOptions::<ExtendedFileOptions>::default(),
+            )
+            .unwrap();
+            zip.write_all(&[0u8; 1024 * 1024]).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_max_total_size() {
+        let td = tempdir().unwrap();
+        // A small zip bomb: a zip file of zeros within another.
+        let inner = zeros_zip(4);
+        let mut outer = ZipWriter::new(Cursor::new(Vec::new()));
+        outer
+            .start_file("inner.zip", FileOptions::<ExtendedFileOptions>::default())
+            .unwrap();
+        outer.write_all(&inner).unwrap();
+        let outer = Arc::new(outer.finish().unwrap().into_inner());
+        for single_threaded in [false, true] {
+            let unzip = |max_total_size| {
+                let options = UnzipOptions {
+                    output_directory: Some(
+                        td.path()
+                            .join(format!("{single_threaded}-{max_total_size}")),
+                    ),
+                    single_threaded,
+                    max_total_size: Some(max_total_size),
+                    ..Default::default()
+                };
+                UnzipEngine::for_bytes(outer.clone())
+                    .unwrap()
+                    .for_entry("inner.zip")
+                    .unwrap()
+                    .unzip(options)
+            };
+            // The sizes the zip file records aren't trusted.
+            match unzip(3 * 1024 * 1024) {
+                Err(RipunzipErrors::TotalSizeExceeded { limit }) => {
+                    assert_eq!(limit, 3 * 1024 * 1024)
+                }
+                result => panic!("{result:?}"),
+            }
+            // Decompressing the inner zip file to get at it counts too.
+            let limit = 4 * 1024 * 1024 + inner.len() as u64;
+            assert!(matches!(
+                unzip(limit - 1),
+                Err(RipunzipErrors::TotalSizeExceeded { .. })
+            ));
+            let outcome = unzip(limit).unwrap();
+            assert_eq!(outcome.bytes_written, 4 * 1024 * 1024);
+        }
+
+        // As does writing the contents elsewhere.
+        let options = |max_total_size| UnzipOptions {
+            max_total_size: Some(max_total_size),
+            ..Default::default()
+        };
+        let mut contents = Vec::new();
+        match UnzipEngine::for_bytes(Arc::new(zeros_zip(1)))
+            .unwrap()
+            .unzip_to_writer(options(1024 * 1024 - 1), &mut contents)
+        {
+            Err(RipunzipErrors::TotalSizeExceeded { .. }) => {}
+            result => panic!("{result:?}"),
+        }
+        let zeros = Arc::new(zeros_zip(4));
+        match UnzipEngine::for_bytes(zeros.clone()).unwrap().concat_to(
+            options(3 * 1024 * 1024),
+            std::io::sink(),
+            |_| String::new(),
+            false,
+        ) {
+            Err(RipunzipErrors::TotalSizeExceeded { .. }) => {}
+            result => panic!("{result:?}"),
+        }
+        let outcome = UnzipEngine::for_bytes(zeros)
+            .unwrap()
+            .concat_to(
+                options(4 * 1024 * 1024),
+                std::io::sink(),
+                |_| String::new(),
+                false,
+            )
+            .unwrap();
+        assert_eq!(outcome.bytes_written, 4 * 1024 * 1024);
     }
 
-    fn check_files_exist(path: &Path, include_a_txt: bool) {
-        let a = path.join("test/a.txt");
-        let b = path.join("b.txt");
-        let c = path.join("test/c.txt");
-        if include_a_txt {
-            assert_eq!(read_to_string(a).unwrap(), "Contents of A\n");
-        } else {
-            assert!(!a.exists());
+    #[test]
+    fn test_cpu_and_wall_time() {
+        let td = tempdir().unwrap();
+        let options = UnzipOptions {
+            output_directory: Some(td.path().to_path_buf()),
+            ..Default::default()
+        };
+        let outcome = UnzipEngine::for_bytes(Arc::new(zeros_zip(4)))
+            .unwrap()
+            .unzip(options)
+            .unwrap();
+        assert!(!outcome.wall_time.is_zero());
+        if cfg!(target_os = "linux") {
+            assert!(!outcome.cpu_time.unwrap().is_zero());
         }
-        assert_eq!(read_to_string(b).unwrap(), "Contents of B\n");
-        assert_eq!(read_to_string(c).unwrap(), "Contents of C\n");
+
+        let mut total = UnzipOutcome {
+            cpu_time: Some(Duration::from_secs(1)),
+            wall_time: Duration::from_secs(2),
+            ..Default::default()
+        };
+        total.add(&UnzipOutcome::default());
+        assert_eq!(total.cpu_time, Some(Duration::from_secs(1)));
+        total.add(&total.clone());
+        assert_eq!(total.cpu_time, Some(Duration::from_secs(2)));
+        assert_eq!(total.wall_time, Duration::from_secs(2));
     }
 
     #[test]
-    #[ignore] // because the chdir changes global state
-    fn test_extract_no_path() {
-        run_with_and_without_a_filename_filter(|create_a, filename_filter| {
-            let td = tempdir().unwrap();
-            let zf = td.path().join("z.zip");
-            create_zip_file(&zf, create_a);
-            let old_dir = current_dir().unwrap();
-            set_current_dir(td.path()).unwrap();
-            let options = UnzipOptions {
-                output_directory: None,
-                password: None,
-                single_threaded: false,
-                filename_filter,
-                progress_reporter: Box::new(NullProgressReporter),
-            };
-            UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
-            set_current_dir(old_dir).unwrap();
-            check_files_exist(td.path(), create_a);
-        });
+    fn test_recursive_bomb_detected() {
+        let inner = Arc::new(zeros_zip(1));
+        let mut outer = ZipWriter::new(Cursor::new(Vec::new()));
+        outer
+            .start_file("inner.zip", FileOptions::<ExtendedFileOptions>::default())
+            .unwrap();
+        outer.write_all(&inner).unwrap();
+        let outer = Arc::new(outer.finish().unwrap().into_inner());
+        let nested = UnzipEngine::for_bytes(outer.clone())
+            .unwrap()
+            .for_entry("inner.zip")
+            .unwrap();
+        assert_eq!(nested.enclosing.len(), 1);
+        // Pretend the outer zip file was itself within the inner one, as
+        // if they were a quine, since a real one is hard to make.
+        let mut engine = UnzipEngine::for_bytes(outer).unwrap();
+        engine.enclosing = vec![UnzipEngine::for_bytes(inner)
+            .unwrap()
+            .zipfile
+            .archive_identity()
+            .unwrap()];
+        match engine.for_entry("inner.zip") {
+            Err(RipunzipErrors::RecursiveBombDetected { name }) => assert_eq!(name, "inner.zip"),
+            Err(e) => panic!("{e}"),
+            Ok(_) => panic!("Recursion not detected"),
+        }
     }
 
     #[test]
-    fn test_extract_with_path() {
+    #[cfg(feature = "http")]
+    fn test_extract_from_server() {
         run_with_and_without_a_filename_filter(|create_a, filename_filter| {
             let td = tempdir().unwrap();
-            let zf = td.path().join("z.zip");
-            create_zip_file(&zf, create_a);
+            let mut zip_data = Cursor::new(Vec::new());
+            create_zip(&mut zip_data, create_a, None);
+            let body = zip_data.into_inner();
+            println!("Whole zip:");
+            hexdump::hexdump(&body);
+
+            let server = Server::run();
+            set_up_server(&server, body, ServerType::Ranges);
+
             let outdir = td.path().join("outdir");
             let options = UnzipOptions {
                 output_directory: Some(outdir.clone()),
@@ -658,75 +7597,549 @@ mod tests {
                 single_threaded: false,
                 filename_filter,
                 progress_reporter: Box::new(NullProgressReporter),
+                ..Default::default()
             };
-            UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
+            UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {})
+                .unwrap()
+                .unzip(options)
+                .unwrap();
             check_files_exist(&outdir, create_a);
         });
     }
 
     #[test]
-    fn test_extract_encrypted_with_path() {
-        run_with_and_without_a_filename_filter(|create_a, filename_filter| {
-            let td = tempdir().unwrap();
-            let zf = td.path().join("z.zip");
-            create_encrypted_zip_file(&zf, create_a);
-            let outdir = td.path().join("outdir");
-            let options = UnzipOptions {
-                output_directory: Some(outdir.clone()),
-                password: Some("1Password".to_string()),
-                single_threaded: false,
-                filename_filter,
-                progress_reporter: Box::new(NullProgressReporter),
-            };
-            UnzipEngine::for_file(zf).unwrap().unzip(options).unwrap();
-            check_files_exist(&outdir, create_a);
-        });
+    #[cfg(feature = "http")]
+    fn test_uri_fallback_temp_dir() {
+        let td = tempdir().unwrap();
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let server = Server::run();
+        set_up_server(&server, zip_data.into_inner(), ServerType::NoContentLength);
+        let temp_dir = td.path().join("spill");
+        std::fs::create_dir(&temp_dir).unwrap();
+        let uri_options = crate::UriOptions {
+            temp_dir: Some(temp_dir.clone()),
+            ..Default::default()
+        };
+        let engine = UnzipEngine::for_uri_with_options(
+            &server.url("/foo").to_string(),
+            None,
+            &uri_options,
+            || {},
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_dir(&temp_dir).unwrap().count(), 1);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        engine.unzip(options).unwrap();
+        check_files_exist(&outdir, true);
+        assert_eq!(std::fs::read_dir(&temp_dir).unwrap().count(), 0);
     }
 
     #[test]
-    fn test_list() {
+    fn test_archive_comment() {
+        let zip_with_comment = |comment: &[u8]| {
+            let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+            zip.set_raw_comment(comment.into()).unwrap();
+            zip.start_file::<_, ExtendedFileOptions>("a.txt", Default::default())
+                .unwrap();
+            zip.finish().unwrap().into_inner()
+        };
         let td = tempdir().unwrap();
         let zf = td.path().join("z.zip");
+        std::fs::write(&zf, zip_with_comment(b"schema=2")).unwrap();
+        let engine = UnzipEngine::for_file(zf.clone()).unwrap();
+        assert_eq!(engine.archive_comment().as_deref(), Some("schema=2"));
+
+        std::fs::write(&zf, zip_with_comment(b"\xff\xfe")).unwrap();
+        let engine = UnzipEngine::for_file(zf.clone()).unwrap();
+        assert_eq!(engine.archive_comment(), None);
+        assert_eq!(engine.archive_comment_bytes(), b"\xff\xfe");
+
         create_zip_file(&zf, true);
-        let filenames: HashSet<_> = UnzipEngine::for_file(zf).unwrap().list().unwrap().collect();
-        assert_eq!(
-            filenames,
-            ["test/", "test/a.txt", "b.txt", "test/c.txt"]
-                .into_iter()
-                .map(|s| s.to_string())
-                .collect()
+        assert_eq!(UnzipEngine::for_file(zf).unwrap().archive_comment(), None);
+
+        #[cfg(feature = "http")]
+        {
+            let server = Server::run();
+            set_up_server(&server, zip_with_comment(b"schema=3"), ServerType::Ranges);
+            let engine =
+                UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap();
+            assert_eq!(engine.archive_comment().as_deref(), Some("schema=3"));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_uri_mirrors() {
+        use crate::UriOptions;
+        use httptest::{matchers::request::method_path, responders::status_code, Expectation};
+        let td = tempdir().unwrap();
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let zip_data = zip_data.into_inner();
+        let good = Server::run();
+        set_up_server(&good, zip_data.clone(), ServerType::Ranges);
+        // Claims to have the zip file, but fails to serve it.
+        let broken = Server::run();
+        broken.expect(
+            Expectation::matching(method_path("HEAD", "/foo"))
+                .times(..)
+                .respond_with(RangeAwareResponse::new(
+                    200,
+                    RangeAwareResponseType::LengthOnly(zip_data.len()),
+                )),
+        );
+        broken.expect(
+            Expectation::matching(method_path("GET", "/foo"))
+                .times(1..)
+                .respond_with(status_code(500)),
+        );
+        let uri_options = UriOptions {
+            mirrors: vec![
+                // Nothing listens on port 1.
+                "http://127.0.0.1:1/foo".to_string(),
+                good.url("/foo").to_string(),
+            ],
+            ..Default::default()
+        };
+        struct EventRecorder(Arc<Mutex<Vec<NetworkEvent>>>);
+        impl UnzipProgressReporter for EventRecorder {
+            fn network_event(&self, event: &NetworkEvent) {
+                self.0.lock().unwrap().push(event.clone());
+            }
+        }
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            progress_reporter: Box::new(EventRecorder(events.clone())),
+            ..Default::default()
+        };
+        UnzipEngine::for_uri_with_options(
+            &broken.url("/foo").to_string(),
+            None,
+            &uri_options,
+            || {},
+        )
+        .unwrap()
+        .unzip(options)
+        .unwrap();
+        check_files_exist(&outdir, true);
+        let events = events.lock().unwrap();
+        // Whichever mirror's turn it is, the data comes from the good one.
+        assert!(events.contains(&NetworkEvent::StreamOpened {
+            uri: good.url("/foo").to_string(),
+            offset: 0,
+        }));
+
+        // A mirror which fails the HEAD request is ignored, whatever length
+        // it gives.
+        let missing = Server::run();
+        missing.expect(
+            Expectation::matching(method_path("HEAD", "/foo"))
+                .times(..)
+                .respond_with(RangeAwareResponse::new(
+                    404,
+                    RangeAwareResponseType::LengthOnly(4),
+                )),
+        );
+        let uri_options = UriOptions {
+            mirrors: vec![good.url("/foo").to_string()],
+            ..Default::default()
+        };
+        let engine = UnzipEngine::for_uri_with_options(
+            &missing.url("/foo").to_string(),
+            None,
+            &uri_options,
+            || {},
         )
+        .unwrap();
+        assert_eq!(engine.zip_length(), zip_data.len() as u64);
+
+        let different = Server::run();
+        set_up_server(&different, b"PK\x05\x06".to_vec(), ServerType::Ranges);
+        let uri_options = UriOptions {
+            mirrors: vec![different.url("/foo").to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            UnzipEngine::for_uri_with_options(
+                &good.url("/foo").to_string(),
+                None,
+                &uri_options,
+                || {},
+            ),
+            Err(RipunzipErrors::MirrorLengthMismatch { .. })
+        ));
     }
 
     #[test]
-    fn test_extract_from_server() {
-        run_with_and_without_a_filename_filter(|create_a, filename_filter| {
+    #[cfg(feature = "http")]
+    fn test_uri_cookies() {
+        use crate::{Cookies, UriOptions};
+        use httptest::{
+            all_of,
+            matchers::{contains, request},
+            Expectation,
+        };
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let zip_data = zip_data.into_inner();
+        for from_jar in [false, true] {
             let td = tempdir().unwrap();
-            let mut zip_data = Cursor::new(Vec::new());
-            create_zip(&mut zip_data, create_a, None);
-            let body = zip_data.into_inner();
-            println!("Whole zip:");
-            hexdump::hexdump(&body);
-
             let server = Server::run();
-            set_up_server(&server, body, ServerType::Ranges);
-
+            // Without the cookie, there's no expectation to match, so the
+            // request fails.
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("HEAD", "/foo"),
+                    request::headers(contains(("cookie", "session=abc"))),
+                ])
+                .times(..)
+                .respond_with(RangeAwareResponse::new(
+                    200,
+                    RangeAwareResponseType::LengthOnly(zip_data.len()),
+                )),
+            );
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", "/foo"),
+                    request::headers(contains(("cookie", "session=abc"))),
+                ])
+                .times(1..)
+                .respond_with(RangeAwareResponse::new(
+                    206,
+                    RangeAwareResponseType::Body {
+                        body: zip_data.clone().into(),
+                        expected_range: None,
+                    },
+                )),
+            );
+            let uri = server.url("/foo").to_string();
+            let cookies = if from_jar {
+                let jar = reqwest::cookie::Jar::default();
+                jar.add_cookie_str("session=abc", &uri.parse().unwrap());
+                Cookies::from_jar(Arc::new(jar))
+            } else {
+                Cookies::from_pairs(&uri, &[("session".to_string(), "abc".to_string())]).unwrap()
+            };
             let outdir = td.path().join("outdir");
             let options = UnzipOptions {
                 output_directory: Some(outdir.clone()),
-                password: None,
-                single_threaded: false,
-                filename_filter,
-                progress_reporter: Box::new(NullProgressReporter),
+                ..Default::default()
             };
-            UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {})
+            let uri_options = UriOptions {
+                cookies,
+                ..Default::default()
+            };
+            UnzipEngine::for_uri_with_options(&uri, None, &uri_options, || {})
                 .unwrap()
                 .unzip(options)
                 .unwrap();
-            check_files_exist(&outdir, create_a);
-        });
+            check_files_exist(&outdir, true);
+        }
+    }
+
+    /// Wrap `data` in gzip format, without compressing it.
+    #[cfg(feature = "http")]
+    fn gzip_stored(data: &[u8]) -> Vec<u8> {
+        let mut gzip = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+        // A single, final, uncompressed deflate block.
+        let len = data.len() as u16;
+        gzip.push(1);
+        gzip.extend(len.to_le_bytes());
+        gzip.extend((!len).to_le_bytes());
+        gzip.extend(data);
+        gzip.extend(crc32fast::hash(data).to_le_bytes());
+        gzip.extend((data.len() as u32).to_le_bytes());
+        gzip
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_uri_content_encoding() {
+        use httptest::{matchers::request::method_path, responders::status_code, Expectation};
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let gzipped = gzip_stored(&zip_data.into_inner());
+        let serve = |encoding: &str| {
+            let server = Server::run();
+            // Both claim to support ranges, but the ranges would be of the
+            // gzipped data.
+            server.expect(
+                Expectation::matching(method_path("HEAD", "/foo"))
+                    .times(..)
+                    .respond_with(
+                        status_code(200)
+                            .insert_header("Accept-Ranges", "bytes")
+                            .insert_header("Content-Encoding", encoding)
+                            .insert_header("Content-Length", gzipped.len()),
+                    ),
+            );
+            server.expect(
+                Expectation::matching(method_path("GET", "/foo"))
+                    .times(..)
+                    .respond_with(
+                        status_code(200)
+                            .insert_header("Accept-Ranges", "bytes")
+                            .insert_header("Content-Encoding", encoding)
+                            .body(gzipped.clone()),
+                    ),
+            );
+            server
+        };
+
+        let server = serve("gzip");
+        let td = tempdir().unwrap();
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        let engine = UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap();
+        engine.unzip(options).unwrap();
+        check_files_exist(&outdir, true);
+
+        let server = serve("br");
+        assert!(matches!(
+            UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}),
+            Err(RipunzipErrors::UnsupportedUri { reason, .. }) if reason.contains("br")
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_uri_inconsistent_archive() {
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let mut body = zip_data.into_inner();
+        // As if the server padded the zip file.
+        body.extend([0u8; 100]);
+        let server = Server::run();
+        set_up_server(&server, body, ServerType::Ranges);
+        assert!(matches!(
+            UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}),
+            Err(RipunzipErrors::InconsistentArchive { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_uri_source_changed() {
+        use httptest::{
+            matchers::{contains, request},
+            responders::status_code,
+            Expectation,
+        };
+        let td = tempdir().unwrap();
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let zip_data = zip_data.into_inner();
+        let mut server = Server::run();
+        let expect_head = |server: &Server| {
+            server.expect(
+                Expectation::matching(request::method_path("HEAD", "/foo"))
+                    .times(..)
+                    .respond_with(
+                        status_code(200)
+                            .insert_header("Content-Length", zip_data.len().to_string())
+                            .insert_header("Accept-Ranges", "bytes")
+                            .insert_header("ETag", "\"v1\""),
+                    ),
+            )
+        };
+        expect_head(&server);
+        server.expect(
+            Expectation::matching(request::headers(contains(("if-range", "\"v1\""))))
+                .times(1..)
+                .respond_with(RangeAwareResponse::new(
+                    206,
+                    RangeAwareResponseType::Body {
+                        body: zip_data.clone().into(),
+                        expected_range: None,
+                    },
+                )),
+        );
+        let engine = UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap();
+
+        // Having read the central directory, the zip file is replaced, so
+        // the server sends the whole of the new one.
+        server.verify_and_clear();
+        expect_head(&server);
+        let mut new_zip_data = Cursor::new(Vec::new());
+        create_zip(&mut new_zip_data, false, None);
+        server.expect(
+            Expectation::matching(request::headers(contains(("if-range", "\"v1\""))))
+                .times(1..)
+                .respond_with(
+                    status_code(200)
+                        .insert_header("ETag", "\"v2\"")
+                        .body(new_zip_data.into_inner()),
+                ),
+        );
+        let options = UnzipOptions {
+            output_directory: Some(td.path().join("outdir")),
+            ..Default::default()
+        };
+        let result = engine.unzip(options);
+        assert!(
+            matches!(result, Err(RipunzipErrors::SourceChanged { ref uri }) if uri.ends_with("/foo")),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_uri_download_budget() {
+        let td = tempdir().unwrap();
+        // Four stored files of 512KiB each, so they take two 1MiB blocks.
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::<()>::default().compression_method(CompressionMethod::Stored);
+        for name in ["a", "b", "c", "d"] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&vec![name.as_bytes()[0]; 512 * 1024])
+                .unwrap();
+        }
+        let zip_data = zip.finish().unwrap().into_inner();
+        let server = Server::run();
+        set_up_server(&server, zip_data, ServerType::Ranges);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            single_threaded: true,
+            download_budget: Some(1536 * 1024),
+            ..Default::default()
+        };
+        let result = UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {})
+            .unwrap()
+            .unzip(options);
+        match result {
+            Err(RipunzipErrors::BudgetExceeded { downloaded, budget }) => {
+                assert_eq!(budget, 1536 * 1024);
+                assert!(downloaded >= 1024 * 1024 && downloaded <= budget);
+            }
+            _ => panic!("{result:?}"),
+        }
+        // What was extracted before then is kept.
+        assert_eq!(std::fs::read(outdir.join("a")).unwrap().len(), 512 * 1024);
+        assert!(!outdir.join("d").exists());
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_uri_range_chunk_size() {
+        use httptest::{
+            all_of,
+            matchers::{contains, key, request},
+            Expectation,
+        };
+        let td = tempdir().unwrap();
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let zip_data = zip_data.into_inner();
+        let mut server = Server::run();
+        set_up_server(&server, zip_data.clone(), ServerType::Ranges);
+        let engine = UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap();
+        server.verify_and_clear();
+        // Rather than one request for all the entries, there are several.
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/foo"),
+                request::headers(contains(key("range"))),
+            ])
+            .times(2..)
+            .respond_with(RangeAwareResponse::new(
+                206,
+                RangeAwareResponseType::Body {
+                    body: zip_data.into(),
+                    expected_range: None,
+                },
+            )),
+        );
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            single_threaded: true,
+            range_chunk_size: Some(64),
+            ..Default::default()
+        };
+        engine.unzip(options).unwrap();
+        server.verify_and_clear();
+        assert_eq!(
+            std::fs::read_to_string(outdir.join("test/c.txt")).unwrap(),
+            "Contents of C\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_uri_list_then_unzip() {
+        use httptest::{
+            all_of,
+            matchers::{contains, matches, request},
+            Expectation,
+        };
+        let td = tempdir().unwrap();
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let zip_data = zip_data.into_inner();
+        let mut server = Server::run();
+        set_up_server(&server, zip_data.clone(), ServerType::Ranges);
+        let engine = UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap();
+        // From now on, the only request should be for the whole zip file,
+        // to extract it; the end of it isn't fetched again.
+        server.verify_and_clear();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/foo"),
+                request::headers(contains(("range", matches("^bytes=0-")))),
+            ])
+            .times(1)
+            .respond_with(RangeAwareResponse::new(
+                206,
+                RangeAwareResponseType::Body {
+                    body: zip_data.into(),
+                    expected_range: None,
+                },
+            )),
+        );
+        let names: Vec<_> = engine.list().unwrap().collect();
+        assert_eq!(names.len(), 4);
+        let outdir = td.path().join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        engine.unzip(options).unwrap();
+        check_files_exist(&outdir, true);
+        server.verify_and_clear();
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_uri_not_found() {
+        let server = Server::run();
+        server.expect(
+            httptest::Expectation::matching(httptest::matchers::any())
+                .times(..)
+                .respond_with(httptest::responders::status_code(404).body("Not found")),
+        );
+        let result = UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {});
+        assert!(matches!(result, Err(RipunzipErrors::ReqwestErrorr(_))));
+    }
+
+    #[test]
+    #[cfg(all(feature = "http", not(feature = "ssh")))]
+    fn test_sftp_needs_feature() {
+        let result = UnzipEngine::for_uri("sftp://example.com/foo.zip", None, || {});
+        assert!(matches!(result, Err(RipunzipErrors::UnsupportedUri { .. })));
     }
 
+    #[cfg(feature = "http")]
     fn unzip_sample_zip(zip_params: ZipParams, server_type: ServerType) {
         let td = tempdir().unwrap();
         let zip_data = ripunzip_test_utils::get_sample_zip(&zip_params);
@@ -741,6 +8154,7 @@ mod tests {
             single_threaded: false,
             filename_filter: None,
             progress_reporter: Box::new(NullProgressReporter),
+            ..Default::default()
         };
         UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {})
             .unwrap()
@@ -749,6 +8163,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "http")]
     fn test_extract_biggish_zip_from_ranges_server() {
         unzip_sample_zip(
             ZipParams::new(FileSizes::Variable, 15, zip::CompressionMethod::Deflated),
@@ -757,6 +8172,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "http")]
     fn test_small_zip_from_ranges_server() {
         unzip_sample_zip(
             ZipParams::new(FileSizes::Variable, 3, zip::CompressionMethod::Deflated),
@@ -765,6 +8181,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "http")]
     fn test_small_zip_from_no_range_server() {
         unzip_sample_zip(
             ZipParams::new(FileSizes::Variable, 3, zip::CompressionMethod::Deflated),
@@ -773,10 +8190,134 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "http")]
     fn test_small_zip_from_no_content_length_server() {
         unzip_sample_zip(
             ZipParams::new(FileSizes::Variable, 3, zip::CompressionMethod::Deflated),
             ServerType::NoContentLength,
         )
     }
+
+    #[test]
+    #[cfg(feature = "signature")]
+    fn test_verify_signature() {
+        use ed25519_dalek::SigningKey;
+        use sha2::{Digest, Sha512};
+
+        let td = tempdir().unwrap();
+        let mut zip_data = Cursor::new(Vec::new());
+        create_zip(&mut zip_data, true, None);
+        let zip_data = zip_data.into_inner();
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let signature = signing_key
+            .sign_prehashed(Sha512::new().chain_update(&zip_data), None)
+            .unwrap()
+            .to_bytes();
+        let config = |signature: &[u8]| SignatureConfig {
+            public_key: signing_key.verifying_key().to_bytes(),
+            signature: SignatureSource::Bytes(signature.to_vec()),
+        };
+        let mut tampered = signature;
+        tampered[0] ^= 1;
+        let zip_data = Arc::new(zip_data);
+        let unzip = |engine: UnzipEngine, name: &str, signature: &[u8]| {
+            let outdir = td.path().join(name);
+            let options = UnzipOptions {
+                output_directory: Some(outdir.clone()),
+                verify_signature: Some(config(signature)),
+                ..Default::default()
+            };
+            engine.unzip(options).map(|_| outdir)
+        };
+
+        let outdir = unzip(
+            UnzipEngine::for_bytes(zip_data.clone()).unwrap(),
+            "good",
+            &signature,
+        )
+        .unwrap();
+        check_files_exist(&outdir, true);
+        match unzip(
+            UnzipEngine::for_bytes(zip_data.clone()).unwrap(),
+            "bad",
+            &tampered,
+        ) {
+            Err(RipunzipErrors::SignatureInvalid { .. }) => {}
+            result => panic!("{result:?}"),
+        }
+        assert!(!td.path().join("bad").join("b.txt").exists());
+
+        let zf = td.path().join("z.zip");
+        std::fs::write(&zf, &*zip_data).unwrap();
+        let outdir = unzip(
+            UnzipEngine::for_file(zf.clone()).unwrap(),
+            "file",
+            &signature,
+        )
+        .unwrap();
+        check_files_exist(&outdir, true);
+
+        // Writing the contents elsewhere checks the signature too.
+        let options = |signature: &[u8]| UnzipOptions {
+            verify_signature: Some(config(signature)),
+            only_entries: Some(HashSet::from(["b.txt".to_string()])),
+            ..Default::default()
+        };
+        let mut contents = Vec::new();
+        UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip_to_writer(options(&signature), &mut contents)
+            .unwrap();
+        assert!(!contents.is_empty());
+        match UnzipEngine::for_file(zf.clone())
+            .unwrap()
+            .unzip_to_writer(options(&tampered), &mut Vec::new())
+        {
+            Err(RipunzipErrors::SignatureInvalid { .. }) => {}
+            result => panic!("{result:?}"),
+        }
+        match UnzipEngine::for_file(zf.clone()).unwrap().concat_to(
+            options(&tampered),
+            Vec::new(),
+            |_| String::new(),
+            false,
+        ) {
+            Err(RipunzipErrors::SignatureInvalid { .. }) => {}
+            result => panic!("{result:?}"),
+        }
+        let mut engine = UnzipEngine::for_file(zf).unwrap();
+        assert!(matches!(
+            engine.verify_signature(&config(&tampered)),
+            Err(RipunzipErrors::SignatureInvalid { .. })
+        ));
+        engine.verify_signature(&config(&signature)).unwrap();
+        let files = engine.extract_to_memory(&UnzipSomeFilter, None).unwrap();
+        assert_eq!(files.len(), 2);
+
+        #[cfg(feature = "http")]
+        {
+            let server = Server::run();
+            set_up_server(&server, zip_data.to_vec(), ServerType::Ranges);
+            let engine =
+                || UnzipEngine::for_uri(&server.url("/foo").to_string(), None, || {}).unwrap();
+            let outdir = unzip(engine(), "good-uri", &signature).unwrap();
+            check_files_exist(&outdir, true);
+            match unzip(engine(), "bad-uri", &tampered) {
+                Err(RipunzipErrors::SignatureInvalid { .. }) => {}
+                result => panic!("{result:?}"),
+            }
+            assert!(!td.path().join("bad-uri").join("b.txt").exists());
+            // The downloaded copy goes in the given temporary directory.
+            let options = UnzipOptions {
+                output_directory: Some(td.path().join("missing-temp-dir")),
+                verify_signature: Some(config(&signature)),
+                temp_dir: Some(td.path().join("missing")),
+                ..Default::default()
+            };
+            match engine().unzip(options) {
+                Err(RipunzipErrors::IOError(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+                result => panic!("{result:?}"),
+            }
+        }
+    }
 }
@@ -0,0 +1,131 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use zip::DateTime;
+
+use crate::RipunzipErrors;
+
+/// A time to give an extracted file, relative to the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(not(unix), allow(dead_code))]
+pub(crate) struct FileTime {
+    seconds: i64,
+    nanoseconds: u32,
+}
+
+impl FileTime {
+    pub(crate) fn from_unix(seconds: i64, nanoseconds: u32) -> Self {
+        Self {
+            seconds,
+            nanoseconds,
+        }
+    }
+}
+
+impl From<DateTime> for FileTime {
+    fn from(time: DateTime) -> Self {
+        Self::from_unix(unix_time(time), 0)
+    }
+}
+
+/// Seconds since the Unix epoch of a time stored in a zip file. Zip files
+/// don't record the time zone, so it's taken to be UTC.
+fn unix_time(time: DateTime) -> i64 {
+    // Howard Hinnant's days_from_civil.
+    let (month, day) = (time.month() as i64, time.day() as i64);
+    let year = time.year() as i64 - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    days * 86400 + time.hour() as i64 * 3600 + time.minute() as i64 * 60 + time.second() as i64
+}
+
+/// Set the modification time of `path`, or of the symbolic link if it is
+/// one, to `modified`, and its access time to `accessed` if given, leaving
+/// it alone otherwise. Only supported on Unix; elsewhere this does nothing.
+pub(crate) fn set_times(
+    path: &Path,
+    modified: FileTime,
+    accessed: Option<FileTime>,
+) -> Result<(), RipunzipErrors> {
+    #[cfg(unix)]
+    {
+        use rustix::fs::{AtFlags, Timespec, Timestamps, CWD, UTIME_OMIT};
+        let timespec = |time: FileTime| Timespec {
+            tv_sec: time.seconds,
+            tv_nsec: time.nanoseconds as _,
+        };
+        let times = Timestamps {
+            last_access: accessed.map(timespec).unwrap_or(Timespec {
+                tv_sec: 0,
+                tv_nsec: UTIME_OMIT,
+            }),
+            last_modification: timespec(modified),
+        };
+        rustix::fs::utimensat(CWD, path, &times, AtFlags::SYMLINK_NOFOLLOW).map_err(|e| {
+            RipunzipErrors::IOErrorWithContext {
+                context: format!("Failed to set the modification time of {}", path.display()),
+                source: e.into(),
+            }
+        })?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, modified, accessed);
+    Ok(())
+}
+
+/// The directories extracted with modification times from the zip file,
+/// to be set again once all the files have been written into them.
+#[derive(Default)]
+pub(crate) struct DirectoryMtimes(Mutex<Vec<(PathBuf, FileTime)>>);
+
+impl DirectoryMtimes {
+    pub(crate) fn record(&self, path: PathBuf, time: FileTime) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((path, time))
+    }
+
+    /// Set the modification time of each directory, deepest first, which
+    /// has no effect on the directories containing it.
+    pub(crate) fn restore(&self) -> Result<(), RipunzipErrors> {
+        let mut directories =
+            std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner));
+        directories.sort_by_key(|(path, _)| std::cmp::Reverse(path.components().count()));
+        for (path, time) in directories {
+            set_times(&path, time, None)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zip::DateTime;
+
+    use super::unix_time;
+
+    #[test]
+    fn test_unix_time() {
+        let time = |year, month, day, hour, minute, second| {
+            unix_time(DateTime::from_date_and_time(year, month, day, hour, minute, second).unwrap())
+        };
+        assert_eq!(time(1980, 1, 1, 0, 0, 0), 315532800);
+        assert_eq!(time(2000, 2, 29, 12, 0, 0), 951825600);
+        assert_eq!(time(2020, 1, 2, 3, 4, 6), 1577934246);
+        assert_eq!(time(2107, 12, 31, 23, 59, 58), 4354819198);
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use crate::RipunzipErrors;
+
+use super::{
+    EntryInfo, ExtractionPlan, NetworkEvent, PlannedAction, SkipReason, UnzipEngine, UnzipOptions,
+    UnzipOutcome, UnzipProgressReporter, ZipEntryMetadata,
+};
+
+/// Unzips several zip files, reporting their combined progress to a single
+/// [`UnzipProgressReporter`]. The zip files are unzipped at the same time,
+/// as are the entries within each of them, all on rayon's global thread
+/// pool, so no matter how many zip files there are, no additional threads
+/// are created.
+#[derive(Default)]
+pub struct MultiUnzip<'a, 'b> {
+    jobs: Vec<(UnzipEngine, UnzipOptions<'a, 'b>)>,
+}
+
+/// Passes progress for one zip file on to the overall progress reporter,
+/// except for the notifications which we instead make once for all the zip
+/// files.
+struct ForwardingProgressReporter<'r>(&'r dyn UnzipProgressReporter);
+
+impl UnzipProgressReporter for ForwardingProgressReporter<'_> {
+    fn extraction_starting(&self, entry: &EntryInfo) {
+        self.0.extraction_starting(entry)
+    }
+
+    fn extraction_finished(&self, display_name: &str) {
+        self.0.extraction_finished(display_name)
+    }
+
+    fn central_directory_read_starting(&self) {
+        self.0.central_directory_read_starting()
+    }
+
+    fn central_directory_read_finished(&self, entry_count: usize) {
+        self.0.central_directory_read_finished(entry_count)
+    }
+
+    fn metadata_ready(&self, entries: &[ZipEntryMetadata]) {
+        self.0.metadata_ready(entries)
+    }
+
+    fn plan_ready(&self, plan: &ExtractionPlan) {
+        self.0.plan_ready(plan)
+    }
+
+    fn bytes_extracted(&self, count: u64) {
+        self.0.bytes_extracted(count)
+    }
+
+    fn entry_skipped(&self, name: &str, reason: SkipReason) {
+        self.0.entry_skipped(name, reason)
+    }
+
+    fn extraction_planned(&self, name: &str, action: PlannedAction) {
+        self.0.extraction_planned(name, action)
+    }
+
+    fn network_event(&self, event: &NetworkEvent) {
+        self.0.network_event(event)
+    }
+
+    // MultiUnzip::unzip makes these once for all the zip files.
+    fn total_bytes_expected(&self, _expected: u64) {}
+
+    fn all_finished(&self, _outcome: &UnzipOutcome) {}
+}
+
+impl<'a, 'b> MultiUnzip<'a, 'b> {
+    /// Create an empty set of zip files to unzip.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a zip file to be unzipped with the given options. The progress
+    /// reporter within the options is ignored; progress is instead reported
+    /// to the reporter given to [`MultiUnzip::unzip`].
+    pub fn add(&mut self, engine: UnzipEngine, options: UnzipOptions<'a, 'b>) {
+        self.jobs.push((engine, options));
+    }
+
+    /// The total compressed length of all the zip files.
+    pub fn zip_length(&self) -> u64 {
+        self.jobs
+            .iter()
+            .map(|(engine, _)| engine.zip_length())
+            .sum()
+    }
+
+    /// Unzip all the zip files at once. If any fails, those which haven't
+    /// started yet aren't unzipped, and the first error is returned once
+    /// those already under way have finished.
+    pub fn unzip(
+        self,
+        progress_reporter: &dyn UnzipProgressReporter,
+    ) -> Result<UnzipOutcome, RipunzipErrors> {
+        let started = Instant::now();
+        progress_reporter.total_bytes_expected(self.zip_length());
+        let outcomes = self
+            .jobs
+            .into_par_iter()
+            .map(|(engine, options)| {
+                engine.unzip(UnzipOptions {
+                    progress_reporter: Box::new(ForwardingProgressReporter(progress_reporter)),
+                    ..options
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut outcome = UnzipOutcome::default();
+        for job_outcome in &outcomes {
+            outcome.add(job_outcome);
+        }
+        outcome.wall_time = started.elapsed();
+        progress_reporter.all_finished(&outcome);
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use ripunzip_test_utils::{get_sample_zip, FileSizes, ZipParams};
+    use tempfile::tempdir;
+
+    use super::MultiUnzip;
+    use crate::{PlannedAction, UnzipEngine, UnzipOptions, UnzipOutcome, UnzipProgressReporter};
+
+    #[derive(Default)]
+    struct RecordingReporter {
+        totals_expected: Mutex<Vec<u64>>,
+        bytes_extracted: Mutex<u64>,
+        finished: Mutex<Option<UnzipOutcome>>,
+        planned: Mutex<Vec<String>>,
+    }
+
+    impl UnzipProgressReporter for RecordingReporter {
+        fn total_bytes_expected(&self, expected: u64) {
+            self.totals_expected.lock().unwrap().push(expected);
+        }
+
+        fn bytes_extracted(&self, count: u64) {
+            *self.bytes_extracted.lock().unwrap() += count;
+        }
+
+        fn extraction_planned(&self, name: &str, _action: PlannedAction) {
+            self.planned.lock().unwrap().push(name.to_string());
+        }
+
+        fn all_finished(&self, outcome: &UnzipOutcome) {
+            *self.finished.lock().unwrap() = Some(outcome.clone());
+        }
+    }
+
+    #[test]
+    fn test_multi_unzip() {
+        let td = tempdir().unwrap();
+        let mut multi_unzip = MultiUnzip::new();
+        for (i, num_files) in [3, 5].into_iter().enumerate() {
+            let zf = td.path().join(format!("{i}.zip"));
+            let params = ZipParams::new(
+                FileSizes::Variable,
+                num_files,
+                zip::CompressionMethod::Deflated,
+            );
+            std::fs::write(&zf, get_sample_zip(&params)).unwrap();
+            let options = UnzipOptions {
+                output_directory: Some(td.path().join(format!("out{i}"))),
+                ..Default::default()
+            };
+            multi_unzip.add(UnzipEngine::for_file(zf).unwrap(), options);
+        }
+        let total = multi_unzip.zip_length();
+        let reporter = RecordingReporter::default();
+        let outcome = multi_unzip.unzip(&reporter).unwrap();
+        assert_eq!(outcome.files_extracted, 8);
+        assert!(!outcome.wall_time.is_zero());
+        assert_eq!(*reporter.totals_expected.lock().unwrap(), vec![total]);
+        assert!(*reporter.bytes_extracted.lock().unwrap() <= total);
+        assert_eq!(
+            reporter
+                .finished
+                .lock()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .files_extracted,
+            8
+        );
+        assert!(td.path().join("out1/4.txt").exists());
+    }
+
+    #[test]
+    fn test_multi_unzip_dry_run() {
+        let td = tempdir().unwrap();
+        let mut multi_unzip = MultiUnzip::new();
+        for i in 0..2 {
+            let zf = td.path().join(format!("{i}.zip"));
+            let params = ZipParams::new(FileSizes::Variable, 2, zip::CompressionMethod::Stored);
+            std::fs::write(&zf, get_sample_zip(&params)).unwrap();
+            let options = UnzipOptions {
+                output_directory: Some(td.path().join(format!("out{i}"))),
+                dry_run: true,
+                ..Default::default()
+            };
+            multi_unzip.add(UnzipEngine::for_file(zf).unwrap(), options);
+        }
+        let reporter = RecordingReporter::default();
+        multi_unzip.unzip(&reporter).unwrap();
+        // Each zip file's plan reaches the overall reporter.
+        assert_eq!(reporter.planned.lock().unwrap().len(), 4);
+        assert!(!td.path().join("out0").exists());
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt::Display;
+#[cfg(feature = "http")]
+use std::sync::{
+    mpsc::{channel, Sender},
+    Mutex, PoisonError,
+};
+
+#[cfg(feature = "http")]
+use super::UnzipProgressReporter;
+
+/// Something which happened while fetching a zip file over HTTP(S), as
+/// reported to [`UnzipProgressReporter::network_event`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NetworkEvent {
+    /// A stream of data starting at `offset` was opened from `uri`.
+    StreamOpened { uri: String, offset: u64 },
+    /// A stream failed at `offset`, so another is being opened there, for
+    /// the `attempt`th time, counting from one.
+    Retry {
+        offset: u64,
+        attempt: usize,
+        error: String,
+    },
+    /// A request to `uri` for data from `offset` failed, so the next mirror
+    /// is being tried.
+    Failover {
+        uri: String,
+        offset: u64,
+        error: String,
+    },
+    /// A stream which had reached `offset` was closed, since the data
+    /// needed next is elsewhere.
+    StreamClosed { offset: u64 },
+}
+
+impl Display for NetworkEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkEvent::StreamOpened { uri, offset } => {
+                write!(f, "Opened stream from {uri} at byte {offset}")
+            }
+            NetworkEvent::Retry {
+                offset,
+                attempt,
+                error,
+            } => write!(
+                f,
+                "Stream failed at byte {offset} ({error}); retrying, attempt {attempt}"
+            ),
+            NetworkEvent::Failover { uri, offset, error } => write!(
+                f,
+                "Request to {uri} for byte {offset} failed ({error}); trying another mirror"
+            ),
+            NetworkEvent::StreamClosed { offset } => write!(f, "Closed stream at byte {offset}"),
+        }
+    }
+}
+
+/// Where the HTTP(S) reader sends [`NetworkEvent`]s, if anyone's listening.
+#[cfg(feature = "http")]
+#[derive(Default)]
+pub(crate) struct NetworkEventSink(Mutex<Option<Sender<NetworkEvent>>>);
+
+#[cfg(feature = "http")]
+impl NetworkEventSink {
+    pub(crate) fn send(&self, event: NetworkEvent) {
+        if let Some(sender) = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            // The receiver only goes away once we've finished.
+            let _ = sender.send(event);
+        }
+    }
+}
+
+/// Clears the sink when dropped, even if the caller panics, so that the
+/// events stop and forwarding finishes.
+#[cfg(feature = "http")]
+struct StopSending<'s>(&'s NetworkEventSink);
+
+#[cfg(feature = "http")]
+impl Drop for StopSending<'_> {
+    fn drop(&mut self) {
+        let sink = self.0;
+        sink.0.lock().unwrap_or_else(PoisonError::into_inner).take();
+    }
+}
+
+/// Call `f`, passing any events sent to `sink` meanwhile on to `reporter`
+/// from another thread. This way the reporter needn't outlive the HTTP(S)
+/// reader, which is shared between threads.
+#[cfg(feature = "http")]
+pub(crate) fn forward_network_events<O>(
+    sink: &NetworkEventSink,
+    reporter: &dyn UnzipProgressReporter,
+    f: impl FnOnce() -> O,
+) -> O {
+    let (sender, receiver) = channel();
+    *sink.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(sender);
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            for event in receiver {
+                reporter.network_event(&event);
+            }
+        });
+        let _stop_sending = StopSending(sink);
+        f()
+    })
+}
+
+#[cfg(all(test, feature = "http"))]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::{forward_network_events, NetworkEvent, NetworkEventSink};
+    use crate::UnzipProgressReporter;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<NetworkEvent>>);
+
+    impl UnzipProgressReporter for Recorder {
+        fn network_event(&self, event: &NetworkEvent) {
+            self.0.lock().unwrap().push(event.clone())
+        }
+    }
+
+    #[test]
+    fn test_forward_network_events() {
+        let sink = NetworkEventSink::default();
+        sink.send(NetworkEvent::StreamClosed { offset: 1 });
+        let recorder = Recorder::default();
+        let result = forward_network_events(&sink, &recorder, || {
+            sink.send(NetworkEvent::StreamClosed { offset: 2 });
+            3
+        });
+        sink.send(NetworkEvent::StreamClosed { offset: 4 });
+        assert_eq!(result, 3);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [NetworkEvent::StreamClosed { offset: 2 }]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashSet;
+
+use super::{FilenameFilter, UnzipOptions, ZipEntryMetadata};
+use crate::RipunzipErrors;
+
+/// What to do if names given in
+/// [`UnzipOptions::only_entries`](super::UnzipOptions::only_entries) aren't
+/// in the zip file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum MissingEntries {
+    /// Fail without extracting anything.
+    #[default]
+    Error,
+    /// Log a warning, and extract the entries which are there.
+    Warn,
+}
+
+/// Accepts only the named entries which the other filter, if any, also
+/// accepts.
+struct OnlyEntriesFilter<'a> {
+    names: HashSet<String>,
+    filter: Option<Box<dyn FilenameFilter + Send + Sync + 'a>>,
+}
+
+impl FilenameFilter for OnlyEntriesFilter<'_> {
+    fn should_unzip(&self, filename: &str) -> bool {
+        self.names.contains(filename)
+            && self
+                .filter
+                .as_ref()
+                .map_or(true, |filter| filter.should_unzip(filename))
+    }
+}
+
+/// Replace [`UnzipOptions::only_entries`], if given, with an equivalent
+/// filename filter, having checked that all of them are among `entries`.
+pub(crate) fn restrict_to_only_entries(
+    options: &mut UnzipOptions,
+    entries: &[ZipEntryMetadata],
+) -> Result<(), RipunzipErrors> {
+    let Some(names) = options.only_entries.take() else {
+        return Ok(());
+    };
+    let present: HashSet<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    let mut missing: Vec<String> = names
+        .iter()
+        .filter(|name| !present.contains(name.as_str()))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        missing.sort();
+        match options.missing_entries {
+            MissingEntries::Error => {
+                return Err(RipunzipErrors::EntriesNotFound { names: missing })
+            }
+            MissingEntries::Warn => {
+                log::warn!("Not found in the zip file: {}", missing.join(", "))
+            }
+        }
+    }
+    options.filename_filter = Some(Box::new(OnlyEntriesFilter {
+        names,
+        filter: options.filename_filter.take(),
+    }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{restrict_to_only_entries, MissingEntries};
+    use crate::{FilenameFilter, RipunzipErrors, UnzipOptions, ZipEntryMetadata};
+
+    fn entry(name: &str) -> ZipEntryMetadata {
+        ZipEntryMetadata {
+            name: name.to_string(),
+            size: 1,
+            compressed_size: 1,
+            crc32: 0,
+            last_modified: None,
+            unix_mode: None,
+        }
+    }
+
+    struct NotB;
+
+    impl FilenameFilter for NotB {
+        fn should_unzip(&self, filename: &str) -> bool {
+            filename != "b"
+        }
+    }
+
+    #[test]
+    fn test_restrict_to_only_entries() {
+        let entries = [entry("a"), entry("b"), entry("c"), entry("dir/a")];
+        let only_entries = || Some(HashSet::from(["a".to_string(), "b".to_string()]));
+
+        let mut options = UnzipOptions {
+            only_entries: only_entries(),
+            filename_filter: Some(Box::new(NotB)),
+            ..Default::default()
+        };
+        restrict_to_only_entries(&mut options, &entries).unwrap();
+        let filter = options.filename_filter.unwrap();
+        assert!(filter.should_unzip("a"));
+        assert!(!filter.should_unzip("b"));
+        assert!(!filter.should_unzip("c"));
+        assert!(!filter.should_unzip("dir/a"));
+
+        let entries = [entry("a"), entry("c")];
+        let mut options = UnzipOptions {
+            only_entries: only_entries(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            restrict_to_only_entries(&mut options, &entries),
+            Err(RipunzipErrors::EntriesNotFound { names }) if names == ["b"]
+        ));
+
+        let mut options = UnzipOptions {
+            only_entries: only_entries(),
+            missing_entries: MissingEntries::Warn,
+            ..Default::default()
+        };
+        restrict_to_only_entries(&mut options, &entries).unwrap();
+        let filter = options.filename_filter.unwrap();
+        assert!(filter.should_unzip("a"));
+        assert!(!filter.should_unzip("c"));
+    }
+}
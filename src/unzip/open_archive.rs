@@ -0,0 +1,162 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use zip::{result::ZipError, ZipArchive};
+
+use crate::RipunzipErrors;
+
+use super::determine_stream_len;
+
+/// The smallest possible zip file is an empty one, consisting of just the
+/// end of central directory record.
+const MIN_ZIP_LEN: u64 = 22;
+const LOCAL_HEADER_SIGNATURE: &[u8] = b"PK\x03\x04";
+const EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+/// Found at the start of the first segment of a spanned zip file.
+const SPANNED_SIGNATURE: &[u8] = b"PK\x07\x08";
+/// The end of central directory record may be followed by a comment of up
+/// to this length.
+const MAX_COMMENT_LEN: u64 = u16::MAX as u64;
+/// Disk numbers in the end of central directory record of a zip64 file.
+const ZIP64_DISK_MARKER: u16 = 0xFFFF;
+
+/// Open a zip file, turning failures to parse it into
+/// [`RipunzipErrors::InvalidArchive`].
+pub(crate) fn open_archive<R: Read + Seek + Clone>(
+    reader: R,
+) -> Result<ZipArchive<R>, RipunzipErrors> {
+    let reason = match ZipArchive::new(reader.clone()) {
+        // The zip library rejects most spanned zip files, but will open the
+        // last segment if it contains the whole central directory, only to
+        // fail to find the files in it.
+        Ok(_) if is_spanned(&mut reader.clone()).unwrap_or(false) => {
+            return Err(RipunzipErrors::SpannedArchiveUnsupported)
+        }
+        Ok(zip_archive) => return Ok(zip_archive),
+        Err(ZipError::InvalidArchive(reason)) => reason.to_string(),
+        Err(ZipError::UnsupportedArchive(reason)) => reason.to_string(),
+        Err(ZipError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+            "unexpected end of file".to_string()
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // The zip library doesn't tell us where it went wrong, but we can
+    // explain some common cases more specifically.
+    Err(diagnose(reader).unwrap_or(RipunzipErrors::InvalidArchive {
+        reason,
+        offset: None,
+    }))
+}
+
+/// Look for obvious reasons why the data isn't a zip file.
+fn diagnose<R: Read + Seek>(mut reader: R) -> Option<RipunzipErrors> {
+    let len = determine_stream_len(&mut reader).ok()?;
+    if len < MIN_ZIP_LEN {
+        return Some(RipunzipErrors::InvalidArchive {
+            reason: format!("only {len} bytes long, which is too short for a zip file"),
+            offset: Some(len),
+        });
+    }
+    reader.rewind().ok()?;
+    let mut signature = [0u8; 4];
+    reader.read_exact(&mut signature).ok()?;
+    if signature == SPANNED_SIGNATURE || is_spanned(&mut reader).unwrap_or(false) {
+        return Some(RipunzipErrors::SpannedArchiveUnsupported);
+    }
+    if signature != LOCAL_HEADER_SIGNATURE && signature != EOCD_SIGNATURE {
+        return Some(RipunzipErrors::InvalidArchive {
+            reason: "no zip signature at the start".to_string(),
+            offset: Some(0),
+        });
+    }
+    None
+}
+
+/// Whether the end of central directory record says that this is one of
+/// several segments of a spanned zip file.
+fn is_spanned<R: Read + Seek>(reader: &mut R) -> std::io::Result<bool> {
+    let len = determine_stream_len(reader)?;
+    // Usually there's no comment, so look at the very end first.
+    for search_len in [MIN_ZIP_LEN, MIN_ZIP_LEN + MAX_COMMENT_LEN] {
+        let search_len = search_len.min(len);
+        reader.seek(SeekFrom::Start(len - search_len))?;
+        let mut tail = vec![0u8; search_len as usize];
+        reader.read_exact(&mut tail)?;
+        let eocd = tail
+            .windows(EOCD_SIGNATURE.len())
+            .rposition(|window| window == EOCD_SIGNATURE)
+            .map(|pos| &tail[pos..])
+            .filter(|eocd| eocd.len() >= MIN_ZIP_LEN as usize);
+        if let Some(eocd) = eocd {
+            let disk = u16::from_le_bytes([eocd[4], eocd[5]]);
+            let central_directory_disk = u16::from_le_bytes([eocd[6], eocd[7]]);
+            return Ok(disk != ZIP64_DISK_MARKER && (disk != 0 || central_directory_disk != 0));
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::open_archive;
+    use crate::RipunzipErrors;
+
+    fn sample_zip() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("a.txt", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"Contents of A\n").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn invalid_archive_offset(data: Vec<u8>) -> Option<u64> {
+        match open_archive(Cursor::new(data)) {
+            Err(RipunzipErrors::InvalidArchive { offset, .. }) => offset,
+            Err(e) => panic!("unexpected error {e}"),
+            Ok(_) => panic!("unexpectedly valid"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_archives() {
+        assert_eq!(invalid_archive_offset(b"PK".to_vec()), Some(2));
+        assert_eq!(
+            invalid_archive_offset(b"<html>Not found</html>".to_vec()),
+            Some(0)
+        );
+        let mut data = sample_zip();
+        data.truncate(data.len() - 10);
+        assert_eq!(invalid_archive_offset(data), None);
+    }
+
+    #[test]
+    fn test_spanned_archives() {
+        // The last segment, containing the whole central directory.
+        let mut data = sample_zip();
+        let len = data.len();
+        data[len - 18..len - 14].copy_from_slice(&[1, 0, 1, 0]);
+        assert!(matches!(
+            open_archive(Cursor::new(data)),
+            Err(RipunzipErrors::SpannedArchiveUnsupported)
+        ));
+        // The first segment.
+        let mut data = b"PK\x07\x08".to_vec();
+        data.extend(&sample_zip()[..40]);
+        assert!(matches!(
+            open_archive(Cursor::new(data)),
+            Err(RipunzipErrors::SpannedArchiveUnsupported)
+        ));
+        assert!(open_archive(Cursor::new(sample_zip())).is_ok());
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::{Condvar, Mutex, PoisonError};
+
+/// Limits how many output files are open at once across all the threads
+/// extracting, so that unzipping many files in parallel can't run out of
+/// file descriptors however many threads there are.
+pub(crate) struct OpenFileLimit {
+    /// How many more files may be opened, or `None` if there's no limit.
+    available: Option<Mutex<usize>>,
+    released: Condvar,
+}
+
+impl OpenFileLimit {
+    /// Allow `limit` files open at once, or by default half as many as the
+    /// process may have open, leaving the rest for reading the zip file
+    /// and anything else. No limit is imposed if the process has none, or
+    /// it can't be found.
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self {
+            available: limit
+                .or_else(default_limit)
+                .map(|limit| Mutex::new(limit.max(1))),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait until another file may be opened. The file counts as open
+    /// until the returned permit is dropped.
+    pub(crate) fn acquire(&self) -> OpenFilePermit<'_> {
+        if let Some(available) = &self.available {
+            let mut available = self
+                .released
+                .wait_while(
+                    available.lock().unwrap_or_else(PoisonError::into_inner),
+                    |available| *available == 0,
+                )
+                .unwrap_or_else(PoisonError::into_inner);
+            *available -= 1;
+        }
+        OpenFilePermit(self)
+    }
+}
+
+/// Permission to have one more output file open.
+pub(crate) struct OpenFilePermit<'a>(&'a OpenFileLimit);
+
+impl Drop for OpenFilePermit<'_> {
+    fn drop(&mut self) {
+        if let Some(available) = &self.0.available {
+            *available.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+            self.0.released.notify_one();
+        }
+    }
+}
+
+#[cfg(unix)]
+fn default_limit() -> Option<usize> {
+    let limit = rustix::process::getrlimit(rustix::process::Resource::Nofile).current?;
+    Some(usize::try_from(limit / 2).unwrap_or(usize::MAX))
+}
+
+#[cfg(not(unix))]
+fn default_limit() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::OpenFileLimit;
+
+    #[test]
+    fn test_open_file_limit() {
+        let limit = OpenFileLimit::new(Some(3));
+        let open = AtomicUsize::new(0);
+        let most_open = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..16 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        let _permit = limit.acquire();
+                        let now_open = open.fetch_add(1, Ordering::SeqCst) + 1;
+                        most_open.fetch_max(now_open, Ordering::SeqCst);
+                        std::thread::yield_now();
+                        open.fetch_sub(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        assert!(most_open.load(Ordering::SeqCst) <= 3);
+        assert_eq!(*limit.available.as_ref().unwrap().lock().unwrap(), 3);
+    }
+}
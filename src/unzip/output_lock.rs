@@ -0,0 +1,100 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use super::UnzipOptions;
+use crate::RipunzipErrors;
+
+/// Whether to take an advisory lock on the output directory while
+/// extracting into it, so that unzips into the same directory don't
+/// interleave. The lock is on a file beside the output directory, named
+/// after it, so `out` is locked with `.out.ripunzip.lock`, which keeps the
+/// output directory itself untouched for
+/// [`UnzipOptions::require_empty_output`]. The file is left there
+/// afterwards, since removing it would let a waiting unzip and a new one
+/// both take the lock. Only unzips which also lock are kept out; anything
+/// else may still write there.
+///
+/// Advisory locks are unreliable on some network filesystems: with NFS
+/// they depend on the server's lock manager, and some SMB and FUSE
+/// filesystems silently grant every lock, so neither waiting nor failing
+/// can be relied on there.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum OutputLock {
+    /// Don't lock.
+    #[default]
+    None,
+    /// Wait until no other unzip holds the lock.
+    Wait,
+    /// Fail with [`RipunzipErrors::OutputLocked`] if another unzip holds
+    /// the lock.
+    FailFast,
+}
+
+/// Take the lock on the output directory, which must exist, if asked to,
+/// returning the file which holds it until it's dropped.
+pub(crate) fn lock_output(options: &UnzipOptions) -> Result<Option<File>, RipunzipErrors> {
+    if options.output_lock == OutputLock::None {
+        return Ok(None);
+    }
+    let directory = options
+        .output_directory
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."));
+    let lock_error = |e| RipunzipErrors::IOErrorWithContext {
+        context: format!("Failed to lock {}", directory.display()),
+        source: e,
+    };
+    let path = lock_path(&directory).map_err(lock_error)?;
+    let context = |e| RipunzipErrors::IOErrorWithContext {
+        context: format!("Failed to lock {}", path.display()),
+        source: e,
+    };
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(context)?;
+    match options.output_lock {
+        OutputLock::Wait => {
+            log::debug!("Waiting for the lock on {}", path.display());
+            fs2::FileExt::lock_exclusive(&file).map_err(context)?;
+        }
+        _ => match fs2::FileExt::try_lock_exclusive(&file) {
+            Ok(()) => {}
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => {
+                return Err(RipunzipErrors::OutputLocked { path })
+            }
+            Err(e) => return Err(context(e)),
+        },
+    }
+    Ok(Some(file))
+}
+
+/// The lock file for `directory`, which must exist, beside it.
+fn lock_path(directory: &Path) -> std::io::Result<PathBuf> {
+    let directory = directory.canonicalize()?;
+    match (directory.parent(), directory.file_name()) {
+        (Some(parent), Some(name)) => {
+            let mut lock_name = std::ffi::OsString::from(".");
+            lock_name.push(name);
+            lock_name.push(".ripunzip.lock");
+            Ok(parent.join(lock_name))
+        }
+        _ => Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "there's nowhere beside the root directory for a lock file",
+        )),
+    }
+}
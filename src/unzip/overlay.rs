@@ -0,0 +1,51 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+use crate::RipunzipErrors;
+
+/// Whether the file at `relative` within `base` has the given size and
+/// CRC-32, so that there's no need to extract an entry with them into the
+/// overlay. Anything but a regular file there doesn't match.
+pub(super) fn matches_base(
+    base: &Path,
+    relative: &Path,
+    size: u64,
+    crc32: u32,
+) -> Result<bool, RipunzipErrors> {
+    let path = base.join(relative);
+    let context = |e| RipunzipErrors::IOErrorWithContext {
+        context: format!("Failed to read overlay base file {}", path.display()),
+        source: e,
+    };
+    let metadata = match path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(context(e)),
+    };
+    if !metadata.is_file() || metadata.len() != size {
+        return Ok(false);
+    }
+    let mut file = File::open(&path).map_err(context)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buffer[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(context(e)),
+        }
+    }
+    Ok(hasher.finalize() == crc32)
+}
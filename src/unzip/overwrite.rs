@@ -0,0 +1,156 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use zip::{read::ZipFile, DateTime};
+
+use crate::RipunzipErrors;
+
+use super::central_directory::CentralDirectoryEntry;
+
+/// Information about a file within a zip.
+#[derive(Debug, Clone)]
+pub struct ZipEntryMetadata {
+    /// The name of the file within the zip.
+    pub name: String,
+    /// Uncompressed size.
+    pub size: u64,
+    /// Compressed size.
+    pub compressed_size: u64,
+    /// CRC-32 of the uncompressed data, or zero if the entry is AES
+    /// encrypted in WinZip's AE-2 format, which authenticates the data
+    /// instead.
+    pub crc32: u32,
+    /// Last modification time, if recorded.
+    pub last_modified: Option<DateTime>,
+    /// Unix permissions, if recorded.
+    pub unix_mode: Option<u32>,
+}
+
+impl ZipEntryMetadata {
+    pub(crate) fn new<R: std::io::Read>(file: &ZipFile<R>) -> Self {
+        Self {
+            name: file.name().to_string(),
+            size: file.size(),
+            compressed_size: file.compressed_size(),
+            crc32: file.crc32(),
+            last_modified: file.last_modified(),
+            unix_mode: file.unix_mode(),
+        }
+    }
+
+    pub(crate) fn from_central_directory(name: String, entry: &CentralDirectoryEntry) -> Self {
+        Self {
+            name,
+            size: entry.size,
+            compressed_size: entry.compressed_size,
+            crc32: entry.crc32,
+            last_modified: entry.last_modified,
+            unix_mode: entry.unix_mode,
+        }
+    }
+}
+
+/// What to do about a file which already exists where we want to extract.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OverwriteDecision {
+    /// Replace the existing file.
+    Overwrite,
+    /// Leave the existing file alone and don't extract this one.
+    Skip,
+    /// Stop unzipping.
+    Abort,
+    /// Replace this and all subsequent existing files without asking again.
+    OverwriteAll,
+}
+
+/// A function deciding what to do about a file which already exists at the
+/// given path.
+pub type OverwriteCallback<'a> =
+    Box<dyn FnMut(&Path, &ZipEntryMetadata) -> OverwriteDecision + Send + Sync + 'a>;
+
+/// What to do when a file we're extracting already exists.
+#[derive(Default)]
+pub enum OverwritePolicy<'a> {
+    /// Replace existing files.
+    #[default]
+    Overwrite,
+    /// Leave existing files alone.
+    Skip,
+    /// Ask the given function what to do about each existing file. Calls
+    /// are serialized even when unzipping in parallel, and any thread that
+    /// finds an existing file must wait for its turn to ask, so this may
+    /// reduce parallelism if there are many conflicts.
+    Callback(OverwriteCallback<'a>),
+}
+
+/// Applies an [`OverwritePolicy`] on behalf of all the threads which are
+/// extracting files.
+pub(crate) struct OverwriteGate<'a> {
+    policy: Mutex<OverwritePolicy<'a>>,
+    /// Set if we're overwriting everything, so we needn't look for existing
+    /// files or take the lock.
+    overwrite_all: AtomicBool,
+    aborted: AtomicBool,
+}
+
+impl<'a> OverwriteGate<'a> {
+    pub(crate) fn new(policy: OverwritePolicy<'a>) -> Self {
+        Self {
+            overwrite_all: AtomicBool::new(matches!(policy, OverwritePolicy::Overwrite)),
+            policy: Mutex::new(policy),
+            aborted: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns an error if an earlier decision was to abort.
+    pub(crate) fn check_not_aborted(&self) -> Result<(), RipunzipErrors> {
+        if self.aborted.load(Ordering::Relaxed) {
+            return Err(RipunzipErrors::Aborted);
+        }
+        Ok(())
+    }
+
+    /// Decide whether we should write the file described by `metadata`,
+    /// which is only called if needed, to `out_path`.
+    pub(crate) fn should_write(
+        &self,
+        out_path: &Path,
+        metadata: impl FnOnce() -> ZipEntryMetadata,
+    ) -> Result<bool, RipunzipErrors> {
+        if self.overwrite_all.load(Ordering::Relaxed) || out_path.symlink_metadata().is_err() {
+            return Ok(true);
+        }
+        let mut policy = self.policy.lock().unwrap_or_else(PoisonError::into_inner);
+        let callback = match &mut *policy {
+            OverwritePolicy::Overwrite => return Ok(true),
+            OverwritePolicy::Skip => return Ok(false),
+            OverwritePolicy::Callback(callback) => callback,
+        };
+        match callback(out_path, &metadata()) {
+            OverwriteDecision::Overwrite => Ok(true),
+            OverwriteDecision::Skip => Ok(false),
+            OverwriteDecision::OverwriteAll => {
+                *policy = OverwritePolicy::Overwrite;
+                self.overwrite_all.store(true, Ordering::Relaxed);
+                Ok(true)
+            }
+            OverwriteDecision::Abort => {
+                self.aborted.store(true, Ordering::Relaxed);
+                Err(RipunzipErrors::Aborted)
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+/// What to do with a file whose extraction failed after it was created,
+/// such as because the entry's data was corrupt or the disk filled up.
+/// Such a file may be truncated, or contain data which failed its CRC
+/// check, yet look like any other.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum PartialFiles {
+    /// Leave it where it is.
+    #[default]
+    Keep,
+    /// Delete it.
+    Delete,
+    /// Rename it by adding `.partial` to its name, so it's obviously
+    /// incomplete but still there to examine.
+    Rename,
+}
+
+/// Keeps track of files being written, and cleans up those which weren't
+/// finished according to the [`PartialFiles`] policy.
+pub(crate) struct PartialFileTracker {
+    policy: PartialFiles,
+    /// Where files which were cleaned up are now, or were.
+    cleaned_up: Mutex<Vec<PathBuf>>,
+}
+
+impl PartialFileTracker {
+    pub(crate) fn new(policy: PartialFiles) -> Self {
+        Self {
+            policy,
+            cleaned_up: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Start tracking the newly created file at `path`. Unless
+    /// [`PartialFile::complete`] is called, it's cleaned up once the
+    /// returned guard is dropped.
+    pub(crate) fn track(&self, path: &Path) -> PartialFile<'_> {
+        PartialFile {
+            tracker: self,
+            path: Some(path.to_path_buf()).filter(|_| self.policy != PartialFiles::Keep),
+        }
+    }
+
+    /// The paths of the files which have been cleaned up: deleted, or
+    /// renamed to.
+    pub(crate) fn cleaned_up(&self) -> Vec<PathBuf> {
+        std::mem::take(
+            &mut *self
+                .cleaned_up
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+
+    fn clean_up(&self, path: &Path) -> std::io::Result<PathBuf> {
+        match self.policy {
+            PartialFiles::Keep => Ok(path.to_path_buf()),
+            PartialFiles::Delete => std::fs::remove_file(path).map(|()| path.to_path_buf()),
+            PartialFiles::Rename => {
+                let mut name = path.file_name().unwrap_or_default().to_os_string();
+                name.push(".partial");
+                let renamed = path.with_file_name(name);
+                std::fs::rename(path, &renamed).map(|()| renamed)
+            }
+        }
+    }
+}
+
+/// A file being written, which is cleaned up when dropped unless it's been
+/// completed.
+pub(crate) struct PartialFile<'a> {
+    tracker: &'a PartialFileTracker,
+    /// `None` once complete, or if partial files are kept.
+    path: Option<PathBuf>,
+}
+
+impl PartialFile<'_> {
+    /// The file has been completely written.
+    pub(crate) fn complete(mut self) {
+        self.path = None;
+    }
+}
+
+impl Drop for PartialFile<'_> {
+    fn drop(&mut self) {
+        let Some(path) = self.path.take() else {
+            return;
+        };
+        match self.tracker.clean_up(&path) {
+            Ok(cleaned_up) => {
+                log::debug!("Cleaned up partial file {}", path.display());
+                self.tracker
+                    .cleaned_up
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(cleaned_up);
+            }
+            Err(e) => log::warn!("Unable to clean up partial file {}: {e}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::{PartialFileTracker, PartialFiles};
+
+    #[test]
+    fn test_partial_file_tracker() {
+        let td = tempdir().unwrap();
+        let path = td.path().join("file");
+        for (policy, exists, partial_exists) in [
+            (PartialFiles::Keep, true, false),
+            (PartialFiles::Delete, false, false),
+            (PartialFiles::Rename, false, true),
+        ] {
+            let tracker = PartialFileTracker::new(policy);
+            std::fs::write(&path, "complete").unwrap();
+            tracker.track(&path).complete();
+            assert!(path.exists());
+            drop(tracker.track(&path));
+            assert_eq!(path.exists(), exists);
+            assert_eq!(td.path().join("file.partial").exists(), partial_exists);
+            let cleaned_up = tracker.cleaned_up();
+            match policy {
+                PartialFiles::Keep => assert!(cleaned_up.is_empty()),
+                PartialFiles::Delete => assert_eq!(cleaned_up, std::slice::from_ref(&path)),
+                PartialFiles::Rename => {
+                    assert_eq!(cleaned_up, [td.path().join("file.partial")])
+                }
+            }
+            let _ = std::fs::remove_file(td.path().join("file.partial"));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use super::{
+    EntryInfo, ExtractionPlan, NetworkEvent, NullProgressReporter, PlannedAction, SkipReason,
+    UnzipOptions, UnzipOutcome, UnzipProgressReporter, ZipEntryMetadata,
+};
+
+/// How to batch up calls to [`UnzipProgressReporter::bytes_extracted`], for
+/// reporters which find many small increments expensive. Bytes are passed
+/// on once either limit is reached, and any remaining bytes are passed on
+/// before the unzip finishes, whether or not it succeeds.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ProgressCoalescing {
+    /// The longest time for which to hold on to bytes before passing them
+    /// on. Bytes are only passed on when more arrive, so a reporter may
+    /// hear nothing for longer than this during a slow download.
+    pub interval: Duration,
+    /// The most bytes to hold on to before passing them on, optionally.
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for ProgressCoalescing {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(50),
+            max_bytes: None,
+        }
+    }
+}
+
+/// Replace the progress reporter in `options` with one which coalesces
+/// progress, if asked to.
+pub(super) fn coalesce_progress(options: &mut UnzipOptions) {
+    if let Some(coalescing) = options.progress_coalescing {
+        let inner = std::mem::replace(
+            &mut options.progress_reporter,
+            Box::new(NullProgressReporter),
+        );
+        options.progress_reporter = Box::new(CoalescingProgressReporter {
+            inner,
+            coalescing,
+            pending: Mutex::new(Pending {
+                bytes: 0,
+                since: Instant::now(),
+            }),
+        });
+    }
+}
+
+struct Pending {
+    bytes: u64,
+    /// When we last passed bytes on.
+    since: Instant,
+}
+
+struct CoalescingProgressReporter<'b> {
+    inner: Box<dyn UnzipProgressReporter + Send + Sync + 'b>,
+    coalescing: ProgressCoalescing,
+    pending: Mutex<Pending>,
+}
+
+impl CoalescingProgressReporter<'_> {
+    fn flush(&self) {
+        let bytes = std::mem::take(
+            &mut self
+                .pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .bytes,
+        );
+        if bytes != 0 {
+            self.inner.bytes_extracted(bytes);
+        }
+    }
+}
+
+impl UnzipProgressReporter for CoalescingProgressReporter<'_> {
+    fn extraction_starting(&self, entry: &EntryInfo) {
+        self.inner.extraction_starting(entry)
+    }
+
+    fn extraction_finished(&self, display_name: &str) {
+        self.inner.extraction_finished(display_name)
+    }
+
+    fn central_directory_read_starting(&self) {
+        self.inner.central_directory_read_starting()
+    }
+
+    fn central_directory_read_finished(&self, entry_count: usize) {
+        self.inner.central_directory_read_finished(entry_count)
+    }
+
+    fn metadata_ready(&self, entries: &[ZipEntryMetadata]) {
+        self.inner.metadata_ready(entries)
+    }
+
+    fn plan_ready(&self, plan: &ExtractionPlan) {
+        self.inner.plan_ready(plan)
+    }
+
+    fn total_bytes_expected(&self, expected: u64) {
+        self.inner.total_bytes_expected(expected)
+    }
+
+    fn bytes_extracted(&self, count: u64) {
+        let bytes = {
+            let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+            pending.bytes += count;
+            let due = pending.since.elapsed() >= self.coalescing.interval
+                || self
+                    .coalescing
+                    .max_bytes
+                    .is_some_and(|max_bytes| pending.bytes >= max_bytes);
+            if !due {
+                return;
+            }
+            pending.since = Instant::now();
+            std::mem::take(&mut pending.bytes)
+        };
+        // Don't hold the lock while the reporter does whatever it does.
+        self.inner.bytes_extracted(bytes)
+    }
+
+    fn entry_skipped(&self, name: &str, reason: SkipReason) {
+        self.inner.entry_skipped(name, reason)
+    }
+
+    fn extraction_planned(&self, name: &str, action: PlannedAction) {
+        self.inner.extraction_planned(name, action)
+    }
+
+    fn network_event(&self, event: &NetworkEvent) {
+        self.inner.network_event(event)
+    }
+
+    fn all_finished(&self, outcome: &UnzipOutcome) {
+        self.flush();
+        self.inner.all_finished(outcome)
+    }
+}
+
+impl Drop for CoalescingProgressReporter<'_> {
+    /// If the unzip failed, `all_finished` isn't called, but the reporter
+    /// should still hear about all the bytes extracted.
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{coalesce_progress, ProgressCoalescing};
+    use crate::{UnzipOptions, UnzipOutcome, UnzipProgressReporter};
+
+    #[derive(Default, Clone)]
+    struct Recorder(Arc<Mutex<Vec<u64>>>);
+
+    impl UnzipProgressReporter for Recorder {
+        fn bytes_extracted(&self, count: u64) {
+            self.0.lock().unwrap().push(count)
+        }
+    }
+
+    fn coalesced(recorder: &Recorder, max_bytes: Option<u64>) -> UnzipOptions<'static, 'static> {
+        let mut options = UnzipOptions {
+            progress_reporter: Box::new(recorder.clone()),
+            progress_coalescing: Some(ProgressCoalescing {
+                interval: Duration::from_secs(3600),
+                max_bytes,
+            }),
+            ..Default::default()
+        };
+        coalesce_progress(&mut options);
+        options
+    }
+
+    #[test]
+    fn test_coalescing() {
+        let recorder = Recorder::default();
+        let options = coalesced(&recorder, Some(10));
+        for _ in 0..7 {
+            options.progress_reporter.bytes_extracted(3);
+        }
+        assert_eq!(*recorder.0.lock().unwrap(), [12]);
+        options
+            .progress_reporter
+            .all_finished(&UnzipOutcome::default());
+        assert_eq!(*recorder.0.lock().unwrap(), [12, 9]);
+        drop(options);
+        assert_eq!(*recorder.0.lock().unwrap(), [12, 9]);
+    }
+
+    #[test]
+    fn test_flush_on_failure() {
+        let recorder = Recorder::default();
+        let options = coalesced(&recorder, None);
+        options.progress_reporter.bytes_extracted(3);
+        options.progress_reporter.bytes_extracted(4);
+        assert!(recorder.0.lock().unwrap().is_empty());
+        drop(options);
+        assert_eq!(*recorder.0.lock().unwrap(), [7]);
+    }
+}
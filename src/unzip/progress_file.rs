@@ -0,0 +1,226 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{LineWriter, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use super::{
+    EntryInfo, ExtractionPlan, NetworkEvent, NullProgressReporter, PlannedAction, SkipReason,
+    UnzipOptions, UnzipOutcome, UnzipProgressReporter, ZipEntryMetadata,
+};
+use crate::RipunzipErrors;
+
+/// A file to which progress events are appended as JSON lines, for
+/// [`UnzipOptions::progress_file`].
+pub(super) struct ProgressFile(Mutex<LineWriter<File>>);
+
+impl ProgressFile {
+    /// Append an event, given as the fields after its `event` field, which
+    /// are already JSON.
+    fn record(&self, event: &str, fields: &[(&str, String)]) {
+        let mut line = format!("{{\"event\":{}", json_string(event));
+        for (name, value) in fields {
+            let _ = write!(line, ",{}:{value}", json_string(name));
+        }
+        line.push_str("}\n");
+        // The line writer flushes at the newline, so whoever's watching the
+        // file sees each event in full as soon as it happens.
+        if let Err(e) = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(line.as_bytes())
+        {
+            log::warn!("Failed to write to progress file: {e}");
+        }
+    }
+
+    /// Record the error which stopped the unzip.
+    pub(super) fn error(&self, error: &RipunzipErrors) {
+        self.record("error", &[("message", json_string(&error.to_string()))])
+    }
+}
+
+/// Reports progress both to the file and to the reporter it replaced.
+struct ProgressFileReporter<'b> {
+    inner: Box<dyn UnzipProgressReporter + Send + Sync + 'b>,
+    file: Arc<ProgressFile>,
+    /// The running total of compressed bytes extracted, which is recorded
+    /// with each event rather than recording every increment.
+    bytes_extracted: AtomicU64,
+}
+
+impl ProgressFileReporter<'_> {
+    fn bytes_extracted(&self) -> String {
+        self.bytes_extracted.load(Ordering::Relaxed).to_string()
+    }
+}
+
+impl UnzipProgressReporter for ProgressFileReporter<'_> {
+    fn extraction_starting(&self, entry: &EntryInfo) {
+        self.file.record(
+            "started",
+            &[
+                ("name", json_string(entry.display_name)),
+                ("size", entry.size.to_string()),
+                ("compressed_size", entry.compressed_size.to_string()),
+                ("bytes_extracted", self.bytes_extracted()),
+            ],
+        );
+        self.inner.extraction_starting(entry)
+    }
+
+    fn extraction_finished(&self, display_name: &str) {
+        self.file.record(
+            "finished",
+            &[
+                ("name", json_string(display_name)),
+                ("bytes_extracted", self.bytes_extracted()),
+            ],
+        );
+        self.inner.extraction_finished(display_name)
+    }
+
+    fn central_directory_read_starting(&self) {
+        self.file.record("central_directory_read_starting", &[]);
+        self.inner.central_directory_read_starting()
+    }
+
+    fn central_directory_read_finished(&self, entry_count: usize) {
+        self.file.record(
+            "central_directory_read_finished",
+            &[("entries", entry_count.to_string())],
+        );
+        self.inner.central_directory_read_finished(entry_count)
+    }
+
+    fn metadata_ready(&self, entries: &[ZipEntryMetadata]) {
+        self.file
+            .record("metadata_ready", &[("entries", entries.len().to_string())]);
+        self.inner.metadata_ready(entries)
+    }
+
+    fn plan_ready(&self, plan: &ExtractionPlan) {
+        self.file
+            .record("plan_ready", &[("entries", plan.entries.len().to_string())]);
+        self.inner.plan_ready(plan)
+    }
+
+    fn total_bytes_expected(&self, expected: u64) {
+        self.file
+            .record("total_bytes_expected", &[("bytes", expected.to_string())]);
+        self.inner.total_bytes_expected(expected)
+    }
+
+    fn bytes_extracted(&self, count: u64) {
+        self.bytes_extracted.fetch_add(count, Ordering::Relaxed);
+        self.inner.bytes_extracted(count)
+    }
+
+    fn entry_skipped(&self, name: &str, reason: SkipReason) {
+        self.file.record(
+            "skipped",
+            &[
+                ("name", json_string(name)),
+                ("reason", json_string(&format!("{reason:?}"))),
+            ],
+        );
+        self.inner.entry_skipped(name, reason)
+    }
+
+    fn extraction_planned(&self, name: &str, action: PlannedAction) {
+        self.inner.extraction_planned(name, action)
+    }
+
+    fn network_event(&self, event: &NetworkEvent) {
+        self.inner.network_event(event)
+    }
+
+    fn all_finished(&self, outcome: &UnzipOutcome) {
+        self.file.record(
+            "all_finished",
+            &[
+                ("files_extracted", outcome.files_extracted.to_string()),
+                ("bytes_written", outcome.bytes_written.to_string()),
+                ("bytes_extracted", self.bytes_extracted()),
+            ],
+        );
+        self.inner.all_finished(outcome)
+    }
+}
+
+/// Also report progress to [`UnzipOptions::progress_file`], if given,
+/// returning the file so that the caller can record any error.
+pub(super) fn add_progress_file(
+    options: &mut UnzipOptions,
+) -> Result<Option<Arc<ProgressFile>>, RipunzipErrors> {
+    let Some(path) = &options.progress_file else {
+        return Ok(None);
+    };
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| RipunzipErrors::IOErrorWithContext {
+            context: format!("Failed to open progress file {}", path.display()),
+            source: e,
+        })?;
+    let file = Arc::new(ProgressFile(Mutex::new(LineWriter::new(file))));
+    let inner = std::mem::replace(
+        &mut options.progress_reporter,
+        Box::new(NullProgressReporter),
+    );
+    options.progress_reporter = Box::new(ProgressFileReporter {
+        inner,
+        file: file.clone(),
+        bytes_extracted: AtomicU64::new(0),
+    });
+    Ok(Some(file))
+}
+
+/// `s` as a JSON string, in quotes.
+pub(super) fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::json_string;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a.txt"), r#""a.txt""#);
+        assert_eq!(
+            json_string("say \"hi\"\\\n\u{1}é"),
+            r#""say \"hi\"\\\n\u0001é""#
+        );
+    }
+}
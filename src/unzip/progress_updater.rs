@@ -18,6 +18,7 @@ pub struct ProgressUpdater<F: Fn(u64)> {
     external_updates_sent: u64,
     remainder_external: u64,
     internal_total: u64,
+    total_updates_expected: u64,
 }
 
 impl<F: Fn(u64)> ProgressUpdater<F> {
@@ -29,19 +30,11 @@ impl<F: Fn(u64)> ProgressUpdater<F> {
         per_update_internal: u64,
     ) -> Self {
         let per_update_internal = min(internal_total, per_update_internal);
-        let total_updates_expected = if per_update_internal == 0 {
-            0
-        } else {
-            internal_total / per_update_internal
-        };
-        let (update_external_amount, remainder_external) = if total_updates_expected == 0 {
-            (0, external_total)
-        } else {
-            (
-                external_total / total_updates_expected,
-                external_total % total_updates_expected,
-            )
-        };
+        let total_updates_expected = internal_total.checked_div(per_update_internal).unwrap_or(0);
+        let (update_external_amount, remainder_external) = external_total
+            .checked_div(total_updates_expected)
+            .map(|amount| (amount, external_total % total_updates_expected))
+            .unwrap_or((0, external_total));
         Self {
             callback,
             internal_progress: 0u64,
@@ -50,6 +43,7 @@ impl<F: Fn(u64)> ProgressUpdater<F> {
             external_updates_sent: 0u64,
             remainder_external,
             internal_total,
+            total_updates_expected,
         }
     }
 
@@ -62,11 +56,13 @@ impl<F: Fn(u64)> ProgressUpdater<F> {
     }
 
     fn send_due_updates(&mut self) {
-        let updates_due = if self.per_update_internal == 0 {
-            0
-        } else {
-            self.internal_progress / self.per_update_internal
-        };
+        // The internal total may be an estimate, so never go beyond the
+        // external total however much progress there is.
+        let updates_due = self
+            .internal_progress
+            .checked_div(self.per_update_internal)
+            .unwrap_or(0)
+            .min(self.total_updates_expected);
         while updates_due > self.external_updates_sent {
             (self.callback)(self.update_external_amount);
             self.external_updates_sent += 1;
@@ -106,6 +102,23 @@ fn test_progress_updater() {
     assert_eq!(*amount_received.borrow(), 100);
 }
 
+#[test]
+fn test_progress_updater_internal_overrun() {
+    let amount_received = std::rc::Rc::new(std::cell::RefCell::new(0u64));
+    let mut progresser = ProgressUpdater::new(
+        |progress| {
+            *(amount_received.borrow_mut()) += progress;
+        },
+        105,
+        1000,
+        100,
+    );
+    progresser.progress(5000);
+    assert_eq!(*amount_received.borrow(), 100);
+    progresser.finish();
+    assert_eq!(*amount_received.borrow(), 105);
+}
+
 #[test]
 fn test_progress_updater_zero_external() {
     let amount_received = std::rc::Rc::new(std::cell::RefCell::new(0u64));
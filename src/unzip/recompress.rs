@@ -0,0 +1,59 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{Read, Seek, Write};
+
+use zip::{write::FullFileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use crate::RipunzipErrors;
+
+/// Anything a zip file can be written to.
+pub(crate) trait WriteSeek: Write + Seek {}
+
+impl<W: Write + Seek> WriteSeek for W {}
+
+/// Decompress every entry in turn and write it to a new zip file on `out`,
+/// compressed with `method` at `level`. Entries keep their order, names,
+/// comments, modification times and Unix permissions, as does the zip
+/// file's comment, so recompressing the same zip file the same way always
+/// gives the same result.
+pub(crate) fn recompress_to<T: Read + Seek>(
+    mut zip_archive: ZipArchive<T>,
+    out: &mut dyn WriteSeek,
+    method: CompressionMethod,
+    level: Option<i64>,
+) -> Result<(), RipunzipErrors> {
+    let mut writer = ZipWriter::new(out).set_auto_large_file();
+    writer.set_raw_comment(zip_archive.comment().into())?;
+    for i in 0..zip_archive.len() {
+        let mut file = zip_archive.by_index(i)?;
+        let mut options = FullFileOptions::default()
+            .compression_method(method)
+            .compression_level(level)
+            // Rather than the current time, which the zip library would
+            // use otherwise.
+            .last_modified_time(file.last_modified().unwrap_or_else(DateTime::default))
+            .with_file_comment(file.comment());
+        if let Some(mode) = file.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+        let name = file.name().to_string();
+        if file.is_dir() {
+            writer.add_directory(name, options)?;
+        } else if file.is_symlink() {
+            let mut target = String::new();
+            file.read_to_string(&mut target)?;
+            writer.add_symlink(name, target, options)?;
+        } else {
+            writer.start_file(name, options)?;
+            std::io::copy(&mut file, &mut writer)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,99 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::Path;
+
+use reqwest::blocking::ClientBuilder;
+#[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+use reqwest::Certificate;
+
+use crate::RipunzipErrors;
+
+#[cfg(not(any(feature = "tls-native", feature = "tls-rustls")))]
+compile_error!("ripunzip needs a TLS backend: enable the tls-native or tls-rustls feature");
+
+/// Stands in for reqwest's, which only exists with a TLS backend, so that
+/// the error above is the only one such a build reports.
+#[cfg(not(any(feature = "tls-native", feature = "tls-rustls")))]
+type Certificate = std::convert::Infallible;
+
+/// Certificate authorities to trust when fetching URIs over HTTPS, in
+/// addition to those the TLS backend trusts anyway, such as the CA of a
+/// corporate proxy. With the default `tls-native` feature, the backend
+/// trusts the system's certificate store; with only `tls-rustls`, it trusts
+/// a built-in copy of Mozilla's.
+#[derive(Clone, Default)]
+pub struct RootCertificates(Vec<Certificate>);
+
+impl RootCertificates {
+    /// Trust every certificate in `pem`, which holds one or more
+    /// PEM-encoded certificates.
+    pub fn add_pem(&mut self, pem: &[u8]) -> Result<(), RipunzipErrors> {
+        #[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+        self.0.extend(Certificate::from_pem_bundle(pem)?);
+        #[cfg(not(any(feature = "tls-native", feature = "tls-rustls")))]
+        let _ = pem;
+        Ok(())
+    }
+
+    /// Trust every certificate in the PEM file at `path`.
+    pub fn add_pem_file(&mut self, path: &Path) -> Result<(), RipunzipErrors> {
+        let pem = std::fs::read(path).map_err(|e| RipunzipErrors::IOErrorWithContext {
+            context: format!("Failed to read certificates from {}", path.display()),
+            source: e,
+        })?;
+        self.add_pem(&pem)
+    }
+
+    /// A builder for HTTP clients which trust these certificates.
+    pub(crate) fn client_builder(&self) -> ClientBuilder {
+        #[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+        return self
+            .0
+            .iter()
+            .cloned()
+            .fold(ClientBuilder::new(), ClientBuilder::add_root_certificate);
+        #[cfg(not(any(feature = "tls-native", feature = "tls-rustls")))]
+        ClientBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RootCertificates;
+
+    /// A self-signed certificate, as for an internal CA.
+    const CA_PEM: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBjTCCATOgAwIBAgIUB4K2HIYwENv2WWspDbEehZP0fOIwCgYIKoZIzj0EAwIw
+GzEZMBcGA1UEAwwQcmlwdW56aXAgdGVzdCBDQTAgFw0yNjEwMTUwODM0MTFaGA8y
+MTI2MDkyMTA4MzQxMVowGzEZMBcGA1UEAwwQcmlwdW56aXAgdGVzdCBDQTBZMBMG
+ByqGSM49AgEGCCqGSM49AwEHA0IABFtc+LoMPNZl9TYnfyj7qzJcX5dW7xUzjTsC
+4oQXqxE7oVVpCAEUYq7V4k+z4TmI3Row74pqW31Twm2/C51GrxWjUzBRMB0GA1Ud
+DgQWBBS8ORHBibstlv+EKOORUHi58QvOczAfBgNVHSMEGDAWgBS8ORHBibstlv+E
+KOORUHi58QvOczAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIFBb
+NBPB5qc0kVHEcpIG+Oebbn29Q+vayFXJK+K93VPoAiEAox9gXpAaZ0bWWT64LhV7
+ST1k0CCdKeFZjQ/A0ttPvN8=
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_add_pem() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("ca.pem");
+        std::fs::write(&path, format!("{CA_PEM}{CA_PEM}")).unwrap();
+        let mut root_certificates = RootCertificates::default();
+        root_certificates.add_pem(CA_PEM.as_bytes()).unwrap();
+        root_certificates.add_pem_file(&path).unwrap();
+        assert_eq!(root_certificates.0.len(), 3);
+        assert!(root_certificates
+            .add_pem_file(&td.path().join("missing.pem"))
+            .is_err());
+        root_certificates.client_builder().build().unwrap();
+    }
+}
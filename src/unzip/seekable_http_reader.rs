@@ -11,13 +11,16 @@ use std::{
     collections::BTreeMap,
     io::{BufReader, ErrorKind, Read, Seek, SeekFrom},
     ops::Range,
-    sync::{Arc, Condvar, Mutex},
+    sync::{Arc, Condvar, Mutex, PoisonError},
 };
 
 use reqwest::blocking::Response;
 use thiserror::Error;
 
-use super::http_range_reader::{self, RangeFetcher};
+use super::{
+    http_range_reader::{self, ClientConfig, RangeFetcher},
+    network_events::{NetworkEvent, NetworkEventSink},
+};
 
 /// This is how much we read from the underlying HTTP stream in a given thread,
 /// before signalling other threads that they may wish to continue with their
@@ -62,6 +65,15 @@ pub(crate) enum Error {
     RangeFetcherError(http_range_reader::Error),
 }
 
+/// A read failed because it would have downloaded more than the budget
+/// set with [`SeekableHttpReaderEngine::set_download_budget`].
+#[derive(Error, Debug)]
+#[error("Reached the download budget of {budget} bytes after downloading {downloaded}")]
+pub(crate) struct BudgetExceeded {
+    downloaded: u64,
+    budget: u64,
+}
+
 /// Some data that we've read from the network, but not yet returned to the
 /// caller.
 struct CacheCell {
@@ -133,8 +145,17 @@ struct State {
     skip_ahead_threshold: u64,
     /// How much to read from the underlying stream each time.
     max_block: usize,
+    /// How much to ask for in each HTTP range request, or `None` for
+    /// everything from where the request starts to the end.
+    range_chunk_size: Option<u64>,
+    /// The largest block which may be in the cache, since `max_block` may
+    /// have been smaller when it was read.
+    largest_block: usize,
     /// Some statistics about how we're doing.
     stats: SeekableHttpReaderStatistics,
+    /// The most bytes to download from the underlying stream(s), if
+    /// there's a limit.
+    download_budget: Option<u64>,
     /// Facilities to read from the underlying HTTP stream(s).
     /// If this is present, a thread may start a read - it must `take`
     /// this. If it's absent, some other thread is doing a read, and
@@ -165,6 +186,7 @@ impl State {
             access_pattern,
             skip_ahead_threshold,
             max_block,
+            largest_block: max_block,
             reader: Some(reader),
             ..Default::default()
         }
@@ -181,14 +203,21 @@ impl State {
         self.cache.insert(pos, CacheCell::new(block));
         self.current_size += extra_size;
         if let Some(readahead_limit) = self.readahead_limit {
-            // Shrink
+            // Shrink, but never by discarding the block we just inserted,
+            // which is needed for the current read. It's not necessarily the
+            // last block if we've rewound.
             while self.current_size > readahead_limit {
+                let first_block = self
+                    .cache
+                    .keys()
+                    .find(|block_pos| **block_pos != pos)
+                    .cloned();
+                let Some(first_block) = first_block else {
+                    break;
+                };
                 self.stats.cache_shrinks += 1;
-                let first_block = self.cache.iter().next().map(|(pos, _)| pos).cloned();
-                if let Some(pos) = first_block {
-                    let block = self.cache.remove(&pos).unwrap();
-                    self.current_size -= block.len();
-                }
+                let block = self.cache.remove(&first_block).unwrap();
+                self.current_size -= block.len();
             }
         }
     }
@@ -205,7 +234,7 @@ impl State {
         let mut return_value = None;
         for (possible_block_start, block) in self
             .cache
-            .range_mut(pos - min(pos, self.max_block as u64)..=pos)
+            .range_mut(pos - min(pos, self.largest_block as u64)..=pos)
         {
             let block_offset = pos as usize - *possible_block_start as usize;
             let block_len = block.len();
@@ -251,6 +280,8 @@ impl std::fmt::Debug for State {
 struct ReadingMaterials {
     range_fetcher: RangeFetcher,
     reader: Option<(BufReader<Response>, u64)>, // second item in tuple is current reader pos
+    /// Where the range requested for the current reader ends.
+    stream_end: u64,
 }
 
 /// A type which can produce objects that can be [`Read`] and [`Seek`] even
@@ -270,6 +301,8 @@ pub(crate) struct SeekableHttpReaderEngine {
     /// readahead cache and all other threads should consider if their read
     /// request can be serviced.
     read_completed: Condvar,
+    /// Where we report streams opening, closing and failing.
+    events: Arc<NetworkEventSink>,
 }
 
 /// Some results about the success (or otherwise) of this reader.
@@ -285,19 +318,25 @@ pub(crate) struct SeekableHttpReaderStatistics {
     /// Number of times we had to discard data from the cache because it
     /// was too big.
     pub(crate) cache_shrinks: usize,
+    /// The number of bytes read from HTTP(S) streams, including any read
+    /// again after a rewind.
+    pub(crate) bytes_downloaded: u64,
 }
 
 impl SeekableHttpReaderEngine {
-    /// Create a new seekable HTTP reader engine for this URI. This constructor
-    /// will query the server to discover whether it supports HTTP ranges;
-    /// if not, an error will be returned.
+    /// Create a new seekable HTTP reader engine for this URI, or for these
+    /// mirrors of the same resource. This constructor will query the servers
+    /// to discover whether they support HTTP ranges; if not, an error will
+    /// be returned.
     pub(crate) fn new(
-        uri: String,
+        uris: Vec<String>,
         readahead_limit: Option<usize>,
         access_pattern: AccessPattern,
+        client_config: &ClientConfig,
     ) -> Result<Arc<Self>, Error> {
         Self::with_configuration(
-            uri,
+            uris,
+            client_config,
             readahead_limit,
             access_pattern,
             DEFAULT_SKIP_AHEAD_THRESHOLD,
@@ -307,13 +346,16 @@ impl SeekableHttpReaderEngine {
 
     /// Constructor with a specific configuration, used for testing.
     fn with_configuration(
-        uri: String,
+        uris: Vec<String>,
+        client_config: &ClientConfig,
         readahead_limit: Option<usize>,
         access_pattern: AccessPattern,
         skip_ahead_threshold: u64,
         max_block: usize,
     ) -> Result<Arc<Self>, Error> {
-        let range_fetcher = RangeFetcher::new(uri).map_err(Error::RangeFetcherError)?;
+        let events = Arc::new(NetworkEventSink::default());
+        let range_fetcher = RangeFetcher::new(uris, client_config, events.clone())
+            .map_err(Error::RangeFetcherError)?;
         if !range_fetcher.accepts_ranges() {
             return Err(Error::AcceptRangesNotSupported);
         }
@@ -328,9 +370,11 @@ impl SeekableHttpReaderEngine {
                 Box::new(ReadingMaterials {
                     range_fetcher,
                     reader: None,
+                    stream_end: len,
                 }),
             )),
             read_completed: Condvar::new(),
+            events,
         }))
     }
 
@@ -381,7 +425,7 @@ impl SeekableHttpReaderEngine {
         }
 
         // Claim CACHE mutex
-        let mut state = self.state.lock().unwrap();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
         // Is there block in cache?
         // - If yes, release CACHE mutex, and return
         if let Some(bytes_read_from_cache) = state.read_from_cache(pos, buf) {
@@ -392,14 +436,20 @@ impl SeekableHttpReaderEngine {
         let mut reading_stuff = state.reader.take();
         //   Is there read in progress?
         while reading_stuff.is_none() {
-            //   - If yes, release CACHE mutex, WAIT on condvar atomically
-            state = self.read_completed.wait(state).unwrap();
+            // Checked before waiting as well as after, since a thread which
+            // fails never puts the reader back, so there'd be nothing to
+            // wait for.
             if state.read_failed_somewhere {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::BrokenPipe,
                     "another thread experienced a problem creating a reader",
                 ));
             }
+            //   - If yes, release CACHE mutex, WAIT on condvar atomically
+            state = self
+                .read_completed
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
             //     check cache again
             if let Some(bytes_read_from_cache) = state.read_from_cache(pos, buf) {
                 log::debug!("Deferred cache success");
@@ -419,8 +469,15 @@ impl SeekableHttpReaderEngine {
         state.expect_skip_ahead = false;
         let skip_ahead_threshold = state.skip_ahead_threshold;
         let max_block = state.max_block;
+        let range_chunk_size = state.range_chunk_size;
         //     release STATE mutex
         drop(state);
+        // Records a failure, including a panic, since then the reader is
+        // never put back, and wakes the other threads either way.
+        let mut guard = ReadGuard {
+            engine: self,
+            succeeded: false,
+        };
         //     perform read
         let read_result = self.perform_read_using_reader(
             buf,
@@ -429,19 +486,14 @@ impl SeekableHttpReaderEngine {
             expect_skip_ahead,
             skip_ahead_threshold,
             max_block,
+            range_chunk_size,
         );
-        if read_result.is_err() {
-            let mut state = self.state.lock().unwrap();
-            state.read_failed_somewhere = true;
-        }
-        // 'state' has been updated to indicate either an error, or the
-        // reading_materials has been repopulated, so wake up all other
-        // threads to check.
-        self.read_completed.notify_all();
+        guard.succeeded = read_result.is_ok();
+        drop(guard);
         read_result
     }
 
-    #[allow(clippy::comparison_chain)]
+    #[allow(clippy::comparison_chain, clippy::too_many_arguments)]
     // Read from the underlying HTTP stream
     // This is a separate function because if it errors at any point
     // we need to take cleanup action in the caller.
@@ -453,6 +505,7 @@ impl SeekableHttpReaderEngine {
         expect_skip_ahead: bool,
         skip_ahead_threshold: u64,
         max_block: usize,
+        range_chunk_size: Option<u64>,
     ) -> std::io::Result<usize> {
         // First check if we need to rewind, OR if we need to fast forward
         // and are expecting to skip over some significant data.
@@ -463,6 +516,8 @@ impl SeekableHttpReaderEngine {
                     pos,
                     *readerpos
                 );
+                self.events
+                    .send(NetworkEvent::StreamClosed { offset: *readerpos });
                 reading_stuff.reader = None;
             } else if pos > *readerpos {
                 let delta = pos - *readerpos;
@@ -475,23 +530,27 @@ impl SeekableHttpReaderEngine {
                         pos,
                         *readerpos
                     );
+                    self.events
+                        .send(NetworkEvent::StreamClosed { offset: *readerpos });
                     reading_stuff.reader = None;
                 }
             }
         }
-        let mut reader_created = false;
+        let mut streams_created = 0;
         if reading_stuff.reader.is_none() {
             log::debug!("create_reader");
+            let end = self.range_end(pos, range_chunk_size);
             reading_stuff.reader = Some((
                 BufReader::new(
                     reading_stuff
                         .range_fetcher
-                        .fetch_range(pos)
-                        .map_err(|e| std::io::Error::new(ErrorKind::Unsupported, e.to_string()))?,
+                        .fetch_range(pos, end)
+                        .map_err(|e| std::io::Error::new(ErrorKind::Unsupported, e))?,
                 ),
                 pos,
             ));
-            reader_created = true;
+            reading_stuff.stream_end = end;
+            streams_created += 1;
         };
 
         let (reader, reader_pos) = reading_stuff.reader.as_mut().unwrap();
@@ -503,14 +562,56 @@ impl SeekableHttpReaderEngine {
             );
         }
         while pos >= *reader_pos {
+            if *reader_pos == reading_stuff.stream_end {
+                // We've had all of the range we asked for, so ask for the
+                // next.
+                log::debug!(
+                    "Range exhausted at 0x{:x}: next range required",
+                    *reader_pos
+                );
+                self.events.send(NetworkEvent::StreamClosed {
+                    offset: *reader_pos,
+                });
+                let end = self.range_end(*reader_pos, range_chunk_size);
+                *reader = BufReader::new(
+                    reading_stuff
+                        .range_fetcher
+                        .fetch_range(*reader_pos, end)
+                        .map_err(|e| std::io::Error::new(ErrorKind::Unsupported, e))?,
+                );
+                reading_stuff.stream_end = end;
+                streams_created += 1;
+            }
             // Fast forward beyond the desired position, recording any reads in the cache
             // for later.
-            let to_read = min(max_block, self.len as usize - *reader_pos as usize);
+            let to_read = min(max_block, (reading_stuff.stream_end - *reader_pos) as usize);
+            self.check_download_budget(to_read as u64)?;
             let mut new_block = vec![0u8; to_read];
-            reader.read_exact(&mut new_block)?;
+            // If the stream fails, pick up where it left off from each of the
+            // other mirrors in turn, if there are any.
+            let mut failures = 0;
+            while let Err(e) = reader.read_exact(&mut new_block) {
+                failures += 1;
+                if failures >= reading_stuff.range_fetcher.mirror_count() {
+                    return Err(e);
+                }
+                log::debug!("Read failed, trying another mirror: {e}");
+                self.events.send(NetworkEvent::Retry {
+                    offset: *reader_pos,
+                    attempt: failures,
+                    error: e.to_string(),
+                });
+                *reader = BufReader::new(
+                    reading_stuff
+                        .range_fetcher
+                        .fetch_range(*reader_pos, reading_stuff.stream_end)
+                        .map_err(|e| std::io::Error::new(ErrorKind::Unsupported, e))?,
+                );
+            }
             //     claim STATE mutex
-            let mut state = self.state.lock().unwrap();
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
             state.insert(*reader_pos, new_block);
+            state.stats.bytes_downloaded += to_read as u64;
             // Tell any waiting threads they should re-check the cache
             self.read_completed.notify_all();
             *reader_pos += to_read as u64;
@@ -519,14 +620,12 @@ impl SeekableHttpReaderEngine {
         // to read at the very end of the file, we know we now have some data in the
         // cache which can satisfy the request.
         //     claim STATE mutex
-        let mut state = self.state.lock().unwrap();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
         let bytes_read = state
             .read_from_cache(pos, buf)
             .expect("Cache still couldn't satisfy request event after reading beyond read pos");
         log::debug!("Cache success after read");
-        if reader_created {
-            state.stats.num_http_streams += 1;
-        }
+        state.stats.num_http_streams += streams_created;
         //     return the underlying reader to the state so that some other
         //     thread can use it
         state.reader = Some(reading_stuff);
@@ -543,7 +642,7 @@ impl SeekableHttpReaderEngine {
     /// any threads might be reading from any [`SeekableHttpReader`] created
     /// by this engine; that may panic.
     pub(crate) fn set_expected_access_pattern(&self, access_pattern: AccessPattern) {
-        let mut state = self.state.lock().unwrap();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
         let old_access_pattern = state.access_pattern;
         if old_access_pattern == access_pattern {
             return;
@@ -556,13 +655,19 @@ impl SeekableHttpReaderEngine {
             // If we're switching to a sequential pattern, recreate
             // the reader at position zero.
             log::debug!("create_reader_at_zero");
+            let end = self.range_end(0, state.range_chunk_size);
             {
                 let reading_materials = state.reader.as_mut().expect(
                     "Must not call set_expected_access_pattern while a read is in progress",
                 );
-                let new_reader = reading_materials.range_fetcher.fetch_range(0);
+                if let Some((_, offset)) = &reading_materials.reader {
+                    self.events
+                        .send(NetworkEvent::StreamClosed { offset: *offset });
+                }
+                let new_reader = reading_materials.range_fetcher.fetch_range(0, end);
                 if let Ok(new_reader) = new_reader {
                     reading_materials.reader = Some((BufReader::new(new_reader), 0));
+                    reading_materials.stream_end = end;
                 }
             }
             state.stats.num_http_streams += 1;
@@ -570,21 +675,120 @@ impl SeekableHttpReaderEngine {
         state.access_pattern = access_pattern;
     }
 
+    /// Change how much is read from the underlying stream at a time, and how
+    /// far ahead we must expect to skip before opening a new stream rather
+    /// than reading on, or go back to the defaults for those which are
+    /// `None`. The readahead limit grows if need be to hold a block.
+    pub(crate) fn set_tuning(&self, block_size: Option<usize>, skip_ahead_threshold: Option<u64>) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let block_size = block_size.unwrap_or(DEFAULT_MAX_BLOCK).max(1);
+        state.max_block = block_size;
+        state.largest_block = state.largest_block.max(block_size);
+        state.readahead_limit = state.readahead_limit.map(|limit| limit.max(block_size));
+        state.skip_ahead_threshold = skip_ahead_threshold.unwrap_or(DEFAULT_SKIP_AHEAD_THRESHOLD);
+    }
+
+    /// Ask for no more than `chunk_size` bytes in each HTTP range request,
+    /// or for everything from where each starts to the end if `None`.
+    /// Takes effect from the next request.
+    pub(crate) fn set_range_chunk_size(&self, chunk_size: Option<usize>) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.range_chunk_size = chunk_size.map(|size| size.max(1) as u64);
+    }
+
+    /// Where a range request starting at `offset` should end.
+    fn range_end(&self, offset: u64, range_chunk_size: Option<u64>) -> u64 {
+        range_chunk_size.map_or(self.len, |size| offset.saturating_add(size).min(self.len))
+    }
+
+    /// Limit how much more may be downloaded, counting everything
+    /// downloaded so far, after which reads fail with
+    /// [`BudgetExceeded`]. `None` removes any limit.
+    pub(crate) fn set_download_budget(&self, download_budget: Option<u64>) {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .download_budget = download_budget;
+    }
+
+    /// Fail if downloading `len` more bytes would go over the budget.
+    fn check_download_budget(&self, len: u64) -> std::io::Result<()> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.download_budget {
+            Some(budget) if state.stats.bytes_downloaded + len > budget => {
+                Err(std::io::Error::new(
+                    ErrorKind::Other,
+                    BudgetExceeded {
+                        downloaded: state.stats.bytes_downloaded,
+                        budget,
+                    },
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Call this if we're going to skip over some part of the zip.
     pub(crate) fn read_skip_expected(&self) {
-        let mut state = self.state.lock().unwrap();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
         state.expect_skip_ahead = true;
     }
 
+    /// Where we report streams opening, closing and failing.
+    pub(crate) fn network_events(&self) -> &NetworkEventSink {
+        &self.events
+    }
+
     /// Return some statistics about the success (or otherwise) of this stream.
     pub(crate) fn get_stats(&self) -> SeekableHttpReaderStatistics {
-        self.state.lock().unwrap().stats.clone()
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .stats
+            .clone()
+    }
+}
+
+/// If `error` is because the download budget ran out, how much had been
+/// downloaded and what the budget was. Errors from the reader reach us
+/// wrapped in I/O errors.
+pub(crate) fn budget_exceeded(error: &std::io::Error) -> Option<(u64, u64)> {
+    let BudgetExceeded { downloaded, budget } = error.get_ref()?.downcast_ref()?;
+    Some((*downloaded, *budget))
+}
+
+/// Held while a thread reads using the reader it took from the state.
+struct ReadGuard<'a> {
+    engine: &'a SeekableHttpReaderEngine,
+    succeeded: bool,
+}
+
+impl Drop for ReadGuard<'_> {
+    fn drop(&mut self) {
+        if !self.succeeded {
+            let mut state = self
+                .engine
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            state.read_failed_somewhere = true;
+        }
+        // 'state' has been updated to indicate either an error, or the
+        // reading_materials has been repopulated, so wake up all other
+        // threads to check.
+        self.engine.read_completed.notify_all();
     }
 }
 
 impl Drop for SeekableHttpReaderEngine {
     fn drop(&mut self) {
-        log::debug!("Dropping: stats are {:?}", self.state.lock().unwrap().stats)
+        log::debug!(
+            "Dropping: stats are {:?}",
+            self.state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .stats
+        )
     }
 }
 
@@ -643,14 +847,17 @@ impl Read for SeekableHttpReader {
 #[cfg(test)]
 mod tests {
     use ripunzip_test_utils::{ExpectedRange, RangeAwareResponse, RangeAwareResponseType};
-    use std::io::{Read, Seek, SeekFrom};
+    use std::{
+        io::{ErrorKind, Read, Seek, SeekFrom},
+        panic::AssertUnwindSafe,
+    };
     use test_log::test;
 
     use httptest::{matchers::*, Expectation, Server};
 
     use crate::unzip::seekable_http_reader::DEFAULT_MAX_BLOCK;
 
-    use super::{AccessPattern, CacheCell, SeekableHttpReaderEngine};
+    use super::{AccessPattern, CacheCell, ReadGuard, SeekableHttpReaderEngine};
 
     #[test]
     fn test_cachecell() {
@@ -699,6 +906,107 @@ mod tests {
         do_test(None, AccessPattern::RandomAccess)
     }
 
+    #[test]
+    fn test_range_chunk_size() {
+        let mut server = Server::run();
+        server.expect(get_head_expectation());
+        let seekable_http_reader_engine = SeekableHttpReaderEngine::with_configuration(
+            vec![server.url("/foo").to_string()],
+            &Default::default(),
+            None,
+            AccessPattern::SequentialIsh,
+            4,
+            2,
+        )
+        .unwrap();
+        seekable_http_reader_engine.set_range_chunk_size(Some(5));
+        let mut seekable_http_reader = seekable_http_reader_engine.clone().create_reader();
+        server.verify_and_clear();
+
+        // Blocks don't have to line up with ranges.
+        let mut throwaway = [0u8; 5];
+        for (expected_range, expected) in [((0, 5), "01234"), ((5, 10), "56789")] {
+            server.expect(get_range_expectation(expected_range.0, expected_range.1));
+            seekable_http_reader.read_exact(&mut throwaway).unwrap();
+            assert_eq!(std::str::from_utf8(&throwaway).unwrap(), expected);
+            server.verify_and_clear();
+        }
+        // The last range is cut short by the end of the resource.
+        let mut throwaway = [0u8; 2];
+        server.expect(get_range_expectation(10, 12));
+        seekable_http_reader.read_exact(&mut throwaway).unwrap();
+        assert_eq!(std::str::from_utf8(&throwaway).unwrap(), "AB");
+        server.verify_and_clear();
+        assert_eq!(seekable_http_reader_engine.get_stats().num_http_streams, 3);
+    }
+
+    #[test]
+    fn test_rewind_with_readahead_limit_of_one_block() {
+        let mut server = Server::run();
+        server.expect(get_head_expectation());
+        let seekable_http_reader_engine = SeekableHttpReaderEngine::with_configuration(
+            vec![server.url("/foo").to_string()],
+            &Default::default(),
+            Some(4),
+            AccessPattern::RandomAccess,
+            4,
+            4,
+        )
+        .unwrap();
+        let mut seekable_http_reader = seekable_http_reader_engine.create_reader();
+        server.verify_and_clear();
+
+        let mut throwaway = [0u8; 4];
+        server.expect(get_range_expectation(8, 12));
+        seekable_http_reader.seek(SeekFrom::Start(8)).unwrap();
+        seekable_http_reader.read_exact(&mut throwaway).unwrap();
+        assert_eq!(std::str::from_utf8(&throwaway).unwrap(), "89AB");
+        server.verify_and_clear();
+
+        // The block just read from the new stream is the first in the cache,
+        // but mustn't be the one discarded to keep within the limit.
+        server.expect(get_range_expectation(0, 12));
+        seekable_http_reader.rewind().unwrap();
+        seekable_http_reader.read_exact(&mut throwaway).unwrap();
+        assert_eq!(std::str::from_utf8(&throwaway).unwrap(), "0123");
+        server.verify_and_clear();
+    }
+
+    #[test]
+    fn test_panic_while_reading() {
+        let server = Server::run();
+        server.expect(get_head_expectation());
+        let seekable_http_reader_engine = SeekableHttpReaderEngine::with_configuration(
+            vec![server.url("/foo").to_string()],
+            &Default::default(),
+            None,
+            AccessPattern::RandomAccess,
+            4,
+            4,
+        )
+        .unwrap();
+        // As if a thread took the reader, then panicked while reading.
+        let reading_stuff = seekable_http_reader_engine
+            .state
+            .lock()
+            .unwrap()
+            .reader
+            .take();
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = ReadGuard {
+                engine: &seekable_http_reader_engine,
+                succeeded: false,
+            };
+            panic!("failed while reading");
+        }));
+        assert!(result.is_err());
+        drop(reading_stuff);
+        // Other threads give up, rather than waiting for the reader forever.
+        let mut seekable_http_reader = seekable_http_reader_engine.create_reader();
+        let error = seekable_http_reader.read(&mut [0u8; 4]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::BrokenPipe);
+    }
+
     fn get_head_expectation() -> Expectation {
         Expectation::matching(request::method_path("HEAD", "/foo")).respond_with(
             RangeAwareResponse::new(200, RangeAwareResponseType::LengthOnly(12)),
@@ -728,7 +1036,8 @@ mod tests {
         server.expect(get_head_expectation());
 
         let seekable_http_reader_engine = SeekableHttpReaderEngine::with_configuration(
-            server.url("/foo").to_string(),
+            vec![server.url("/foo").to_string()],
+            &Default::default(),
             readahead_limit,
             access_pattern,
             4,
@@ -806,7 +1115,8 @@ mod tests {
 
             server.expect(get_head_expectation());
             let seekable_http_reader_engine = SeekableHttpReaderEngine::with_configuration(
-                server.url("/foo").to_string(),
+                vec![server.url("/foo").to_string()],
+                &Default::default(),
                 readahead_limit,
                 access_pattern,
                 4,
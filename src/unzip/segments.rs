@@ -0,0 +1,530 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use super::local_header::{u16_at, u32_at, u64_at};
+use crate::{
+    io::{CloneableFile, Concatenated, HasLength, SharedBytes},
+    RipunzipErrors,
+};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const CENTRAL_HEADER_LEN: usize = 46;
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const EOCD_LEN: usize = 22;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_EOCD_LEN: usize = 56;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const ZIP64_LOCATOR_LEN: usize = 20;
+const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+const ZIP64_MARKER: u32 = 0xFFFFFFFF;
+const ZIP64_DISK_MARKER: u16 = 0xFFFF;
+const ZIP64_VERSION: u16 = 45;
+/// The end of central directory record may be followed by a comment of up
+/// to this length.
+const MAX_COMMENT_LEN: u64 = u16::MAX as u64;
+
+/// A segment, or the central directory rewritten to follow them.
+#[derive(Clone)]
+pub(crate) enum Segment {
+    File(CloneableFile),
+    Rewritten(Cursor<SharedBytes<[u8]>>),
+}
+
+impl Read for Segment {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Rewritten(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for Segment {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Rewritten(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+impl HasLength for Segment {
+    fn len(&self) -> std::io::Result<u64> {
+        match self {
+            Self::File(file) => file.len(),
+            Self::Rewritten(cursor) => cursor.len(),
+        }
+    }
+}
+
+/// Join the segments of a split zip file, in order, so that it can be read
+/// as one. Both zip files split by `zip -s`, named `part.z01`, `part.z02`
+/// and so on up to `part.zip`, and zip files simply cut into pieces, such
+/// as 7-Zip's `part.zip.001`, `part.zip.002` and so on, are supported. The
+/// former record where each entry is relative to the start of the segment
+/// it's in, which the zip library can't follow, so their central directory
+/// is rewritten in memory with offsets within the whole, and read instead.
+/// Either way, the segments are checked against the end of central
+/// directory record and every local header is looked for, so that a
+/// segment which is missing, truncated or out of order is found before
+/// anything is extracted. The exception is segments holding nothing but
+/// the middle of one entry, which can't be told apart if swapped, so that
+/// entry fails its CRC check instead.
+pub(crate) fn join_segments(paths: &[PathBuf]) -> Result<Concatenated<Segment>, RipunzipErrors> {
+    if paths.is_empty() {
+        return Err(invalid("no segments were given".to_string()));
+    }
+    let segments = paths
+        .iter()
+        .map(|path| {
+            CloneableFile::open(path).map(Segment::File).map_err(|e| {
+                RipunzipErrors::IOErrorWithContext {
+                    context: format!("Failed to open segment {}", path.display()),
+                    source: e,
+                }
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut joined = Concatenated::new(segments)?;
+    let end = read_end(&mut joined)?;
+    let spanned = end.disk != 0 || end.central_directory_disk != 0;
+    let segment_count = joined.part_starts().len();
+    if spanned && end.disk as usize != segment_count - 1 {
+        return Err(invalid(format!(
+            "the zip file has {} segments, but {segment_count} were given",
+            end.disk as u64 + 1,
+        )));
+    }
+    let central_directory_start = segment_start(&joined, end.central_directory_disk)
+        .and_then(|start| start.checked_add(end.central_directory_offset))
+        .ok_or_else(|| invalid("the central directory is in a missing segment".to_string()))?;
+    let central_directory_end = central_directory_start.checked_add(end.central_directory_size);
+    if central_directory_end != Some(end.position) {
+        return Err(invalid(format!(
+            "the central directory should end at offset {}, not {}, so a segment is missing or truncated",
+            end.position,
+            central_directory_start.saturating_add(end.central_directory_size),
+        )));
+    }
+    let mut central_directory = vec![0u8; end.central_directory_size as usize];
+    joined.seek(SeekFrom::Start(central_directory_start))?;
+    joined.read_exact(&mut central_directory)?;
+    let rewritten = rewrite_central_directory(&central_directory, &end, spanned, &mut joined)?;
+    if !spanned {
+        // Everything is already where the zip file says.
+        return Ok(joined);
+    }
+    let zip_len = joined.len()?;
+    let mut segments = joined.into_parts();
+    let tail = end_records(rewritten, &end, zip_len);
+    segments.push(Segment::Rewritten(Cursor::new(SharedBytes::new(
+        Arc::from(tail),
+    ))));
+    Ok(Concatenated::new(segments)?)
+}
+
+fn invalid(reason: String) -> RipunzipErrors {
+    RipunzipErrors::InvalidSegments { reason }
+}
+
+/// Where the segment with the given number starts, if it was given.
+fn segment_start(joined: &Concatenated<Segment>, disk: u32) -> Option<u64> {
+    joined.part_starts().get(disk as usize).copied()
+}
+
+/// What the end of central directory record says, or the zip64 one if
+/// there is one.
+struct End {
+    /// Where the central directory should end, which is where the zip64
+    /// end of central directory record starts if there is one, or else the
+    /// end of central directory record.
+    position: u64,
+    /// The number of the last segment, counting from zero.
+    disk: u32,
+    central_directory_disk: u32,
+    entries: u64,
+    central_directory_size: u64,
+    /// Relative to the start of `central_directory_disk`.
+    central_directory_offset: u64,
+    comment: Vec<u8>,
+}
+
+/// Find and read the end of central directory record at the end of the
+/// last segment.
+fn read_end(joined: &mut Concatenated<Segment>) -> Result<End, RipunzipErrors> {
+    let not_found = || invalid("the last segment doesn't end a zip file".to_string());
+    let len = joined.len()?;
+    let tail_start = len.saturating_sub(EOCD_LEN as u64 + MAX_COMMENT_LEN);
+    let mut tail = Vec::new();
+    joined.seek(SeekFrom::Start(tail_start))?;
+    joined.read_to_end(&mut tail)?;
+    // The last record whose comment reaches exactly to the end, so that a
+    // signature within the comment isn't mistaken for one.
+    let eocd_pos = (0..=tail.len().checked_sub(EOCD_LEN).ok_or_else(not_found)?)
+        .rev()
+        .find(|&pos| {
+            u32_at(&tail, pos) == EOCD_SIGNATURE
+                && pos + EOCD_LEN + u16_at(&tail, pos + 20) as usize == tail.len()
+        })
+        .ok_or_else(not_found)?;
+    let eocd = &tail[eocd_pos..];
+    let position = tail_start + eocd_pos as u64;
+    let mut end = End {
+        position,
+        disk: u16_at(eocd, 4) as u32,
+        central_directory_disk: u16_at(eocd, 6) as u32,
+        entries: u16_at(eocd, 10) as u64,
+        central_directory_size: u32_at(eocd, 12) as u64,
+        central_directory_offset: u32_at(eocd, 16) as u64,
+        comment: eocd[EOCD_LEN..].to_vec(),
+    };
+    let Some(locator_start) = position.checked_sub(ZIP64_LOCATOR_LEN as u64) else {
+        return Ok(end);
+    };
+    let mut locator = [0u8; ZIP64_LOCATOR_LEN];
+    joined.seek(SeekFrom::Start(locator_start))?;
+    joined.read_exact(&mut locator)?;
+    if u32_at(&locator, 0) != ZIP64_LOCATOR_SIGNATURE {
+        return Ok(end);
+    }
+    let record_start = segment_start(joined, u32_at(&locator, 4))
+        .and_then(|start| start.checked_add(u64_at(&locator, 8)))
+        .ok_or_else(|| {
+            invalid("the zip64 end of central directory record is in a missing segment".to_string())
+        })?;
+    let mut record = [0u8; ZIP64_EOCD_LEN];
+    joined.seek(SeekFrom::Start(record_start))?;
+    joined.read_exact(&mut record)?;
+    if u32_at(&record, 0) != ZIP64_EOCD_SIGNATURE {
+        return Err(invalid(
+            "there's no zip64 end of central directory record where the locator says".to_string(),
+        ));
+    }
+    end.position = record_start;
+    end.disk = u32_at(&record, 16);
+    end.central_directory_disk = u32_at(&record, 20);
+    end.entries = u64_at(&record, 32);
+    end.central_directory_size = u64_at(&record, 40);
+    end.central_directory_offset = u64_at(&record, 48);
+    Ok(end)
+}
+
+/// The values in an entry's zip64 extra field, each of which is only there
+/// if the corresponding field of the central header is set to its maximum.
+#[derive(Default)]
+struct Zip64Values {
+    size: Option<u64>,
+    compressed_size: Option<u64>,
+    offset: Option<u64>,
+    disk: Option<u32>,
+}
+
+impl Zip64Values {
+    fn parse(header: &[u8], extra: &[u8]) -> Option<Self> {
+        let data = extra_fields(extra)
+            .find(|(id, _)| *id == ZIP64_EXTRA_FIELD_ID)
+            .map(|(_, data)| data)
+            .unwrap_or_default();
+        let mut pos = 0;
+        let mut next = |len: usize| {
+            let value = data.get(pos..pos + len)?;
+            pos += len;
+            Some(value)
+        };
+        let mut values = Self::default();
+        if u32_at(header, 24) == ZIP64_MARKER {
+            values.size = Some(u64_at(next(8)?, 0));
+        }
+        if u32_at(header, 20) == ZIP64_MARKER {
+            values.compressed_size = Some(u64_at(next(8)?, 0));
+        }
+        if u32_at(header, 42) == ZIP64_MARKER {
+            values.offset = Some(u64_at(next(8)?, 0));
+        }
+        if u16_at(header, 34) == ZIP64_DISK_MARKER {
+            values.disk = Some(u32_at(next(4)?, 0));
+        }
+        Some(values)
+    }
+
+    /// `extra` with its zip64 extra field replaced by one holding these
+    /// values, if any.
+    fn replace_in(&self, extra: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [self.size, self.compressed_size, self.offset]
+            .into_iter()
+            .flatten()
+        {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        if let Some(disk) = self.disk {
+            data.extend_from_slice(&disk.to_le_bytes());
+        }
+        let mut replaced = Vec::with_capacity(extra.len() + 4 + data.len());
+        if !data.is_empty() {
+            replaced.extend_from_slice(&ZIP64_EXTRA_FIELD_ID.to_le_bytes());
+            replaced.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            replaced.extend_from_slice(&data);
+        }
+        for (id, field) in extra_fields(extra) {
+            if id != ZIP64_EXTRA_FIELD_ID {
+                replaced.extend_from_slice(&id.to_le_bytes());
+                replaced.extend_from_slice(&(field.len() as u16).to_le_bytes());
+                replaced.extend_from_slice(field);
+            }
+        }
+        replaced
+    }
+}
+
+/// The ID and data of each field in an extra field, as far as they're
+/// intact.
+fn extra_fields(mut extra: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        let id = u16_at(extra.get(..4)?, 0);
+        let len = u16_at(extra, 2) as usize;
+        let data = extra.get(4..4 + len)?;
+        extra = &extra[4 + len..];
+        Some((id, data))
+    })
+}
+
+/// Check that there's a local header where each entry in the central
+/// directory says, and return the central directory rewritten so that, if
+/// `spanned`, each says where it is in the whole rather than in the segment
+/// it starts in.
+fn rewrite_central_directory(
+    central_directory: &[u8],
+    end: &End,
+    spanned: bool,
+    joined: &mut Concatenated<Segment>,
+) -> Result<Vec<u8>, RipunzipErrors> {
+    let corrupt = || invalid("the central directory is corrupt".to_string());
+    let mut rewritten = Vec::with_capacity(central_directory.len());
+    let mut rest = central_directory;
+    for _ in 0..end.entries {
+        let header = rest
+            .get(..CENTRAL_HEADER_LEN)
+            .filter(|header| u32_at(header, 0) == CENTRAL_HEADER_SIGNATURE)
+            .ok_or_else(corrupt)?;
+        let name_len = u16_at(header, 28) as usize;
+        let extra_len = u16_at(header, 30) as usize;
+        let comment_len = u16_at(header, 32) as usize;
+        let variable = rest
+            .get(CENTRAL_HEADER_LEN..CENTRAL_HEADER_LEN + name_len + extra_len + comment_len)
+            .ok_or_else(corrupt)?;
+        rest = &rest[CENTRAL_HEADER_LEN + variable.len()..];
+        let (raw_name, variable) = variable.split_at(name_len);
+        let (extra, comment) = variable.split_at(extra_len);
+        let name = String::from_utf8_lossy(raw_name);
+        let mut zip64 = Zip64Values::parse(header, extra).ok_or_else(corrupt)?;
+        let offset = zip64.offset.unwrap_or(u32_at(header, 42) as u64);
+        let local_header = if spanned {
+            let disk = zip64.disk.unwrap_or(u16_at(header, 34) as u32);
+            segment_start(joined, disk)
+                .and_then(|start| start.checked_add(offset))
+                .ok_or_else(|| invalid(format!("{name} is in a missing segment")))?
+        } else {
+            offset
+        };
+        let mut signature = [0u8; 4];
+        joined.seek(SeekFrom::Start(local_header))?;
+        if joined.read_exact(&mut signature).is_err()
+            || u32::from_le_bytes(signature) != LOCAL_HEADER_SIGNATURE
+        {
+            return Err(invalid(format!(
+                "{name} isn't where the central directory says, so the segments are out of order"
+            )));
+        }
+        let mut header = header.to_vec();
+        header[34..36].copy_from_slice(&0u16.to_le_bytes());
+        zip64.disk = None;
+        if local_header >= ZIP64_MARKER as u64 {
+            header[42..46].copy_from_slice(&ZIP64_MARKER.to_le_bytes());
+            zip64.offset = Some(local_header);
+        } else {
+            header[42..46].copy_from_slice(&(local_header as u32).to_le_bytes());
+            zip64.offset = None;
+        }
+        let extra = zip64.replace_in(extra);
+        let extra_len = u16::try_from(extra.len())
+            .map_err(|_| invalid(format!("{name}'s extra field is too long to rewrite")))?;
+        header[30..32].copy_from_slice(&extra_len.to_le_bytes());
+        rewritten.extend_from_slice(&header);
+        rewritten.extend_from_slice(raw_name);
+        rewritten.extend_from_slice(&extra);
+        rewritten.extend_from_slice(comment);
+    }
+    Ok(rewritten)
+}
+
+/// The rewritten central directory, which is to start at `offset`,
+/// followed by end of central directory records for it, all on one disk.
+fn end_records(mut central_directory: Vec<u8>, end: &End, offset: u64) -> Vec<u8> {
+    let size = central_directory.len() as u64;
+    let record_start = offset + size;
+    let zip64 = end.entries >= ZIP64_DISK_MARKER as u64
+        || size >= ZIP64_MARKER as u64
+        || offset >= ZIP64_MARKER as u64;
+    let out = &mut central_directory;
+    if zip64 {
+        out.extend_from_slice(&ZIP64_EOCD_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&(ZIP64_EOCD_LEN as u64 - 12).to_le_bytes());
+        out.extend_from_slice(&ZIP64_VERSION.to_le_bytes());
+        out.extend_from_slice(&ZIP64_VERSION.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&end.entries.to_le_bytes());
+        out.extend_from_slice(&end.entries.to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&record_start.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+    }
+    let entries = end.entries.min(ZIP64_DISK_MARKER as u64) as u16;
+    out.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&entries.to_le_bytes());
+    out.extend_from_slice(&entries.to_le_bytes());
+    out.extend_from_slice(&(size.min(ZIP64_MARKER as u64) as u32).to_le_bytes());
+    out.extend_from_slice(&(offset.min(ZIP64_MARKER as u64) as u32).to_le_bytes());
+    out.extend_from_slice(&(end.comment.len() as u16).to_le_bytes());
+    out.extend_from_slice(&end.comment);
+    central_directory
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Write},
+        path::{Path, PathBuf},
+    };
+
+    use tempfile::tempdir;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+    use super::{u16_at, u32_at, CENTRAL_HEADER_LEN, EOCD_LEN};
+    use crate::{RipunzipErrors, UnzipEngine, UnzipOptions};
+
+    fn sample_files() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("a.txt", b"Contents of A\n".to_vec()),
+            (
+                "b.bin",
+                (0..5000u32).map(|i| (i * 7919 % 251) as u8).collect(),
+            ),
+            ("c.txt", b"Contents of C\n".repeat(200)),
+        ]
+    }
+
+    fn sample_zip() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in sample_files() {
+            let options =
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&contents).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// Split `zip` as `zip -s` does into segments of `segment_len` bytes,
+    /// but the last, the first starting with a marker and everything
+    /// located relative to the start of the segment it's in.
+    fn split(zip: &[u8], segment_len: usize) -> Vec<Vec<u8>> {
+        let central_directory_start = ZipArchive::new(Cursor::new(zip))
+            .unwrap()
+            .central_directory_start() as usize;
+        let mut data = b"PK\x07\x08".to_vec();
+        data.extend_from_slice(zip);
+        let locate = |pos: usize| ((pos / segment_len) as u16, (pos % segment_len) as u32);
+        let eocd = data.len() - EOCD_LEN;
+        let mut pos = central_directory_start + 4;
+        while pos < eocd {
+            let (disk, offset) = locate(u32_at(&data, pos + 42) as usize + 4);
+            data[pos + 34..pos + 36].copy_from_slice(&disk.to_le_bytes());
+            data[pos + 42..pos + 46].copy_from_slice(&offset.to_le_bytes());
+            pos += CENTRAL_HEADER_LEN
+                + u16_at(&data, pos + 28) as usize
+                + u16_at(&data, pos + 30) as usize
+                + u16_at(&data, pos + 32) as usize;
+        }
+        let (disk, offset) = locate(central_directory_start + 4);
+        let (last_disk, _) = locate(data.len() - 1);
+        data[eocd + 4..eocd + 6].copy_from_slice(&last_disk.to_le_bytes());
+        data[eocd + 6..eocd + 8].copy_from_slice(&disk.to_le_bytes());
+        data[eocd + 16..eocd + 20].copy_from_slice(&offset.to_le_bytes());
+        data.chunks(segment_len).map(<[u8]>::to_vec).collect()
+    }
+
+    fn unzip_segments(
+        td: &Path,
+        segments: &[Vec<u8>],
+        order: &[usize],
+    ) -> Result<PathBuf, RipunzipErrors> {
+        let paths = order
+            .iter()
+            .map(|&i| {
+                let path = td.join(format!("part.z{:02}", i + 1));
+                std::fs::write(&path, &segments[i]).unwrap();
+                path
+            })
+            .collect();
+        let outdir = td.join("outdir");
+        let options = UnzipOptions {
+            output_directory: Some(outdir.clone()),
+            ..Default::default()
+        };
+        UnzipEngine::for_segments(paths)?.unzip(options)?;
+        Ok(outdir)
+    }
+
+    #[test]
+    fn test_segments() {
+        let zip = sample_zip();
+        for segments in [
+            split(&zip, 1000),
+            zip.chunks(1000).map(<[u8]>::to_vec).collect(),
+        ] {
+            assert!(segments.len() > 5);
+            let all: Vec<_> = (0..segments.len()).collect();
+            let td = tempdir().unwrap();
+            let outdir = unzip_segments(td.path(), &segments, &all).unwrap();
+            for (name, contents) in sample_files() {
+                assert_eq!(std::fs::read(outdir.join(name)).unwrap(), contents);
+            }
+
+            // The first segment holds the first local headers.
+            let mut swapped = all.clone();
+            swapped.swap(0, 1);
+            let mut missing = all.clone();
+            missing.remove(2);
+            for order in [swapped, missing, all[1..].to_vec()] {
+                let td = tempdir().unwrap();
+                match unzip_segments(td.path(), &segments, &order) {
+                    Err(RipunzipErrors::InvalidSegments { .. }) => {}
+                    result => panic!("{order:?}: {result:?}"),
+                }
+            }
+        }
+        assert!(matches!(
+            UnzipEngine::for_segments(Vec::new()),
+            Err(RipunzipErrors::InvalidSegments { .. })
+        ));
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
+use ssh2::{CheckResult, KnownHostFileKind, Session};
+
+use crate::RipunzipErrors;
+
+/// Private keys we try, in order, if the SSH agent can't authenticate us.
+const DEFAULT_KEY_FILES: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// A reader of a file over SFTP, which reads only the parts of the file
+/// which are asked for. Clones share a single SFTP session, and so take
+/// turns to read.
+#[derive(Clone)]
+pub(crate) struct SftpReader {
+    file: Arc<Mutex<ssh2::File>>,
+    len: u64,
+    pos: u64,
+}
+
+impl SftpReader {
+    /// Open a URI of the form `sftp://user@host:port/path`. The user
+    /// defaults to the current user and the port to 22. A path starting
+    /// with `/~/` is relative to the user's home directory. The server's
+    /// host key must be in `~/.ssh/known_hosts`, and we authenticate using
+    /// the SSH agent or, failing that, the usual private key files. The
+    /// user and path may be percent-encoded, as in `/my%20files/a.zip`.
+    pub(crate) fn open(uri: &str) -> Result<Self, RipunzipErrors> {
+        let unsupported = |reason: String| RipunzipErrors::UnsupportedUri {
+            uri: uri.to_string(),
+            reason,
+        };
+        let url = Url::parse(uri).map_err(|e| unsupported(e.to_string()))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| unsupported("no host".to_string()))?;
+        let port = url.port().unwrap_or(22);
+        let user = match url.username() {
+            "" => std::env::var("USER").map_err(|_| unsupported("no user".to_string()))?,
+            user => decode(user).map_err(unsupported)?,
+        };
+        let path = remote_path(&url).map_err(unsupported)?;
+        let ssh_dir = std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".ssh"))
+            .ok_or_else(|| unsupported("can't find ~/.ssh".to_string()))?;
+
+        let mut session = Session::new()?;
+        session.set_tcp_stream(TcpStream::connect((host, port))?);
+        session.handshake()?;
+        check_host_key(&session, host, port, &ssh_dir).map_err(unsupported)?;
+        authenticate(&session, &user, &ssh_dir).map_err(unsupported)?;
+
+        let mut file = session.sftp()?.open(Path::new(&path))?;
+        let len = file
+            .stat()?
+            .size
+            .ok_or_else(|| unsupported("server didn't report the file size".to_string()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+            len,
+            pos: 0,
+        })
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+}
+
+/// The path on the server of the file `url` names. It's relative to the
+/// user's home directory if it starts with `/~/`.
+fn remote_path(url: &Url) -> Result<String, String> {
+    let path = url.path();
+    decode(path.strip_prefix("/~/").unwrap_or(path))
+}
+
+/// Percent-decode part of a URI.
+fn decode(part: &str) -> Result<String, String> {
+    percent_decode_str(part)
+        .decode_utf8()
+        .map(String::from)
+        .map_err(|_| format!("{part} isn't UTF-8 once decoded"))
+}
+
+fn check_host_key(session: &Session, host: &str, port: u16, ssh_dir: &Path) -> Result<(), String> {
+    let mut known_hosts = session.known_hosts().map_err(|e| e.to_string())?;
+    known_hosts
+        .read_file(&ssh_dir.join("known_hosts"), KnownHostFileKind::OpenSSH)
+        .map_err(|e| format!("can't read known_hosts: {e}"))?;
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| "server sent no host key".to_string())?;
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(format!("host key for {host} has changed")),
+        CheckResult::NotFound => Err(format!("{host} is not in known_hosts")),
+        CheckResult::Failure => Err(format!("unable to check host key for {host}")),
+    }
+}
+
+fn authenticate(session: &Session, user: &str, ssh_dir: &Path) -> Result<(), String> {
+    if session.userauth_agent(user).is_ok() {
+        return Ok(());
+    }
+    for key_file in DEFAULT_KEY_FILES {
+        let key_file = ssh_dir.join(key_file);
+        if key_file.exists()
+            && session
+                .userauth_pubkey_file(user, None, &key_file, None)
+                .is_ok()
+        {
+            return Ok(());
+        }
+    }
+    Err(format!(
+        "unable to authenticate as {user} using the SSH agent or key files"
+    ))
+}
+
+impl Read for SftpReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        file.seek(SeekFrom::Start(self.pos))?;
+        let bytes_read = file.read(buf)?;
+        self.pos += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl Seek for SftpReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use super::remote_path;
+
+    #[test]
+    fn test_remote_path() {
+        let path = |uri: &str| remote_path(&Url::parse(uri).unwrap());
+        assert_eq!(path("sftp://host/srv/a.zip").unwrap(), "/srv/a.zip");
+        assert_eq!(path("sftp://host/~/a.zip").unwrap(), "a.zip");
+        assert_eq!(
+            path("sftp://user@host:2222/my%20files/50%25.zip").unwrap(),
+            "/my files/50%.zip"
+        );
+        // Spaces are encoded when parsing, and decoded again.
+        assert_eq!(
+            path("sftp://host/~/my files/a.zip").unwrap(),
+            "my files/a.zip"
+        );
+        assert!(path("sftp://host/%ff.zip").is_err());
+    }
+}
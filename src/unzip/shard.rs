@@ -0,0 +1,125 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex, PoisonError},
+};
+
+/// Where to put the file with the given enclosed name when sharding into
+/// `shards` directories: `shard-<k>/<basename>`, where `k` is the CRC-32 of
+/// its path within the zip file, with `/` separators, modulo `shards`. This
+/// is the same on every platform and every run, so anyone can work out
+/// which shard a file went to.
+pub(crate) fn shard_path(name: &Path, shards: usize) -> PathBuf {
+    let joined = name
+        .iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let shard = crc32fast::hash(joined.as_bytes()) as usize % shards;
+    let mut path = PathBuf::from(format!("shard-{shard}"));
+    if let Some(basename) = name.file_name() {
+        path.push(basename);
+    }
+    path
+}
+
+/// Stops two threads from writing to the same path at once, as when files
+/// from different directories with the same name are sharded together, so
+/// that the overwrite policy sees each file finished before deciding about
+/// the next.
+#[derive(Default)]
+pub(crate) struct PathLocks {
+    held: Mutex<HashSet<PathBuf>>,
+    released: Condvar,
+}
+
+impl PathLocks {
+    /// Wait until no other thread holds `path`, then hold it until the
+    /// returned guard is dropped.
+    pub(crate) fn lock(&self, path: &Path) -> PathGuard<'_> {
+        let mut held = self
+            .released
+            .wait_while(
+                self.held.lock().unwrap_or_else(PoisonError::into_inner),
+                |held| held.contains(path),
+            )
+            .unwrap_or_else(PoisonError::into_inner);
+        held.insert(path.to_path_buf());
+        PathGuard {
+            locks: self,
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+pub(crate) struct PathGuard<'a> {
+    locks: &'a PathLocks,
+    path: PathBuf,
+}
+
+impl Drop for PathGuard<'_> {
+    fn drop(&mut self) {
+        self.locks
+            .held
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.path);
+        self.locks.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use super::{shard_path, PathLocks};
+
+    #[test]
+    fn test_shard_path() {
+        // The CRC-32 of "a/b.txt" is 0x063e55bb, which is 104748475.
+        assert_eq!(
+            shard_path(Path::new("a/b.txt"), 10),
+            Path::new("shard-5/b.txt")
+        );
+        assert_eq!(
+            shard_path(Path::new("a/b.txt"), 1),
+            Path::new("shard-0/b.txt")
+        );
+        for shards in 1..20 {
+            let path = shard_path(Path::new("dir/file"), shards);
+            assert_eq!(path.file_name().unwrap(), "file");
+        }
+    }
+
+    #[test]
+    fn test_path_locks() {
+        let locks = PathLocks::default();
+        let writing = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        let _guard = locks.lock(Path::new("same"));
+                        assert!(!writing.swap(true, Ordering::SeqCst));
+                        std::thread::yield_now();
+                        writing.store(false, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        // Other paths aren't held up.
+        let _a = locks.lock(Path::new("a"));
+        let _b = locks.lock(Path::new("b"));
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha512};
+
+use crate::RipunzipErrors;
+
+/// Where to find a detached signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureSource {
+    /// The signature itself.
+    Bytes(Vec<u8>),
+    /// A file containing just the signature.
+    Path(PathBuf),
+}
+
+/// How to check a detached signature over the whole zip file, for
+/// [`UnzipOptions::verify_signature`](super::UnzipOptions::verify_signature).
+/// The signature must be a raw 64-byte Ed25519ph signature, that is
+/// Ed25519 over the SHA-512 hash of the zip file with no context, as
+/// defined by RFC 8032, so that it can be checked without holding the
+/// zip file in memory. Plain Ed25519 signatures, and those made by
+/// minisign or signify, won't match. OpenSSL 3.2 or later can make one:
+///
+/// ```text
+/// openssl genpkey -algorithm ed25519 -out key.pem
+/// openssl pkeyutl -sign -rawin -inkey key.pem -pkeyopt instance:Ed25519ph \
+///     -in archive.zip -out archive.zip.sig
+/// ```
+///
+/// and the public key, as hex, is the last 32 bytes of the output of
+/// `openssl pkey -in key.pem -pubout -outform DER`. In Rust,
+/// `ed25519_dalek::SigningKey::sign_prehashed` with a SHA-512 hash of the
+/// zip file and no context makes one too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureConfig {
+    /// The signer's 32-byte Ed25519 public key.
+    pub public_key: [u8; 32],
+    /// The signature.
+    pub signature: SignatureSource,
+}
+
+impl SignatureConfig {
+    fn signature(&self) -> Result<Signature, RipunzipErrors> {
+        let bytes = match &self.signature {
+            SignatureSource::Bytes(bytes) => bytes.clone(),
+            SignatureSource::Path(path) => {
+                std::fs::read(path).map_err(|e| RipunzipErrors::IOErrorWithContext {
+                    context: format!("Failed to read signature {}", path.display()),
+                    source: e,
+                })?
+            }
+        };
+        Signature::from_slice(&bytes).map_err(|_| invalid("it isn't 64 bytes long"))
+    }
+
+    /// Read the whole of the zip file from `reader`, also writing it to
+    /// `copy`, if given, and check that it's what was signed.
+    pub(crate) fn verify(
+        &self,
+        reader: &mut dyn Read,
+        copy: Option<&mut dyn Write>,
+    ) -> Result<(), RipunzipErrors> {
+        // Fail before reading anything if we could never succeed.
+        let signature = self.signature()?;
+        let public_key =
+            VerifyingKey::from_bytes(&self.public_key).map_err(|_| invalid("bad public key"))?;
+        let mut hasher = HashingWriter {
+            hasher: Sha512::new(),
+            copy,
+        };
+        std::io::copy(reader, &mut hasher)?;
+        public_key
+            .verify_prehashed(hasher.hasher, None, &signature)
+            .map_err(|_| invalid("it doesn't match the zip file"))
+    }
+}
+
+fn invalid(reason: &str) -> RipunzipErrors {
+    RipunzipErrors::SignatureInvalid {
+        reason: reason.to_string(),
+    }
+}
+
+struct HashingWriter<'w> {
+    hasher: Sha512,
+    copy: Option<&'w mut dyn Write>,
+}
+
+impl Write for HashingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let count = match &mut self.copy {
+            Some(copy) => copy.write(buf)?,
+            None => buf.len(),
+        };
+        self.hasher.update(&buf[..count]);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.copy {
+            Some(copy) => copy.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+    use sha2::{Digest, Sha512};
+
+    use super::{SignatureConfig, SignatureSource};
+    use crate::RipunzipErrors;
+
+    /// A signature over `data` and the public key to check it with.
+    fn sign(data: &[u8]) -> SignatureConfig {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let signature = signing_key
+            .sign_prehashed(Sha512::new().chain_update(data), None)
+            .unwrap();
+        SignatureConfig {
+            public_key: signing_key.verifying_key().to_bytes(),
+            signature: SignatureSource::Bytes(signature.to_bytes().to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let config = sign(b"Contents");
+        let mut copy = Vec::new();
+        config
+            .verify(&mut &b"Contents"[..], Some(&mut copy))
+            .unwrap();
+        assert_eq!(copy, b"Contents");
+
+        let result = config.verify(&mut &b"Contents!"[..], None);
+        assert!(matches!(
+            result,
+            Err(RipunzipErrors::SignatureInvalid { .. })
+        ));
+
+        let truncated = SignatureConfig {
+            signature: SignatureSource::Bytes(vec![0; 63]),
+            ..config.clone()
+        };
+        let result = truncated.verify(&mut &b"Contents"[..], None);
+        assert!(matches!(
+            result,
+            Err(RipunzipErrors::SignatureInvalid { .. })
+        ));
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let SignatureSource::Bytes(signature) = &config.signature else {
+            unreachable!()
+        };
+        std::fs::write(file.path(), signature).unwrap();
+        let from_file = SignatureConfig {
+            signature: SignatureSource::Path(file.path().to_path_buf()),
+            ..config
+        };
+        from_file.verify(&mut &b"Contents"[..], None).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::PathBuf;
+
+use super::{dry_run::enclosed_components, UnzipOptions, ZipEntryMetadata};
+
+/// The one directory containing everything in the zip file, if there is
+/// one and there's anything in it. Entries with unsafe names, which won't
+/// be extracted, are ignored.
+fn single_root<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    normalize_separators: bool,
+) -> Option<String> {
+    let mut root: Option<&str> = None;
+    let mut anything_inside = false;
+    for name in names {
+        let Some(components) = enclosed_components(name, normalize_separators) else {
+            continue;
+        };
+        let Some((first, rest)) = components.split_first() else {
+            continue;
+        };
+        let is_dir = name.ends_with('/')
+            || (name.ends_with('\\') && (normalize_separators || cfg!(windows)));
+        if rest.is_empty() && !is_dir {
+            // A file at the top level.
+            return None;
+        }
+        if root.is_some_and(|root| root != *first) {
+            return None;
+        }
+        root = Some(first);
+        anything_inside |= !rest.is_empty();
+    }
+    root.filter(|_| anything_inside).map(str::to_string)
+}
+
+/// Replace [`UnzipOptions::flatten_single_root`], if set, with an
+/// equivalent prefix mapping from the single root directory among
+/// `entries` to the output directory, if there is such a directory.
+pub(crate) fn apply_flatten_single_root(options: &mut UnzipOptions, entries: &[ZipEntryMetadata]) {
+    if !std::mem::take(&mut options.flatten_single_root) {
+        return;
+    }
+    let names = entries.iter().map(|entry| entry.name.as_str());
+    let Some(root) = single_root(names, options.normalize_separators) else {
+        log::debug!("No single root directory to flatten");
+        return;
+    };
+    log::info!("Extracting the contents of {root}/ into the output directory");
+    let directory = options
+        .output_directory
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."));
+    options.prefix_map.push((root, directory));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::single_root;
+
+    #[test]
+    fn test_single_root() {
+        let root = |names: &[&str]| single_root(names.iter().copied(), false);
+        assert_eq!(
+            root(&["app-1.0/", "app-1.0/bin/app", "app-1.0/README"]),
+            Some("app-1.0".to_string())
+        );
+        // The root directory needn't have an entry of its own.
+        assert_eq!(root(&["./app/a", "app/b/c"]), Some("app".to_string()));
+        assert_eq!(root(&["app/a", "README"]), None);
+        assert_eq!(root(&["app/a", "other/b"]), None);
+        // Nothing to flatten.
+        assert_eq!(root(&["app/"]), None);
+        assert_eq!(root(&[]), None);
+        // Unsafe names don't count.
+        assert_eq!(root(&["app/a", "../evil"]), Some("app".to_string()));
+        assert_eq!(
+            single_root(["app\\a", "app\\b"], true),
+            Some("app".to_string())
+        );
+    }
+}
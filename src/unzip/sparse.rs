@@ -0,0 +1,112 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom, Write},
+};
+
+/// The granularity at which we look for runs of zeros. Filesystems allocate
+/// whole blocks, so there's no point skipping smaller runs, and most use
+/// blocks of this size.
+const BLOCK_SIZE: u64 = 4096;
+
+/// Writes to a newly created file, optionally seeking past blocks which are
+/// entirely zero rather than writing them, so that filesystems which
+/// support it leave holes in the file instead of allocating disk space.
+/// Call [`SparseWriter::finish`] once everything has been written, to set
+/// the length of the file if it ends with zeros.
+#[derive(Debug)]
+pub(crate) struct SparseWriter {
+    file: File,
+    sparse: bool,
+    /// The number of bytes written so far, including skipped zeros.
+    len: u64,
+    /// Zeros which we've skipped but not yet seeked past.
+    pending_zeros: u64,
+}
+
+impl SparseWriter {
+    pub(crate) fn new(file: File, sparse: bool) -> Self {
+        Self {
+            file,
+            sparse,
+            len: 0,
+            pending_zeros: 0,
+        }
+    }
+
+    pub(crate) fn finish(self) -> std::io::Result<File> {
+        if self.pending_zeros > 0 {
+            self.file.set_len(self.len)?;
+        }
+        Ok(self.file)
+    }
+}
+
+impl Write for SparseWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.sparse {
+            let written = self.file.write(buf)?;
+            self.len += written as u64;
+            return Ok(written);
+        }
+        let mut remaining = buf;
+        while !remaining.is_empty() {
+            // Line chunks up with the file's blocks.
+            let to_block_end = BLOCK_SIZE - self.len % BLOCK_SIZE;
+            let (chunk, rest) = remaining.split_at(remaining.len().min(to_block_end as usize));
+            if chunk.iter().all(|&b| b == 0) {
+                self.pending_zeros += chunk.len() as u64;
+            } else {
+                if self.pending_zeros > 0 {
+                    self.file
+                        .seek(SeekFrom::Current(self.pending_zeros as i64))?;
+                    self.pending_zeros = 0;
+                }
+                self.file.write_all(chunk)?;
+            }
+            self.len += chunk.len() as u64;
+            remaining = rest;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::tempdir;
+
+    use super::{SparseWriter, BLOCK_SIZE};
+
+    #[test]
+    fn test_sparse_writer() {
+        let td = tempdir().unwrap();
+        let block = BLOCK_SIZE as usize;
+        let mut data = vec![0u8; block * 5 + 10];
+        data[3] = 1;
+        data[block * 2 + 7] = 2;
+        // Check both zero runs in the middle and at the end, written in
+        // pieces which don't line up with blocks.
+        for sparse in [false, true] {
+            let path = td.path().join(format!("{sparse}"));
+            let mut writer = SparseWriter::new(std::fs::File::create(&path).unwrap(), sparse);
+            for piece in data.chunks(1000) {
+                writer.write_all(piece).unwrap();
+            }
+            writer.finish().unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), data);
+        }
+    }
+}
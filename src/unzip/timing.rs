@@ -0,0 +1,211 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    cell::Cell,
+    io::{Read, Seek, SeekFrom, Write},
+    time::{Duration, Instant},
+};
+
+/// How long was spent on each part of extracting files' contents, added up
+/// across all threads. Creating directories, checking existing files and
+/// the like aren't counted.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ExtractionTimings {
+    /// Time spent decompressing files, and checking their CRCs.
+    pub decompressing: Duration,
+    /// Time spent reading the zip file, whether compressed data or stored,
+    /// that is uncompressed, files. For a URI this includes waiting for
+    /// downloads.
+    pub reading: Duration,
+    /// Time spent writing files.
+    pub writing: Duration,
+}
+
+/// What seems to have limited the speed of an unzip.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Bottleneck {
+    /// Decompression, so more threads may help, if there are spare cores.
+    Decompression,
+    /// Reading the zip file, so for a URI a larger readahead limit may help.
+    Reading,
+    /// Writing files, so more threads are unlikely to help.
+    Writing,
+}
+
+impl ExtractionTimings {
+    pub(crate) fn add(&mut self, other: &ExtractionTimings) {
+        self.decompressing += other.decompressing;
+        self.reading += other.reading;
+        self.writing += other.writing;
+    }
+
+    /// The time spent on all parts together.
+    pub fn total(&self) -> Duration {
+        self.decompressing + self.reading + self.writing
+    }
+
+    /// Guess what limited the speed of the unzip: whichever took longest.
+    /// `None` if nothing was timed.
+    pub fn bottleneck(&self) -> Option<Bottleneck> {
+        [
+            (self.decompressing, Bottleneck::Decompression),
+            (self.reading, Bottleneck::Reading),
+            (self.writing, Bottleneck::Writing),
+        ]
+        .into_iter()
+        .filter(|(duration, _)| !duration.is_zero())
+        .max_by_key(|(duration, _)| *duration)
+        .map(|(_, bottleneck)| bottleneck)
+    }
+}
+
+/// The CPU time the current thread has used so far, or `None` where that
+/// can't be measured: on Windows, NetBSD, Solaris and illumos, among others.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    let time = rustix::time::clock_gettime(rustix::time::ClockId::ThreadCPUTime);
+    Some(Duration::new(
+        u64::try_from(time.tv_sec).ok()?,
+        u32::try_from(time.tv_nsec).ok()?,
+    ))
+}
+
+/// The CPU time the current thread has used so far, or `None` where that
+/// can't be measured: on Windows, NetBSD, Solaris and illumos, among others.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+)))]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Wraps a reader or writer, adding up the time spent reading or writing.
+pub(crate) struct Timed<T> {
+    inner: T,
+    elapsed: Duration,
+}
+
+impl<T> Timed<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self {
+            inner,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub(crate) fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn time<O>(&mut self, f: impl FnOnce(&mut T) -> O) -> O {
+        let start = Instant::now();
+        let result = f(&mut self.inner);
+        self.elapsed += start.elapsed();
+        result
+    }
+}
+
+impl<R: Read> Read for Timed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.time(|inner| inner.read(buf))
+    }
+}
+
+impl<W: Write> Write for Timed<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.time(|inner| inner.write(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.time(|inner| inner.flush())
+    }
+}
+
+thread_local! {
+    static SOURCE_READING: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// How long this thread has spent reading zip files through
+/// [`TimedSource`]s, so that the time spent reading an entry's compressed
+/// data can be told apart from the time spent decompressing it.
+pub(crate) fn source_reading_time() -> Duration {
+    SOURCE_READING.with(Cell::get)
+}
+
+/// Wraps the reader a zip file is read from, adding up the time each
+/// thread spends reading it, for [`source_reading_time`].
+#[derive(Clone)]
+pub(crate) struct TimedSource<R>(R);
+
+impl<R> TimedSource<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self(inner)
+    }
+
+    fn time<O>(&mut self, f: impl FnOnce(&mut R) -> O) -> O {
+        let start = Instant::now();
+        let result = f(&mut self.0);
+        SOURCE_READING.with(|total| total.set(total.get() + start.elapsed()));
+        result
+    }
+}
+
+impl<R: Read> Read for TimedSource<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.time(|inner| inner.read(buf))
+    }
+}
+
+impl<R: Seek> Seek for TimedSource<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.time(|inner| inner.seek(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Bottleneck, ExtractionTimings};
+
+    #[test]
+    fn test_bottleneck() {
+        assert_eq!(ExtractionTimings::default().bottleneck(), None);
+        let mut timings = ExtractionTimings {
+            decompressing: Duration::from_millis(30),
+            reading: Duration::from_millis(10),
+            writing: Duration::from_millis(20),
+        };
+        assert_eq!(timings.bottleneck(), Some(Bottleneck::Decompression));
+        assert_eq!(timings.total(), Duration::from_millis(60));
+        timings.add(&ExtractionTimings {
+            writing: Duration::from_millis(20),
+            ..Default::default()
+        });
+        assert_eq!(timings.bottleneck(), Some(Bottleneck::Writing));
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    io::{ErrorKind, Read},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::RipunzipErrors;
+
+/// How much uncompressed data has been read so far, by every thread, for
+/// [`UnzipOptions::max_total_size`](super::UnzipOptions::max_total_size).
+pub(crate) struct TotalSize {
+    limit: Option<u64>,
+    read: AtomicU64,
+}
+
+impl TotalSize {
+    /// Count towards `limit`, starting from `already` read, failing at once
+    /// if that's over the limit.
+    pub(crate) fn new(limit: Option<u64>, already: u64) -> Result<Self, RipunzipErrors> {
+        if let Some(limit) = limit.filter(|limit| already > *limit) {
+            return Err(RipunzipErrors::TotalSizeExceeded { limit });
+        }
+        Ok(Self {
+            limit,
+            read: AtomicU64::new(already),
+        })
+    }
+
+    /// Wrap a reader of an entry's decompressed data so that it fails once
+    /// the total read exceeds the limit, if there is one.
+    pub(crate) fn limit<'r>(&'r self, reader: Box<dyn Read + 'r>) -> Box<dyn Read + 'r> {
+        match self.limit {
+            None => reader,
+            Some(limit) => Box::new(TotalSizeLimitingReader {
+                inner: reader,
+                total: self,
+                limit,
+            }),
+        }
+    }
+}
+
+/// The total was exceeded, carried through [`std::io::Error`] so that it
+/// can be told apart from other failures to read an entry.
+#[derive(Debug)]
+struct TotalSizeExceeded {
+    limit: u64,
+}
+
+impl std::fmt::Display for TotalSizeExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "more than {} bytes extracted", self.limit)
+    }
+}
+
+impl std::error::Error for TotalSizeExceeded {}
+
+struct TotalSizeLimitingReader<'r> {
+    inner: Box<dyn Read + 'r>,
+    total: &'r TotalSize,
+    limit: u64,
+}
+
+impl Read for TotalSizeLimitingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        let read = self.total.read.fetch_add(count as u64, Ordering::Relaxed) + count as u64;
+        if read > self.limit {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                TotalSizeExceeded { limit: self.limit },
+            ));
+        }
+        Ok(count)
+    }
+}
+
+/// If `e` is because the total size was exceeded, the error to report for
+/// it.
+pub(crate) fn total_size_error(e: &std::io::Error) -> Option<RipunzipErrors> {
+    let exceeded = e.get_ref()?.downcast_ref::<TotalSizeExceeded>()?;
+    Some(RipunzipErrors::TotalSizeExceeded {
+        limit: exceeded.limit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::{total_size_error, TotalSize};
+    use crate::RipunzipErrors;
+
+    #[test]
+    fn test_total_size() {
+        let total = TotalSize::new(Some(10), 0).unwrap();
+        let mut contents = Vec::new();
+        total
+            .limit(Box::new(&[0u8; 6][..]))
+            .read_to_end(&mut contents)
+            .unwrap();
+        // The limit is on the total across all readers.
+        let e = total
+            .limit(Box::new(&[0u8; 6][..]))
+            .read_to_end(&mut contents)
+            .unwrap_err();
+        assert!(matches!(
+            total_size_error(&e),
+            Some(RipunzipErrors::TotalSizeExceeded { limit: 10 })
+        ));
+
+        let unlimited = TotalSize::new(None, 100).unwrap();
+        for _ in 0..3 {
+            unlimited
+                .limit(Box::new(&[0u8; 6][..]))
+                .read_to_end(&mut contents)
+                .unwrap();
+        }
+
+        // What was read before counts too.
+        let total = TotalSize::new(Some(10), 8).unwrap();
+        assert!(total
+            .limit(Box::new(&[0u8; 6][..]))
+            .read_to_end(&mut contents)
+            .is_err());
+        assert!(matches!(
+            TotalSize::new(Some(10), 11),
+            Err(RipunzipErrors::TotalSizeExceeded { limit: 10 })
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::Write;
+
+/// A function which is given each chunk of an entry's decompressed data in
+/// turn and returns what to write in its place. It's called once more with
+/// an empty chunk after all the data, so that it can return anything it's
+/// held back, such as the end of a multi-byte character split between
+/// chunks.
+pub type ByteTransformer = Box<dyn FnMut(&[u8]) -> Vec<u8> + Send>;
+
+/// A function deciding, from an entry's name within the zip file, whether
+/// to transform the entry's data as it's written, returning the
+/// [`ByteTransformer`] to do so. Both are called on whichever worker thread
+/// is extracting the entry, so must be `Send`.
+pub type EntryTransform<'a> = Box<dyn Fn(&str) -> Option<ByteTransformer> + Send + Sync + 'a>;
+
+/// A [`Write`] which passes everything written to it through a
+/// [`ByteTransformer`] on its way to another.
+pub(crate) struct TransformingWriter<W: Write> {
+    inner: W,
+    transformer: ByteTransformer,
+}
+
+impl<W: Write> TransformingWriter<W> {
+    pub(crate) fn new(inner: W, transformer: ByteTransformer) -> Self {
+        Self { inner, transformer }
+    }
+
+    /// Write whatever the transformer has held back.
+    pub(crate) fn finish(mut self) -> std::io::Result<()> {
+        let rest = (self.transformer)(&[]);
+        self.inner.write_all(&rest)?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for TransformingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // An empty chunk means the end of the data to the transformer.
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let transformed = (self.transformer)(buf);
+        self.inner.write_all(&transformed)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::TransformingWriter;
+
+    #[test]
+    fn test_transforming_writer() {
+        let mut out = Vec::new();
+        // Upper-cases whole words, holding back any partial word.
+        let mut pending = Vec::new();
+        let mut writer = TransformingWriter::new(
+            &mut out,
+            Box::new(move |chunk: &[u8]| {
+                pending.extend_from_slice(chunk);
+                let split = match chunk {
+                    [] => pending.len(),
+                    _ => pending
+                        .iter()
+                        .rposition(|b| *b == b' ')
+                        .map_or(0, |i| i + 1),
+                };
+                pending
+                    .drain(..split)
+                    .map(|b| b.to_ascii_uppercase())
+                    .collect()
+            }),
+        );
+        writer.write_all(b"hello wo").unwrap();
+        writer.write_all(b"").unwrap();
+        writer.write_all(b"rld and").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(out, b"HELLO WORLD AND");
+    }
+}
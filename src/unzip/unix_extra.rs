@@ -0,0 +1,254 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    io::{Read, Seek},
+    path::Path,
+};
+
+use zip::read::ZipFile;
+
+use super::{
+    local_header::{read_local_extra_field, u16_at, u32_at, u64_at},
+    mtimes::FileTime,
+};
+use crate::RipunzipErrors;
+
+/// Info-ZIP's extended timestamp, in seconds since the Unix epoch, signed
+/// so as to reach back to 1901.
+const EXTENDED_TIMESTAMP_ID: u16 = 0x5455;
+/// Info-ZIP's Unix extra field with 16-bit user and group IDs, only
+/// stored in local headers.
+const UNIX_TYPE_2_ID: u16 = 0x7855;
+/// Info-ZIP's Unix extra field with user and group IDs of any size.
+const UNIX_TYPE_3_ID: u16 = 0x7875;
+/// Windows times, in 100ns units since 1601.
+const NTFS_ID: u16 = 0x000a;
+const NTFS_TIMES_TAG: u16 = 0x0001;
+/// 100ns intervals between 1601 and the Unix epoch.
+const NTFS_UNIX_EPOCH: i64 = 116_444_736_000_000_000;
+
+/// What an entry's extra fields record about it beyond the central
+/// directory's DOS time, which is only to two seconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct UnixExtra {
+    pub(crate) modified: Option<FileTime>,
+    pub(crate) accessed: Option<FileTime>,
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
+}
+
+impl UnixExtra {
+    /// Parse the extra fields we know of from a block of them, ignoring
+    /// anything malformed. NTFS times, being more precise, are preferred
+    /// to extended timestamps.
+    pub(crate) fn parse(mut extra: &[u8]) -> Self {
+        let mut parsed = Self::default();
+        let mut ntfs = Self::default();
+        while extra.len() >= 4 {
+            let id = u16_at(extra, 0);
+            let len = u16_at(extra, 2) as usize;
+            let data = &extra[4..(4 + len).min(extra.len())];
+            match id {
+                EXTENDED_TIMESTAMP_ID if !data.is_empty() => {
+                    // Whichever times the flags say are present follow in
+                    // order, though the central directory only has room
+                    // for the modification time.
+                    let flags = data[0];
+                    let mut times = data[1..]
+                        .chunks_exact(4)
+                        .map(|time| FileTime::from_unix(u32_at(time, 0) as i32 as i64, 0));
+                    if flags & 1 != 0 {
+                        parsed.modified = times.next();
+                    }
+                    if flags & 2 != 0 {
+                        parsed.accessed = times.next();
+                    }
+                }
+                UNIX_TYPE_2_ID if data.len() >= 4 => {
+                    parsed.uid = Some(u16_at(data, 0) as u32);
+                    parsed.gid = Some(u16_at(data, 2) as u32);
+                }
+                UNIX_TYPE_3_ID if data.first() == Some(&1) => {
+                    let (uid, rest) = variable_id(&data[1..]);
+                    let (gid, _) = variable_id(rest);
+                    parsed.uid = uid;
+                    parsed.gid = gid;
+                }
+                NTFS_ID if data.len() >= 4 => {
+                    let mut attributes = &data[4..];
+                    while attributes.len() >= 4 {
+                        let tag = u16_at(attributes, 0);
+                        let size = u16_at(attributes, 2) as usize;
+                        let value = &attributes[4..(4 + size).min(attributes.len())];
+                        if tag == NTFS_TIMES_TAG && value.len() >= 16 {
+                            ntfs.modified = FileTime::from_ntfs(u64_at(value, 0));
+                            ntfs.accessed = FileTime::from_ntfs(u64_at(value, 8));
+                        }
+                        attributes = &attributes[(4 + size).min(attributes.len())..];
+                    }
+                }
+                _ => {}
+            }
+            extra = &extra[(4 + len).min(extra.len())..];
+        }
+        Self {
+            modified: ntfs.modified.or(parsed.modified),
+            accessed: ntfs.accessed.or(parsed.accessed),
+            ..parsed
+        }
+    }
+
+    /// Fill in whatever this lacks from `other`.
+    fn or(self, other: Self) -> Self {
+        Self {
+            modified: self.modified.or(other.modified),
+            accessed: self.accessed.or(other.accessed),
+            uid: self.uid.or(other.uid),
+            gid: self.gid.or(other.gid),
+        }
+    }
+
+    /// What the extra fields of `file` record, reading its local header
+    /// with `header_reader`, since Info-ZIP only stores user and group IDs
+    /// and access times there, and falling back to the central directory.
+    pub(crate) fn of<R: Read + Seek, F: Read>(
+        header_reader: &mut R,
+        file: &ZipFile<F>,
+    ) -> Result<Self, RipunzipErrors> {
+        let local = read_local_extra_field(header_reader, file.header_start())?;
+        let central = file.extra_data().unwrap_or_default();
+        Ok(Self::parse(&local).or(Self::parse(central)))
+    }
+}
+
+impl FileTime {
+    fn from_ntfs(time: u64) -> Option<Self> {
+        // Zero means that the time isn't recorded.
+        if time == 0 {
+            return None;
+        }
+        let since_epoch = (time as i64).checked_sub(NTFS_UNIX_EPOCH)?;
+        Some(Self::from_unix(
+            since_epoch.div_euclid(10_000_000),
+            since_epoch.rem_euclid(10_000_000) as u32 * 100,
+        ))
+    }
+}
+
+/// An ID preceded by its size in bytes, and whatever follows. IDs too
+/// large for 32 bits are ignored.
+fn variable_id(data: &[u8]) -> (Option<u32>, &[u8]) {
+    let Some((&size, rest)) = data.split_first() else {
+        return (None, data);
+    };
+    let size = size as usize;
+    if rest.len() < size {
+        return (None, &[]);
+    }
+    let (id, rest) = rest.split_at(size);
+    let value = id.iter().rev().try_fold(0u32, |value, byte| {
+        value.checked_mul(256).map(|v| v | *byte as u32)
+    });
+    (value, rest)
+}
+
+/// Give `path`, or the symbolic link if it is one, the user and group IDs
+/// recorded for it, if any. Usually only root may do this. Only supported
+/// on Unix; elsewhere this does nothing.
+pub(crate) fn set_owner(path: &Path, extra: &UnixExtra) -> Result<(), RipunzipErrors> {
+    #[cfg(unix)]
+    {
+        use rustix::fs::{AtFlags, Gid, Uid, CWD};
+        // -1 would mean to leave the ID alone.
+        let uid = extra.uid.filter(|id| *id != u32::MAX).map(Uid::from_raw);
+        let gid = extra.gid.filter(|id| *id != u32::MAX).map(Gid::from_raw);
+        if uid.is_none() && gid.is_none() {
+            return Ok(());
+        }
+        rustix::fs::chownat(CWD, path, uid, gid, AtFlags::SYMLINK_NOFOLLOW).map_err(|e| {
+            RipunzipErrors::IOErrorWithContext {
+                context: format!("Failed to set the owner of {}", path.display()),
+                source: e.into(),
+            }
+        })?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, extra);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileTime, UnixExtra};
+
+    #[test]
+    fn test_parse() {
+        let mut extra = Vec::new();
+        // Extended timestamp with modification and access times.
+        extra.extend([0x55, 0x54, 9, 0, 3]);
+        extra.extend(1_600_000_000u32.to_le_bytes());
+        extra.extend(1_700_000_000u32.to_le_bytes());
+        // Unix type 3 with a 4-byte UID and 2-byte GID.
+        extra.extend([0x75, 0x78, 9, 0, 1, 4]);
+        extra.extend(1000u32.to_le_bytes());
+        extra.extend([2, 0xe8, 0x03]);
+        assert_eq!(
+            UnixExtra::parse(&extra),
+            UnixExtra {
+                modified: Some(FileTime::from_unix(1_600_000_000, 0)),
+                accessed: Some(FileTime::from_unix(1_700_000_000, 0)),
+                uid: Some(1000),
+                gid: Some(1000),
+            }
+        );
+
+        // NTFS times are more precise, so win.
+        extra.extend([0x0a, 0, 32, 0, 0, 0, 0, 0, 1, 0, 24, 0]);
+        let ntfs = |seconds: u64, hundreds_of_ns: u64| {
+            (116_444_736_000_000_000 + seconds * 10_000_000 + hundreds_of_ns).to_le_bytes()
+        };
+        extra.extend(ntfs(1_600_000_000, 1_234_567));
+        extra.extend([0; 16]);
+        let parsed = UnixExtra::parse(&extra);
+        assert_eq!(
+            parsed.modified,
+            Some(FileTime::from_unix(1_600_000_000, 123_456_700))
+        );
+        // A zero time isn't recorded.
+        assert_eq!(parsed.accessed, Some(FileTime::from_unix(1_700_000_000, 0)));
+
+        // Central directory style, with only the modification time, and
+        // Unix type 2.
+        let mut extra = vec![0x55, 0x54, 5, 0, 3];
+        extra.extend(1_600_000_000u32.to_le_bytes());
+        extra.extend([0x55, 0x78, 4, 0, 0xe8, 0x03, 0x64, 0]);
+        assert_eq!(
+            UnixExtra::parse(&extra),
+            UnixExtra {
+                modified: Some(FileTime::from_unix(1_600_000_000, 0)),
+                accessed: None,
+                uid: Some(1000),
+                gid: Some(100),
+            }
+        );
+        // Extended timestamps before 1970 are negative.
+        let mut extra = vec![0x55, 0x54, 5, 0, 1];
+        extra.extend((-86_400i32).to_le_bytes());
+        assert_eq!(
+            UnixExtra::parse(&extra).modified,
+            Some(FileTime::from_unix(-86_400, 0))
+        );
+
+        // Truncated fields are ignored.
+        assert_eq!(
+            UnixExtra::parse(&[0x75, 0x78, 3, 0, 1, 4, 0]),
+            Default::default()
+        );
+    }
+}
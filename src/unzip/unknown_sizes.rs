@@ -0,0 +1,58 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/// The uncompressed size recorded for an entry whose real size is in a
+/// Zip64 extra field, which some broken tools leave out.
+const ZIP64_MARKER: u64 = 0xFFFFFFFF;
+
+/// What to assume about the uncompressed size of an entry when the zip file
+/// doesn't say: when the central directory records `0xFFFFFFFF`, meaning
+/// that the size is in a Zip64 extra field, but there isn't one. Entries
+/// written with data descriptors are fine, since the sizes from the data
+/// descriptor are repeated in the central directory. Whatever's assumed,
+/// the entry is extracted in full, and progress adds up to the same total
+/// by the end. Such entries are still skipped by
+/// [`UnzipOptions::max_entry_size`](super::UnzipOptions::max_entry_size)
+/// unless it's at least `0xFFFFFFFF`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum UnknownSizes {
+    /// Assume it's the same as the compressed size, so that progress is
+    /// reported as the entry is extracted, if not at an even rate.
+    #[default]
+    Estimate,
+    /// Assume nothing, so that progress for the entry is reported only once
+    /// it's all been extracted.
+    Stream,
+}
+
+impl UnknownSizes {
+    /// The uncompressed size to assume for an entry, or `None` if it's
+    /// unknown and shouldn't be estimated.
+    pub(crate) fn size(self, size: u64, compressed_size: u64) -> Option<u64> {
+        if size != ZIP64_MARKER {
+            return Some(size);
+        }
+        match self {
+            UnknownSizes::Estimate => Some(compressed_size),
+            UnknownSizes::Stream => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnknownSizes;
+
+    #[test]
+    fn test_unknown_sizes() {
+        assert_eq!(UnknownSizes::Estimate.size(10, 5), Some(10));
+        assert_eq!(UnknownSizes::Stream.size(10, 5), Some(10));
+        assert_eq!(UnknownSizes::Estimate.size(0xFFFFFFFF, 5), Some(5));
+        assert_eq!(UnknownSizes::Stream.size(0xFFFFFFFF, 5), None);
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::PathBuf;
+
+use super::{Cookies, RootCertificates};
+
+/// Options for fetching a zip file over HTTP(S), for
+/// [`UnzipEngine::for_uri_with_options`](super::UnzipEngine::for_uri_with_options).
+/// The defaults are what [`UnzipEngine::for_uri`](super::UnzipEngine::for_uri)
+/// uses.
+#[derive(Clone, Default)]
+pub struct UriOptions {
+    /// Other URIs from which the same zip file can be fetched. Each must
+    /// report the same length, and is assumed to have the same contents.
+    /// Requests go to the URI and each mirror in turn, and if a request
+    /// fails, or a download fails part way through, the others are tried.
+    /// Mirrors which can't be reached to begin with are ignored.
+    pub mirrors: Vec<String>,
+    /// Certificate authorities to trust for HTTPS, as well as the TLS
+    /// backend's usual ones.
+    pub root_certificates: RootCertificates,
+    /// Cookies to send, to the servers they're for.
+    pub cookies: Cookies,
+    /// Where to put the temporary copy of the zip file made if the server
+    /// doesn't support range requests, and those of any nested zip files
+    /// later opened with [`UnzipEngine::for_entry`](super::UnzipEngine::for_entry).
+    /// By default, the system's temporary directory.
+    pub temp_dir: Option<PathBuf>,
+}
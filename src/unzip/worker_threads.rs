@@ -0,0 +1,85 @@
+// Copyright 2025 Google LLC
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use rayon::ThreadPoolBuilder;
+
+use crate::RipunzipErrors;
+
+/// How to set up the threads which extract files in parallel, which are
+/// those of rayon's global thread pool. Without this, they're whatever
+/// rayon makes by default, with no names.
+#[derive(Debug, Clone, Default)]
+pub struct WorkerThreadOptions {
+    /// How much to lower the threads' scheduling priority, as for the
+    /// `nice` command, so that extracting in the background doesn't slow
+    /// down more important work. This is best effort: it's only supported
+    /// on Linux, and elsewhere, or if it fails, a warning is logged.
+    pub nice: Option<i32>,
+}
+
+impl WorkerThreadOptions {
+    /// Create rayon's global thread pool, with threads named
+    /// `ripunzip-worker-N`. This must be done before anything uses rayon,
+    /// including ripunzip, or it fails. Files extracted with
+    /// [`UnzipOptions::single_threaded`](super::UnzipOptions::single_threaded)
+    /// are extracted on the calling thread instead, which is unaffected.
+    pub fn build_global(self) -> Result<(), RipunzipErrors> {
+        Ok(self.builder().build_global()?)
+    }
+
+    fn builder(self) -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new()
+            .thread_name(|i| format!("ripunzip-worker-{i}"))
+            .start_handler(move |_| {
+                if let Some(nice) = self.nice {
+                    lower_priority(nice);
+                }
+            })
+    }
+}
+
+/// Lower the priority of the calling thread by `nice`. On Linux, unlike
+/// elsewhere, `setpriority` affects only the calling thread.
+#[cfg(target_os = "linux")]
+fn lower_priority(nice: i32) {
+    use rustix::process::{getpriority_process, setpriority_process};
+    let result =
+        getpriority_process(None).and_then(|current| setpriority_process(None, current + nice));
+    if let Err(e) = result {
+        log::warn!("Unable to lower the priority of a worker thread: {e}");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_priority(_nice: i32) {
+    log::warn!("Lowering the priority of worker threads is only supported on Linux");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WorkerThreadOptions;
+
+    #[test]
+    fn test_worker_threads() {
+        let pool = WorkerThreadOptions { nice: Some(1) }
+            .builder()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let name = pool.install(|| std::thread::current().name().map(str::to_string));
+        assert!(name.unwrap().starts_with("ripunzip-worker-"));
+        #[cfg(target_os = "linux")]
+        {
+            use rustix::process::getpriority_process;
+            let ours = getpriority_process(None).unwrap();
+            let theirs = pool.install(|| getpriority_process(None).unwrap());
+            // Unless we were already as low as can be.
+            assert!(theirs == ours + 1 || ours == 19);
+        }
+    }
+}
@@ -41,6 +41,7 @@ pub enum RangeAwareResponseType {
 /// The range we expect to read. If it's different from this, assert failure.
 pub struct ExpectedRange {
     pub expected_start: u64,
+    /// Exclusive, unlike the end in a `Range` header.
     pub expected_end: u64,
 }
 
@@ -81,10 +82,12 @@ impl httptest::responders::Responder for RangeAwareResponse {
                                 .get(1)
                                 .and_then(|s| s.as_str().parse::<usize>().ok())
                                 .unwrap();
-                            let to = captures
+                            // The last byte to send, rather than the one after.
+                            let last = captures
                                 .get(2)
                                 .and_then(|s| s.as_str().parse::<usize>().ok())
                                 .unwrap();
+                            let to = (last + 1).min(body.len());
 
                             if let Some(expected_range) = expected_range {
                                 assert_eq!(
@@ -92,7 +95,8 @@ impl httptest::responders::Responder for RangeAwareResponse {
                                     "Unexpected start location"
                                 );
                                 assert_eq!(
-                                    expected_range.expected_end, to as u64,
+                                    expected_range.expected_end,
+                                    last as u64 + 1,
                                     "Unexpected end location"
                                 );
                             }